@@ -0,0 +1,106 @@
+//! Names of the functions built into jaq, suggested after a `|` or a `(`.
+
+pub const NAMES: [&str; 102] = [
+    "add",
+    "all",
+    "any",
+    "arrays",
+    "ascii_downcase",
+    "ascii_upcase",
+    "booleans",
+    "capture",
+    "ceil",
+    "contains",
+    "del",
+    "endswith",
+    "env",
+    "error",
+    "explode",
+    "false",
+    "finites",
+    "first",
+    "flatten",
+    "floor",
+    "from_entries",
+    "fromdate",
+    "fromdateiso8601",
+    "fromjson",
+    "group_by",
+    "gsub",
+    "has",
+    "implode",
+    "in",
+    "infinite",
+    "input",
+    "inputs",
+    "inside",
+    "isarray",
+    "isboolean",
+    "isempty",
+    "isfinite",
+    "isinfinite",
+    "isnan",
+    "isnormal",
+    "isnumber",
+    "isobject",
+    "isstring",
+    "iterables",
+    "join",
+    "keys",
+    "keys_unsorted",
+    "last",
+    "length",
+    "limit",
+    "ltrimstr",
+    "map",
+    "map_values",
+    "match",
+    "max",
+    "max_by",
+    "min",
+    "min_by",
+    "nan",
+    "normals",
+    "not",
+    "now",
+    "nth",
+    "null",
+    "nulls",
+    "numbers",
+    "objects",
+    "paths",
+    "range",
+    "recurse",
+    "repeat",
+    "reverse",
+    "round",
+    "rtrimstr",
+    "scalars",
+    "scan",
+    "select",
+    "sort",
+    "sort_by",
+    "split",
+    "splits",
+    "startswith",
+    "strings",
+    "sub",
+    "test",
+    "to_entries",
+    "todate",
+    "todateiso8601",
+    "tojson",
+    "tonumber",
+    "tostring",
+    "transpose",
+    "true",
+    "type",
+    "unique",
+    "unique_by",
+    "until",
+    "utf8bytelength",
+    "values",
+    "walk",
+    "while",
+    "with_entries",
+];
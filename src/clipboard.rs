@@ -0,0 +1,258 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use promkit::{crossterm::style::Color, style::StyleBuilder, text};
+use tokio::sync::{oneshot, watch};
+
+use crate::safe::{Effect, SafeMode};
+
+/// Time a copy waits for the clipboard to be set up before being queued.
+const READY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Clipboard the copies are set to and the pastes are read from, once set up.
+pub trait ClipboardProvider: Send + 'static {
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+    fn get_text(&mut self) -> Result<String, String>;
+}
+
+impl ClipboardProvider for arboard::Clipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        arboard::Clipboard::set_text(self, text).map_err(|e| e.to_string())
+    }
+
+    fn get_text(&mut self) -> Result<String, String> {
+        arboard::Clipboard::get_text(self).map_err(|e| e.to_string())
+    }
+}
+
+enum Backend {
+    Initializing,
+    Ready(Box<dyn ClipboardProvider>),
+    /// Setup failed, so copies go through the terminal instead (OSC 52).
+    Failed(String),
+}
+
+/// Outcome of [`Clipboard::copy`].
+pub enum Copied {
+    Now(text::State),
+    /// The clipboard is still being set up;
+    /// the copy completes (with the guide to show) once it is ready.
+    Later(oneshot::Receiver<text::State>),
+}
+
+/// System clipboard, set up once in the background.
+///
+/// Setting up the clipboard can take seconds on some environments
+/// (waiting on a Wayland or X11 roundtrip), which would freeze the UI
+/// if it were done on every copy.
+///
+/// Copies are refused in safe mode.
+#[derive(Clone)]
+pub struct Clipboard {
+    backend: Arc<Mutex<Backend>>,
+    ready: watch::Receiver<bool>,
+    safe_mode: SafeMode,
+}
+
+impl Clipboard {
+    pub fn spawn(safe_mode: SafeMode) -> Self {
+        // arboard fails (in the specific environment like linux?) on Clipboard::new()
+        // https://github.com/1Password/arboard/issues/153
+        Self::spawn_with(safe_mode, || {
+            arboard::Clipboard::new().map_err(|e| e.to_string())
+        })
+    }
+
+    /// Sets up the clipboard with `setup` on a thread of its own,
+    /// which the runtime does not wait for on exit should it never return.
+    pub fn spawn_with<P, F>(safe_mode: SafeMode, setup: F) -> Self
+    where
+        P: ClipboardProvider,
+        F: FnOnce() -> Result<P, String> + Send + 'static,
+    {
+        let backend = Arc::new(Mutex::new(Backend::Initializing));
+        let (ready_tx, ready) = watch::channel(false);
+
+        let shared = backend.clone();
+        thread::spawn(move || {
+            let initialized = match setup() {
+                Ok(clipboard) => Backend::Ready(Box::new(clipboard)),
+                Err(e) => Backend::Failed(e),
+            };
+            if let Ok(mut backend) = shared.lock() {
+                *backend = initialized;
+            }
+            let _ = ready_tx.send(true);
+        });
+
+        Self {
+            backend,
+            ready,
+            safe_mode,
+        }
+    }
+
+    /// Copies the content, telling it as `description` (e.g. the copied command)
+    /// if given rather than by its size.
+    pub async fn copy(&self, content: String, description: Option<String>) -> Copied {
+        if let Err(e) = self.safe_mode.permit(Effect::Clipboard) {
+            return Copied::Now(text::State {
+                text: e.to_string(),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            });
+        }
+        let mut ready = self.ready.clone();
+        if tokio::time::timeout(READY_TIMEOUT, ready.wait_for(|ready| *ready))
+            .await
+            .is_ok()
+        {
+            return Copied::Now(self.copy_now(&content, description));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let clipboard = self.clone();
+        tokio::spawn(async move {
+            let _ = ready.wait_for(|ready| *ready).await;
+            let _ = tx.send(clipboard.copy_now(&content, description));
+        });
+        Copied::Later(rx)
+    }
+
+    /// Returns the text in the clipboard, or why it cannot be read.
+    pub fn paste(&self) -> Result<String, String> {
+        match self.backend.lock() {
+            Ok(mut backend) => match &mut *backend {
+                Backend::Ready(clipboard) => clipboard.get_text(),
+                Backend::Failed(e) => Err(e.clone()),
+                Backend::Initializing => Err("clipboard is not ready".to_string()),
+            },
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn copy_now(&self, content: &str, description: Option<String>) -> text::State {
+        let description = description.unwrap_or_else(|| format!("{} bytes", content.len()));
+        let error = match self.backend.lock() {
+            Ok(mut backend) => match &mut *backend {
+                Backend::Ready(clipboard) => match clipboard.set_text(content) {
+                    Ok(_) => {
+                        return text::State {
+                            text: format!("Copied {} to clipboard", description),
+                            style: StyleBuilder::new().fgc(Color::Green).build(),
+                        }
+                    }
+                    Err(e) => e,
+                },
+                Backend::Failed(e) => e.clone(),
+                Backend::Initializing => "clipboard is not ready".to_string(),
+            },
+            Err(e) => e.to_string(),
+        };
+
+        match copy_via_terminal(content) {
+            Ok(()) => text::State {
+                text: format!(
+                    "Copied {} via the terminal (clipboard unavailable: {})",
+                    description, error
+                ),
+                style: StyleBuilder::new().fgc(Color::Green).build(),
+            },
+            Err(e) => text::State {
+                text: format!("Failed to copy to clipboard: {}, {}", error, e),
+                style: StyleBuilder::new().fgc(Color::Red).build(),
+            },
+        }
+    }
+}
+
+/// Asks the terminal to set the clipboard with the OSC 52 escape sequence.
+fn copy_via_terminal(content: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(content.as_bytes()))?;
+    stdout.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut ret = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                ret.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                ret.push('=');
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clipboard keeping the text copied.
+    #[derive(Clone, Default)]
+    struct Memory(Arc<Mutex<String>>);
+
+    impl ClipboardProvider for Memory {
+        fn set_text(&mut self, text: &str) -> Result<(), String> {
+            *self.0.lock().unwrap() = text.to_string();
+            Ok(())
+        }
+
+        fn get_text(&mut self) -> Result<String, String> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    /// A clipboard never set up (e.g. hanging on a Wayland roundtrip)
+    /// holds neither the first render, spawned before it, nor the copies.
+    #[tokio::test]
+    async fn a_hanging_setup_holds_nothing() {
+        let clipboard = tokio::time::timeout(READY_TIMEOUT * 10, async {
+            let clipboard =
+                Clipboard::spawn_with(SafeMode::new(false), || -> Result<Memory, String> {
+                    loop {
+                        thread::park();
+                    }
+                });
+            assert!(matches!(
+                clipboard.copy("text".to_string(), None).await,
+                Copied::Later(_)
+            ));
+            clipboard
+        })
+        .await;
+        assert!(clipboard.is_ok());
+    }
+
+    #[tokio::test]
+    async fn a_copy_queued_completes_once_set_up() {
+        let memory = Memory::default();
+        let provider = memory.clone();
+        let (release, gate) = std::sync::mpsc::channel::<()>();
+        let clipboard = Clipboard::spawn_with(SafeMode::new(false), move || {
+            let _ = gate.recv();
+            Ok(provider)
+        });
+        assert_eq!(clipboard.paste(), Err("clipboard is not ready".to_string()));
+        let Copied::Later(copied) = clipboard.copy("text".to_string(), None).await else {
+            panic!("copied before the clipboard was set up");
+        };
+
+        release.send(()).unwrap();
+        assert_eq!(copied.await.unwrap().text, "Copied 4 bytes to clipboard");
+        assert_eq!(*memory.0.lock().unwrap(), "text");
+        assert_eq!(clipboard.paste(), Ok("text".to_string()));
+    }
+}
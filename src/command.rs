@@ -0,0 +1,147 @@
+//! Command line running the filter with `jq`, to reproduce the result outside jnv.
+
+use std::path::PathBuf;
+
+/// Templates of the command, in which `{query}` and `{input}` are replaced
+/// by the filter and the input file, quoted for POSIX shells.
+#[derive(Clone)]
+pub struct CommandTemplate {
+    template: String,
+    /// Used when the input is read from standard input, e.g. to be piped into.
+    stdin_template: String,
+    input: Option<PathBuf>,
+}
+
+impl CommandTemplate {
+    pub fn new(template: String, stdin_template: String, input: Option<PathBuf>) -> Self {
+        Self {
+            template,
+            stdin_template,
+            // `-` stands for standard input too.
+            input: input.filter(|path| path.as_os_str() != "-"),
+        }
+    }
+
+    /// Returns the command running the query.
+    pub fn render(&self, query: &str) -> String {
+        let query = shell_quote(query);
+        match &self.input {
+            Some(path) => {
+                let path = path.to_string_lossy();
+                self.template
+                    .replace("{input}", &shell_word(&path))
+                    .replace("{query}", &query)
+            }
+            None => self.stdin_template.replace("{query}", &query),
+        }
+    }
+}
+
+/// Quotes the string in single quotes for POSIX shells,
+/// each single quote in it being written as `'\''`.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Returns the string as is if the shell takes it as a single word, or quoted otherwise.
+fn shell_word(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '_' | '-' | '+' | ',' | ':' | '@')
+        });
+    if plain {
+        s.to_string()
+    } else {
+        shell_quote(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(input: Option<&str>) -> CommandTemplate {
+        CommandTemplate::new(
+            "jq {query} {input}".to_string(),
+            "jq {query}".to_string(),
+            input.map(PathBuf::from),
+        )
+    }
+
+    #[test]
+    fn shell_quote_table() {
+        for (s, expected) in [
+            ("", "''"),
+            (".", "'.'"),
+            (".a | .b", "'.a | .b'"),
+            ("\"it's\"", r#"'"it'\''s"'"#),
+            ("''", r"''\'''\'''"),
+            ("$HOME `x` $(y)", "'$HOME `x` $(y)'"),
+            ("a\\b", "'a\\b'"),
+            ("a\nb", "'a\nb'"),
+            ("!*?~#", "'!*?~#'"),
+        ] {
+            assert_eq!(shell_quote(s), expected, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn shell_word_table() {
+        for (s, expected) in [
+            ("data.json", "data.json"),
+            ("/tmp/a-b_c+d,e:f@g.json", "/tmp/a-b_c+d,e:f@g.json"),
+            ("", "''"),
+            ("my data.json", "'my data.json'"),
+            ("it's.json", r"'it'\''s.json'"),
+            ("$x.json", "'$x.json'"),
+            ("*.json", "'*.json'"),
+            ("~/a.json", "'~/a.json'"),
+            ("データ.json", "'データ.json'"),
+        ] {
+            assert_eq!(shell_word(s), expected, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn render_with_and_without_input() {
+        assert_eq!(
+            template(Some("my data.json")).render(".a"),
+            "jq '.a' 'my data.json'"
+        );
+        assert_eq!(
+            template(Some("data.json")).render(".a"),
+            "jq '.a' data.json"
+        );
+        assert_eq!(template(None).render(".a"), "jq '.a'");
+        assert_eq!(template(Some("-")).render(".a"), "jq '.a'");
+    }
+
+    #[test]
+    fn render_does_not_expand_placeholders_in_the_query() {
+        assert_eq!(
+            template(Some("data.json")).render("\"{input}\""),
+            "jq '\"{input}\"' data.json"
+        );
+    }
+
+    /// The quoted strings read back as they were through a real shell.
+    #[cfg(unix)]
+    #[test]
+    fn round_trip_through_sh() {
+        for s in [
+            "",
+            ".a | select(.b == \"it's\")",
+            "'''",
+            "$HOME `echo x` $(echo y) \\n",
+            "a\nb\tc",
+            "!*?~#;&|<>",
+        ] {
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf '%s' {}", shell_quote(s)))
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), s);
+        }
+    }
+}
@@ -0,0 +1,775 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    paths::{PathInsertion, PathMatching, PathOrder},
+    render::Layout,
+};
+
+mod duration;
+use duration::duration_serde;
+pub mod event;
+use event::{EventDefSet, EventSeq};
+mod style;
+pub use style::StyleDef;
+mod theme;
+pub use theme::{ThemeConfig, ThemeName};
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompletionConfig {
+    /// Pause inserted between chunks while indexing paths for suggestions,
+    /// which keeps the indexing at a low priority compared to editing.
+    #[serde(with = "duration_serde")]
+    pub load_throttle: Duration,
+    /// Order of the suggestions: "depth" (shallower paths first)
+    /// or "frequency" (paths appearing in more documents first).
+    pub order: PathOrder,
+    /// Matching of the suggestions against the filter:
+    /// "prefix", "substring" or "fuzzy" (the characters in order).
+    pub matching: PathMatching,
+    /// Match the suggestions regardless of the case of the letters
+    /// (e.g. `.user` for `.UserId`).
+    pub case_insensitive: bool,
+    /// Suggest the paths of the result of the filter before the last `|`,
+    /// which takes time and memory to index for huge results.
+    pub result_paths: bool,
+    /// Number of the first elements of each array whose paths are suggested
+    /// with their index (e.g. `.items[0].name`), besides those with `[]` (e.g. `.items[].name`).
+    pub array_indices: usize,
+}
+
+impl Default for CompletionConfig {
+    fn default() -> Self {
+        Self {
+            load_throttle: Duration::ZERO,
+            order: PathOrder::Depth,
+            matching: PathMatching::Prefix,
+            case_insensitive: false,
+            result_paths: true,
+            array_indices: 3,
+        }
+    }
+}
+
+/// Delays trading the responsiveness of the interface for less work on large inputs.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReactivityControl {
+    /// Time to wait after the last edit before applying the filter.
+    #[serde(with = "duration_serde")]
+    pub query_debounce_duration: Duration,
+    /// Time to wait after the last resize before drawing again.
+    #[serde(with = "duration_serde")]
+    pub resize_debounce_duration: Duration,
+    /// Interval between the frames of the spinner shown while processing.
+    #[serde(with = "duration_serde")]
+    pub spin_duration: Duration,
+    /// Time during which the keys following one pressed in the JSON viewer
+    /// are gathered into a single redraw, e.g. while holding Down.
+    /// With 0, only those pressed while drawing are gathered.
+    #[serde(with = "duration_serde")]
+    pub viewer_coalesce_duration: Duration,
+    /// Interval between the checks of the input file for changes, with --watch.
+    #[serde(with = "duration_serde")]
+    pub watch_interval: Duration,
+}
+
+impl Default for ReactivityControl {
+    fn default() -> Self {
+        Self {
+            query_debounce_duration: Duration::from_millis(600),
+            resize_debounce_duration: Duration::from_millis(200),
+            spin_duration: Duration::from_millis(300),
+            viewer_coalesce_duration: Duration::ZERO,
+            watch_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct HintConfig {
+    /// Show a line listing the keys most relevant to the current context.
+    pub footer: bool,
+    /// Time after which the startup shows the phase in progress
+    /// (reading, parsing...) instead of a blank screen.
+    #[serde(with = "duration_serde")]
+    pub progress_after: Duration,
+    /// Tell the number of values and the time jq took after each evaluation.
+    pub timing: bool,
+}
+
+impl Default for HintConfig {
+    fn default() -> Self {
+        Self {
+            footer: false,
+            progress_after: Duration::from_secs(2),
+            timing: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct JqConfig {
+    /// Abort on the first error instead of showing the values
+    /// of the documents on which the filter succeeded.
+    pub strict_errors: bool,
+    /// How the path of the selected row is put into the filter:
+    /// "append" (applied to the result of the filter) or "replace".
+    pub insert_path: PathInsertion,
+    /// Number of the latest results kept to show them again without running jq
+    /// (0 disables the cache).
+    pub cache_entries: usize,
+    /// Command line copied by `copy_command`, in which `{query}` and `{input}`
+    /// are replaced by the filter and the input file, quoted for the shell.
+    pub command_template: String,
+    /// Command line copied by `copy_command` when reading standard input.
+    pub stdin_command_template: String,
+    /// What runs the filters: "jaq" (embedded) or "jq" (the binary of `binary`).
+    pub engine: Engine,
+    /// jq binary run for each filter with the "jq" engine.
+    pub binary: String,
+    /// Time after which the evaluation of a filter is given up,
+    /// the previous result staying on screen ("0ms" disables the timeout).
+    /// jaq only stops between the values the filter outputs.
+    #[serde(with = "duration_serde")]
+    pub query_timeout: Duration,
+    /// Number of the threads jaq runs a filter on, splitting the documents between them
+    /// (0 for as many as the available parallelism).
+    pub workers: usize,
+}
+
+/// What runs the filters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Engine {
+    /// jaq, embedded in jnv.
+    #[default]
+    Jaq,
+    /// An external jq binary, for the builtins and the behaviors jaq lacks.
+    Jq,
+}
+
+impl Default for JqConfig {
+    fn default() -> Self {
+        Self {
+            strict_errors: false,
+            insert_path: PathInsertion::Append,
+            cache_entries: 32,
+            command_template: "jq {query} {input}".to_string(),
+            stdin_command_template: "jq {query}".to_string(),
+            engine: Engine::Jaq,
+            binary: "jq".to_string(),
+            query_timeout: Duration::from_secs(10),
+            workers: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorConfig {
+    /// Templates inserted at the cursor by keybind (e.g. "Alt+s" = "| select($0)"),
+    /// the cursor being put at `$0` (at the end if there is none).
+    pub snippets: BTreeMap<String, String>,
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self {
+            snippets: BTreeMap::from(
+                [
+                    ("Alt+s", "| select($0)"),
+                    ("Alt+m", "| map($0)"),
+                    ("Alt+k", "| keys$0"),
+                ]
+                .map(|(keybind, template)| (keybind.to_string(), template.to_string())),
+            ),
+        }
+    }
+}
+
+impl EditorConfig {
+    /// Returns the keybinds of the snippets with their templates.
+    pub fn snippet_bindings(&self) -> anyhow::Result<Vec<(EventSeq, String)>> {
+        self.snippets
+            .iter()
+            .map(|(keybind, template)| {
+                let seq = keybind
+                    .parse::<EventSeq>()
+                    .map_err(|e| anyhow::anyhow!("snippet keybind '{}': {}", keybind, e))?;
+                Ok((seq, template.clone()))
+            })
+            .collect()
+    }
+
+    /// Rejects snippets bound to a keybind of the editor (or a global one).
+    fn validate(&self, keybinds: &Keybinds) -> anyhow::Result<()> {
+        let bound = keybinds
+            .global
+            .named_sequences()
+            .into_iter()
+            .chain(keybinds.on_editor.named_sequences())
+            .collect::<Vec<_>>();
+        for (seq, _) in self.snippet_bindings()? {
+            if let Some((name, _)) = bound.iter().find(|(_, s)| s.is_equivalent(&seq)) {
+                return Err(anyhow::anyhow!(
+                    "keybind '{}' is bound to both a snippet and '{}'",
+                    seq,
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonConfig {
+    /// Tell apart the upper and lower cases when searching the JSON viewer.
+    pub case_sensitive_search: bool,
+    /// Depth up to which the containers are expanded as a result is shown
+    /// (everything if unset).
+    pub default_expand_depth: Option<usize>,
+    /// Prefix the rows with their line number in the result as `jq` formats it.
+    pub show_line_numbers: bool,
+    /// Show the row of the cursor over the number of rows below the result.
+    pub show_position: bool,
+    /// Show the path of the container holding the top row above the rows.
+    pub sticky_path: bool,
+    /// Hide the nulls and the empty objects and arrays from the rows at first.
+    pub hide_nulls: bool,
+    /// Copy the values hidden with `hide_nulls` along with the others.
+    pub copy_hidden: bool,
+    /// Number of the values of a result beyond which the others are left out
+    /// (all if unset).
+    pub max_results: Option<usize>,
+}
+
+impl Default for JsonConfig {
+    fn default() -> Self {
+        Self {
+            case_sensitive_search: false,
+            default_expand_depth: None,
+            show_line_numbers: false,
+            show_position: false,
+            sticky_path: false,
+            hide_nulls: false,
+            copy_hidden: true,
+            max_results: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// File storing the filters across sessions
+    /// (`jnv/history` under the configuration directory if unset).
+    pub path: Option<PathBuf>,
+    /// Maximum number of filters remembered.
+    pub max_entries: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_entries: 1000,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegistersConfig {
+    /// Keep the filters stored in the registers across sessions.
+    pub persist: bool,
+    /// File storing the registers
+    /// (`jnv/registers.json` under the configuration directory if unset).
+    pub path: Option<PathBuf>,
+}
+
+/// Defines a set of keybinds together with the enum of actions they trigger.
+macro_rules! keybinds {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident => $action:ident {
+            $( $field:ident => $variant:ident = [$($default:expr),* $(,)?], )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct $name {
+            $( pub $field: EventDefSet, )*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self {
+                    $( $field: EventDefSet::from_defaults(&[$($default),*]), )*
+                }
+            }
+        }
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum $action {
+            $( $variant, )*
+        }
+
+        impl $name {
+            /// Returns every set of keybinds paired with its action.
+            pub fn bindings(&self) -> Vec<(&EventDefSet, $action)> {
+                vec![$( (&self.$field, $action::$variant), )*]
+            }
+
+            /// Returns every set of keybinds paired with its name in the configuration.
+            pub fn entries(&self) -> Vec<(&'static str, &EventDefSet)> {
+                vec![$( (stringify!($field), &self.$field), )*]
+            }
+
+            fn named_sequences(&self) -> Vec<(&'static str, &EventSeq)> {
+                let mut ret = Vec::new();
+                $( ret.extend(self.$field.iter().map(|seq| (stringify!($field), seq))); )*
+                ret
+            }
+        }
+    };
+}
+
+keybinds! {
+    /// Keybinds available regardless of the focused pane.
+    pub struct GlobalKeybinds => GlobalAction {
+        exit => Exit = ["Ctrl+c"],
+        exit_with_output => ExitWithOutput = ["Ctrl+x"],
+        copy_query => CopyQuery = ["Ctrl+q"],
+        copy_result => CopyResult = ["Ctrl+o"],
+        copy_command => CopyCommand = ["Alt+q"],
+        external_editor => ExternalEditor = ["Alt+e"],
+        switch_mode => SwitchMode = ["Shift+Down", "Shift+Up", "BackTab"],
+        reset => Reset = ["Ctrl+g"],
+        help => Help = ["F1"],
+        reload => Reload = ["Ctrl+r"],
+        toggle_slurp => ToggleSlurp = ["Alt+a"],
+        show_all_results => ShowAllResults = ["Alt+l"],
+        commit_result => CommitResult = ["Alt+i"],
+        pop_base => PopBase = ["Alt+o"],
+        toggle_stats => ToggleStats = ["Alt+t"],
+    }
+}
+
+keybinds! {
+    /// Keybinds for the filter editor.
+    pub struct EditorKeybinds => EditorAction {
+        backward => Backward = ["Left"],
+        forward => Forward = ["Right"],
+        move_to_head => MoveToHead = ["Ctrl+a"],
+        move_to_tail => MoveToTail = ["Ctrl+e"],
+        move_to_previous_nearest => MoveToPreviousNearest = ["Alt+b"],
+        move_to_next_nearest => MoveToNextNearest = ["Alt+f"],
+        erase => Erase = ["Backspace"],
+        erase_all => EraseAll = ["Ctrl+u"],
+        erase_to_previous_nearest => EraseToPreviousNearest = ["Ctrl+w"],
+        erase_to_next_nearest => EraseToNextNearest = ["Alt+d"],
+        paste => Paste = ["Ctrl+v"],
+        increment_index => IncrementIndex = ["Ctrl+Up"],
+        decrement_index => DecrementIndex = ["Ctrl+Down"],
+        completion => Completion = ["Tab"],
+        history_prev => HistoryPrevious = ["Up"],
+        history_next => HistoryNext = ["Down"],
+        store_register => StoreRegister = [
+            "Alt+w 1", "Alt+w 2", "Alt+w 3", "Alt+w 4", "Alt+w 5",
+            "Alt+w 6", "Alt+w 7", "Alt+w 8", "Alt+w 9",
+        ],
+        recall_register => RecallRegister = [
+            "Alt+r 1", "Alt+r 2", "Alt+r 3", "Alt+r 4", "Alt+r 5",
+            "Alt+r 6", "Alt+r 7", "Alt+r 8", "Alt+r 9",
+        ],
+        list_registers => ListRegisters = ["Alt+r l"],
+    }
+}
+
+keybinds! {
+    /// Keybinds while the suggestions are listed after a completion.
+    pub struct CompletionKeybinds => CompletionAction {
+        up => Up = ["Up"],
+        down => Down = ["Tab", "Down"],
+        cancel => Cancel = ["Esc"],
+        accept_segment => AcceptSegment = ["Right"],
+    }
+}
+
+keybinds! {
+    /// Keybinds for the JSON viewer.
+    pub struct JsonViewerKeybinds => JsonViewerAction {
+        up => Up = ["Up", "Ctrl+k", "ScrollUp"],
+        down => Down = ["Down", "Ctrl+j", "ScrollDown"],
+        page_up => PageUp = ["PageUp"],
+        page_down => PageDown = ["PageDown"],
+        half_page_up => HalfPageUp = ["Ctrl+u"],
+        half_page_down => HalfPageDown = ["Ctrl+d"],
+        move_to_head => MoveToHead = ["Ctrl+l"],
+        move_to_tail => MoveToTail = ["Ctrl+h"],
+        move_to_parent => MoveToParent = ["p"],
+        next_sibling => NextSibling = ["}", "Alt+Down"],
+        prev_sibling => PrevSibling = ["{", "Alt+Up"],
+        toggle => Toggle = ["Enter"],
+        expand_all => ExpandAll = ["Ctrl+p"],
+        collapse_all => CollapseAll = ["Ctrl+n"],
+        remember_collapsed => RememberCollapsed = ["z c"],
+        remember_expanded => RememberExpanded = ["z o"],
+        list_remembered => ListRemembered = ["z l"],
+        forget_remembered => ForgetRemembered = ["z d"],
+        fold_similar => FoldSimilar = ["z s"],
+        toggle_nulls => ToggleNulls = ["z n"],
+        toggle_raw_output => ToggleRawOutput = ["r"],
+        toggle_table => ToggleTable = ["t"],
+        toggle_gron => ToggleGron = ["g"],
+        toggle_pretty => TogglePretty = ["P"],
+        scroll_left => ScrollLeft = ["Left"],
+        scroll_right => ScrollRight = ["Right"],
+        scroll_home => ScrollHome = ["Home"],
+        collect_field => CollectField = ["f a"],
+        emit_projection => EmitProjection = ["f p"],
+        copy_path => CopyPath = ["y p"],
+        copy_selected_node => CopySelectedNode = ["y y"],
+        insert_path => InsertPath = ["i"],
+        expand_to_depth => ExpandToDepth = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"],
+        search => Search = ["/"],
+        grep_rows => GrepRows = ["&"],
+        write_result => WriteResult = ["w"],
+        next_match => NextMatch = ["n"],
+        previous_match => PreviousMatch = ["N"],
+        toggle_bookmark => ToggleBookmark = ["m"],
+        next_bookmark => NextBookmark = ["]"],
+        prev_bookmark => PrevBookmark = ["["],
+        toggle_split => ToggleSplit = ["v"],
+        switch_focus => SwitchFocus = ["Tab"],
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keybinds {
+    /// Time to wait for the next chord of a multi-chord keybind (e.g. "g g").
+    #[serde(with = "duration_serde")]
+    pub sequence_timeout: Duration,
+    #[serde(flatten)]
+    pub global: GlobalKeybinds,
+    pub on_editor: EditorKeybinds,
+    pub on_completion: CompletionKeybinds,
+    pub on_json_viewer: JsonViewerKeybinds,
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Self {
+            sequence_timeout: Duration::from_millis(500),
+            global: Default::default(),
+            on_editor: Default::default(),
+            on_completion: Default::default(),
+            on_json_viewer: Default::default(),
+        }
+    }
+}
+
+impl Keybinds {
+    /// Rejects keybinds that can never be told apart.
+    ///
+    /// Within a pane (including the global keybinds, which take precedence),
+    /// the same sequence must not be bound twice.
+    /// A sequence may be a prefix of another one though;
+    /// such ambiguities are resolved by `sequence_timeout`.
+    fn validate(&self) -> anyhow::Result<()> {
+        let global = self.global.named_sequences();
+        for context in [
+            self.on_editor.named_sequences(),
+            self.on_completion.named_sequences(),
+            self.on_json_viewer.named_sequences(),
+        ] {
+            let all = global.iter().chain(context.iter()).collect::<Vec<_>>();
+            for (i, (name, seq)) in all.iter().enumerate() {
+                if let Some((other, _)) = all[i + 1..].iter().find(|(_, s)| s.is_equivalent(seq)) {
+                    return Err(anyhow::anyhow!(
+                        "keybind '{}' is bound to both '{}' and '{}'",
+                        seq,
+                        name,
+                        other
+                    ));
+                }
+            }
+        }
+        // The depth is given by the digit ending the keybind.
+        if let Some(seq) = self
+            .on_json_viewer
+            .expand_to_depth
+            .iter()
+            .find(|seq| seq.last_digit().is_none())
+        {
+            return Err(anyhow::anyhow!(
+                "keybind '{}' for 'expand_to_depth' must end with a digit",
+                seq
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Disable everything letting data leave the terminal
+    /// (the clipboard, saving files and so on).
+    pub safe_mode: bool,
+    /// Ask to press the exit keybind again before quitting.
+    pub confirm_quit: bool,
+    /// Capture the mouse: the wheel scrolls the JSON viewer
+    /// and a click focuses the editor or the JSON viewer.
+    pub mouse: bool,
+    pub completion: CompletionConfig,
+    pub editor: EditorConfig,
+    pub hint: HintConfig,
+    pub history: HistoryConfig,
+    pub jq: JqConfig,
+    pub json: JsonConfig,
+    pub layout: Layout,
+    pub reactivity_control: ReactivityControl,
+    pub registers: RegistersConfig,
+    pub theme: ThemeConfig,
+    pub keybinds: Keybinds,
+}
+
+impl Config {
+    /// Loads the configuration file, or `jnv/config.toml` under
+    /// the user's configuration directory if none is given.
+    ///
+    /// An explicitly specified file must exist, whereas a missing file
+    /// at the default location simply results in the default configuration.
+    /// The unknown keys are reported on standard error.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match dirs::config_dir().map(|dir| dir.join("jnv").join("config.toml")) {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let (config, unknown_keys) = Self::load_from(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        for key in unknown_keys {
+            eprintln!(
+                "warning: ignoring unknown key '{}' in config file {}",
+                key,
+                path.display()
+            );
+        }
+        Ok(config)
+    }
+
+    /// Parses a configuration in which every key is optional,
+    /// those left out taking their default values.
+    ///
+    /// Also returns the paths of the keys which are not part of the configuration
+    /// (e.g. misspelled ones), which are ignored.
+    pub fn load_from(content: &str) -> anyhow::Result<(Self, Vec<String>)> {
+        let table: toml::Table = toml::from_str(content)?;
+        let config: Self = toml::from_str(content)?;
+        config.keybinds.validate()?;
+        config.editor.validate(&config.keybinds)?;
+
+        // Keys left unset (`None`) are not serialized, hence the default configuration
+        // does not know all of them, unlike the parsed one for the keys it sets.
+        let mut unknown_keys = Vec::new();
+        collect_unknown_keys(
+            &table,
+            &[
+                &toml::Table::try_from(Self::default())?,
+                &toml::Table::try_from(&config)?,
+            ],
+            "",
+            &mut unknown_keys,
+        );
+        Ok((config, unknown_keys))
+    }
+}
+
+/// Collects the paths (e.g. "editor.foo") of the keys in `table`
+/// which none of the `known` tables have.
+fn collect_unknown_keys(
+    table: &toml::Table,
+    known: &[&toml::Table],
+    prefix: &str,
+    unknown_keys: &mut Vec<String>,
+) {
+    for (key, value) in table {
+        let path = format!("{}{}", prefix, key);
+        let known_values = known
+            .iter()
+            .filter_map(|known| known.get(key))
+            .collect::<Vec<_>>();
+        if known_values.is_empty() {
+            unknown_keys.push(path);
+            continue;
+        }
+        if let toml::Value::Table(table) = value {
+            let known = known_values
+                .iter()
+                .filter_map(|value| value.as_table())
+                .collect::<Vec<_>>();
+            if !known.is_empty() {
+                collect_unknown_keys(table, &known, &format!("{}.", path), unknown_keys);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(content: &str) -> (Config, Vec<String>) {
+        Config::load_from(content).unwrap_or_else(|e| panic!("{:?}: {:#}", content, e))
+    }
+
+    #[test]
+    fn empty_config_is_the_default() {
+        let (config, unknown_keys) = load("");
+        assert!(unknown_keys.is_empty());
+        assert_eq!(
+            toml::to_string(&config).unwrap(),
+            toml::to_string(&Config::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn one_line_config() {
+        let (config, unknown_keys) = load("mouse = true\n");
+        assert!(unknown_keys.is_empty());
+        assert!(config.mouse);
+        assert!(!config.safe_mode);
+        assert_eq!(config.history.max_entries, 1000);
+        assert_eq!(
+            config.keybinds.global.exit,
+            EventDefSet::from_defaults(&["Ctrl+c"])
+        );
+    }
+
+    #[test]
+    fn nested_partial_override() {
+        let (config, unknown_keys) = load(
+            r#"
+            [jq]
+            query_timeout = "2s"
+
+            [reactivity_control]
+            spin_duration = "100ms"
+
+            [keybinds]
+            exit = ["Ctrl+c", "Ctrl+z"]
+
+            [keybinds.on_json_viewer]
+            search = ["?"]
+            "#,
+        );
+        assert!(unknown_keys.is_empty(), "{:?}", unknown_keys);
+        assert_eq!(config.jq.query_timeout, Duration::from_secs(2));
+        // The other keys of the overridden sections keep their defaults.
+        assert_eq!(config.jq.binary, "jq");
+        assert_eq!(config.jq.cache_entries, 32);
+        assert_eq!(
+            config.reactivity_control.spin_duration,
+            Duration::from_millis(100)
+        );
+        assert_eq!(
+            config.reactivity_control.query_debounce_duration,
+            Duration::from_millis(600)
+        );
+        assert_eq!(
+            config.keybinds.global.exit,
+            EventDefSet::from_defaults(&["Ctrl+c", "Ctrl+z"])
+        );
+        assert_eq!(
+            config.keybinds.global.copy_query,
+            EventDefSet::from_defaults(&["Ctrl+q"])
+        );
+        assert_eq!(
+            config.keybinds.on_json_viewer.search,
+            EventDefSet::from_defaults(&["?"])
+        );
+        assert_eq!(
+            config.keybinds.on_json_viewer.next_match,
+            EventDefSet::from_defaults(&["n"])
+        );
+        assert_eq!(config.keybinds.sequence_timeout, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn typoed_keys_are_reported() {
+        let (config, unknown_keys) = load(
+            r#"
+            mous = true
+
+            [jq]
+            query_timout = "2s"
+            binary = "gojq"
+
+            [keybinds]
+            exitt = ["Ctrl+x"]
+
+            [keybinds.on_json_viewr]
+            search = ["?"]
+            "#,
+        );
+        assert_eq!(
+            unknown_keys,
+            vec![
+                "jq.query_timout",
+                "keybinds.exitt",
+                "keybinds.on_json_viewr",
+                "mous",
+            ]
+        );
+        // The known keys around them still apply.
+        assert_eq!(config.jq.binary, "gojq");
+        assert!(!config.mouse);
+    }
+
+    #[test]
+    fn optional_keys_are_known() {
+        let (config, unknown_keys) = load(
+            r#"
+            [history]
+            path = "/tmp/history"
+
+            [json]
+            max_results = 10
+            "#,
+        );
+        assert!(unknown_keys.is_empty(), "{:?}", unknown_keys);
+        assert_eq!(config.json.max_results, Some(10));
+    }
+
+    #[test]
+    fn invalid_values_fail() {
+        for content in [
+            "mouse = \"yes\"",
+            "[jq]\nengine = \"gojq\"",
+            "[keybinds]\nexit = [\"Ctrl+c\"]\ncopy_query = [\"Ctrl+c\"]",
+        ] {
+            assert!(Config::load_from(content).is_err(), "{:?}", content);
+        }
+    }
+}
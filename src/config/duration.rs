@@ -0,0 +1,38 @@
+pub mod duration_serde {
+    use std::time::Duration;
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format!("{}ms", duration.as_millis()))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        parse(&s).map_err(de::Error::custom)
+    }
+
+    /// Parses durations such as "300ms", "2s" or "1m".
+    pub fn parse(s: &str) -> Result<Duration, String> {
+        let s = s.trim();
+        let (value, unit) = s
+            .find(|c: char| !c.is_ascii_digit())
+            .map(|i| s.split_at(i))
+            .ok_or_else(|| format!("missing unit in duration '{}' (use ms, s or m)", s))?;
+        let value = value
+            .parse::<u64>()
+            .map_err(|_| format!("invalid number in duration '{}'", s))?;
+        match unit {
+            "ms" => Ok(Duration::from_millis(value)),
+            "s" => Ok(Duration::from_secs(value)),
+            "m" => Ok(Duration::from_secs(value * 60)),
+            _ => Err(format!("unknown unit '{}' in duration '{}'", unit, s)),
+        }
+    }
+}
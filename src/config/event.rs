@@ -0,0 +1,505 @@
+use std::{fmt, str::FromStr};
+
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use serde::{Deserialize, Serialize};
+
+/// Matches a definition against an actual value.
+pub trait Matcher<T> {
+    fn matches(&self, other: &T) -> bool;
+}
+
+/// Definition of a key press, written as e.g. "Ctrl+c", "Shift+Down" or "Space".
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyEventDef {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyEventDef {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+}
+
+impl From<&KeyEvent> for KeyEventDef {
+    fn from(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+impl From<&KeyEventDef> for KeyEvent {
+    fn from(def: &KeyEventDef) -> Self {
+        KeyEvent {
+            code: def.code,
+            modifiers: def.modifiers,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }
+    }
+}
+
+impl Matcher<KeyEvent> for KeyEventDef {
+    fn matches(&self, other: &KeyEvent) -> bool {
+        other.kind == KeyEventKind::Press
+            && normalize(self.code, self.modifiers) == normalize(other.code, other.modifiers)
+    }
+}
+
+/// Returns the character typed with the modifiers,
+/// e.g. 'G' for 'g' with Shift, as some terminals report.
+pub fn typed_char(ch: char, modifiers: KeyModifiers) -> char {
+    if modifiers.contains(KeyModifiers::SHIFT) && ch.is_lowercase() {
+        ch.to_uppercase().next().unwrap_or(ch)
+    } else {
+        ch
+    }
+}
+
+/// Normalizes a key so that characters compare regardless of how Shift is reported.
+///
+/// Terminals report a capital letter either as "G" or as "Shift+G",
+/// and a few as "Shift+g"; all of them are treated as "G".
+/// Since Shift is reflected in the character itself,
+/// it is ignored for characters and applies to other keys only (e.g. "Shift+Down").
+/// Likewise, "Shift+Tab", "BackTab" and "Shift+BackTab" are all treated as "BackTab".
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    match code {
+        KeyCode::Char(ch) => (
+            KeyCode::Char(typed_char(ch, modifiers)),
+            modifiers - KeyModifiers::SHIFT,
+        ),
+        KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+            (KeyCode::BackTab, modifiers - KeyModifiers::SHIFT)
+        }
+        KeyCode::BackTab => (KeyCode::BackTab, modifiers - KeyModifiers::SHIFT),
+        _ => (code, modifiers),
+    }
+}
+
+/// Definition of a mouse action, written as e.g. "ScrollUp" or "Ctrl+LeftDown".
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MouseEventDef {
+    pub kind: MouseEventKind,
+    pub modifiers: KeyModifiers,
+}
+
+impl From<&MouseEvent> for MouseEventDef {
+    fn from(event: &MouseEvent) -> Self {
+        Self {
+            kind: event.kind,
+            modifiers: event.modifiers,
+        }
+    }
+}
+
+impl Matcher<MouseEvent> for MouseEventDef {
+    fn matches(&self, other: &MouseEvent) -> bool {
+        self.kind == other.kind && self.modifiers == other.modifiers
+    }
+}
+
+/// A single chord: one key press or mouse action.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EventDef {
+    Key(KeyEventDef),
+    Mouse(MouseEventDef),
+}
+
+impl EventDef {
+    /// Returns whether the event can take part in a keybinding.
+    /// Key releases and mouse moves are ignored, for example.
+    pub fn is_bindable(event: &Event) -> bool {
+        match event {
+            Event::Key(key) => key.kind == KeyEventKind::Press,
+            Event::Mouse(mouse) => mouse.kind != MouseEventKind::Moved,
+            _ => false,
+        }
+    }
+}
+
+impl From<&EventDef> for Event {
+    fn from(def: &EventDef) -> Self {
+        match def {
+            EventDef::Key(def) => Event::Key(KeyEvent::from(def)),
+            EventDef::Mouse(def) => Event::Mouse(MouseEvent {
+                kind: def.kind,
+                column: 0,
+                row: 0,
+                modifiers: def.modifiers,
+            }),
+        }
+    }
+}
+
+impl Matcher<Event> for EventDef {
+    fn matches(&self, other: &Event) -> bool {
+        match (self, other) {
+            (EventDef::Key(def), Event::Key(event)) => def.matches(event),
+            (EventDef::Mouse(def), Event::Mouse(event)) => def.matches(event),
+            _ => false,
+        }
+    }
+}
+
+/// A sequence of chords, written as chords separated by spaces (e.g. "g g").
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct EventSeq(pub Vec<EventDef>);
+
+impl EventSeq {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether the events match the beginning of this sequence.
+    pub fn starts_with(&self, events: &[Event]) -> bool {
+        events.len() <= self.0.len()
+            && self
+                .0
+                .iter()
+                .zip(events)
+                .all(|(def, event)| def.matches(event))
+    }
+
+    /// Returns the digit typed by the last chord, if it is a digit key.
+    pub fn last_digit(&self) -> Option<u32> {
+        match self.0.last()? {
+            EventDef::Key(KeyEventDef {
+                code: KeyCode::Char(ch),
+                ..
+            }) => ch.to_digit(10),
+            _ => None,
+        }
+    }
+
+    /// Returns whether both sequences match the same events (e.g. "G" and "Shift+g").
+    pub fn is_equivalent(&self, other: &EventSeq) -> bool {
+        self.len() == other.len()
+            && self.starts_with(&other.0.iter().map(Event::from).collect::<Vec<_>>())
+    }
+}
+
+/// Set of sequences bound to a single action.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EventDefSet(pub Vec<EventSeq>);
+
+impl EventDefSet {
+    /// Builds the set from chord strings, panicking on invalid ones.
+    /// Intended for the built-in defaults only.
+    pub fn from_defaults(defs: &[&str]) -> Self {
+        Self(
+            defs.iter()
+                .map(|def| {
+                    def.parse()
+                        .unwrap_or_else(|e| panic!("invalid default keybind '{}': {}", def, e))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &EventSeq> {
+        self.0.iter()
+    }
+}
+
+impl Matcher<Event> for EventDefSet {
+    /// Matches single-chord bindings against the event.
+    fn matches(&self, other: &Event) -> bool {
+        self.0
+            .iter()
+            .any(|seq| seq.len() == 1 && seq.starts_with(std::slice::from_ref(other)))
+    }
+}
+
+fn fmt_modifiers(f: &mut fmt::Formatter<'_>, modifiers: KeyModifiers) -> fmt::Result {
+    for (modifier, name) in [
+        (KeyModifiers::CONTROL, "Ctrl"),
+        (KeyModifiers::ALT, "Alt"),
+        (KeyModifiers::SHIFT, "Shift"),
+        (KeyModifiers::SUPER, "Super"),
+        (KeyModifiers::HYPER, "Hyper"),
+        (KeyModifiers::META, "Meta"),
+    ] {
+        if modifiers.contains(modifier) {
+            write!(f, "{}+", name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Splits a chord such as "Ctrl+Alt+x" into its modifiers and the remaining key name.
+fn parse_chord(s: &str) -> Result<(KeyModifiers, &str), String> {
+    let (mods, key) = if s == "+" {
+        ("", s)
+    } else if let Some(mods) = s.strip_suffix("++") {
+        (mods, "+")
+    } else {
+        match s.rsplit_once('+') {
+            Some((mods, key)) => (mods, key),
+            None => ("", s),
+        }
+    };
+
+    let mut modifiers = KeyModifiers::NONE;
+    for name in mods.split('+').filter(|name| !name.is_empty()) {
+        modifiers |= match name.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            "super" => KeyModifiers::SUPER,
+            "hyper" => KeyModifiers::HYPER,
+            "meta" => KeyModifiers::META,
+            _ => return Err(format!("unknown modifier '{}' in '{}'", name, s)),
+        };
+    }
+    if key.is_empty() {
+        return Err(format!("missing key in '{}'", s));
+    }
+    Ok((modifiers, key))
+}
+
+const MOUSE_BUTTONS: [(MouseButton, &str); 3] = [
+    (MouseButton::Left, "Left"),
+    (MouseButton::Right, "Right"),
+    (MouseButton::Middle, "Middle"),
+];
+
+fn mouse_kind_from_name(name: &str) -> Option<MouseEventKind> {
+    match name {
+        "ScrollUp" => return Some(MouseEventKind::ScrollUp),
+        "ScrollDown" => return Some(MouseEventKind::ScrollDown),
+        "ScrollLeft" => return Some(MouseEventKind::ScrollLeft),
+        "ScrollRight" => return Some(MouseEventKind::ScrollRight),
+        "Moved" => return Some(MouseEventKind::Moved),
+        _ => {}
+    }
+    MOUSE_BUTTONS
+        .iter()
+        .find_map(|(button, prefix)| match name.strip_prefix(prefix)? {
+            "Down" => Some(MouseEventKind::Down(*button)),
+            "Up" => Some(MouseEventKind::Up(*button)),
+            "Drag" => Some(MouseEventKind::Drag(*button)),
+            _ => None,
+        })
+}
+
+fn key_code_from_name(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(ch));
+    }
+    let code = match name.to_ascii_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "enter" => KeyCode::Enter,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "null" => KeyCode::Null,
+        "esc" => KeyCode::Esc,
+        "capslock" => KeyCode::CapsLock,
+        "scrolllock" => KeyCode::ScrollLock,
+        "numlock" => KeyCode::NumLock,
+        "printscreen" => KeyCode::PrintScreen,
+        "pause" => KeyCode::Pause,
+        "menu" => KeyCode::Menu,
+        "keypadbegin" => KeyCode::KeypadBegin,
+        other => {
+            let n = other.strip_prefix('f')?.parse::<u8>().ok()?;
+            KeyCode::F(n)
+        }
+    };
+    Some(code)
+}
+
+impl fmt::Display for KeyEventDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_modifiers(f, self.modifiers)?;
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(ch) => write!(f, "{}", ch),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Media(media) => write!(f, "{:?}", media),
+            KeyCode::Modifier(modifier) => write!(f, "{:?}", modifier),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+impl FromStr for KeyEventDef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, key) = parse_chord(s)?;
+        let code = key_code_from_name(key).ok_or_else(|| format!("unknown key '{}'", key))?;
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for MouseEventDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_modifiers(f, self.modifiers)?;
+        let button_name = |button: &MouseButton| {
+            MOUSE_BUTTONS
+                .iter()
+                .find(|(b, _)| b == button)
+                .map(|(_, name)| *name)
+                .unwrap_or_default()
+        };
+        match &self.kind {
+            MouseEventKind::Down(button) => write!(f, "{}Down", button_name(button)),
+            MouseEventKind::Up(button) => write!(f, "{}Up", button_name(button)),
+            MouseEventKind::Drag(button) => write!(f, "{}Drag", button_name(button)),
+            kind => write!(f, "{:?}", kind),
+        }
+    }
+}
+
+impl FromStr for MouseEventDef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, name) = parse_chord(s)?;
+        let kind =
+            mouse_kind_from_name(name).ok_or_else(|| format!("unknown mouse action '{}'", name))?;
+        Ok(Self { kind, modifiers })
+    }
+}
+
+impl fmt::Display for EventDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventDef::Key(def) => def.fmt(f),
+            EventDef::Mouse(def) => def.fmt(f),
+        }
+    }
+}
+
+impl FromStr for EventDef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, name) = parse_chord(s)?;
+        if mouse_kind_from_name(name).is_some() {
+            s.parse().map(EventDef::Mouse)
+        } else {
+            s.parse().map(EventDef::Key)
+        }
+    }
+}
+
+impl fmt::Display for EventSeq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, def) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            def.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for EventSeq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let defs = s
+            .split_whitespace()
+            .map(EventDef::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        if defs.is_empty() {
+            return Err("empty keybind".to_string());
+        }
+        Ok(Self(defs))
+    }
+}
+
+macro_rules! impl_string_conversions {
+    ($($ty:ty),*) => {
+        $(
+            impl TryFrom<String> for $ty {
+                type Error = String;
+
+                fn try_from(s: String) -> Result<Self, Self::Error> {
+                    s.parse()
+                }
+            }
+
+            impl From<$ty> for String {
+                fn from(def: $ty) -> Self {
+                    def.to_string()
+                }
+            }
+        )*
+    };
+}
+
+impl_string_conversions!(KeyEventDef, MouseEventDef, EventSeq);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+        Event::Key(KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn shift_char_matches_however_the_terminal_reports_it() {
+        for def in ["Shift+g", "Shift+G", "G"] {
+            let seq: EventSeq = def.parse().unwrap();
+            let set = EventDefSet(vec![seq]);
+            for event in [
+                key(KeyCode::Char('g'), KeyModifiers::SHIFT),
+                key(KeyCode::Char('G'), KeyModifiers::SHIFT),
+                key(KeyCode::Char('G'), KeyModifiers::NONE),
+            ] {
+                assert!(set.matches(&event), "{} on {:?}", def, event);
+            }
+            assert!(
+                !set.matches(&key(KeyCode::Char('g'), KeyModifiers::NONE)),
+                "{}",
+                def
+            );
+            assert!(
+                !set.matches(&key(KeyCode::Char('G'), KeyModifiers::CONTROL)),
+                "{}",
+                def
+            );
+        }
+    }
+
+    #[test]
+    fn shift_applies_to_other_keys() {
+        let set = EventDefSet::from_defaults(&["Shift+Down", "Shift+Tab"]);
+        assert!(set.matches(&key(KeyCode::Down, KeyModifiers::SHIFT)));
+        assert!(!set.matches(&key(KeyCode::Down, KeyModifiers::NONE)));
+        assert!(set.matches(&key(KeyCode::BackTab, KeyModifiers::NONE)));
+        assert!(set.matches(&key(KeyCode::BackTab, KeyModifiers::SHIFT)));
+        assert!(!set.matches(&key(KeyCode::Tab, KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn shift_char_bindings_are_equivalent() {
+        let a: EventSeq = "Shift+g g".parse().unwrap();
+        let b: EventSeq = "G g".parse().unwrap();
+        assert!(a.is_equivalent(&b));
+        assert!(!a.is_equivalent(&"g g".parse().unwrap()));
+    }
+}
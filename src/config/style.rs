@@ -0,0 +1,32 @@
+use crossterm::style::{Attribute, Color, ContentStyle};
+use promkit::style::StyleBuilder;
+use serde::{Deserialize, Serialize};
+
+/// Style written as its colors and attributes,
+/// e.g. `{ fg = "red", attributes = ["Bold", "Underlined"] }`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StyleDef {
+    /// Foreground color, e.g. "red", "dark_grey" or "#ff8800".
+    pub fg: Option<Color>,
+    /// Background color.
+    pub bg: Option<Color>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl StyleDef {
+    pub fn to_style(&self) -> ContentStyle {
+        let mut builder = StyleBuilder::new();
+        if let Some(fg) = self.fg {
+            builder = builder.fgc(fg);
+        }
+        if let Some(bg) = self.bg {
+            builder = builder.bgc(bg);
+        }
+        let mut style = builder.build();
+        for attribute in &self.attributes {
+            style.attributes.set(*attribute);
+        }
+        style
+    }
+}
@@ -0,0 +1,244 @@
+use crossterm::style::{Attribute, Attributes, Color, ContentStyle};
+use promkit::style::StyleBuilder;
+use serde::{Deserialize, Serialize};
+
+use super::StyleDef;
+
+/// Built-in set of styles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    /// For terminals with a dark background.
+    #[default]
+    Dark,
+    /// For terminals with a light background.
+    Light,
+    /// Colors of the Solarized palette, for its dark variant.
+    SolarizedDark,
+    /// Attributes only, without any color.
+    Monochrome,
+}
+
+/// Styles of the interface, from a built-in theme and the overrides of the configuration.
+#[derive(Clone)]
+pub struct Theme {
+    pub curly_brackets: ContentStyle,
+    pub square_brackets: ContentStyle,
+    pub key: ContentStyle,
+    pub string_value: ContentStyle,
+    pub number_value: ContentStyle,
+    pub boolean_value: ContentStyle,
+    pub null_value: ContentStyle,
+    pub prompt: ContentStyle,
+    pub cursor: ContentStyle,
+    pub matching_bracket: ContentStyle,
+    pub unmatched_bracket: ContentStyle,
+    pub suggestion: ContentStyle,
+    pub selected_suggestion: ContentStyle,
+    pub error_span: ContentStyle,
+    pub bookmark: ContentStyle,
+}
+
+fn fg(color: Color) -> ContentStyle {
+    StyleBuilder::new().fgc(color).build()
+}
+
+fn bg(color: Color) -> ContentStyle {
+    StyleBuilder::new().bgc(color).build()
+}
+
+fn attrs(attributes: &[Attribute]) -> ContentStyle {
+    StyleBuilder::new()
+        .attrs(
+            attributes
+                .iter()
+                .fold(Attributes::default(), |attrs, attr| attrs | *attr),
+        )
+        .build()
+}
+
+impl Theme {
+    pub fn builtin(name: ThemeName) -> Self {
+        let bold = attrs(&[Attribute::Bold]);
+        let error_span = ContentStyle {
+            foreground_color: Some(Color::Red),
+            ..attrs(&[Attribute::Bold, Attribute::Reverse])
+        };
+        match name {
+            ThemeName::Dark => Self {
+                curly_brackets: bold,
+                square_brackets: bold,
+                key: fg(Color::Cyan),
+                string_value: fg(Color::Green),
+                number_value: ContentStyle::default(),
+                boolean_value: ContentStyle::default(),
+                null_value: fg(Color::Grey),
+                prompt: fg(Color::Blue),
+                cursor: bg(Color::Magenta),
+                matching_bracket: ContentStyle {
+                    foreground_color: Some(Color::Yellow),
+                    ..bold
+                },
+                unmatched_bracket: ContentStyle {
+                    foreground_color: Some(Color::Red),
+                    ..bold
+                },
+                suggestion: fg(Color::Grey),
+                selected_suggestion: StyleBuilder::new()
+                    .fgc(Color::Grey)
+                    .bgc(Color::Yellow)
+                    .build(),
+                error_span,
+                bookmark: ContentStyle {
+                    foreground_color: Some(Color::Yellow),
+                    ..bold
+                },
+            },
+            ThemeName::Light => Self {
+                curly_brackets: bold,
+                square_brackets: bold,
+                key: fg(Color::DarkBlue),
+                string_value: fg(Color::DarkGreen),
+                number_value: fg(Color::DarkCyan),
+                boolean_value: fg(Color::DarkMagenta),
+                null_value: fg(Color::DarkGrey),
+                prompt: fg(Color::DarkBlue),
+                cursor: bg(Color::Cyan),
+                matching_bracket: ContentStyle {
+                    foreground_color: Some(Color::DarkYellow),
+                    ..bold
+                },
+                unmatched_bracket: ContentStyle {
+                    foreground_color: Some(Color::DarkRed),
+                    ..bold
+                },
+                suggestion: fg(Color::DarkGrey),
+                selected_suggestion: StyleBuilder::new()
+                    .fgc(Color::Black)
+                    .bgc(Color::Yellow)
+                    .build(),
+                error_span,
+                bookmark: ContentStyle {
+                    foreground_color: Some(Color::DarkYellow),
+                    ..bold
+                },
+            },
+            ThemeName::SolarizedDark => {
+                let rgb = |r, g, b| Color::Rgb { r, g, b };
+                Self {
+                    curly_brackets: ContentStyle {
+                        foreground_color: Some(rgb(0x93, 0xa1, 0xa1)),
+                        ..bold
+                    },
+                    square_brackets: ContentStyle {
+                        foreground_color: Some(rgb(0x93, 0xa1, 0xa1)),
+                        ..bold
+                    },
+                    key: fg(rgb(0x26, 0x8b, 0xd2)),
+                    string_value: fg(rgb(0x2a, 0xa1, 0x98)),
+                    number_value: fg(rgb(0xd3, 0x36, 0x82)),
+                    boolean_value: fg(rgb(0xb5, 0x89, 0x00)),
+                    null_value: fg(rgb(0x58, 0x6e, 0x75)),
+                    prompt: fg(rgb(0x26, 0x8b, 0xd2)),
+                    cursor: bg(rgb(0x6c, 0x71, 0xc4)),
+                    matching_bracket: ContentStyle {
+                        foreground_color: Some(rgb(0xb5, 0x89, 0x00)),
+                        ..bold
+                    },
+                    unmatched_bracket: ContentStyle {
+                        foreground_color: Some(rgb(0xdc, 0x32, 0x2f)),
+                        ..bold
+                    },
+                    suggestion: fg(rgb(0x83, 0x94, 0x96)),
+                    selected_suggestion: StyleBuilder::new()
+                        .fgc(rgb(0x00, 0x2b, 0x36))
+                        .bgc(rgb(0xb5, 0x89, 0x00))
+                        .build(),
+                    error_span: ContentStyle {
+                        foreground_color: Some(rgb(0xdc, 0x32, 0x2f)),
+                        ..error_span
+                    },
+                    bookmark: ContentStyle {
+                        foreground_color: Some(rgb(0xb5, 0x89, 0x00)),
+                        ..bold
+                    },
+                }
+            }
+            ThemeName::Monochrome => Self {
+                curly_brackets: bold,
+                square_brackets: bold,
+                key: bold,
+                string_value: ContentStyle::default(),
+                number_value: ContentStyle::default(),
+                boolean_value: ContentStyle::default(),
+                null_value: attrs(&[Attribute::Dim]),
+                prompt: bold,
+                cursor: attrs(&[Attribute::Reverse]),
+                matching_bracket: attrs(&[Attribute::Bold, Attribute::Underlined]),
+                unmatched_bracket: attrs(&[Attribute::Bold, Attribute::CrossedOut]),
+                suggestion: ContentStyle::default(),
+                selected_suggestion: attrs(&[Attribute::Reverse]),
+                error_span: attrs(&[Attribute::Bold, Attribute::Reverse]),
+                bookmark: bold,
+            },
+        }
+    }
+}
+
+/// Theme written in the configuration: a built-in theme and the styles overriding it,
+/// e.g. `key = { fg = "yellow", attributes = ["Bold"] }`.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub name: ThemeName,
+    pub curly_brackets: Option<StyleDef>,
+    pub square_brackets: Option<StyleDef>,
+    pub key: Option<StyleDef>,
+    pub string_value: Option<StyleDef>,
+    pub number_value: Option<StyleDef>,
+    pub boolean_value: Option<StyleDef>,
+    pub null_value: Option<StyleDef>,
+    /// Prompt of the editor.
+    pub prompt: Option<StyleDef>,
+    /// Character under the cursor in the editor.
+    pub cursor: Option<StyleDef>,
+    /// Bracket (or parenthesis) matching the one at or right before the cursor.
+    pub matching_bracket: Option<StyleDef>,
+    /// Bracket (or parenthesis) at or right before the cursor matching none.
+    pub unmatched_bracket: Option<StyleDef>,
+    pub suggestion: Option<StyleDef>,
+    pub selected_suggestion: Option<StyleDef>,
+    /// Part of the filter a parse error is about, in the guide.
+    pub error_span: Option<StyleDef>,
+    /// Marker of the bookmarked rows, in the gutter of the JSON viewer.
+    pub bookmark: Option<StyleDef>,
+}
+
+impl ThemeConfig {
+    /// Styles of the built-in theme `name` (the configured one if `None`),
+    /// overridden by those set in the configuration.
+    pub fn resolve(&self, name: Option<ThemeName>) -> Theme {
+        let theme = Theme::builtin(name.unwrap_or(self.name));
+        let style = |def: &Option<StyleDef>, builtin| match def {
+            Some(def) => def.to_style(),
+            None => builtin,
+        };
+        Theme {
+            curly_brackets: style(&self.curly_brackets, theme.curly_brackets),
+            square_brackets: style(&self.square_brackets, theme.square_brackets),
+            key: style(&self.key, theme.key),
+            string_value: style(&self.string_value, theme.string_value),
+            number_value: style(&self.number_value, theme.number_value),
+            boolean_value: style(&self.boolean_value, theme.boolean_value),
+            null_value: style(&self.null_value, theme.null_value),
+            prompt: style(&self.prompt, theme.prompt),
+            cursor: style(&self.cursor, theme.cursor),
+            matching_bracket: style(&self.matching_bracket, theme.matching_bracket),
+            unmatched_bracket: style(&self.unmatched_bracket, theme.unmatched_bracket),
+            suggestion: style(&self.suggestion, theme.suggestion),
+            selected_suggestion: style(&self.selected_suggestion, theme.selected_suggestion),
+            error_span: style(&self.error_span, theme.error_span),
+            bookmark: style(&self.bookmark, theme.bookmark),
+        }
+    }
+}
@@ -1,22 +1,95 @@
-use std::{future::Future, pin::Pin};
+use std::{
+    collections::HashMap,
+    future::Future,
+    ops::Range,
+    pin::Pin,
+    time::{Duration, Instant},
+};
 
 use crossterm::{
     event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers},
     style::{Color, ContentStyle},
 };
-use promkit::{pane::Pane, style::StyleBuilder, text, text_editor, PaneFactory};
+use promkit::{
+    grapheme::StyledGraphemes, pane::Pane, style::StyleBuilder, text, text_editor, PaneFactory,
+};
 
-use crate::search::IncrementalSearcher;
+use crate::{
+    clipboard::Clipboard,
+    config::{
+        event::{typed_char, EventDefSet, EventSeq},
+        CompletionAction, EditorAction, Keybinds,
+    },
+    history::HistoryFile,
+    registers::Registers,
+    search::IncrementalSearcher,
+    sequence::{SequenceMatcher, Step},
+};
 
 pub struct Editor {
     keybind: Keybind,
+    edit_matcher: SequenceMatcher<EditStep>,
+    completion_matcher: SequenceMatcher<CompletionAction>,
     state: text_editor::State,
+    /// Copy of the filter kept in sync with `state`,
+    /// so that reading it does not allocate on every event.
+    text: String,
+    /// Filter being typed before recalling the history, restored past its newest entry.
+    draft: Option<String>,
+    history_file: Option<HistoryFile>,
+    registers: Registers,
+    /// Digit of the last key pressed, giving the register to store into or recall.
+    last_digit: Option<u32>,
     focus_theme: EditorTheme,
     defocus_theme: EditorTheme,
     guide: text::State,
     searcher: IncrementalSearcher,
+    clipboard: Clipboard,
+    /// Step of an array index waiting for the length of the array.
+    index_step: Option<IndexStep>,
+    /// Templates of the snippets, by their index in `EditStep::Snippet`.
+    snippets: Vec<String>,
+    /// Styles of the brackets around the cursor, as long as it is shown.
+    bracket_styles: Option<BracketStyles>,
+}
+
+/// Increment (or decrement) of the array index nearest the cursor,
+/// wrapping around the array once its length is known.
+pub struct IndexStep {
+    /// Filter the step applies to, ignored once it is edited further.
+    text: String,
+    /// Filter producing the array, before the `[` of the index.
+    pub array: String,
+    /// Characters of the index in the filter.
+    range: Range<usize>,
+    value: i64,
+    delta: i64,
+}
+
+impl IndexStep {
+    /// Returns the index after the step, negative ones staying negative
+    /// (e.g. from -1 to -3 for an array of 3 elements).
+    fn next_value(&self, length: Option<usize>) -> i64 {
+        let next = self.value + self.delta;
+        match (length.map(|length| length as i64), self.value < 0) {
+            (Some(0), _) => self.value,
+            (Some(length), false) => next.rem_euclid(length),
+            (Some(length), true) => (next + length).rem_euclid(length) - length,
+            (None, false) => next.max(0),
+            (None, true) => next.min(-1),
+        }
+    }
+}
+
+/// Action of a keybind in the editor.
+#[derive(Clone, Copy)]
+enum EditStep {
+    Action(EditorAction),
+    /// Insert the snippet with this index.
+    Snippet(usize),
 }
 
+#[derive(Clone)]
 pub struct EditorTheme {
     pub prefix: String,
 
@@ -26,25 +99,82 @@ pub struct EditorTheme {
     pub active_char_style: ContentStyle,
     /// Style applied to characters that are not currently selected.
     pub inactive_char_style: ContentStyle,
+    /// Styles of the brackets around the cursor, if they are highlighted.
+    pub bracket_styles: Option<BracketStyles>,
+}
+
+/// Styles of the bracket (or parenthesis) matching the one at or right before the cursor,
+/// and of one matching none.
+#[derive(Clone, Copy)]
+pub struct BracketStyles {
+    pub matching: ContentStyle,
+    pub unmatched: ContentStyle,
+}
+
+/// How the editor looks whether it is focused or not,
+/// and the snippets inserted by their keybinds.
+pub struct EditorSettings {
+    pub focus_theme: EditorTheme,
+    pub defocus_theme: EditorTheme,
+    pub snippets: Vec<(EventSeq, String)>,
 }
 
 impl Editor {
     pub fn new(
-        state: text_editor::State,
+        mut state: text_editor::State,
         searcher: IncrementalSearcher,
-        focus_theme: EditorTheme,
-        defocus_theme: EditorTheme,
+        settings: &EditorSettings,
+        keybinds: &Keybinds,
+        mut history_file: Option<HistoryFile>,
+        registers: Registers,
+        clipboard: Clipboard,
     ) -> Self {
+        if let Some(file) = &mut history_file {
+            state.history = Some(file.load());
+        }
+        let text = state.texteditor.text_without_cursor().to_string();
+        let (snippet_sets, snippets): (Vec<_>, Vec<_>) = settings
+            .snippets
+            .iter()
+            .map(|(seq, template)| (EventDefSet(vec![seq.clone()]), template.clone()))
+            .unzip();
         Self {
             keybind: BOXED_EDITOR_KEYBIND,
+            edit_matcher: SequenceMatcher::new(
+                keybinds
+                    .on_editor
+                    .bindings()
+                    .into_iter()
+                    .map(|(set, action)| (set, EditStep::Action(action)))
+                    .chain(
+                        snippet_sets
+                            .iter()
+                            .enumerate()
+                            .map(|(i, set)| (set, EditStep::Snippet(i))),
+                    ),
+                keybinds.sequence_timeout,
+            ),
+            completion_matcher: SequenceMatcher::new(
+                keybinds.on_completion.bindings(),
+                keybinds.sequence_timeout,
+            ),
             state,
-            focus_theme,
-            defocus_theme,
+            text,
+            draft: None,
+            history_file,
+            registers,
+            last_digit: None,
             guide: text::State {
                 text: Default::default(),
                 style: Default::default(),
             },
             searcher,
+            clipboard,
+            index_step: None,
+            snippets,
+            bracket_styles: settings.focus_theme.bracket_styles,
+            focus_theme: settings.focus_theme.clone(),
+            defocus_theme: settings.defocus_theme.clone(),
         }
     }
 
@@ -53,6 +183,7 @@ impl Editor {
         self.state.prefix_style = self.focus_theme.prefix_style;
         self.state.inactive_char_style = self.focus_theme.inactive_char_style;
         self.state.active_char_style = self.focus_theme.active_char_style;
+        self.bracket_styles = self.focus_theme.bracket_styles;
     }
 
     pub fn defocus(&mut self) {
@@ -60,19 +191,171 @@ impl Editor {
         self.state.prefix_style = self.defocus_theme.prefix_style;
         self.state.inactive_char_style = self.defocus_theme.inactive_char_style;
         self.state.active_char_style = self.defocus_theme.active_char_style;
+        self.bracket_styles = self.defocus_theme.bracket_styles;
 
         self.searcher.leave_search();
         self.keybind = BOXED_EDITOR_KEYBIND;
+        self.edit_matcher.reset();
+        self.completion_matcher.reset();
 
         self.guide.text = Default::default();
     }
 
-    pub fn text(&self) -> String {
-        self.state.texteditor.text_without_cursor().to_string()
+    /// Clears the filter and leaves any completion to start over.
+    pub fn reset(&mut self) {
+        self.state.texteditor.erase_all();
+        self.sync_text();
+        self.searcher.leave_search();
+        self.keybind = BOXED_EDITOR_KEYBIND;
+        self.edit_matcher.reset();
+        self.completion_matcher.reset();
+
+        self.guide.text = "Reset the filter and the view".to_string();
+        self.guide.style = StyleBuilder::new().fgc(Color::Green).build();
+    }
+
+    /// Replaces the filter, its lines joined by spaces, leaving any completion.
+    pub fn set_text(&mut self, text: &str) {
+        replace_text(&mut self.state.texteditor, &one_line(text));
+        self.sync_text();
+        self.searcher.leave_search();
+        self.keybind = BOXED_EDITOR_KEYBIND;
+        self.edit_matcher.reset();
+        self.completion_matcher.reset();
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn sync_text(&mut self) {
+        self.text = self.state.texteditor.text_without_cursor().to_string();
+    }
+
+    /// Remembers the filter in the history, as it produced a result.
+    pub fn remember(&mut self, query: &str) {
+        if query.trim().is_empty() || self.is_recalling() {
+            return;
+        }
+        if let Some(history) = &mut self.state.history {
+            history.insert(query);
+            self.draft = None;
+        }
+    }
+
+    /// Saves the history for the next sessions once quitting,
+    /// giving up after `timeout` rather than holding up the exit.
+    pub async fn save_history(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        match (self.history_file.take(), self.state.history.clone()) {
+            (Some(file), Some(history)) => file.save_within(history, timeout).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Tells whether the filter is an entry of the history recalled as is.
+    fn is_recalling(&self) -> bool {
+        self.draft.is_some()
+            && self.state.history.as_ref().is_some_and(|history| {
+                history.get() == self.state.texteditor.text_without_cursor().to_string()
+            })
+    }
+
+    /// Takes the step of an array index requested by the last event, if any.
+    pub fn take_index_step(&mut self) -> Option<IndexStep> {
+        self.index_step.take()
+    }
+
+    /// Writes the array index after the step,
+    /// keeping the cursor where it is relative to the rest of the filter.
+    pub fn apply_index_step(&mut self, step: &IndexStep, length: Option<usize>) {
+        if self.text != step.text {
+            return;
+        }
+        let chars = step.text.chars().collect::<Vec<_>>();
+        let index = step.next_value(length).to_string();
+        let text = chars[..step.range.start]
+            .iter()
+            .chain(index.chars().collect::<Vec<_>>().iter())
+            .chain(chars[step.range.end..].iter())
+            .collect::<String>();
+
+        let position = self.state.texteditor.position();
+        let position = if position >= step.range.end {
+            position + index.len() - step.range.len()
+        } else {
+            position.min(step.range.start + index.len())
+        };
+        replace_text(&mut self.state.texteditor, &text);
+        for _ in position..text.chars().count() {
+            self.state.texteditor.backward();
+        }
+        self.sync_text();
+    }
+
+    /// Tells whether the suggestions are listed after a completion.
+    pub fn is_completing(&self) -> bool {
+        self.searcher.is_searching()
     }
 
+    /// Draws the filter as `text_editor::State` does,
+    /// highlighting the bracket matching the one at or right before the cursor.
     pub fn create_editor_pane(&self, width: u16, height: u16) -> Pane {
-        self.state.create_pane(width, height)
+        let Some(styles) = self.bracket_styles.filter(|_| self.state.mask.is_none()) else {
+            return self.state.create_pane(width, height);
+        };
+        let position = self.state.texteditor.position();
+        let mut text = self
+            .state
+            .texteditor
+            .text()
+            .apply_style(self.state.inactive_char_style)
+            .apply_style_at(position, self.state.active_char_style);
+
+        let chars = self.state.texteditor.text_without_cursor().chars();
+        let pairs = bracket_pairs(&chars);
+        let at_cursor = [position, position.wrapping_sub(1)]
+            .into_iter()
+            .find_map(|i| pairs.get(&i).map(|partner| (i, *partner)));
+        if let Some((bracket, partner)) = at_cursor {
+            let highlighted = match partner {
+                Some(partner) => vec![(bracket, styles.matching), (partner, styles.matching)],
+                None => vec![(bracket, styles.unmatched)],
+            };
+            for (i, style) in highlighted {
+                let style = if i == position {
+                    // Keep the cursor visible on the bracket.
+                    ContentStyle {
+                        foreground_color: style
+                            .foreground_color
+                            .or(self.state.active_char_style.foreground_color),
+                        background_color: self.state.active_char_style.background_color,
+                        underline_color: style.underline_color,
+                        attributes: style.attributes | self.state.active_char_style.attributes,
+                    }
+                } else {
+                    style
+                };
+                text = text.apply_style_at(i, style);
+            }
+        }
+
+        let mut buf = StyledGraphemes::from_str(&self.state.prefix, self.state.prefix_style);
+        let prefix_width = buf.widths();
+        buf.append(&mut text);
+        let height = match self.state.lines {
+            Some(lines) => lines.min(height as usize),
+            None => height as usize,
+        };
+        let (matrix, offset) = buf.matrixify(
+            width as usize,
+            height,
+            (prefix_width + position) / width as usize,
+        );
+        Pane::new(matrix, offset)
+    }
+
+    pub fn searcher(&self) -> &IncrementalSearcher {
+        &self.searcher
     }
 
     pub fn create_searcher_pane(&self, width: u16, height: u16) -> Pane {
@@ -86,6 +369,37 @@ impl Editor {
     pub async fn operate(&mut self, event: &Event) -> anyhow::Result<()> {
         (self.keybind)(event, self).await
     }
+
+    /// Returns the time at which a partially typed keybind gives up waiting.
+    pub fn sequence_deadline(&self) -> Option<Instant> {
+        self.completion_matcher
+            .deadline()
+            .or(self.edit_matcher.deadline())
+    }
+
+    /// Handles the events held for a partially typed keybind after its timeout.
+    pub fn flush_sequence(&mut self) {
+        self.guide.text = Default::default();
+        let steps = self.completion_matcher.flush();
+        let mut edited = apply_search_steps(steps, self);
+        let steps = self.edit_matcher.flush();
+        edited |= apply_edit_steps(steps, self);
+        if edited {
+            self.sync_text();
+        }
+        self.show_pending_sequence();
+    }
+
+    fn show_pending_sequence(&mut self) {
+        if let Some(text) = self
+            .completion_matcher
+            .pending_text()
+            .or(self.edit_matcher.pending_text())
+        {
+            self.guide.text = text;
+            self.guide.style = StyleBuilder::new().fgc(Color::Grey).build();
+        }
+    }
 }
 
 pub type Keybind = for<'a> fn(
@@ -105,209 +419,850 @@ const BOXED_SEARCHER_KEYBIND: Keybind =
 pub async fn edit<'a>(event: &'a Event, editor: &'a mut Editor) -> anyhow::Result<()> {
     editor.guide.text = Default::default();
 
-    match event {
-        Event::Key(KeyEvent {
-            code: KeyCode::Tab,
-            modifiers: KeyModifiers::NONE,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            let prefix = editor.state.texteditor.text_without_cursor().to_string();
-            match editor.searcher.start_search(&prefix) {
-                Ok(result) => match result.head_item {
-                    Some(head) => {
-                        if result.load_state.loaded {
-                            editor.guide.text = format!(
-                                "Loaded all ({}) suggestions",
-                                result.load_state.loaded_item_len
-                            );
-                            editor.guide.style = StyleBuilder::new().fgc(Color::Green).build();
-                        } else {
+    if let Event::Key(KeyEvent {
+        code,
+        kind: KeyEventKind::Press,
+        ..
+    }) = event
+    {
+        editor.last_digit = match code {
+            KeyCode::Char(ch) => ch.to_digit(10),
+            _ => None,
+        };
+    }
+    let steps = editor.edit_matcher.feed(event);
+    if apply_edit_steps(steps, editor) {
+        editor.sync_text();
+    }
+    editor.show_pending_sequence();
+    Ok(())
+}
+
+/// Returns whether the filter may have been modified.
+fn apply_edit_steps(steps: Vec<Step<EditStep>>, editor: &mut Editor) -> bool {
+    let mut edited = false;
+    for step in steps {
+        let step = match step {
+            Step::Action(EditStep::Snippet(i)) => {
+                insert_snippet(editor, i);
+                edited = true;
+                continue;
+            }
+            Step::Action(EditStep::Action(action)) => Step::Action(action),
+            Step::Event(event) => Step::Event(event),
+        };
+        match step {
+            Step::Action(EditorAction::Completion) => {
+                let prefix = editor.state.texteditor.text_without_cursor().to_string();
+                match editor.searcher.start_search(&prefix) {
+                    Ok(result) => match result.head_item {
+                        Some(head) => {
+                            if result.functions {
+                                editor.guide.text = "Suggesting jq functions".to_string();
+                                editor.guide.style = StyleBuilder::new().fgc(Color::Green).build();
+                            } else if result.variables {
+                                editor.guide.text =
+                                    "Suggesting the variables of the filters".to_string();
+                                editor.guide.style = StyleBuilder::new().fgc(Color::Green).build();
+                            } else if result.from_result {
+                                editor.guide.text =
+                                    "Suggesting the paths of the result before `|`".to_string();
+                                editor.guide.style = StyleBuilder::new().fgc(Color::Green).build();
+                            } else if result.load_state.loaded {
+                                editor.guide.text = format!(
+                                    "Loaded all ({}) suggestions",
+                                    result.load_state.loaded_item_len
+                                );
+                                editor.guide.style = StyleBuilder::new().fgc(Color::Green).build();
+                            } else {
+                                editor.guide.text = format!(
+                                    "Loaded partially ({}) suggestions",
+                                    result.load_state.loaded_item_len
+                                );
+                                editor.guide.style = StyleBuilder::new().fgc(Color::Green).build();
+                            }
+                            editor.state.texteditor.replace(&head);
+                            edited = true;
+                            editor.keybind = BOXED_SEARCHER_KEYBIND;
+                        }
+                        None if result.load_state.skipped
+                            && !result.functions
+                            && !result.variables =>
+                        {
                             editor.guide.text = format!(
-                                "Loaded partially ({}) suggestions",
-                                result.load_state.loaded_item_len
+                                "No suggestion found for '{}' (paths of the input are not suggested with --no-suggestions)",
+                                prefix
                             );
-                            editor.guide.style = StyleBuilder::new().fgc(Color::Green).build();
+                            editor.guide.style = StyleBuilder::new().fgc(Color::Yellow).build();
+                        }
+                        None => {
+                            editor.guide.text = format!("No suggestion found for '{}'", prefix);
+                            editor.guide.style = StyleBuilder::new().fgc(Color::Yellow).build();
+                        }
+                    },
+                    Err(e) => {
+                        editor.guide.text = format!("Failed to lookup suggestions: {}", e);
+                        editor.guide.style = StyleBuilder::new().fgc(Color::Yellow).build();
+                    }
+                }
+            }
+
+            // Recall the history.
+            Step::Action(EditorAction::HistoryPrevious) => {
+                let recalling = editor.is_recalling();
+                let text = editor.state.texteditor.text_without_cursor().to_string();
+                if let Some(history) = &mut editor.state.history {
+                    if !recalling {
+                        // Start over from the newest entry once the recalled one is edited.
+                        history.move_to_tail();
+                    }
+                    if history.backward() {
+                        if !recalling {
+                            editor.draft = Some(text);
                         }
-                        editor.state.texteditor.replace(&head);
-                        editor.keybind = BOXED_SEARCHER_KEYBIND;
+                        replace_text(&mut editor.state.texteditor, &history.get());
+                        edited = true;
+                    }
+                }
+            }
+            Step::Action(EditorAction::HistoryNext) => {
+                if editor.is_recalling() {
+                    if let Some(history) = &mut editor.state.history {
+                        if history.forward() {
+                            let entry = history.get();
+                            // The tail of the history is empty, standing for the draft.
+                            let text = if entry.is_empty() {
+                                editor.draft.take().unwrap_or_default()
+                            } else {
+                                entry
+                            };
+                            replace_text(&mut editor.state.texteditor, &text);
+                            edited = true;
+                        }
+                    }
+                }
+            }
+
+            // Move cursor.
+            Step::Action(EditorAction::Backward) => {
+                editor.state.texteditor.backward();
+            }
+            Step::Action(EditorAction::Forward) => {
+                editor.state.texteditor.forward();
+            }
+            Step::Action(EditorAction::MoveToHead) => {
+                editor.state.texteditor.move_to_head();
+            }
+            Step::Action(EditorAction::MoveToTail) => {
+                editor.state.texteditor.move_to_tail();
+            }
+
+            // Move cursor to the nearest character.
+            Step::Action(EditorAction::MoveToPreviousNearest) => {
+                editor
+                    .state
+                    .texteditor
+                    .move_to_previous_nearest(&editor.state.word_break_chars);
+            }
+            Step::Action(EditorAction::MoveToNextNearest) => {
+                editor
+                    .state
+                    .texteditor
+                    .move_to_next_nearest(&editor.state.word_break_chars);
+            }
+
+            // Erase char(s).
+            Step::Action(EditorAction::Erase) => {
+                editor.state.texteditor.erase();
+                edited = true;
+            }
+            Step::Action(EditorAction::EraseAll) => {
+                editor.state.texteditor.erase_all();
+                edited = true;
+            }
+
+            // Erase to the nearest character.
+            Step::Action(EditorAction::EraseToPreviousNearest) => {
+                editor
+                    .state
+                    .texteditor
+                    .erase_to_previous_nearest(&editor.state.word_break_chars);
+                edited = true;
+            }
+            Step::Action(EditorAction::EraseToNextNearest) => {
+                editor
+                    .state
+                    .texteditor
+                    .erase_to_next_nearest(&editor.state.word_break_chars);
+                edited = true;
+            }
+
+            // Step the array index.
+            Step::Action(EditorAction::StoreRegister) => {
+                let Some(register) = editor.last_digit else {
+                    continue;
+                };
+                let filter = editor.text.clone();
+                (editor.guide.text, editor.guide.style) =
+                    match editor.registers.set(register, &filter) {
+                        Ok(()) => (
+                            format!("Stored the filter in register {}", register),
+                            StyleBuilder::new().fgc(Color::Green).build(),
+                        ),
+                        Err(e) => (
+                            format!("Failed to save the registers: {}", e),
+                            StyleBuilder::new().fgc(Color::Red).build(),
+                        ),
+                    };
+            }
+            Step::Action(EditorAction::RecallRegister) => {
+                let Some(register) = editor.last_digit else {
+                    continue;
+                };
+                match editor.registers.get(register).map(str::to_string) {
+                    Some(filter) => {
+                        replace_text(&mut editor.state.texteditor, &filter);
+                        edited = true;
                     }
                     None => {
-                        editor.guide.text = format!("No suggestion found for '{}'", prefix);
+                        editor.guide.text = format!("Register {} is empty", register);
                         editor.guide.style = StyleBuilder::new().fgc(Color::Yellow).build();
                     }
-                },
+                }
+            }
+            Step::Action(EditorAction::ListRegisters) => {
+                editor.guide.text = editor
+                    .registers
+                    .describe()
+                    .unwrap_or_else(|| "No filter is stored in the registers".to_string());
+                editor.guide.style = StyleBuilder::new().fgc(Color::Grey).build();
+            }
+            Step::Action(EditorAction::IncrementIndex) => request_index_step(editor, 1),
+            Step::Action(EditorAction::DecrementIndex) => request_index_step(editor, -1),
+
+            // Paste text.
+            Step::Action(EditorAction::Paste) => match editor.clipboard.paste() {
+                Ok(text) => {
+                    insert_text(editor, &text);
+                    edited = true;
+                }
                 Err(e) => {
-                    editor.guide.text = format!("Failed to lookup suggestions: {}", e);
-                    editor.guide.style = StyleBuilder::new().fgc(Color::Yellow).build();
+                    editor.guide.text = format!("Failed to paste from clipboard: {}", e);
+                    editor.guide.style = StyleBuilder::new().fgc(Color::Red).build();
+                }
+            },
+            Step::Event(Event::Paste(text)) => {
+                insert_text(editor, &text);
+                edited = true;
+            }
+
+            // Input char.
+            Step::Event(Event::Key(KeyEvent {
+                code: KeyCode::Char(ch),
+                modifiers: modifiers @ (KeyModifiers::NONE | KeyModifiers::SHIFT),
+                kind: KeyEventKind::Press,
+                state: KeyEventState::NONE,
+            })) => {
+                let ch = typed_char(ch, modifiers);
+                match editor.state.edit_mode {
+                    text_editor::Mode::Insert => editor.state.texteditor.insert(ch),
+                    text_editor::Mode::Overwrite => editor.state.texteditor.overwrite(ch),
                 }
+                edited = true;
             }
+
+            Step::Event(_) => {}
         }
+    }
+    edited
+}
 
-        // Move cursor.
-        Event::Key(KeyEvent {
-            code: KeyCode::Left,
-            modifiers: KeyModifiers::NONE,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor.state.texteditor.backward();
+/// Asks for the array index nearest the cursor to be stepped by `delta`,
+/// once the length of the array is known.
+fn request_index_step(editor: &mut Editor, delta: i64) {
+    let chars = editor.state.texteditor.text_without_cursor().chars();
+    let position = editor.state.texteditor.position();
+    match nearest_array_index(&chars, position) {
+        Some((range, value)) => {
+            // The array is produced by the filter up to the `[`.
+            let array = chars[..range.start - 1].iter().collect::<String>();
+            editor.index_step = Some(IndexStep {
+                text: editor.text.clone(),
+                array,
+                range,
+                value,
+                delta,
+            });
+        }
+        None => {
+            editor.guide.text = "No array index around the cursor".to_string();
+            editor.guide.style = StyleBuilder::new().fgc(Color::Yellow).build();
         }
-        Event::Key(KeyEvent {
-            code: KeyCode::Right,
-            modifiers: KeyModifiers::NONE,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor.state.texteditor.forward();
-        }
-        Event::Key(KeyEvent {
-            code: KeyCode::Char('a'),
-            modifiers: KeyModifiers::CONTROL,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor.state.texteditor.move_to_head();
-        }
-        Event::Key(KeyEvent {
-            code: KeyCode::Char('e'),
-            modifiers: KeyModifiers::CONTROL,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor.state.texteditor.move_to_tail();
-        }
-
-        // Move cursor to the nearest character.
-        Event::Key(KeyEvent {
-            code: KeyCode::Char('b'),
-            modifiers: KeyModifiers::ALT,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor
-                .state
-                .texteditor
-                .move_to_previous_nearest(&editor.state.word_break_chars);
-        }
-
-        Event::Key(KeyEvent {
-            code: KeyCode::Char('f'),
-            modifiers: KeyModifiers::ALT,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor
-                .state
-                .texteditor
-                .move_to_next_nearest(&editor.state.word_break_chars);
-        }
-
-        // Erase char(s).
-        Event::Key(KeyEvent {
-            code: KeyCode::Backspace,
-            modifiers: KeyModifiers::NONE,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor.state.texteditor.erase();
-        }
-        Event::Key(KeyEvent {
-            code: KeyCode::Char('u'),
-            modifiers: KeyModifiers::CONTROL,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor.state.texteditor.erase_all();
-        }
-
-        // Erase to the nearest character.
-        Event::Key(KeyEvent {
-            code: KeyCode::Char('w'),
-            modifiers: KeyModifiers::CONTROL,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor
-                .state
-                .texteditor
-                .erase_to_previous_nearest(&editor.state.word_break_chars);
-        }
-
-        Event::Key(KeyEvent {
-            code: KeyCode::Char('d'),
-            modifiers: KeyModifiers::ALT,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor
-                .state
-                .texteditor
-                .erase_to_next_nearest(&editor.state.word_break_chars);
-        }
-
-        // Input char.
-        Event::Key(KeyEvent {
-            code: KeyCode::Char(ch),
-            modifiers: KeyModifiers::NONE,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        })
-        | Event::Key(KeyEvent {
-            code: KeyCode::Char(ch),
-            modifiers: KeyModifiers::SHIFT,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => match editor.state.edit_mode {
-            text_editor::Mode::Insert => editor.state.texteditor.insert(*ch),
-            text_editor::Mode::Overwrite => editor.state.texteditor.overwrite(*ch),
-        },
-
-        _ => {}
     }
-    Ok(())
 }
 
-pub async fn search<'a>(event: &'a Event, editor: &'a mut Editor) -> anyhow::Result<()> {
-    match event {
-        Event::Key(KeyEvent {
-            code: KeyCode::Tab,
-            modifiers: KeyModifiers::NONE,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        })
-        | Event::Key(KeyEvent {
-            code: KeyCode::Down,
-            modifiers: KeyModifiers::NONE,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor.searcher.down_with_load();
-            editor
-                .state
-                .texteditor
-                .replace(&editor.searcher.get_current_item());
-        }
-
-        Event::Key(KeyEvent {
-            code: KeyCode::Up,
-            modifiers: KeyModifiers::NONE,
-            kind: KeyEventKind::Press,
-            state: KeyEventState::NONE,
-        }) => {
-            editor.searcher.up();
-            editor
-                .state
-                .texteditor
-                .replace(&editor.searcher.get_current_item());
-        }
-
-        _ => {
-            editor.searcher.leave_search();
-            editor.keybind = BOXED_EDITOR_KEYBIND;
-            return edit(event, editor).await;
+/// Returns the characters of the array index (e.g. `-1` of `.[-1]`, out of the strings)
+/// around the cursor, or the nearest one, with its value.
+fn nearest_array_index(chars: &[char], position: usize) -> Option<(Range<usize>, i64)> {
+    let mut indices = Vec::new();
+    let mut quoted = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if quoted => i += 1,
+            '"' => quoted = !quoted,
+            '[' if !quoted => {
+                let start = i + 1;
+                let mut end = start + usize::from(chars.get(start) == Some(&'-'));
+                while chars.get(end).is_some_and(char::is_ascii_digit) {
+                    end += 1;
+                }
+                if chars.get(end) == Some(&']') {
+                    let digits = chars[start..end].iter().collect::<String>();
+                    if let Ok(value) = digits.parse::<i64>() {
+                        indices.push((start..end, value));
+                    }
+                }
+            }
+            _ => {}
         }
+        i += 1;
     }
+    // Distance from the cursor to the brackets around the index.
+    indices.into_iter().min_by_key(|(range, _)| {
+        if position + 1 < range.start {
+            range.start - 1 - position
+        } else {
+            position.saturating_sub(range.end + 1)
+        }
+    })
+}
+
+/// Returns the brackets and parentheses of the filter (out of its strings and comments)
+/// by index, with the index of the one matching each.
+fn bracket_pairs(chars: &[char]) -> HashMap<usize, Option<usize>> {
+    let mut ret = HashMap::new();
+    // Brackets opened, with whether each opens an interpolation (`\(` in a string).
+    let mut opened: Vec<(char, usize, bool)> = Vec::new();
+    let mut quoted = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        if quoted {
+            match ch {
+                '\\' if chars.get(i + 1) == Some(&'(') => {
+                    opened.push(('(', i + 1, true));
+                    quoted = false;
+                    i += 1;
+                }
+                '\\' => i += 1,
+                '"' => quoted = false,
+                _ => {}
+            }
+        } else {
+            match ch {
+                '"' => quoted = true,
+                '#' => {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                }
+                '(' | '[' => opened.push((ch, i, false)),
+                ')' | ']' => match opened.last() {
+                    Some(&(open, j, interpolation)) if (open == '(') == (ch == ')') => {
+                        opened.pop();
+                        ret.insert(j, Some(i));
+                        ret.insert(i, Some(j));
+                        quoted = interpolation;
+                    }
+                    _ => {
+                        ret.insert(i, None);
+                    }
+                },
+                _ => {}
+            }
+        }
+        i += 1;
+    }
+    for (_, j, _) in opened {
+        ret.insert(j, None);
+    }
+    ret
+}
 
+/// Inserts the snippet at the cursor, putting the cursor at its `$0`.
+///
+/// The snippet is inserted in the overwrite mode too,
+/// rather than typed over the rest of the filter.
+fn insert_snippet(editor: &mut Editor, i: usize) {
+    let Some(template) = editor.snippets.get(i) else {
+        return;
+    };
+    let (before, after) = template.split_once("$0").unwrap_or((template, ""));
+    let chars = before.chars().chain(after.chars()).collect::<Vec<_>>();
+    editor.state.texteditor.insert_chars(&chars);
+    for _ in after.chars() {
+        editor.state.texteditor.backward();
+    }
+}
+
+/// Joins the lines of the text by spaces, since the filter is edited on a single line
+/// (and remembered as one line of the history).
+fn one_line(text: &str) -> String {
+    text.lines().collect::<Vec<_>>().join(" ")
+}
+
+/// Inserts the text at the cursor, its lines joined by spaces.
+fn insert_text(editor: &mut Editor, text: &str) {
+    let chars = one_line(text).chars().collect::<Vec<_>>();
+    match editor.state.edit_mode {
+        text_editor::Mode::Insert => editor.state.texteditor.insert_chars(&chars),
+        text_editor::Mode::Overwrite => editor.state.texteditor.overwrite_chars(&chars),
+    }
+}
+
+/// Replaces the text, putting the cursor at its end.
+///
+/// `TextEditor::replace` puts the cursor at the byte length of the text,
+/// which is off for non-ASCII characters.
+fn replace_text(texteditor: &mut text_editor::TextEditor, text: &str) {
+    texteditor.erase_all();
+    for ch in text.chars() {
+        texteditor.insert(ch);
+    }
+}
+
+pub async fn search<'a>(event: &'a Event, editor: &'a mut Editor) -> anyhow::Result<()> {
+    let steps = editor.completion_matcher.feed(event);
+    if apply_search_steps(steps, editor) {
+        editor.sync_text();
+    }
+    editor.show_pending_sequence();
     Ok(())
 }
+
+/// Returns whether the filter may have been modified.
+fn apply_search_steps(steps: Vec<Step<CompletionAction>>, editor: &mut Editor) -> bool {
+    let mut left = false;
+    let mut edited = false;
+    for step in steps {
+        match step {
+            Step::Action(_) if left => {}
+
+            Step::Action(CompletionAction::Down) => {
+                editor.searcher.down_with_load();
+                editor
+                    .state
+                    .texteditor
+                    .replace(&editor.searcher.get_current_item());
+                edited = true;
+            }
+
+            Step::Action(CompletionAction::Up) => {
+                editor.searcher.up();
+                editor
+                    .state
+                    .texteditor
+                    .replace(&editor.searcher.get_current_item());
+                edited = true;
+            }
+
+            Step::Action(CompletionAction::AcceptSegment) => {
+                match editor.searcher.accept_segment() {
+                    Ok(prefix) => {
+                        editor.state.texteditor.replace(&prefix);
+                        edited = true;
+                        if !editor.searcher.is_searching() {
+                            editor.keybind = BOXED_EDITOR_KEYBIND;
+                            editor.guide.text = Default::default();
+                            left = true;
+                        }
+                    }
+                    Err(e) => {
+                        editor.guide.text = format!("Failed to lookup suggestions: {}", e);
+                        editor.guide.style = StyleBuilder::new().fgc(Color::Yellow).build();
+                    }
+                }
+            }
+
+            Step::Action(CompletionAction::Cancel) => {
+                if let Some(original) = editor.searcher.cancel_search() {
+                    editor.state.texteditor.replace(&original);
+                    edited = true;
+                }
+                editor.keybind = BOXED_EDITOR_KEYBIND;
+                editor.guide.text = Default::default();
+                left = true;
+            }
+
+            // Other events return to the editor and are handled there.
+            Step::Event(event) => {
+                if !left {
+                    editor.searcher.leave_search();
+                    editor.keybind = BOXED_EDITOR_KEYBIND;
+                    editor.guide.text = Default::default();
+                    left = true;
+                }
+                let steps = editor.edit_matcher.feed(&event);
+                edited |= apply_edit_steps(steps, editor);
+            }
+        }
+    }
+    edited
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        alloc::{self, GlobalAlloc, System},
+        cell::Cell,
+        collections::HashSet,
+        fs,
+        time::Duration,
+    };
+
+    use promkit::{
+        listbox::{self, Listbox},
+        serde_json::json,
+    };
+
+    use super::*;
+    use crate::{
+        config::{HistoryConfig, RegistersConfig},
+        history::SAVE_TIMEOUT,
+        paths::{self, PathMatching, PathOrder},
+        safe::SafeMode,
+        search::SearchSettings,
+    };
+
+    /// Allocator counting the allocations made on each thread.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout, new_size: usize) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    pub(crate) fn allocations() -> usize {
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    fn theme() -> EditorTheme {
+        EditorTheme {
+            prefix: "❯❯ ".to_string(),
+            prefix_style: ContentStyle::default(),
+            active_char_style: ContentStyle::default(),
+            inactive_char_style: ContentStyle::default(),
+            bracket_styles: None,
+        }
+    }
+
+    /// Returns an editor suggesting no path, with nothing written outside.
+    pub(crate) fn editor() -> Editor {
+        editor_with(None, SafeMode::new(true))
+    }
+
+    fn editor_with(history_file: Option<HistoryFile>, safe_mode: SafeMode) -> Editor {
+        Editor::new(
+            text_editor::State {
+                texteditor: Default::default(),
+                history: Some(Default::default()),
+                prefix: "❯❯ ".to_string(),
+                mask: Default::default(),
+                prefix_style: ContentStyle::default(),
+                active_char_style: ContentStyle::default(),
+                inactive_char_style: ContentStyle::default(),
+                edit_mode: text_editor::Mode::Insert,
+                word_break_chars: HashSet::from(['.', '|']),
+                lines: Default::default(),
+            },
+            IncrementalSearcher::new(
+                listbox::State {
+                    listbox: Listbox::from_displayable(Vec::<String>::new()),
+                    cursor: String::from("❯ "),
+                    active_item_style: None,
+                    inactive_item_style: None,
+                    lines: Some(3),
+                },
+                &SearchSettings {
+                    result_chunk_size: 100,
+                    load_chunk_size: 100,
+                    load_throttle: Duration::ZERO,
+                    order: PathOrder::Depth,
+                    matching: PathMatching::Prefix,
+                    case_insensitive: false,
+                    array_indices: 3,
+                    result_paths: false,
+                    index_input: false,
+                    variables: Vec::new(),
+                },
+            ),
+            &EditorSettings {
+                focus_theme: theme(),
+                defocus_theme: theme(),
+                snippets: Vec::new(),
+            },
+            &Keybinds::default(),
+            history_file,
+            Registers::new(&RegistersConfig::default(), safe_mode),
+            Clipboard::spawn(safe_mode),
+        )
+    }
+
+    pub(crate) fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[tokio::test]
+    async fn multi_line_edit_is_one_entry_of_the_history() {
+        let dir = std::env::temp_dir().join(format!("jnv-editor-{}", std::process::id()));
+        let config = HistoryConfig {
+            path: Some(dir.join("history")),
+            max_entries: 10,
+        };
+        let history_file = || HistoryFile::new(&config, SafeMode::new(false));
+
+        let mut editor = editor_with(history_file(), SafeMode::new(false));
+        // As saved by the external editor.
+        editor.set_text(".items[]\n| select(.id > 1)\r\n| .name");
+        assert_eq!(editor.text(), ".items[] | select(.id > 1) | .name");
+        let query = editor.text().to_string();
+        editor.remember(&query);
+        editor.save_history(SAVE_TIMEOUT).await.unwrap();
+
+        let mut history = editor_with(history_file(), SafeMode::new(false))
+            .state
+            .history
+            .unwrap();
+        assert!(history.backward());
+        assert_eq!(history.get(), ".items[] | select(.id > 1) | .name");
+        assert!(!history.backward());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Allocations made while handling a key.
+    struct Counted {
+        total: usize,
+        /// Made to tell whether the filter changed.
+        diff_check: usize,
+    }
+
+    /// Handles the key as the editor task of the prompt does, counting the allocations.
+    async fn keystroke(editor: &mut Editor, last_text: &mut String, event: &Event) -> Counted {
+        let before = allocations();
+        editor.operate(event).await.unwrap();
+        let checking = allocations();
+        let changed = editor.text() != last_text;
+        let diff_check = allocations() - checking;
+        if changed {
+            last_text.clear();
+            last_text.push_str(editor.text());
+        }
+        let panes = (
+            editor.create_editor_pane(80, 24),
+            editor.create_guide_pane(80, 24),
+            editor.create_searcher_pane(80, 24),
+        );
+        let total = allocations() - before;
+        drop(panes);
+        Counted { total, diff_check }
+    }
+
+    #[tokio::test]
+    async fn keystroke_allocations() {
+        let mut editor = editor();
+        let mut last_text = String::with_capacity(64);
+        for ch in ".items".chars() {
+            keystroke(&mut editor, &mut last_text, &key(KeyCode::Char(ch))).await;
+        }
+
+        let typed = keystroke(&mut editor, &mut last_text, &key(KeyCode::Char('x'))).await;
+        let moved = keystroke(&mut editor, &mut last_text, &key(KeyCode::Left)).await;
+        assert_eq!(last_text, ".itemsx");
+        // The filter is compared without being copied,
+        // and only copied once it changes.
+        assert_eq!((typed.diff_check, moved.diff_check), (0, 0));
+        assert!(moved.total < typed.total, "{} {}", moved.total, typed.total);
+        // Budget of the whole path, to notice allocations creeping back in.
+        assert!(typed.total <= 16, "{}", typed.total);
+
+        let before = allocations();
+        for _ in 0..100 {
+            assert_eq!(editor.text(), ".itemsx");
+        }
+        assert_eq!(allocations() - before, 0);
+    }
+
+    /// Time taken by the keystroke path, to compare changes to it:
+    /// `cargo test --release keystroke_benchmark -- --ignored --nocapture`.
+    #[tokio::test]
+    #[ignore]
+    async fn keystroke_benchmark() {
+        const KEYSTROKES: usize = 100_000;
+        let mut editor = editor();
+        let mut last_text = String::new();
+        for ch in ".items[].metadata.name".chars() {
+            keystroke(&mut editor, &mut last_text, &key(KeyCode::Char(ch))).await;
+        }
+        let events = [key(KeyCode::Char('x')), key(KeyCode::Backspace)];
+
+        let started = Instant::now();
+        let mut allocated = 0;
+        for i in 0..KEYSTROKES {
+            allocated += keystroke(&mut editor, &mut last_text, &events[i % 2])
+                .await
+                .total;
+        }
+        let elapsed = started.elapsed();
+        println!(
+            "{:?} and {} allocations per keystroke",
+            elapsed / KEYSTROKES as u32,
+            allocated / KEYSTROKES
+        );
+    }
+
+    #[tokio::test]
+    async fn escape_restores_the_filter_from_before_the_completion() {
+        let mut editor = editor();
+        {
+            let index = editor.searcher().shared_index();
+            let mut index = index.lock().await;
+            for path in paths::get_all_paths([&json!({"items": 1, "id": 2})]) {
+                index.insert(path);
+            }
+        }
+        editor.set_text(".i");
+
+        let mut completed = Vec::new();
+        for _ in 0..2 {
+            editor.operate(&key(KeyCode::Tab)).await.unwrap();
+            completed.push(editor.text().to_string());
+        }
+        completed.sort();
+        assert_eq!(completed, [".id", ".items"]);
+        editor.operate(&key(KeyCode::Esc)).await.unwrap();
+        assert_eq!(editor.text(), ".i");
+
+        // Back in the editor, the keys edit the filter again.
+        editor.operate(&key(KeyCode::Char('d'))).await.unwrap();
+        assert_eq!(editor.text(), ".id");
+    }
+
+    /// Steps the array index nearest the cursor at `position` in `text`,
+    /// for an array of the given length, returning the filter and the cursor after it.
+    async fn stepped(
+        text: &str,
+        position: usize,
+        up: bool,
+        length: Option<usize>,
+    ) -> Option<(String, usize)> {
+        let mut editor = editor();
+        editor.set_text(text);
+        for _ in position..text.chars().count() {
+            editor.state.texteditor.backward();
+        }
+        let code = if up { KeyCode::Up } else { KeyCode::Down };
+        editor
+            .operate(&Event::Key(KeyEvent::new(code, KeyModifiers::CONTROL)))
+            .await
+            .unwrap();
+        let step = editor.take_index_step()?;
+        editor.apply_index_step(&step, length);
+        Some((
+            editor.text().to_string(),
+            editor.state.texteditor.position(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn steps_the_index_wherever_the_cursor_is() {
+        let text = ".items[1].name";
+        // Before, on and after the index.
+        for (position, expected) in [(0, 0), (7, 7), (8, 8), (14, 14)] {
+            assert_eq!(
+                stepped(text, position, true, Some(3)).await,
+                Some((".items[2].name".to_string(), expected)),
+                "cursor at {}",
+                position
+            );
+        }
+        // The cursor after the index follows it as it gets longer (or shorter).
+        assert_eq!(
+            stepped(".a[9] | .b", 10, true, Some(20)).await,
+            Some((".a[10] | .b".to_string(), 11))
+        );
+        assert_eq!(
+            stepped(".a[10] | .b", 11, false, Some(20)).await,
+            Some((".a[9] | .b".to_string(), 10))
+        );
+    }
+
+    #[tokio::test]
+    async fn wraps_the_index_around_the_array() {
+        for (text, up, length, expected) in [
+            (".a[0]", false, Some(3), ".a[2]"),
+            (".a[2]", true, Some(3), ".a[0]"),
+            (".a[0]", false, None, ".a[0]"),
+            (".a[7]", true, None, ".a[8]"),
+            // Negative indices stay negative.
+            (".a[-1]", true, Some(3), ".a[-3]"),
+            (".a[-3]", false, Some(3), ".a[-1]"),
+            (".a[-1]", true, None, ".a[-1]"),
+            (".a[-1]", false, None, ".a[-2]"),
+            // Nothing to step through.
+            (".a[1]", true, Some(0), ".a[1]"),
+        ] {
+            let stepped = stepped(text, 0, up, length).await.map(|(text, _)| text);
+            assert_eq!(
+                stepped.as_deref(),
+                Some(expected),
+                "{} {} of {:?}",
+                text,
+                if up { "up" } else { "down" },
+                length
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn steps_the_index_nearest_the_cursor() {
+        let text = r#".a[0]."b[1]"[5] | .c[3]"#;
+        for (position, expected) in [
+            (0, r#".a[1]."b[1]"[5] | .c[3]"#),
+            // Out of the string, whose brackets are no index.
+            (10, r#".a[0]."b[1]"[6] | .c[3]"#),
+            (15, r#".a[0]."b[1]"[6] | .c[3]"#),
+            (24, r#".a[0]."b[1]"[5] | .c[4]"#),
+        ] {
+            assert_eq!(
+                stepped(text, position, true, Some(10))
+                    .await
+                    .map(|(text, _)| text)
+                    .as_deref(),
+                Some(expected),
+                "cursor at {}",
+                position
+            );
+        }
+        assert_eq!(stepped(".a.b", 2, true, Some(10)).await, None);
+        assert_eq!(stepped(r#"."[1]""#, 2, true, Some(10)).await, None);
+    }
+}
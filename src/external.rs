@@ -0,0 +1,117 @@
+//! Editing of the filter in the user's editor (`$VISUAL` or `$EDITOR`),
+//! more comfortable than the single line of the prompt for long filters.
+
+use std::{
+    collections::hash_map::RandomState,
+    fs::{self, File, OpenOptions},
+    hash::BuildHasher,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::SystemTime,
+};
+
+use crate::safe::SafeMode;
+
+/// Returns the command of the user's editor, `$VISUAL` taking precedence over `$EDITOR`.
+pub fn editor_command() -> Option<String> {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|command| !command.trim().is_empty())
+}
+
+/// Opens the text in the editor through a temporary file,
+/// returning it as saved once the editor exits.
+///
+/// The command may carry arguments (e.g. `code --wait`), the file being given last.
+/// The terminal must be handed over to the editor beforehand.
+pub async fn edit(command: &str, text: &str, safe_mode: SafeMode) -> anyhow::Result<String> {
+    let path = temp_path();
+    writeln!(create_new(&path, safe_mode)?, "{}", text)?;
+    let edited = match run(command, &path, safe_mode).await {
+        Ok(()) => fs::read_to_string(&path).map_err(anyhow::Error::from),
+        Err(e) => Err(e),
+    };
+    let _ = fs::remove_file(&path);
+    // Editors end the file with a newline, which is not part of the filter.
+    Ok(edited?.trim_end_matches(['\n', '\r']).to_string())
+}
+
+async fn run(command: &str, path: &Path, safe_mode: SafeMode) -> anyhow::Result<()> {
+    let mut words = command.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("the editor command is empty"))?;
+    let status = safe_mode
+        .command(program)?
+        .args(words)
+        .arg(path)
+        .stdin(keyboard()?)
+        .status()
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to run {}: {}", program, e))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("{} exited with {}", program, status));
+    }
+    Ok(())
+}
+
+/// Returns the device the editor reads the keys from:
+/// standard input, unless the input was piped through it.
+fn keyboard() -> io::Result<Stdio> {
+    if io::stdin().is_terminal() {
+        return Ok(Stdio::inherit());
+    }
+    #[cfg(unix)]
+    let terminal = "/dev/tty";
+    #[cfg(windows)]
+    let terminal = "CONIN$";
+    Ok(File::open(terminal)?.into())
+}
+
+/// Returns a path for the temporary file, with an extension
+/// that lets editors highlight the filter as jq.
+///
+/// The name is random so that it cannot be guessed beforehand.
+fn temp_path() -> PathBuf {
+    let random = RandomState::new().hash_one((std::process::id(), SystemTime::now()));
+    std::env::temp_dir().join(format!("jnv-{:016x}.jq", random))
+}
+
+/// Creates the temporary file, readable by the user only,
+/// failing if anything (e.g. a symbolic link planted by another user) is there already.
+fn create_new(path: &Path, safe_mode: SafeMode) -> anyhow::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    safe_mode.open_file(path, &options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_paths_differ() {
+        let (a, b) = (temp_path(), temp_path());
+        assert_ne!(a, b);
+        assert_eq!(a.extension().and_then(|ext| ext.to_str()), Some("jq"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn planted_link_is_not_followed() {
+        let dir = std::env::temp_dir().join(format!("jnv-external-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("target");
+        fs::write(&target, "precious").unwrap();
+        let path = dir.join("planted.jq");
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+
+        assert!(create_new(&path, SafeMode::new(false)).is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "precious");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
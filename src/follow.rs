@@ -0,0 +1,61 @@
+//! Following of a file growing with JSON Lines (e.g. service logs).
+
+use std::{io::SeekFrom, path::PathBuf, time::Duration};
+
+use promkit::serde_json;
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+/// Interval between the checks for appended lines.
+///
+/// Longer than the debounce of the query,
+/// so that the refreshes are not postponed forever by a busy file.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches the file from `offset` on, sending the documents of every complete line appended.
+///
+/// Lines not holding a JSON document are skipped, and the file is read again
+/// from the start if it shrinks (e.g. when it is truncated by a log rotation).
+pub fn spawn_follow_task(
+    path: PathBuf,
+    mut offset: u64,
+    documents_tx: mpsc::Sender<Vec<serde_json::Value>>,
+) -> JoinHandle<anyhow::Result<()>> {
+    tokio::spawn(async move {
+        let mut file = File::open(&path).await?;
+        // Bytes of the line being written.
+        let mut partial = Vec::new();
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let len = file.metadata().await?.len();
+            if len < offset {
+                offset = 0;
+                partial.clear();
+            }
+            if len == offset {
+                continue;
+            }
+            file.seek(SeekFrom::Start(offset)).await?;
+            let read = file.read_to_end(&mut partial).await?;
+            offset += read as u64;
+
+            let Some(end) = partial.iter().rposition(|b| *b == b'\n') else {
+                continue;
+            };
+            let documents = partial[..end]
+                .split(|b| *b == b'\n')
+                .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+                .filter_map(|line| serde_json::from_slice(line).ok())
+                .collect::<Vec<_>>();
+            partial.drain(..=end);
+            if !documents.is_empty() && documents_tx.send(documents).await.is_err() {
+                return Ok(());
+            }
+        }
+    })
+}
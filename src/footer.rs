@@ -0,0 +1,165 @@
+use promkit::{
+    crossterm::style::{Attribute, Attributes},
+    pane::Pane,
+    style::StyleBuilder,
+    text, PaneFactory,
+};
+
+use crate::config::{event::EventDefSet, Keybinds};
+
+/// Situation deciding which keys the footer lists.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FooterContext {
+    /// The editor is focused without any filter typed yet.
+    EmptyEditor,
+    Editor,
+    /// The suggestions are listed after a completion.
+    Completion,
+    Processor,
+}
+
+impl FooterContext {
+    /// Returns the context given the pane focused and the state of the editor.
+    pub fn select(editor_focused: bool, completing: bool, empty: bool) -> Self {
+        if !editor_focused {
+            FooterContext::Processor
+        } else if completing {
+            FooterContext::Completion
+        } else if empty {
+            FooterContext::EmptyEditor
+        } else {
+            FooterContext::Editor
+        }
+    }
+}
+
+/// One-line quick reference of the keys most relevant to the current context,
+/// following the configured keybinds.
+#[derive(Clone)]
+pub struct Footer {
+    empty_editor: String,
+    editor: String,
+    completion: String,
+    processor: String,
+}
+
+impl Footer {
+    pub fn new(keybinds: &Keybinds) -> Self {
+        let global = &keybinds.global;
+        Self {
+            empty_editor: describe(&[
+                (&keybinds.on_editor.completion, "complete"),
+                (&global.switch_mode, "focus result"),
+                (&global.help, "help"),
+                (&global.exit, "quit"),
+            ]),
+            editor: describe(&[
+                (&keybinds.on_editor.completion, "complete"),
+                (&global.copy_query, "copy query"),
+                (&global.copy_result, "copy result"),
+            ]),
+            completion: describe(&[
+                (&keybinds.on_completion.down, "next"),
+                (&keybinds.on_completion.up, "previous"),
+                (&keybinds.on_completion.cancel, "cancel"),
+                (&keybinds.on_completion.accept_segment, "descend"),
+                (&global.exit, "quit"),
+            ]),
+            processor: describe(&[
+                (&keybinds.on_json_viewer.toggle, "fold"),
+                (&global.copy_result, "copy result"),
+                (&global.switch_mode, "focus filter"),
+            ]),
+        }
+    }
+
+    /// Returns the keys listed in the context.
+    fn text(&self, context: FooterContext) -> &str {
+        match context {
+            FooterContext::EmptyEditor => &self.empty_editor,
+            FooterContext::Editor => &self.editor,
+            FooterContext::Completion => &self.completion,
+            FooterContext::Processor => &self.processor,
+        }
+    }
+
+    pub fn create_pane(&self, context: FooterContext, width: u16, height: u16) -> Pane {
+        text::State {
+            text: self.text(context).to_string(),
+            style: StyleBuilder::new()
+                .attrs(Attributes::from(Attribute::Dim))
+                .build(),
+        }
+        .create_pane(width, height)
+    }
+}
+
+/// Lists each action with the first of its keybinds, skipping unbound actions.
+fn describe(entries: &[(&EventDefSet, &str)]) -> String {
+    entries
+        .iter()
+        .filter_map(|(set, name)| set.iter().next().map(|seq| format!("{}: {}", seq, name)))
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_by_focus_and_editor_state() {
+        for (editor_focused, completing, empty, expected) in [
+            (true, false, true, FooterContext::EmptyEditor),
+            (true, false, false, FooterContext::Editor),
+            (true, true, false, FooterContext::Completion),
+            (true, true, true, FooterContext::Completion),
+            (false, false, true, FooterContext::Processor),
+            (false, true, false, FooterContext::Processor),
+        ] {
+            assert!(
+                FooterContext::select(editor_focused, completing, empty) == expected,
+                "focused {}, completing {}, empty {}",
+                editor_focused,
+                completing,
+                empty
+            );
+        }
+    }
+
+    #[test]
+    fn lists_the_default_keybinds() {
+        let footer = Footer::new(&Keybinds::default());
+        assert_eq!(
+            footer.text(FooterContext::EmptyEditor),
+            "Tab: complete · Shift+Down: focus result · F1: help · Ctrl+c: quit"
+        );
+        assert_eq!(
+            footer.text(FooterContext::Processor),
+            "Enter: fold · Ctrl+o: copy result · Shift+Down: focus filter"
+        );
+    }
+
+    #[test]
+    fn follows_the_remapped_keybinds() {
+        let mut keybinds = Keybinds::default();
+        keybinds.global.exit = EventDefSet::from_defaults(&["Ctrl+x", "Ctrl+c"]);
+        keybinds.on_editor.completion = EventDefSet::from_defaults(&["Ctrl+space"]);
+        let footer = Footer::new(&keybinds);
+        assert_eq!(
+            footer.text(FooterContext::EmptyEditor),
+            "Ctrl+Space: complete · Shift+Down: focus result · F1: help · Ctrl+x: quit"
+        );
+    }
+
+    #[test]
+    fn skips_the_unbound_actions() {
+        let mut keybinds = Keybinds::default();
+        keybinds.global.copy_query = EventDefSet::from_defaults(&[]);
+        let footer = Footer::new(&keybinds);
+        assert_eq!(
+            footer.text(FooterContext::Editor),
+            "Tab: complete · Ctrl+o: copy result"
+        );
+    }
+}
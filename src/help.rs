@@ -0,0 +1,111 @@
+use crossterm::style::{Attribute, Attributes, Color};
+use promkit::{grapheme::StyledGraphemes, pane::Pane, style::StyleBuilder};
+
+use crate::config::{event::EventDefSet, Keybinds};
+
+/// Overlay listing the effective keybinds, page by page.
+pub struct Help {
+    lines: Vec<StyledGraphemes>,
+    page: usize,
+}
+
+impl Help {
+    /// Lists the keybinds as configured, so that remappings are reflected.
+    pub fn new(keybinds: &Keybinds) -> Self {
+        let mut lines = Vec::new();
+        for (title, entries) in [
+            ("Global", keybinds.global.entries()),
+            ("Editor", keybinds.on_editor.entries()),
+            ("Suggestions", keybinds.on_completion.entries()),
+            ("JSON viewer", keybinds.on_json_viewer.entries()),
+        ] {
+            if !lines.is_empty() {
+                lines.push(StyledGraphemes::default());
+            }
+            lines.push(
+                StyledGraphemes::from(title).apply_style(
+                    StyleBuilder::new()
+                        .fgc(Color::Blue)
+                        .attrs(Attributes::from(Attribute::Bold))
+                        .build(),
+                ),
+            );
+            let width = entries
+                .iter()
+                .map(|(name, _)| name.len())
+                .max()
+                .unwrap_or(0);
+            for (name, set) in entries {
+                lines.push(
+                    [
+                        StyledGraphemes::from(format!("  {:width$}  ", name, width = width)),
+                        StyledGraphemes::from(describe(set))
+                            .apply_style(StyleBuilder::new().fgc(Color::Cyan).build()),
+                    ]
+                    .into_iter()
+                    .collect(),
+                );
+            }
+        }
+        Self { lines, page: 0 }
+    }
+
+    pub fn first_page(&mut self) {
+        self.page = 0;
+    }
+
+    pub fn next_page(&mut self, height: u16) {
+        if self.page + 1 < self.page_count(height) {
+            self.page += 1;
+        }
+    }
+
+    pub fn previous_page(&mut self) {
+        self.page = self.page.saturating_sub(1);
+    }
+
+    /// Number of lines of keybinds per page, leaving a line for the status.
+    fn page_size(height: u16) -> usize {
+        (height as usize).saturating_sub(1).max(1)
+    }
+
+    fn page_count(&self, height: u16) -> usize {
+        self.lines.len().div_ceil(Self::page_size(height)).max(1)
+    }
+
+    pub fn create_pane(&self, height: u16) -> Pane {
+        let size = Self::page_size(height);
+        let count = self.page_count(height);
+        let page = self.page.min(count - 1);
+        let mut lines = self
+            .lines
+            .iter()
+            .skip(page * size)
+            .take(size)
+            .cloned()
+            .collect::<Vec<_>>();
+        lines.push(
+            StyledGraphemes::from(format!(
+                "Page {}/{} · Down/Up: next/previous page · Esc: close",
+                page + 1,
+                count
+            ))
+            .apply_style(
+                StyleBuilder::new()
+                    .attrs(Attributes::from(Attribute::Dim))
+                    .build(),
+            ),
+        );
+        Pane::new(lines, 0)
+    }
+}
+
+/// Joins the keybinds of an action, or tells that it has none.
+fn describe(set: &EventDefSet) -> String {
+    let seqs = set.iter().map(|seq| seq.to_string()).collect::<Vec<_>>();
+    if seqs.is_empty() {
+        "(unbound)".to_string()
+    } else {
+        seqs.join(", ")
+    }
+}
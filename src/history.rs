@@ -0,0 +1,309 @@
+//! Filters remembered across sessions, whatever the input.
+//!
+//! They are stored one per line, from the oldest to the newest,
+//! in `jnv/history` under the user's configuration directory by default.
+//!
+//! Instances of jnv saving at the same time take turns,
+//! each adding its new filters to what the others have saved.
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use promkit::text_editor::History;
+
+use crate::{
+    config::HistoryConfig,
+    safe::{self, SafeMode},
+    state,
+};
+
+/// Time after which saving the history on exit is given up, so that quitting stays quick.
+pub const SAVE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// File storing the history of the filters.
+pub struct HistoryFile {
+    path: PathBuf,
+    max_entries: usize,
+    safe_mode: SafeMode,
+    /// Entries read when loading, the others being those of this session.
+    loaded: HashSet<String>,
+}
+
+impl HistoryFile {
+    /// Returns the file configured, or `None` if there is no directory to store it.
+    pub fn new(config: &HistoryConfig, safe_mode: SafeMode) -> Option<Self> {
+        let path = match &config.path {
+            Some(path) => path.clone(),
+            None => dirs::config_dir()?.join("jnv").join("history"),
+        };
+        Some(Self {
+            path,
+            max_entries: config.max_entries,
+            safe_mode,
+            loaded: HashSet::new(),
+        })
+    }
+
+    /// Loads the history, which is empty if the file cannot be read.
+    pub fn load(&mut self) -> History {
+        let mut ret = History::default();
+        ret.limit_size = Some(self.max_entries);
+        for entry in read_entries(&self.path) {
+            ret.insert(&entry);
+            self.loaded.insert(entry);
+        }
+        ret
+    }
+
+    /// Saves the entries added to the history since it was loaded
+    /// after those in the file, replacing it atomically.
+    ///
+    /// Nothing is saved in safe mode.
+    pub fn save(&self, history: &History) -> anyhow::Result<()> {
+        match self.write(history) {
+            Err(e) if safe::is_disabled(&e) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Saves the history on a thread of its own, giving up waiting for it after `timeout`.
+    ///
+    /// The file is replaced atomically, so it is left as it was
+    /// if the process exits before the thread is done.
+    pub async fn save_within(self, history: History, timeout: Duration) -> anyhow::Result<()> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        // Not a blocking task of the runtime, which would wait for it once shut down.
+        thread::spawn(move || {
+            let _ = tx.send(self.save(&history));
+        });
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(anyhow::anyhow!("the history could not be saved")),
+            Err(_) => Err(anyhow::anyhow!(
+                "gave up saving the history after {:?}",
+                timeout
+            )),
+        }
+    }
+
+    fn write(&self, history: &History) -> anyhow::Result<()> {
+        // History only lets its entries be visited from the newest one backward.
+        let mut history = history.clone();
+        history.move_to_tail();
+        let mut additions = Vec::new();
+        while history.backward() {
+            let entry = history.get();
+            if !self.loaded.contains(&entry) {
+                additions.push(entry);
+            }
+        }
+        additions.reverse();
+
+        let _lock = state::lock(&self.path, self.safe_mode)?;
+        let entries = merge(read_entries(&self.path), additions, self.max_entries);
+        self.safe_mode.replace_file(&self.path, |file| {
+            for entry in &entries {
+                writeln!(file, "{}", entry)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Reads the entries of the file, from the oldest to the newest
+/// (none if it cannot be read).
+fn read_entries(path: &Path) -> Vec<String> {
+    fs::read(path)
+        .map(|content| {
+            String::from_utf8_lossy(&content)
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Appends the additions missing from the saved entries,
+/// keeping the newest `max_entries` entries.
+fn merge(saved: Vec<String>, additions: Vec<String>, max_entries: usize) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut entries = saved
+        .into_iter()
+        .chain(additions)
+        .filter(|entry| seen.insert(entry.clone()))
+        .collect::<Vec<_>>();
+    entries.drain(..entries.len().saturating_sub(max_entries));
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Barrier, time::Instant};
+
+    use super::*;
+
+    fn strings(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    /// Returns a history file of its own under the temporary directory.
+    fn history_file(name: &str, max_entries: usize) -> HistoryFile {
+        let dir = std::env::temp_dir().join(format!("jnv-history-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        let config = HistoryConfig {
+            path: Some(dir.join("history")),
+            max_entries,
+        };
+        HistoryFile::new(&config, SafeMode::new(false)).unwrap()
+    }
+
+    /// Returns another instance of the history file, as another jnv would have.
+    fn reopen(file: &HistoryFile) -> HistoryFile {
+        let config = HistoryConfig {
+            path: Some(file.path.clone()),
+            max_entries: file.max_entries,
+        };
+        HistoryFile::new(&config, SafeMode::new(false)).unwrap()
+    }
+
+    #[test]
+    fn merge_appends_the_missing_additions() {
+        assert_eq!(
+            merge(strings(&[".a", ".b"]), strings(&[".c", ".a", ".d"]), 10),
+            strings(&[".a", ".b", ".c", ".d"])
+        );
+    }
+
+    #[test]
+    fn merge_keeps_the_newest_entries() {
+        assert_eq!(
+            merge(strings(&[".a", ".b", ".c"]), strings(&[".d", ".e"]), 3),
+            strings(&[".c", ".d", ".e"])
+        );
+        assert_eq!(merge(Vec::new(), strings(&[".a"]), 0), Vec::<String>::new());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut file = history_file("save-and-load", 10);
+        let mut history = file.load();
+        history.insert(".a");
+        history.insert(".b");
+        file.save(&history).unwrap();
+        assert_eq!(read_entries(&file.path), strings(&[".a", ".b"]));
+
+        let mut history = reopen(&file).load();
+        assert!(history.backward());
+        assert_eq!(history.get(), ".b");
+        assert!(history.backward());
+        assert_eq!(history.get(), ".a");
+    }
+
+    #[test]
+    fn interleaved_sessions_keep_each_others_entries() {
+        let mut a = history_file("interleaved", 10);
+        let mut history = a.load();
+        history.insert(".old");
+        a.save(&history).unwrap();
+
+        let mut a = reopen(&a);
+        let mut b = reopen(&a);
+        let mut history_a = a.load();
+        let mut history_b = b.load();
+        history_a.insert(".a1");
+        history_b.insert(".b1");
+        a.save(&history_a).unwrap();
+        b.save(&history_b).unwrap();
+        history_a.insert(".a2");
+        a.save(&history_a).unwrap();
+
+        assert_eq!(
+            read_entries(&a.path),
+            strings(&[".old", ".a1", ".b1", ".a2"])
+        );
+    }
+
+    #[test]
+    fn concurrent_writers_lose_no_entry() {
+        const WRITERS: usize = 4;
+        const ENTRIES: usize = 20;
+        let file = history_file("concurrent", WRITERS * ENTRIES);
+        let barrier = Barrier::new(WRITERS);
+        thread::scope(|scope| {
+            for writer in 0..WRITERS {
+                let mut file = reopen(&file);
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    let mut history = file.load();
+                    barrier.wait();
+                    for i in 0..ENTRIES {
+                        history.insert(format!(".w{}[{}]", writer, i));
+                        file.save(&history).unwrap();
+                    }
+                });
+            }
+        });
+
+        let entries = read_entries(&file.path);
+        assert_eq!(entries.len(), WRITERS * ENTRIES);
+        for writer in 0..WRITERS {
+            // The entries of each writer stay in the order it added them.
+            let own = entries
+                .iter()
+                .filter(|entry| entry.starts_with(&format!(".w{}[", writer)))
+                .cloned()
+                .collect::<Vec<_>>();
+            let expected = (0..ENTRIES)
+                .map(|i| format!(".w{}[{}]", writer, i))
+                .collect::<Vec<_>>();
+            assert_eq!(own, expected);
+        }
+    }
+
+    #[test]
+    fn safe_mode_saves_nothing() {
+        let file = history_file("safe-mode", 10);
+        let config = HistoryConfig {
+            path: Some(file.path.clone()),
+            max_entries: 10,
+        };
+        let mut file = HistoryFile::new(&config, SafeMode::new(true)).unwrap();
+        let mut history = file.load();
+        history.insert(".a");
+        file.save(&history).unwrap();
+        assert!(!file.path.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn saving_gives_up_after_the_timeout() {
+        let file = history_file("timeout", 1000);
+        fs::create_dir_all(file.path.parent().unwrap()).unwrap();
+        // Reading the entries blocks until something writes to the pipe.
+        let path = std::ffi::CString::new(file.path.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+        let mut history = History::default();
+        for i in 0..1000 {
+            history.insert(format!(".items[{}]", i));
+        }
+        let fifo = file.path.clone();
+
+        let started = Instant::now();
+        let result = file.save_within(history, SAVE_TIMEOUT).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "gave up saving the history after 500ms"
+        );
+        assert!(started.elapsed() < SAVE_TIMEOUT * 4);
+
+        // Lets the thread saving it finish.
+        drop(fs::OpenOptions::new().write(true).open(&fifo).unwrap());
+    }
+}
@@ -0,0 +1,213 @@
+//! Formats of the input other than JSON, converted to JSON Lines
+//! so that the viewer, the suggestions and jq all see the same documents.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use promkit::serde_json;
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputFormat {
+    Json,
+    /// JSON with comments and trailing commas, as in VS Code settings or tsconfig files.
+    Jsonc,
+    Yaml,
+}
+
+impl InputFormat {
+    /// Returns the format given explicitly, or the one told by the extension of the file.
+    pub fn detect(explicit: Option<InputFormat>, path: Option<&Path>) -> Self {
+        explicit.unwrap_or_else(|| {
+            match path
+                .and_then(|path| path.extension())
+                .and_then(|ext| ext.to_str())
+            {
+                Some(ext)
+                    if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") =>
+                {
+                    InputFormat::Yaml
+                }
+                Some(ext) if ext.eq_ignore_ascii_case("jsonc") => InputFormat::Jsonc,
+                _ => InputFormat::Json,
+            }
+        })
+    }
+}
+
+/// File the input is read from, to read it again on reload.
+#[derive(Clone, Debug)]
+pub struct InputFile {
+    pub path: PathBuf,
+    format: InputFormat,
+}
+
+impl InputFile {
+    pub fn new(path: PathBuf, format: InputFormat) -> Self {
+        Self { path, format }
+    }
+
+    /// Reads the whole file, converted to JSON.
+    pub fn read(&self) -> anyhow::Result<String> {
+        to_json(std::fs::read_to_string(&self.path)?, self.format)
+    }
+}
+
+/// Returns the local time of day as "HH:MM:SS" (UTC where it is not known).
+pub fn local_time() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    #[cfg(unix)]
+    {
+        let time = secs as libc::time_t;
+        // SAFETY: `tm` is plain data, filled in by `localtime_r` on success.
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        if !unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+            return format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec);
+        }
+    }
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Converts the input to JSON, with a line per document of a multi-document YAML.
+pub fn to_json(input: String, format: InputFormat) -> anyhow::Result<String> {
+    match format {
+        InputFormat::Json => Ok(input),
+        InputFormat::Jsonc => Ok(strip_jsonc(input)),
+        InputFormat::Yaml => {
+            let mut ret = String::with_capacity(input.len());
+            for (i, document) in serde_yaml::Deserializer::from_str(&input).enumerate() {
+                let value = serde_json::Value::deserialize(document).map_err(|e| {
+                    anyhow::anyhow!("Failed to convert YAML document {}: {}", i + 1, e)
+                })?;
+                ret.push_str(&serde_json::to_string(&value)?);
+                ret.push('\n');
+            }
+            Ok(ret)
+        }
+    }
+}
+
+/// Blanks out the comments (`//` and `/* */`) and trailing commas of JSONC,
+/// outside the strings.
+///
+/// Every byte taken out is replaced with a space (line breaks being kept),
+/// so that the positions of syntax errors still refer to the original text.
+fn strip_jsonc(input: String) -> String {
+    let mut bytes = input.into_bytes();
+    // Last comma seen, trailing if only blanks come before the closing bracket.
+    let mut comma = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                comma = None;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    bytes[i] = b' ';
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = bytes[i + 2..]
+                    .windows(2)
+                    .position(|w| w == b"*/")
+                    .map_or(bytes.len(), |pos| i + 2 + pos + 2);
+                for byte in &mut bytes[i..end] {
+                    if !matches!(byte, b'\n' | b'\r') {
+                        *byte = b' ';
+                    }
+                }
+                i = end;
+                continue;
+            }
+            b',' => comma = Some(i),
+            b'}' | b']' => {
+                if let Some(comma) = comma.take() {
+                    bytes[comma] = b' ';
+                }
+            }
+            byte if byte.is_ascii_whitespace() => {}
+            _ => comma = None,
+        }
+        i += 1;
+    }
+    // Characters are replaced whole, so the text is still UTF-8.
+    String::from_utf8(bytes).expect("blanked JSONC is UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::serde_json::{self, json};
+
+    use super::*;
+
+    /// Strips the JSONC, checking the positions are kept.
+    fn strip(input: &str) -> String {
+        let ret = strip_jsonc(input.to_string());
+        assert_eq!(ret.len(), input.len(), "{:?}", input);
+        ret
+    }
+
+    #[test]
+    fn keeps_the_comment_markers_inside_strings() {
+        let input = r#"{"url": "http://x/*y*/", "a": "//", "b": "/*"}"#;
+        assert_eq!(strip(input), input);
+    }
+
+    #[test]
+    fn skips_the_escaped_quotes() {
+        let input = "{\"a\": \"say \\\"//hi\\\" \\\\\", // note\n\"b\": \"\\\"\"}";
+        let stripped = strip(input);
+        assert_eq!(
+            stripped,
+            "{\"a\": \"say \\\"//hi\\\" \\\\\",        \n\"b\": \"\\\"\"}"
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&stripped).unwrap(),
+            json!({"a": "say \"//hi\" \\", "b": "\""})
+        );
+    }
+
+    #[test]
+    fn blanks_the_block_comments_across_lines() {
+        let stripped = strip("[1, /* one\r\n two */ 2 /**/]\n/* unterminated\n");
+        assert_eq!(stripped, "[1,       \r\n        2     ]\n               \n");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&stripped).unwrap(),
+            json!([1, 2])
+        );
+    }
+
+    #[test]
+    fn blanks_the_trailing_commas() {
+        for (input, expected) in [
+            (
+                r#"{"a": [1, 2,], "b": {"c": 1,},}"#,
+                r#"{"a": [1, 2 ], "b": {"c": 1 } }"#,
+            ),
+            ("[1, // last\n]", "[1         \n]"),
+            ("[1, /* last */ ]", "[1             ]"),
+            (r#"[",]"]"#, r#"[",]"]"#),
+            // Not trailing, left to fail.
+            ("[1,,2]", "[1,,2]"),
+            ("[,]", "[ ]"),
+        ] {
+            assert_eq!(strip(input), expected, "{:?}", input);
+        }
+    }
+}
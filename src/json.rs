@@ -1,262 +1,3455 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashSet},
+    fmt,
+    hash::{DefaultHasher, Hasher},
+    io::{self, Write},
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
 use crossterm::{
-    event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers},
-    style::{Attribute, Attributes},
+    event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    style::{Attribute, Attributes, ContentStyle},
 };
-use jaq_interpret::{Ctx, FilterT, ParseCtx, RcIter, Val};
+use jaq_interpret::{Ctx, Filter, FilterT, ParseCtx, RcIter, Val};
 use promkit::{
     crossterm::style::Color,
-    jsonstream::{self, JsonStream},
-    jsonz::{self, format::RowFormatter},
+    grapheme::{StyledGrapheme, StyledGraphemes},
+    jsonz::{format::RowFormatter, Value as RowValue},
     pane::Pane,
     serde_json::{self, Deserializer, Value},
     style::StyleBuilder,
     text, PaneFactory,
 };
+use serde::Serialize;
 
 use crate::{
-    processor::{ViewProvider, Visualizer},
+    config::{event::typed_char, JsonViewerAction, Keybinds},
+    empty_pane, input,
+    paths::{self, PathEntry, PathInsertion},
+    processor::{QueryOutcome, ViewProvider, Visualizer},
+    progress::StartupProgress,
+    projection,
+    safe::{Effect, SafeMode},
     search::SearchProvider,
+    sequence::{SequenceMatcher, Step},
+    state::StateFile,
+    trie::FilterTrie,
 };
 
+mod expansion;
+pub use expansion::ExpansionMemory;
+mod similar;
+mod stream;
+use stream::JsonStream;
+mod grep;
+use grep::Grep;
+mod gron;
+use gron::Gron;
+mod pretty;
+use pretty::Pretty;
+mod table;
+use table::Table;
+mod jq;
+mod library;
+
+/// Number of the columns the rows are scrolled sideways by at once.
+const HORIZONTAL_SCROLL_STEP: usize = 8;
+pub use library::Library;
+
+/// What the filters are compiled and run with besides the definitions of jq.
+pub struct FilterScope {
+    /// Variables bound by `--arg` and `--argjson`, by name without the `$`.
+    pub variables: Vec<(String, Value)>,
+    /// Modules the filters may include.
+    pub library: Library,
+    /// Whether the environment of the process is available as `env` and `$ENV`,
+    /// as it may hold secrets.
+    pub env: bool,
+    /// jq binary running the filters instead of jaq, if any,
+    /// which safe mode refuses to run.
+    pub external: Option<String>,
+    pub safe_mode: SafeMode,
+    /// Time after which the evaluation of a filter is given up, if bounded.
+    pub timeout: Option<Duration>,
+    /// Number of the threads jaq runs the filters on, splitting the documents between them.
+    pub workers: usize,
+}
+
 #[derive(Clone)]
 pub struct Json {
-    state: jsonstream::State,
-    json: &'static [serde_json::Value],
+    stream: JsonStream,
+    formatter: RowFormatter,
+    /// Shared with the query running on it, as documents may be appended meanwhile.
+    json: Arc<Vec<serde_json::Value>>,
+    /// Number of the latest documents kept as more are appended (all if unset).
+    max_streams: Option<usize>,
+    matcher: SequenceMatcher<JsonViewerAction>,
+    expansion: ExpansionMemory,
+    state_file: Option<StateFile>,
+    /// Abort on the first error instead of showing the values of the other documents.
+    strict_errors: bool,
+    insert_path: PathInsertion,
+    case_sensitive_search: bool,
+    /// Depth up to which the containers of a result are expanded at first.
+    expand_depth: Option<usize>,
+    /// Digit of the last key pressed, giving the depth to expand to.
+    last_digit: Option<u32>,
+    /// Pattern being typed after the search key, which takes the key events meanwhile.
+    search_input: Option<String>,
+    /// Pattern searched last, to find its next or previous match.
+    search_pattern: Option<String>,
+    /// Pattern being typed after the grep key, the rows being grepped as it changes.
+    grep_input: Option<String>,
+    /// Path being typed after the write key, which takes the key events meanwhile.
+    path_input: Option<String>,
+    /// Query the rows are the result of.
+    query: String,
+    /// Paths collected to build a projection, relative to the result of `query`.
+    collected: Vec<String>,
+    query_to_edit: Option<String>,
+    text_to_copy: Option<String>,
+    /// Values produced by the last query, to be taken for the suggestions.
+    result: Option<Arc<Vec<serde_json::Value>>>,
+    /// Results of the previous queries, to show them again without running jq.
+    cache: FilterTrie,
+    /// Style of the part of the filter a parse error is about.
+    error_span_style: ContentStyle,
+    /// Style of the marker of the bookmarked rows.
+    bookmark_style: ContentStyle,
+    /// Rows bookmarked, by the hash of their document and their path
+    /// so that they are found again as the result is refreshed.
+    bookmarks: HashSet<(u64, String)>,
+    /// Indices of the rows bookmarked, found again as the rows are rebuilt.
+    bookmarked_rows: BTreeSet<usize>,
+    /// Show the array elements with the same structure as the one before them as one row.
+    fold_similar: bool,
+    /// Show the strings unquoted and unescaped when every value is one, as `jq -r` does.
+    raw_output: bool,
+    /// Number of the columns the rows are scrolled sideways by.
+    column_offset: usize,
+    /// How the input was last replaced (e.g. the time it was read again at),
+    /// told once the query is applied again.
+    input_note: Option<String>,
+    /// Values the rows are built from.
+    shown: Arc<Vec<serde_json::Value>>,
+    /// Presentation of the values shown instead of the rows,
+    /// which keep their expansion meanwhile.
+    view: Option<View>,
+    /// Prefix the rows with their line number in the result as formatted by `jq`.
+    show_line_numbers: bool,
+    /// Tell the row of the cursor over the number of rows below the result.
+    show_position: bool,
+    /// Show the path of the container holding the top row above the rows.
+    sticky_path: bool,
+    /// Hide the nulls and the empty objects and arrays from the rows.
+    hide_nulls: bool,
+    /// Copy the values hidden with `hide_nulls` along with the others.
+    copy_hidden: bool,
+    /// Tell the number of values and the time jq took after each evaluation.
+    show_timing: bool,
+    safe_mode: SafeMode,
+    /// Whether more documents may still be appended to the input.
+    receiving: bool,
+    /// Number of the lines of the input skipped as they are not JSON.
+    skipped_lines: usize,
+    /// Whether the documents of the input are wrapped into one array, as `jq -s` does.
+    slurp: bool,
+    scope: Arc<FilterScope>,
+    /// Number of the values of a result beyond which the others are left out (all if unset).
+    max_results: Option<usize>,
+    /// Query shown with all its values despite `max_results`, until it changes.
+    unlimited_query: Option<String>,
+    /// Results committed as the values the filters run on, the latest last,
+    /// each with its query and the values it replaced (the input for the first one).
+    bases: Vec<(String, Arc<Vec<serde_json::Value>>)>,
+    /// Show the values the filters run on beside the result.
+    split: bool,
+    /// Rows of the values the filters run on, shown beside the result once split
+    /// (built again as they change).
+    original: Option<JsonStream>,
+    /// Whether the keys moving the cursor scroll the input rather than the result.
+    input_focused: bool,
+}
+
+/// How the values are shown and the filters run, the same for every input read.
+#[derive(Clone)]
+pub struct JsonSettings {
+    pub formatter: RowFormatter,
+    /// Abort on the first error instead of showing the values of the other documents.
+    pub strict_errors: bool,
+    pub insert_path: PathInsertion,
+    pub case_sensitive_search: bool,
+    /// Depth up to which the containers of a result are expanded at first.
+    pub expand_depth: Option<usize>,
+    /// Number of the results of the previous queries kept to show them again.
+    pub cache_entries: usize,
+    pub error_span_style: ContentStyle,
+    pub bookmark_style: ContentStyle,
+    pub raw_output: bool,
+    pub show_line_numbers: bool,
+    pub show_position: bool,
+    pub sticky_path: bool,
+    pub hide_nulls: bool,
+    pub copy_hidden: bool,
+    pub show_timing: bool,
+    pub safe_mode: SafeMode,
+    pub scope: Arc<FilterScope>,
+    pub max_results: Option<usize>,
+    pub split: bool,
 }
 
 impl Json {
     pub fn new(
-        formatter: RowFormatter,
-        input_stream: &'static [serde_json::Value],
+        settings: &JsonSettings,
+        mut input_stream: Vec<serde_json::Value>,
+        max_streams: Option<usize>,
+        matcher: SequenceMatcher<JsonViewerAction>,
+        state_file: Option<StateFile>,
+        skipped_lines: usize,
+        slurp: bool,
     ) -> anyhow::Result<Self> {
+        let JsonSettings {
+            formatter,
+            strict_errors,
+            insert_path,
+            case_sensitive_search,
+            expand_depth,
+            cache_entries,
+            error_span_style,
+            bookmark_style,
+            raw_output,
+            show_line_numbers,
+            show_position,
+            sticky_path,
+            hide_nulls,
+            copy_hidden,
+            show_timing,
+            safe_mode,
+            scope,
+            max_results,
+            split,
+        } = settings.clone();
+        if let Some(max) = max_streams {
+            input_stream.drain(..input_stream.len().saturating_sub(max));
+        }
+        let expansion = state_file
+            .as_ref()
+            .map(|file| file.load().expansion)
+            .unwrap_or_default();
+        let mut stream = JsonStream::new(input_stream.iter());
+        if let Some(depth) = expand_depth {
+            stream.expand_to_depth(depth);
+        }
+        expansion.apply(&mut stream);
+        stream.set_hide_blank(hide_nulls);
+        let json = Arc::new(input_stream);
         Ok(Self {
-            json: input_stream,
-            state: jsonstream::State {
-                stream: JsonStream::new(input_stream.iter()),
-                formatter,
-                lines: Default::default(),
-            },
+            stream,
+            formatter,
+            shown: json.clone(),
+            json,
+            max_streams,
+            matcher,
+            expansion,
+            state_file,
+            strict_errors,
+            insert_path,
+            case_sensitive_search,
+            expand_depth,
+            last_digit: None,
+            search_input: None,
+            search_pattern: None,
+            grep_input: None,
+            path_input: None,
+            query: String::new(),
+            collected: Vec::new(),
+            query_to_edit: None,
+            text_to_copy: None,
+            result: None,
+            cache: FilterTrie::new(cache_entries),
+            error_span_style,
+            bookmark_style,
+            bookmarks: HashSet::new(),
+            bookmarked_rows: BTreeSet::new(),
+            fold_similar: false,
+            raw_output,
+            column_offset: 0,
+            input_note: None,
+            view: None,
+            show_line_numbers,
+            show_position,
+            sticky_path,
+            hide_nulls,
+            copy_hidden,
+            show_timing,
+            safe_mode,
+            receiving: false,
+            skipped_lines,
+            slurp,
+            scope,
+            max_results,
+            unlimited_query: None,
+            bases: Vec::new(),
+            split,
+            original: None,
+            input_focused: false,
         })
     }
 
-    fn operate(&mut self, event: &Event) {
-        match event {
-            // Move up.
-            Event::Key(KeyEvent {
-                code: KeyCode::Up,
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            })
-            | Event::Key(KeyEvent {
-                code: KeyCode::Char('k'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }) => {
-                self.state.stream.up();
-            }
-
-            // Move down.
-            Event::Key(KeyEvent {
-                code: KeyCode::Down,
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            })
-            | Event::Key(KeyEvent {
-                code: KeyCode::Char('j'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }) => {
-                self.state.stream.down();
-            }
+    fn create_pane(&self, width: u16, height: u16) -> Pane {
+        match &self.original {
+            Some(original) => self.create_split_pane(original, width, height),
+            None => self.create_result_pane(width, height),
+        }
+    }
 
-            // Move to tail
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('h'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }) => {
-                self.state.stream.tail();
+    fn create_result_pane(&self, width: u16, height: u16) -> Pane {
+        match &self.view {
+            Some(View::Table(table)) => {
+                let header_style = ContentStyle {
+                    attributes: Attribute::Bold.into(),
+                    ..self.formatter.key_style
+                };
+                return Pane::new(
+                    table.create_lines(
+                        width,
+                        height,
+                        header_style,
+                        self.formatter.active_item_attribute,
+                        self.formatter.inactive_item_attribute,
+                    ),
+                    0,
+                );
+            }
+            Some(View::Gron(gron)) => {
+                return Pane::new(gron.create_lines(&self.formatter, width, height), 0);
+            }
+            Some(View::Grep(grep)) => {
+                return Pane::new(grep.create_lines(&self.formatter, width, height), 0);
             }
+            Some(View::Pretty(pretty)) => {
+                return Pane::new(pretty.create_lines(width, height), 0);
+            }
+            None => {}
+        }
+        if let Some(strings) = self.raw_strings(self.stream.position()) {
+            return self.create_raw_pane(strings, width, height);
+        }
+        let marked = !self.bookmarked_rows.is_empty();
+        let height = if self.sticky_path {
+            height.saturating_sub(1)
+        } else {
+            height
+        };
+        let mut lines = self.stream_lines(
+            &self.stream,
+            width.saturating_sub(if marked { 2 } else { 0 }),
+            height,
+        );
+        if marked {
+            let indices = self.stream.row_indices_from_current(lines.len());
+            for (line, index) in lines.iter_mut().zip(indices) {
+                let marker = if self.bookmarked_rows.contains(&index) {
+                    StyledGraphemes::from("● ").apply_style(self.bookmark_style)
+                } else {
+                    StyledGraphemes::from("  ")
+                };
+                *line = vec![marker, std::mem::take(line)].into_iter().collect();
+            }
+        }
+        if self.sticky_path {
+            // Kept (empty) on the documents themselves, so that the rows do not move.
+            let path = self.stream.parent_path().unwrap_or_default();
+            lines.insert(
+                0,
+                StyledGraphemes::from(path).apply_attribute(Attribute::Dim),
+            );
+        }
+        Pane::new(lines, 0)
+    }
 
-            // Move to head
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('l'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }) => {
-                self.state.stream.head();
+    /// Formats the rows of the stream from its cursor.
+    fn stream_lines(&self, stream: &JsonStream, width: u16, height: u16) -> Vec<StyledGraphemes> {
+        let (rows, folds) = stream.extract_rows_from_current(height as usize);
+        let gutter = if self.show_line_numbers {
+            stream.rows().len().to_string().len() + 1
+        } else {
+            0
+        };
+        let offset = self.column_offset;
+        let mut lines = self.formatter.format_for_terminal_display(
+            &rows,
+            width
+                .saturating_sub(gutter as u16)
+                .saturating_add(offset.min(u16::MAX as usize) as u16),
+        );
+        for (i, count) in folds {
+            let style = StyleBuilder::new()
+                .fgc(Color::Grey)
+                .attrs(Attributes::from(if i == 0 {
+                    self.formatter.active_item_attribute
+                } else {
+                    self.formatter.inactive_item_attribute
+                }))
+                .build();
+            lines[i] = vec![
+                StyledGraphemes::from(" ".repeat(self.formatter.indent * rows[i].depth)),
+                StyledGraphemes::from(format!("… ×{} similar", format_count(count)))
+                    .apply_style(style),
+            ]
+            .into_iter()
+            .collect();
+        }
+        if offset > 0 {
+            lines = lines.into_iter().map(|line| shift(line, offset)).collect();
+        }
+        if self.show_line_numbers {
+            let style = StyleBuilder::new()
+                .attrs(Attributes::from(Attribute::Dim))
+                .build();
+            let indices = stream.row_indices_from_current(lines.len());
+            for (line, index) in lines.iter_mut().zip(indices) {
+                *line = vec![
+                    StyledGraphemes::from(format!("{:>1$} ", index + 1, gutter - 1))
+                        .apply_style(style),
+                    std::mem::take(line),
+                ]
+                .into_iter()
+                .collect();
             }
+        }
+        lines
+    }
+
+    /// Shows the input on the left and the result on the right,
+    /// under a title telling which of them the cursor keys scroll.
+    fn create_split_pane(&self, original: &JsonStream, width: u16, height: u16) -> Pane {
+        let left = width.saturating_sub(1) / 2;
+        let right = width.saturating_sub(left + 1);
+        let height = height.saturating_sub(1);
+        let input = self.stream_lines(original, left, height);
+        let result = self
+            .create_result_pane(right, height)
+            .extract(height as usize);
+        let separator = StyledGraphemes::from("│").apply_style(
+            StyleBuilder::new()
+                .attrs(Attributes::from(Attribute::Dim))
+                .build(),
+        );
+        let title = |name: &str, focused: bool| {
+            let (marker, attribute) = if focused {
+                ("▸ ", Attribute::Bold)
+            } else {
+                ("  ", Attribute::Dim)
+            };
+            StyledGraphemes::from(format!("{}{}", marker, name)).apply_attribute(attribute)
+        };
+        let mut lines = vec![vec![
+            fit(title(&self.unfiltered_title(), self.input_focused), left),
+            separator.clone(),
+            title("result", !self.input_focused),
+        ]
+        .into_iter()
+        .collect()];
+        for i in 0..input.len().max(result.len()) {
+            lines.push(
+                vec![
+                    fit(input.get(i).cloned().unwrap_or_default(), left),
+                    separator.clone(),
+                    fit(result.get(i).cloned().unwrap_or_default(), right),
+                ]
+                .into_iter()
+                .collect(),
+            );
+        }
+        Pane::new(lines, 0)
+    }
+
+    /// Names the values the filters run on in the title of the split pane.
+    fn unfiltered_title(&self) -> String {
+        if self.bases.is_empty() {
+            "input".to_string()
+        } else {
+            format!("result of {}", self.breadcrumb())
+        }
+    }
 
-            // Toggle collapse/expand
-            Event::Key(KeyEvent {
-                code: KeyCode::Enter,
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }) => {
-                self.state.stream.toggle();
+    /// Builds the rows of the values the filters run on if they are to be shown beside the result.
+    fn refresh_original(&mut self) {
+        if self.split && self.original.is_none() {
+            let mut original = JsonStream::new(self.json.iter());
+            if let Some(depth) = self.expand_depth {
+                original.expand_to_depth(depth);
             }
+            self.expansion.apply(&mut original);
+            original.set_hide_blank(self.hide_nulls);
+            self.original = Some(original);
+        }
+    }
+
+    fn toggle_split(&mut self) -> text::State {
+        self.split = !self.split;
+        self.original = None;
+        self.input_focused = false;
+        self.refresh_original();
+        text::State {
+            text: if self.split {
+                "Showing the input beside the result".to_string()
+            } else {
+                "Showing the result only".to_string()
+            },
+            style: StyleBuilder::new().fgc(Color::Grey).build(),
+        }
+    }
+
+    fn switch_focus(&mut self) -> text::State {
+        if !self.split {
+            return text::State {
+                text: "The input is shown beside the result once split".to_string(),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            };
+        }
+        self.input_focused = !self.input_focused;
+        text::State {
+            text: if self.input_focused {
+                "Scrolling the input".to_string()
+            } else {
+                "Scrolling the result".to_string()
+            },
+            style: StyleBuilder::new().fgc(Color::Grey).build(),
+        }
+    }
 
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('p'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }) => {
-                self.state.stream.set_nodes_visibility(false);
+    /// Returns the row of the cursor over the number of rows (e.g. `123/45678 (0.3%)`)
+    /// if it is to be told, or over the rows of the table with the columns it shows.
+    fn describe_position(&self, width: u16) -> Option<String> {
+        let (current, total) = match &self.view {
+            // The columns shown are told whether or not the position is.
+            Some(View::Table(table)) => {
+                return Some(format!(
+                    "{}/{} · {}",
+                    table.position() + 1,
+                    table.len(),
+                    table.describe_columns(width)
+                ));
             }
+            Some(View::Gron(gron)) => (gron.position() + 1, gron.len()),
+            Some(View::Grep(grep)) => (grep.position() + 1, grep.len()),
+            Some(View::Pretty(pretty)) => (pretty.position() + 1, pretty.len()),
+            None => (self.stream.position() + 1, self.stream.rows().len()),
+        };
+        if !self.show_position || total == 0 {
+            return None;
+        }
+        Some(format!(
+            "{}/{} ({:.1}%)",
+            current,
+            total,
+            current as f64 * 100.0 / total as f64
+        ))
+    }
+
+    /// Returns the strings of the rows from `from` if raw output is enabled
+    /// and every value is a string.
+    fn raw_strings(&self, from: usize) -> Option<impl Iterator<Item = &str>> {
+        if !self.raw_output || !self.stream.strings_only() {
+            return None;
+        }
+        Some(
+            self.stream.rows()[from..]
+                .iter()
+                .filter_map(|row| match &row.v {
+                    RowValue::String(s) => Some(s.as_str()),
+                    _ => None,
+                }),
+        )
+    }
 
-            Event::Key(KeyEvent {
-                code: KeyCode::Char('n'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE,
-            }) => {
-                self.state.stream.set_nodes_visibility(true);
+    /// Shows the strings from the cursor one after another, a line of them per row.
+    fn create_raw_pane<'a>(
+        &self,
+        strings: impl Iterator<Item = &'a str>,
+        width: u16,
+        height: u16,
+    ) -> Pane {
+        let height = height as usize;
+        let mut lines = Vec::new();
+        for (i, string) in strings.enumerate() {
+            let attribute = if i == 0 {
+                self.formatter.active_item_attribute
+            } else {
+                self.formatter.inactive_item_attribute
+            };
+            for line in string.split('\n') {
+                if line.is_empty() {
+                    lines.push(StyledGraphemes::default());
+                } else {
+                    let (matrix, _) = StyledGraphemes::from(line)
+                        .apply_attribute(attribute)
+                        .matrixify(width as usize, height, 0);
+                    lines.extend(matrix);
+                }
+            }
+            if lines.len() >= height {
+                break;
             }
+        }
+        lines.truncate(height);
+        Pane::new(lines, 0)
+    }
+
+    /// Returns the queries of the results committed as bases, e.g. "`.items` › `.[0]`".
+    fn breadcrumb(&self) -> String {
+        self.bases
+            .iter()
+            .map(|(query, _)| format!("`{}`", query))
+            .collect::<Vec<_>>()
+            .join(" › ")
+    }
 
-            _ => (),
+    /// Tells what is shown while the values the filters run on are not filtered.
+    fn unfiltered_note(&self) -> String {
+        if self.bases.is_empty() {
+            "showing unfiltered input".to_string()
+        } else {
+            format!("showing the result of {}", self.breadcrumb())
         }
     }
-}
 
-#[async_trait::async_trait]
-impl Visualizer for Json {
-    async fn content_to_copy(&self) -> String {
-        self.state
-            .formatter
-            .format_raw_json(self.state.stream.rows())
+    /// Goes back to the input, dropping the results committed as bases.
+    fn drop_bases(&mut self) {
+        if let Some((_, input)) = self.bases.drain(..).next() {
+            self.json = input;
+        }
     }
 
-    async fn create_init_pane(&mut self, area: (u16, u16)) -> Pane {
-        self.state.create_pane(area.0, area.1)
+    /// Rebuilds the rows from the values, keeping the expansion and the folding,
+    /// and the view if any (unless the values no longer make a table).
+    fn rebuild(&mut self, values: Arc<Vec<Value>>) {
+        self.stream = JsonStream::new(values.iter());
+        if let Some(depth) = self.expand_depth {
+            self.stream.expand_to_depth(depth);
+        }
+        self.expansion.apply(&mut self.stream);
+        if self.fold_similar {
+            self.stream.set_fold_similar(true);
+        }
+        self.stream.set_hide_blank(self.hide_nulls);
+        self.view = match &self.view {
+            Some(View::Table(_)) => Table::new(&values).map(View::Table),
+            Some(View::Gron(_)) => Some(View::Gron(Gron::new(&values))),
+            Some(View::Grep(grep)) => Some(View::Grep(grep.again(&self.stream))),
+            Some(View::Pretty(_)) => Some(View::Pretty(Pretty::new(&values))),
+            None => None,
+        };
+        self.shown = values;
     }
 
-    async fn create_pane_from_event(&mut self, area: (u16, u16), event: &Event) -> Pane {
-        self.operate(event);
-        self.state.create_pane(area.0, area.1)
+    /// Handles the event on the pane of the given height.
+    fn operate(&mut self, event: &Event, height: u16) -> Option<Pane> {
+        if self.search_input.is_some() {
+            return Some(self.edit_search(event));
+        }
+        if self.path_input.is_some() {
+            return Some(self.edit_path(event));
+        }
+        if self.grep_input.is_some() {
+            return Some(self.edit_grep(event));
+        }
+        if let Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event
+        {
+            self.last_digit = match code {
+                KeyCode::Char(ch) => ch.to_digit(10),
+                _ => None,
+            };
+        }
+        let had_pending = self.matcher.pending_text().is_some();
+        let steps = self.matcher.feed(event);
+        match self.apply(steps, height) {
+            Some(guide) => Some(guide.create_pane(u16::MAX, 1)),
+            None => self.pending_guide(had_pending),
+        }
     }
 
-    async fn create_panes_from_query(
-        &mut self,
-        area: (u16, u16),
-        input: String,
-    ) -> (Option<Pane>, Option<Pane>) {
-        match run_jaq(&input, self.json) {
-            Ok(ret) => {
-                let mut guide = None;
-                if ret.iter().all(|val| *val == Value::Null) {
+    /// Applies the actions, returning the guide to show if any,
+    /// which is the path of the row at the cursor once it moves.
+    fn apply(&mut self, steps: Vec<Step<JsonViewerAction>>, height: u16) -> Option<text::State> {
+        let page = height.max(1) as usize;
+        let position = self.stream.position();
+        let mut guide = None;
+        for step in steps {
+            let Step::Action(action) = step else {
+                continue;
+            };
+            match action {
+                JsonViewerAction::ToggleSplit => {
+                    guide = Some(self.toggle_split());
+                    continue;
+                }
+                JsonViewerAction::SwitchFocus => {
+                    guide = Some(self.switch_focus());
+                    continue;
+                }
+                _ => {}
+            }
+            if let Some(original) = self.original.as_mut().filter(|_| self.input_focused) {
+                // The other actions are still about the result.
+                if move_in(original, action, page) {
                     guide = Some(text::State {
-                        text: format!("jq returned 'null', which may indicate a typo or incorrect filter: `{}`", input),
-                        style: StyleBuilder::new()
-                            .fgc(Color::Yellow)
-                            .attrs(Attributes::from(Attribute::Bold))
-                            .build(),
-                    }.create_pane(area.0, area.1));
+                        text: original.current_path(),
+                        style: StyleBuilder::new().fgc(Color::Grey).build(),
+                    });
+                    continue;
+                }
+            }
+            if self.view.is_some() {
+                guide = self.apply_on_view(action, page).or(guide);
+                continue;
+            }
+            match action {
+                JsonViewerAction::Up
+                | JsonViewerAction::Down
+                | JsonViewerAction::PageUp
+                | JsonViewerAction::PageDown
+                | JsonViewerAction::HalfPageUp
+                | JsonViewerAction::HalfPageDown
+                | JsonViewerAction::MoveToTail
+                | JsonViewerAction::MoveToHead
+                | JsonViewerAction::Toggle
+                | JsonViewerAction::ExpandAll
+                | JsonViewerAction::CollapseAll
+                | JsonViewerAction::MoveToParent
+                | JsonViewerAction::NextSibling
+                | JsonViewerAction::PrevSibling => {
+                    move_in(&mut self.stream, action, page);
+                }
+                JsonViewerAction::RememberCollapsed => {
+                    guide = Some(self.remember_current(true));
+                }
+                JsonViewerAction::RememberExpanded => {
+                    guide = Some(self.remember_current(false));
+                }
+                JsonViewerAction::ListRemembered => {
+                    guide = Some(text::State {
+                        text: self.expansion.describe(),
+                        style: StyleBuilder::new().fgc(Color::Grey).build(),
+                    });
+                }
+                JsonViewerAction::CollectField => {
+                    guide = Some(self.collect_current());
+                }
+                JsonViewerAction::EmitProjection => {
+                    guide = Some(self.emit_projection());
+                }
+                JsonViewerAction::ExpandToDepth => {
+                    if let Some(depth) = self.last_digit {
+                        self.stream.expand_to_depth(depth as usize);
+                        guide = Some(text::State {
+                            text: format!("Expanded to depth {}", depth),
+                            style: StyleBuilder::new().fgc(Color::Grey).build(),
+                        });
+                    }
+                }
+                JsonViewerAction::Search => {
+                    self.search_input = Some(String::new());
+                    guide = Some(search_prompt(""));
+                }
+                JsonViewerAction::GrepRows => {
+                    guide = Some(self.start_grep());
+                }
+                JsonViewerAction::WriteResult => {
+                    guide = Some(match self.safe_mode.permit(Effect::WriteFile) {
+                        Ok(()) => {
+                            self.path_input = Some(String::new());
+                            path_prompt("")
+                        }
+                        Err(e) => text::State {
+                            text: e.to_string(),
+                            style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                        },
+                    });
+                }
+                JsonViewerAction::NextMatch => {
+                    guide = Some(self.find_match(true));
+                }
+                JsonViewerAction::PreviousMatch => {
+                    guide = Some(self.find_match(false));
+                }
+                JsonViewerAction::InsertPath => {
+                    guide = Some(self.insert_current());
+                }
+                JsonViewerAction::ToggleBookmark => {
+                    guide = Some(self.toggle_bookmark());
+                }
+                JsonViewerAction::NextBookmark => {
+                    guide = Some(self.find_bookmark(true));
+                }
+                JsonViewerAction::PrevBookmark => {
+                    guide = Some(self.find_bookmark(false));
+                }
+                JsonViewerAction::CopyPath => {
+                    self.text_to_copy = Some(self.stream.current_path());
+                }
+                JsonViewerAction::CopySelectedNode => {
+                    self.text_to_copy = self
+                        .stream
+                        .current_value()
+                        .map(|value| {
+                            if self.hide_nulls && !self.copy_hidden {
+                                without_blanks(value)
+                            } else {
+                                value
+                            }
+                        })
+                        .map(|value| serde_json::to_string_pretty(&value).unwrap_or_default());
+                }
+                JsonViewerAction::FoldSimilar => {
+                    self.fold_similar = !self.fold_similar;
+                    self.stream.set_fold_similar(self.fold_similar);
+                    guide = Some(text::State {
+                        text: if self.fold_similar {
+                            "Folding the elements similar to the previous one".to_string()
+                        } else {
+                            "Showing every element".to_string()
+                        },
+                        style: StyleBuilder::new().fgc(Color::Grey).build(),
+                    });
+                }
+                JsonViewerAction::ToggleNulls => {
+                    self.hide_nulls = !self.hide_nulls;
+                    self.stream.set_hide_blank(self.hide_nulls);
+                    if let Some(original) = &mut self.original {
+                        original.set_hide_blank(self.hide_nulls);
+                    }
+                    guide = Some(text::State {
+                        text: if self.hide_nulls {
+                            "Hiding the nulls and the empty objects and arrays".to_string()
+                        } else {
+                            "Showing the nulls and the empty objects and arrays".to_string()
+                        },
+                        style: StyleBuilder::new().fgc(Color::Grey).build(),
+                    });
+                }
+                JsonViewerAction::ToggleRawOutput => {
+                    self.raw_output = !self.raw_output;
+                    guide = Some(match (self.raw_output, self.raw_strings(0).is_some()) {
+                        (false, _) => text::State {
+                            text: "Showing the strings as JSON".to_string(),
+                            style: StyleBuilder::new().fgc(Color::Grey).build(),
+                        },
+                        (true, true) => text::State {
+                            text: "Showing the strings raw".to_string(),
+                            style: StyleBuilder::new().fgc(Color::Grey).build(),
+                        },
+                        (true, false) => text::State {
+                            text: "Showing the strings raw once every value is a string"
+                                .to_string(),
+                            style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                        },
+                    });
+                }
+                JsonViewerAction::ToggleTable => {
+                    guide = Some(self.show_table());
+                }
+                JsonViewerAction::ToggleGron => {
+                    guide = Some(self.show_gron());
+                }
+                JsonViewerAction::TogglePretty => {
+                    guide = Some(self.show_pretty());
+                }
+                JsonViewerAction::ScrollLeft => {
+                    self.column_offset = self.column_offset.saturating_sub(HORIZONTAL_SCROLL_STEP);
+                }
+                JsonViewerAction::ScrollRight => {
+                    self.column_offset = (self.column_offset + HORIZONTAL_SCROLL_STEP)
+                        .min(u16::MAX as usize - HORIZONTAL_SCROLL_STEP);
                 }
+                JsonViewerAction::ScrollHome => {
+                    self.column_offset = 0;
+                }
+                // Taken before the view.
+                JsonViewerAction::ToggleSplit | JsonViewerAction::SwitchFocus => {}
+                JsonViewerAction::ForgetRemembered => {
+                    self.expansion = ExpansionMemory::default();
+                    guide = Some(self.save_expansion(
+                        "Forgot the remembered expansion".to_string(),
+                        |expansion| *expansion = ExpansionMemory::default(),
+                    ));
+                }
+            }
+        }
+        if guide.is_none() && self.stream.position() != position {
+            guide = Some(text::State {
+                text: self.stream.current_path(),
+                style: StyleBuilder::new().fgc(Color::Grey).build(),
+            });
+        }
+        guide
+    }
 
-                self.state.stream = JsonStream::new(ret.iter());
+    /// Applies the action to the view shown in place of the rows,
+    /// returning the guide to show if any.
+    fn apply_on_view(&mut self, action: JsonViewerAction, page: usize) -> Option<text::State> {
+        let view = self.view.as_mut()?;
+        let position = view.position();
+        let target = match action {
+            JsonViewerAction::Up => Some(position.saturating_sub(1)),
+            JsonViewerAction::Down => Some(position + 1),
+            JsonViewerAction::PageUp => Some(position.saturating_sub(page)),
+            JsonViewerAction::PageDown => Some(position + page),
+            JsonViewerAction::HalfPageUp => Some(position.saturating_sub(page.div_ceil(2))),
+            JsonViewerAction::HalfPageDown => Some(position + page.div_ceil(2)),
+            JsonViewerAction::MoveToHead => Some(0),
+            JsonViewerAction::MoveToTail => Some(usize::MAX),
+            _ => None,
+        };
+        if let Some(target) = target {
+            view.set_position(target);
+            return None;
+        }
+        match (view, action) {
+            (View::Table(table), JsonViewerAction::ScrollLeft) => table.scroll_left(),
+            (View::Table(table), JsonViewerAction::ScrollRight) => table.scroll_right(),
+            (View::Table(table), JsonViewerAction::ScrollHome) => table.scroll_home(),
+            // The rows are shown as they were before the view.
+            (View::Table(_), JsonViewerAction::ToggleTable)
+            | (View::Gron(_), JsonViewerAction::ToggleGron)
+            | (View::Pretty(_), JsonViewerAction::TogglePretty) => {
+                self.view = None;
+                return Some(text::State {
+                    text: "Showing the tree".to_string(),
+                    style: StyleBuilder::new().fgc(Color::Grey).build(),
+                });
+            }
+            (_, JsonViewerAction::ToggleTable) => return Some(self.show_table()),
+            (_, JsonViewerAction::ToggleGron) => return Some(self.show_gron()),
+            (_, JsonViewerAction::TogglePretty) => return Some(self.show_pretty()),
+            (_, JsonViewerAction::GrepRows) => return Some(self.start_grep()),
+            (View::Gron(_), JsonViewerAction::Search) => {
+                self.search_input = Some(String::new());
+                return Some(search_prompt(""));
+            }
+            (View::Gron(_), JsonViewerAction::NextMatch) => return Some(self.find_match(true)),
+            (View::Gron(_), JsonViewerAction::PreviousMatch) => {
+                return Some(self.find_match(false))
+            }
+            (View::Gron(gron), JsonViewerAction::CopyPath) => {
+                self.text_to_copy = gron.current_path().map(str::to_string);
+            }
+            (View::Grep(grep), JsonViewerAction::CopyPath) => {
+                self.text_to_copy = grep.current_path().map(str::to_string);
+            }
+            (view, _) => {
+                return Some(text::State {
+                    text: format!("Not available in the {} view", view.name()),
+                    style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                })
+            }
+        }
+        None
+    }
 
-                (guide, Some(self.state.create_pane(area.0, area.1)))
+    /// Shows the values as a table if they are an array of objects.
+    fn show_table(&mut self) -> text::State {
+        match Table::new(&self.shown) {
+            Some(table) => {
+                let text = format!("Showing {} rows as a table", format_count(table.len()));
+                self.view = Some(View::Table(table));
+                text::State {
+                    text,
+                    style: StyleBuilder::new().fgc(Color::Grey).build(),
+                }
             }
-            Err(e) => (
-                Some(
-                    text::State {
-                        text: format!("jq failed: `{}`", e),
-                        style: StyleBuilder::new()
-                            .fgc(Color::Red)
-                            .attrs(Attributes::from(Attribute::Bold))
-                            .build(),
+            None => text::State {
+                text: "Only an array of objects can be shown as a table".to_string(),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            },
+        }
+    }
+
+    /// Shows the values as a `path = value;` line per leaf.
+    fn show_gron(&mut self) -> text::State {
+        let gron = Gron::new(&self.shown);
+        let text = format!("Showing {} leaves as assignments", format_count(gron.len()));
+        self.view = Some(View::Gron(gron));
+        text::State {
+            text,
+            style: StyleBuilder::new().fgc(Color::Grey).build(),
+        }
+    }
+
+    /// Shows the values as the pretty-printed text, scrolled line by line.
+    fn show_pretty(&mut self) -> text::State {
+        let pretty = Pretty::new(&self.shown);
+        let text = format!("Showing {} lines of text", format_count(pretty.len()));
+        self.view = Some(View::Pretty(pretty));
+        text::State {
+            text,
+            style: StyleBuilder::new().fgc(Color::Grey).build(),
+        }
+    }
+
+    /// Starts or stops remembering the container at the cursor as collapsed (or expanded)
+    /// for every index of the arrays it is in.
+    fn remember_current(&mut self, collapsed: bool) -> text::State {
+        let is_container = self
+            .stream
+            .rows()
+            .get(self.stream.position())
+            .is_some_and(|row| matches!(row.v, RowValue::Open { .. } | RowValue::Close { .. }));
+        if !is_container {
+            return text::State {
+                text: "Only objects and arrays can be collapsed".to_string(),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            };
+        }
+
+        let pattern = paths::generalize_indices(&self.stream.current_path());
+        let remembered = self.expansion.toggle(&pattern, collapsed);
+        if remembered {
+            self.expansion.apply(&mut self.stream);
+        }
+        let message = match (remembered, collapsed) {
+            (true, true) => format!("`{}` will always be collapsed", pattern),
+            (true, false) => format!("`{}` will always be expanded", pattern),
+            (false, _) => format!("`{}` is no longer remembered", pattern),
+        };
+        self.save_expansion(message, |expansion| {
+            expansion.set(&pattern, remembered.then_some(collapsed))
+        })
+    }
+
+    /// Adds the path at the cursor to the collected fields,
+    /// or removes it if it is already collected.
+    fn collect_current(&mut self) -> text::State {
+        let path = self.stream.current_path();
+        match self.collected.iter().position(|p| *p == path) {
+            Some(i) => {
+                self.collected.remove(i);
+            }
+            None => self.collected.push(path),
+        }
+        text::State {
+            text: if self.collected.is_empty() {
+                "No field is collected".to_string()
+            } else {
+                format!("Collected: {}", self.collected.join(", "))
+            },
+            style: StyleBuilder::new().fgc(Color::Grey).build(),
+        }
+    }
+
+    /// Builds the projection of the collected fields to put into the editor.
+    fn emit_projection(&mut self) -> text::State {
+        if self.collected.is_empty() {
+            return text::State {
+                text: "Collect fields first to build a projection".to_string(),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            };
+        }
+        let projection = projection::build(&self.collected);
+        self.collected.clear();
+        // The paths are relative to the result, so apply the projection after the query.
+        self.query_to_edit = Some(match self.query.trim() {
+            "" | "." => projection,
+            query => format!("{} | {}", query, projection),
+        });
+        text::State {
+            text: "Inserted the projection into the filter".to_string(),
+            style: StyleBuilder::new().fgc(Color::Green).build(),
+        }
+    }
+
+    /// Edits the pattern being typed, searching it once confirmed with Enter,
+    /// and returns the guide.
+    fn edit_search(&mut self, event: &Event) -> Pane {
+        let Some(input) = &mut self.search_input else {
+            return empty_pane();
+        };
+        match edit_line(input, event) {
+            LineEdit::Editing => search_prompt(input).create_pane(u16::MAX, 1),
+            LineEdit::Confirmed(_) => {
+                let pattern = self.search_input.take().unwrap_or_default();
+                if pattern.is_empty() {
+                    return empty_pane();
+                }
+                self.search_pattern = Some(pattern);
+                self.find_match(true).create_pane(u16::MAX, 1)
+            }
+            LineEdit::Cancelled => {
+                self.search_input = None;
+                empty_pane()
+            }
+        }
+    }
+
+    /// Starts typing the pattern to grep the rows for, from the one grepped for if any.
+    fn start_grep(&mut self) -> text::State {
+        let pattern = match &self.view {
+            Some(View::Grep(grep)) => grep.pattern().to_string(),
+            _ => String::new(),
+        };
+        let prompt = self.grep_prompt(&pattern);
+        self.grep_input = Some(pattern);
+        prompt
+    }
+
+    /// Edits the pattern being typed, showing the rows containing it as it changes,
+    /// and returns the guide. Once cancelled with Esc, every row is shown again.
+    fn edit_grep(&mut self, event: &Event) -> Pane {
+        let Some(input) = &mut self.grep_input else {
+            return empty_pane();
+        };
+        match edit_line(input, event) {
+            LineEdit::Editing => {
+                let pattern = input.clone();
+                self.grep(&pattern);
+                self.grep_prompt(&pattern).create_pane(u16::MAX, 1)
+            }
+            LineEdit::Confirmed(_) => {
+                self.grep_input = None;
+                match &self.view {
+                    Some(View::Grep(grep)) => text::State {
+                        text: format!("&{} · {}", grep.pattern(), grep.describe()),
+                        style: StyleBuilder::new().fgc(Color::Grey).build(),
                     }
-                    .create_pane(area.0, area.1),
-                ),
-                None,
-            ),
+                    .create_pane(u16::MAX, 1),
+                    _ => empty_pane(),
+                }
+            }
+            LineEdit::Cancelled => {
+                self.grep_input = None;
+                self.grep("");
+                text::State {
+                    text: "Showing every row".to_string(),
+                    style: StyleBuilder::new().fgc(Color::Grey).build(),
+                }
+                .create_pane(u16::MAX, 1)
+            }
         }
     }
-}
 
-fn run_jaq(
-    query: &str,
-    json_stream: &'static [serde_json::Value],
-) -> anyhow::Result<Vec<serde_json::Value>> {
-    let mut ret = Vec::<serde_json::Value>::new();
-
-    for input in json_stream {
-        let mut ctx = ParseCtx::new(Vec::new());
-        ctx.insert_natives(jaq_core::core());
-        ctx.insert_defs(jaq_std::std());
-
-        let (f, errs) = jaq_parse::parse(query, jaq_parse::main());
-        if !errs.is_empty() {
-            let error_message = errs
-                .iter()
-                .map(|e| e.to_string())
-                .collect::<Vec<_>>()
-                .join(", ");
-            return Err(anyhow::anyhow!(error_message));
+    /// Shows the rows containing the pattern in place of the others, or every row if it is empty.
+    fn grep(&mut self, pattern: &str) {
+        if pattern.is_empty() {
+            if let Some(View::Grep(_)) = self.view {
+                self.view = None;
+            }
+            return;
         }
+        self.view = Some(View::Grep(Grep::new(
+            &self.stream,
+            pattern,
+            self.case_sensitive_search,
+        )));
+    }
 
-        let f = ctx.compile(f.unwrap());
-        let inputs = RcIter::new(core::iter::empty());
-        let mut out = f.run((Ctx::new([], &inputs), Val::from(input.clone())));
+    /// Returns the pattern being typed with the number of the rows containing it.
+    fn grep_prompt(&self, input: &str) -> text::State {
+        let text = match &self.view {
+            Some(View::Grep(grep)) if !input.is_empty() => {
+                format!("&{} · {}", input, grep.describe())
+            }
+            _ => format!("&{}", input),
+        };
+        text::State {
+            text,
+            style: StyleBuilder::new().build(),
+        }
+    }
 
-        while let Some(Ok(val)) = out.next() {
-            ret.push(val.into());
+    /// Edits the path being typed, writing the result to it once confirmed with Enter
+    /// (on a single line per document with Alt+Enter), and returns the guide.
+    fn edit_path(&mut self, event: &Event) -> Pane {
+        let Some(input) = &mut self.path_input else {
+            return empty_pane();
+        };
+        match edit_line(input, event) {
+            LineEdit::Editing => path_prompt(input).create_pane(u16::MAX, 1),
+            LineEdit::Confirmed(modifiers) => {
+                let target = self.path_input.take().unwrap_or_default();
+                if target.is_empty() {
+                    return empty_pane();
+                }
+                self.write_result(&target, modifiers.contains(KeyModifiers::ALT))
+                    .create_pane(u16::MAX, 1)
+            }
+            LineEdit::Cancelled => {
+                self.path_input = None;
+                empty_pane()
+            }
         }
     }
 
-    Ok(ret)
-}
+    /// Writes the values of the last result to the file, replacing it only if
+    /// its path ends with `!`, whatever is collapsed or scrolled off.
+    fn write_result(&self, target: &str, compact: bool) -> text::State {
+        let (path, overwrite) = match target.strip_suffix('!') {
+            Some(path) => (path, true),
+            None => (target, false),
+        };
+        match write_values_to(
+            Path::new(path),
+            &self.shown,
+            compact,
+            self.formatter.indent,
+            overwrite,
+            self.safe_mode,
+        ) {
+            Ok(()) => text::State {
+                text: format!(
+                    "Wrote {} document(s) to {}",
+                    format_count(self.shown.len()),
+                    path
+                ),
+                style: StyleBuilder::new().fgc(Color::Green).build(),
+            },
+            Err(e) => text::State {
+                text: format!("Failed to write to {}: {}", path, e),
+                style: StyleBuilder::new().fgc(Color::Red).build(),
+            },
+        }
+    }
 
-#[derive(Clone)]
-pub struct JsonStreamProvider {
-    formatter: RowFormatter,
-    max_streams: Option<usize>,
-}
+    /// Moves the cursor to the next (or previous) row matching the searched pattern.
+    fn find_match(&mut self, forward: bool) -> text::State {
+        let Some(pattern) = &self.search_pattern else {
+            return text::State {
+                text: "Search a pattern first".to_string(),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            };
+        };
+        let found = match &self.view {
+            Some(View::Gron(gron)) => gron.find(pattern, self.case_sensitive_search, forward),
+            _ => self
+                .stream
+                .find(pattern, self.case_sensitive_search, forward),
+        };
+        match found {
+            Some((index, wrapped)) => {
+                match &mut self.view {
+                    Some(View::Gron(gron)) => gron.set_position(index),
+                    _ => self.stream.reveal(index),
+                }
+                text::State {
+                    text: if wrapped {
+                        format!("/{} (wrapped around)", pattern)
+                    } else {
+                        format!("/{}", pattern)
+                    },
+                    style: StyleBuilder::new().fgc(Color::Grey).build(),
+                }
+            }
+            None => text::State {
+                text: format!("Pattern not found: {}", pattern),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            },
+        }
+    }
 
-impl JsonStreamProvider {
-    pub fn new(formatter: RowFormatter, max_streams: Option<usize>) -> Self {
-        Self {
-            formatter,
-            max_streams,
+    /// Bookmarks the row at the cursor, or removes its bookmark.
+    fn toggle_bookmark(&mut self) -> text::State {
+        let Some(key) = self.stream.keys().nth(self.stream.position()) else {
+            return text::State {
+                text: "Nothing to bookmark".to_string(),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            };
+        };
+        let text = if self.bookmarks.remove(&key) {
+            format!("Removed the bookmark of {}", key.1)
+        } else {
+            let text = format!("Bookmarked {}", key.1);
+            self.bookmarks.insert(key);
+            text
+        };
+        self.index_bookmarks();
+        text::State {
+            text,
+            style: StyleBuilder::new().fgc(Color::Grey).build(),
         }
     }
 
-    fn deserialize_json(&self, json_str: &str) -> anyhow::Result<Vec<serde_json::Value>> {
-        let deserializer: serde_json::StreamDeserializer<'_, serde_json::de::StrRead<'_>, Value> =
-            Deserializer::from_str(json_str).into_iter::<serde_json::Value>();
-        let results = match self.max_streams {
-            Some(l) => deserializer.take(l).collect::<Result<Vec<_>, _>>(),
-            None => deserializer.collect::<Result<Vec<_>, _>>(),
+    /// Finds the rows bookmarked among the current ones.
+    fn index_bookmarks(&mut self) {
+        self.bookmarked_rows = if self.bookmarks.is_empty() {
+            BTreeSet::new()
+        } else {
+            self.stream
+                .keys()
+                .enumerate()
+                // A closing bracket has the same key as its opening one.
+                .filter(|(i, key)| {
+                    !matches!(self.stream.rows()[*i].v, RowValue::Close { .. })
+                        && self.bookmarks.contains(key)
+                })
+                .map(|(i, _)| i)
+                .collect()
         };
-        results.map_err(anyhow::Error::from)
     }
-}
 
-#[async_trait::async_trait]
-impl ViewProvider for JsonStreamProvider {
-    async fn provide(&mut self, item: &'static str) -> anyhow::Result<Json> {
-        let stream = self.deserialize_json(item)?;
-        let static_stream = Box::leak(stream.into_boxed_slice());
-        Json::new(std::mem::take(&mut self.formatter), static_stream)
+    /// Moves to the next bookmarked row (or the previous one if `forward` is unset),
+    /// wrapping around the ends.
+    fn find_bookmark(&mut self, forward: bool) -> text::State {
+        let position = self.stream.position();
+        let (found, wrapped) = if forward {
+            match self.bookmarked_rows.range(position + 1..).next() {
+                Some(index) => (Some(index), false),
+                None => (self.bookmarked_rows.first(), true),
+            }
+        } else {
+            match self.bookmarked_rows.range(..position).next_back() {
+                Some(index) => (Some(index), false),
+                None => (self.bookmarked_rows.last(), true),
+            }
+        };
+        let Some(&index) = found else {
+            return text::State {
+                text: "No bookmark in the result".to_string(),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            };
+        };
+        self.stream.reveal(index);
+        let nth = self.bookmarked_rows.range(..=index).count();
+        text::State {
+            text: format!(
+                "Bookmark {}/{}: {}{}",
+                nth,
+                self.bookmarked_rows.len(),
+                self.stream.current_path(),
+                if wrapped { " (wrapped around)" } else { "" }
+            ),
+            style: StyleBuilder::new().fgc(Color::Grey).build(),
+        }
     }
-}
 
-#[async_trait::async_trait]
-impl SearchProvider for JsonStreamProvider {
-    async fn provide(
+    /// Puts the path at the cursor into the editor, after the query or in place of it.
+    fn insert_current(&mut self) -> text::State {
+        let path = self.stream.current_path();
+        self.query_to_edit = Some(match (self.insert_path, self.query.trim()) {
+            (PathInsertion::Replace, _) | (PathInsertion::Append, "" | ".") => path,
+            (PathInsertion::Append, query) => format!("{} | {}", query, path),
+        });
+        text::State {
+            text: "Inserted the path into the filter".to_string(),
+            style: StyleBuilder::new().fgc(Color::Green).build(),
+        }
+    }
+
+    /// Saves the change made to the remembered expansion into the state file,
+    /// returning the guide with `message` or the failure.
+    ///
+    /// The change is applied to the saved expansion rather than overwriting it,
+    /// so that the patterns remembered meanwhile by other instances of jnv
+    /// on the same input are kept (and adopted by this one).
+    fn save_expansion<F: FnOnce(&mut ExpansionMemory)>(
         &mut self,
-        item: &str,
-    ) -> anyhow::Result<Box<dyn Iterator<Item = String> + Send>> {
-        let stream = self.deserialize_json(item)?;
-        let static_stream = Box::leak(stream.into_boxed_slice());
-        Ok(Box::new(jsonz::get_all_paths(static_stream.iter())))
+        message: String,
+        change: F,
+    ) -> text::State {
+        let result = match &self.state_file {
+            Some(file) => file.update(|state| {
+                change(&mut state.expansion);
+                self.expansion = state.expansion.clone();
+            }),
+            None => Err(anyhow::anyhow!("no directory to store the state")),
+        };
+        match result {
+            Ok(()) => text::State {
+                text: message,
+                style: StyleBuilder::new().fgc(Color::Green).build(),
+            },
+            Err(e) => text::State {
+                text: format!("{} (not saved: {})", message, e),
+                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+            },
+        }
+    }
+
+    /// Shows the error of the query together with the cached result
+    /// of its longest prefix (e.g. `.items` for `.items[`), if any,
+    /// rather than leaving the view blank.
+    fn fall_back_to_prefix(
+        &mut self,
+        area: (u16, u16),
+        input: &str,
+        error: StyledGraphemes,
+    ) -> (Option<Pane>, Option<Pane>, QueryOutcome) {
+        let Some((prefix, values)) = self.cache.prefix_search(input) else {
+            return (Some(line_pane(error, area)), None, QueryOutcome::Failed);
+        };
+        self.rebuild(values);
+        if self.query != prefix {
+            self.query = prefix;
+            self.collected.clear();
+        }
+        let note = StyledGraphemes::from_str(
+            format!(" · showing the cached result of `{}`", self.query),
+            error_style(),
+        );
+        (
+            Some(line_pane([error, note].into_iter().collect(), area)),
+            Some(self.create_pane(area.0, area.1)),
+            QueryOutcome::Fallback,
+        )
+    }
+
+    /// Returns the guide describing a partially typed keybind,
+    /// or an empty one to clear the previous description.
+    fn pending_guide(&self, had_pending: bool) -> Option<Pane> {
+        match self.matcher.pending_text() {
+            Some(text) => Some(
+                text::State {
+                    text,
+                    style: StyleBuilder::new().fgc(Color::Grey).build(),
+                }
+                .create_pane(u16::MAX, 1),
+            ),
+            None if had_pending => Some(empty_pane()),
+            None => None,
+        }
+    }
+}
+
+/// Presentation of the values in place of the tree of rows.
+#[derive(Clone)]
+enum View {
+    Table(Table),
+    /// A `path = value;` line per leaf, as `gron` prints.
+    Gron(Gron),
+    /// The rows containing a pattern, under their containers.
+    Grep(Grep),
+    /// The values pretty-printed as text, without a row at the cursor.
+    Pretty(Pretty),
+}
+
+impl View {
+    fn name(&self) -> &'static str {
+        match self {
+            View::Table(_) => "table",
+            View::Gron(_) => "gron",
+            View::Grep(_) => "grep",
+            View::Pretty(_) => "pretty",
+        }
+    }
+
+    fn position(&self) -> usize {
+        match self {
+            View::Table(table) => table.position(),
+            View::Gron(gron) => gron.position(),
+            View::Grep(grep) => grep.position(),
+            View::Pretty(pretty) => pretty.position(),
+        }
+    }
+
+    /// Moves the cursor to the row, or to the last one if there are fewer.
+    fn set_position(&mut self, position: usize) {
+        match self {
+            View::Table(table) => table.set_position(position),
+            View::Gron(gron) => gron.set_position(position),
+            View::Grep(grep) => grep.set_position(position),
+            View::Pretty(pretty) => pretty.set_position(position),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Visualizer for Json {
+    fn values(&self) -> Vec<serde_json::Value> {
+        self.shown.to_vec()
+    }
+
+    fn shown_values(&self) -> Arc<Vec<serde_json::Value>> {
+        self.shown.clone()
+    }
+
+    async fn content_to_copy(&self) -> String {
+        match &self.view {
+            Some(View::Table(table)) => return table.to_tsv(),
+            Some(View::Gron(gron)) => return gron.to_text(),
+            Some(View::Grep(grep)) => return grep.to_text(&self.formatter),
+            Some(View::Pretty(pretty)) => return pretty.to_text(),
+            None => {}
+        }
+        match self.raw_strings(0) {
+            Some(strings) => strings.collect::<Vec<_>>().join("\n"),
+            None if self.hide_nulls && !self.copy_hidden => {
+                let rows = self
+                    .stream
+                    .rows()
+                    .iter()
+                    .filter(|row| !stream::is_blank(row))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                self.formatter.format_raw_json(&rows)
+            }
+            None => self.formatter.format_raw_json(self.stream.rows()),
+        }
+    }
+
+    async fn create_init_pane(&mut self, area: (u16, u16)) -> Pane {
+        self.create_pane(area.0, area.1)
+    }
+
+    fn create_status_pane(&self, width: u16) -> Pane {
+        let text = [
+            self.describe_position(width),
+            (self.view.is_none() && self.column_offset > 0)
+                .then(|| format!("→ {}", self.column_offset)),
+            self.hide_nulls.then(|| "(nulls hidden)".to_string()),
+            self.receiving.then(|| "receiving…".to_string()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" · ");
+        if text.is_empty() {
+            return empty_pane();
+        }
+        text::State {
+            text,
+            style: StyleBuilder::new().fgc(Color::DarkGrey).build(),
+        }
+        .create_pane(width, 1)
+    }
+
+    fn create_init_guide_pane(&self, width: u16) -> Pane {
+        if self.skipped_lines == 0 {
+            return empty_pane();
+        }
+        text::State {
+            text: format!(
+                "Skipped {} non-JSON line(s)",
+                format_count(self.skipped_lines)
+            ),
+            style: StyleBuilder::new().fgc(Color::Yellow).build(),
+        }
+        .create_pane(width, 1)
+    }
+
+    fn set_receiving(&mut self, receiving: bool) {
+        self.receiving = receiving;
+    }
+
+    async fn create_panes_from_events(
+        &mut self,
+        area: (u16, u16),
+        events: &[Event],
+    ) -> (Option<Pane>, Pane) {
+        self.refresh_original();
+        let guide = events
+            .iter()
+            .fold(None, |guide, event| self.operate(event, area.1).or(guide));
+        (guide, self.create_pane(area.0, area.1))
+    }
+
+    fn sequence_deadline(&self) -> Option<Instant> {
+        self.matcher.deadline()
+    }
+
+    async fn create_panes_on_sequence_timeout(&mut self, area: (u16, u16)) -> (Option<Pane>, Pane) {
+        let steps = self.matcher.flush();
+        let guide = match self.apply(steps, area.1) {
+            Some(guide) => Some(guide.create_pane(u16::MAX, 1)),
+            None => self.pending_guide(true),
+        };
+        (guide, self.create_pane(area.0, area.1))
+    }
+
+    fn take_query_to_edit(&mut self) -> Option<String> {
+        self.query_to_edit.take()
+    }
+
+    fn take_text_to_copy(&mut self) -> Option<String> {
+        self.text_to_copy.take()
+    }
+
+    fn take_result(&mut self) -> Option<Arc<Vec<serde_json::Value>>> {
+        self.result.take()
+    }
+
+    async fn array_length(&self, array: &str) -> Option<usize> {
+        let query = format!("{} | length", array);
+        let first = self.json.first()?.clone();
+        let output = evaluate(
+            query,
+            Arc::new(vec![first]),
+            self.scope.clone(),
+            false,
+            None,
+            Default::default(),
+        )
+        .await
+        .ok()?;
+        output
+            .values
+            .first()?
+            .as_u64()
+            .map(|length| length as usize)
+    }
+
+    fn append(&mut self, documents: Vec<serde_json::Value>) {
+        // The cached results no longer reflect the input.
+        self.cache.clear();
+        self.original = None;
+        // Committed results stay as they are, the documents going into the input beneath them.
+        let json = match self.bases.first_mut() {
+            Some((_, input)) => Arc::make_mut(input),
+            None => Arc::make_mut(&mut self.json),
+        };
+        // Slurped, the documents go into the array of the others.
+        let json = match json.as_mut_slice() {
+            [array] if self.slurp && array.is_array() => array.as_array_mut().unwrap(),
+            _ => json,
+        };
+        json.extend(documents);
+        if let Some(max) = self.max_streams {
+            json.drain(..json.len().saturating_sub(max));
+        }
+    }
+
+    fn replace(&mut self, mut documents: Vec<serde_json::Value>) {
+        self.cache.clear();
+        self.original = None;
+        if let Some(max) = self.max_streams {
+            documents.drain(..documents.len().saturating_sub(max));
+        }
+        self.json = Arc::new(documents);
+        self.bases.clear();
+        self.input_note = Some(format!("reloaded at {}", input::local_time()));
+    }
+
+    fn commit_result(&mut self) -> Option<Arc<Vec<serde_json::Value>>> {
+        if matches!(self.query.trim(), "" | ".") {
+            return None;
+        }
+        self.cache.clear();
+        self.collected.clear();
+        self.original = None;
+        let values = self.shown.clone();
+        let query = std::mem::take(&mut self.query);
+        let replaced = std::mem::replace(&mut self.json, values.clone());
+        self.bases.push((query, replaced));
+        self.input_note = Some(format!("based on {}", self.breadcrumb()));
+        Some(values)
+    }
+
+    fn pop_base(&mut self) -> Option<(String, Option<Arc<Vec<serde_json::Value>>>)> {
+        let (query, replaced) = self.bases.pop()?;
+        self.cache.clear();
+        self.collected.clear();
+        self.original = None;
+        self.json = replaced;
+        self.input_note = Some(if self.bases.is_empty() {
+            "back to the input".to_string()
+        } else {
+            format!("back to the result of {}", self.breadcrumb())
+        });
+        let base = (!self.bases.is_empty()).then(|| self.json.clone());
+        Some((query, base))
+    }
+
+    fn show_all_results(&mut self, query: String) {
+        self.unlimited_query = Some(query);
+    }
+
+    fn toggle_slurp(&mut self) -> bool {
+        self.cache.clear();
+        self.original = None;
+        self.drop_bases();
+        // Let go of the values shown, which are the input when it is not filtered,
+        // so that the documents are moved rather than cloned (unless a query still runs on them).
+        self.shown = Arc::default();
+        let mut documents = Arc::try_unwrap(std::mem::take(&mut self.json))
+            .unwrap_or_else(|json| json.as_ref().clone());
+        self.slurp = !self.slurp;
+        let (documents, note) = if self.slurp {
+            let note = format!("slurped {} document(s) into an array", documents.len());
+            (vec![Value::Array(documents)], note)
+        } else {
+            let documents = match documents.pop() {
+                Some(Value::Array(documents)) => documents,
+                _ => Vec::new(),
+            };
+            let note = format!("split the array into {} document(s)", documents.len());
+            (documents, note)
+        };
+        self.json = Arc::new(documents);
+        self.input_note = Some(note);
+        self.slurp
+    }
+
+    async fn create_panes_on_reset(&mut self, area: (u16, u16)) -> (Option<Pane>, Pane) {
+        self.matcher.reset();
+        self.query.clear();
+        self.collected.clear();
+        self.bookmarks.clear();
+        self.bookmarked_rows.clear();
+        self.refresh_original();
+        self.rebuild(self.json.clone());
+        (
+            Some(identity_note(&self.unfiltered_note()).create_pane(area.0, area.1)),
+            self.create_pane(area.0, area.1),
+        )
+    }
+
+    async fn create_panes_from_query(
+        &mut self,
+        area: (u16, u16),
+        input: String,
+        cancel: Arc<AtomicBool>,
+    ) -> (Option<Pane>, Option<Pane>, QueryOutcome) {
+        let is_identity = matches!(input.trim(), "" | ".");
+        let input_note = self.input_note.take();
+        self.refresh_original();
+        let json = self.json.clone();
+        let cached = if is_identity {
+            None
+        } else {
+            self.cache.exact_search(&input)
+        };
+        // Time jq took, if run.
+        let mut elapsed = None;
+        // Whether values past the limit were left out.
+        let mut truncated = false;
+        // Lifted for the query the limit was lifted for, until it changes.
+        let limit = self
+            .max_results
+            .filter(|_| self.unlimited_query.as_ref() != Some(&input));
+        // No need to run jq to show the input as is, or a result already obtained.
+        let result = if is_identity {
+            Ok((Cow::Borrowed(json.as_slice()), None))
+        } else if let Some(values) = &cached {
+            Ok((Cow::Borrowed(values.as_slice()), None))
+        } else {
+            let started = Instant::now();
+            let output = evaluate(
+                input.clone(),
+                json.clone(),
+                self.scope.clone(),
+                self.strict_errors,
+                limit,
+                cancel,
+            )
+            .await;
+            elapsed = Some(started.elapsed());
+            output.map(|output| {
+                truncated = output.truncated;
+                (Cow::Owned(output.values), output.errors)
+            })
+        };
+        // Appended to the messages about the evaluation.
+        let took = match elapsed {
+            Some(elapsed) if self.show_timing => format!(" (in {})", format_elapsed(elapsed)),
+            _ => String::new(),
+        };
+        match result {
+            Ok((ret, Some(errors))) if ret.is_empty() => {
+                let text = failure_hint(&input, &errors) + &took;
+                self.fall_back_to_prefix(
+                    area,
+                    &input,
+                    StyledGraphemes::from_str(text, error_style()),
+                )
+            }
+            Ok((ret, errors)) => {
+                // Bookmarks are about the result they were set on.
+                let cleared = if self.query != input {
+                    self.bookmarked_rows.clear();
+                    std::mem::take(&mut self.bookmarks).len()
+                } else {
+                    0
+                };
+                let mut guide = None;
+                if let Some(errors) = &errors {
+                    guide = Some(
+                        text::State {
+                            text: format!(
+                                "{} values · {} {} errored: {} (first at doc {})",
+                                format_count(ret.len()),
+                                format_count(errors.documents),
+                                if errors.documents == 1 {
+                                    "document"
+                                } else {
+                                    "documents"
+                                },
+                                errors.first,
+                                errors.first_document,
+                            ) + &took,
+                            style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                        }
+                        .create_pane(area.0, area.1),
+                    );
+                } else if truncated {
+                    guide = Some(
+                        text::State {
+                            text: format!(
+                                "showing first {} results (truncated){}",
+                                format_count(ret.len()),
+                                took
+                            ),
+                            style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                        }
+                        .create_pane(area.0, area.1),
+                    );
+                } else if ret.iter().all(|val| *val == Value::Null) {
+                    guide = Some(text::State {
+                        text: format!("jq returned 'null', which may indicate a typo or incorrect filter: `{}`{}", input, took),
+                        style: StyleBuilder::new()
+                            .fgc(Color::Yellow)
+                            .attrs(Attributes::from(Attribute::Bold))
+                            .build(),
+                    }.create_pane(area.0, area.1));
+                } else if cleared > 0 {
+                    guide = Some(
+                        identity_note(&format!(
+                            "cleared {} bookmark(s), as the result changed",
+                            cleared
+                        ))
+                        .create_pane(area.0, area.1),
+                    );
+                } else if let Some(note) = &input_note {
+                    guide = Some(identity_note(note).create_pane(area.0, area.1));
+                } else if is_identity {
+                    guide =
+                        Some(identity_note(&self.unfiltered_note()).create_pane(area.0, area.1));
+                } else if looks_identical(&json, &ret) {
+                    guide = Some(
+                        identity_note("result is identical to the input")
+                            .create_pane(area.0, area.1),
+                    );
+                } else if cached.is_some() {
+                    guide = Some(identity_note("result from cache").create_pane(area.0, area.1));
+                } else if let Some(elapsed) = elapsed.filter(|_| self.show_timing) {
+                    guide = Some(
+                        identity_note(&format!(
+                            "{} {} in {}",
+                            format_count(ret.len()),
+                            if ret.len() == 1 { "value" } else { "values" },
+                            format_elapsed(elapsed)
+                        ))
+                        .create_pane(area.0, area.1),
+                    );
+                }
+
+                let (values, owned) = match ret {
+                    Cow::Owned(values) => (Arc::new(values), true),
+                    Cow::Borrowed(_) => (cached.unwrap_or(json), false),
+                };
+                let previous = std::mem::replace(&mut self.stream, JsonStream::new([]));
+                self.rebuild(values.clone());
+                if self.query != input {
+                    self.collected.clear();
+                } else {
+                    // Refreshed (e.g. on appended documents), so keep the view as it was.
+                    self.stream.keep_state_of(&previous, input_note.is_some());
+                    self.index_bookmarks();
+                }
+                self.result = if owned {
+                    // Only complete results are reused.
+                    if errors.is_none() && !truncated {
+                        self.cache.insert(&input, values.clone());
+                    }
+                    Some(values)
+                } else {
+                    None
+                };
+                self.query = input;
+
+                (
+                    guide,
+                    Some(self.create_pane(area.0, area.1)),
+                    QueryOutcome::Succeeded,
+                )
+            }
+            Err(e) if e.is::<Timeout>() => {
+                // The previous result stays, as the filter may only be half typed.
+                let guide = text::State {
+                    text: e.to_string(),
+                    style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                }
+                .create_pane(area.0, area.1);
+                (
+                    Some(guide),
+                    Some(self.create_pane(area.0, area.1)),
+                    QueryOutcome::Fallback,
+                )
+            }
+            Err(e) => {
+                let guide = match e.downcast_ref::<FilterError>() {
+                    Some(e) => e.guide(&input, self.error_span_style),
+                    None => StyledGraphemes::from_str(format!("jq failed: `{}`", e), error_style()),
+                };
+                let guide = [guide, StyledGraphemes::from_str(took, error_style())]
+                    .into_iter()
+                    .collect();
+                self.fall_back_to_prefix(area, &input, guide)
+            }
+        }
+    }
+}
+
+/// Outcome of a key typed in a line being edited in the guide.
+enum LineEdit {
+    Editing,
+    /// Enter was pressed, with these modifiers.
+    Confirmed(KeyModifiers),
+    Cancelled,
+}
+
+/// Edits the line typed in the guide (e.g. the pattern to search) by the event.
+fn edit_line(input: &mut String, event: &Event) -> LineEdit {
+    let Event::Key(KeyEvent {
+        code,
+        modifiers,
+        kind: KeyEventKind::Press,
+        ..
+    }) = event
+    else {
+        return LineEdit::Editing;
+    };
+    match code {
+        KeyCode::Char(ch) if matches!(*modifiers, KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+            input.push(typed_char(*ch, *modifiers));
+        }
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Enter => return LineEdit::Confirmed(*modifiers),
+        KeyCode::Esc => return LineEdit::Cancelled,
+        _ => {}
+    }
+    LineEdit::Editing
+}
+
+/// Applies the action to the stream if it moves the cursor or expands the rows,
+/// returning whether it does.
+fn move_in(stream: &mut JsonStream, action: JsonViewerAction, page: usize) -> bool {
+    match action {
+        JsonViewerAction::Up => {
+            stream.up();
+        }
+        JsonViewerAction::Down => {
+            stream.down();
+        }
+        JsonViewerAction::PageUp => {
+            stream.up_by(page);
+        }
+        JsonViewerAction::PageDown => {
+            stream.down_by(page);
+        }
+        JsonViewerAction::HalfPageUp => {
+            stream.up_by(page.div_ceil(2));
+        }
+        JsonViewerAction::HalfPageDown => {
+            stream.down_by(page.div_ceil(2));
+        }
+        JsonViewerAction::MoveToTail => {
+            stream.tail();
+        }
+        JsonViewerAction::MoveToHead => {
+            stream.head();
+        }
+        JsonViewerAction::Toggle => {
+            stream.toggle();
+        }
+        JsonViewerAction::ExpandAll => {
+            stream.set_nodes_visibility(false);
+        }
+        JsonViewerAction::CollapseAll => {
+            stream.set_nodes_visibility(true);
+        }
+        JsonViewerAction::MoveToParent => {
+            stream.parent();
+        }
+        JsonViewerAction::NextSibling => {
+            stream.next_sibling();
+        }
+        JsonViewerAction::PrevSibling => {
+            stream.prev_sibling();
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Drops the first `offset` columns of the line, a grapheme at a time,
+/// a wide character cut in two giving way to spaces.
+fn shift(line: StyledGraphemes, offset: usize) -> StyledGraphemes {
+    let mut skipped = 0;
+    let mut shifted = StyledGraphemes::default();
+    for g in line.iter() {
+        if skipped < offset {
+            skipped += g.width();
+            for _ in offset..skipped {
+                shifted.push_back(StyledGrapheme::new(' ', ContentStyle::default()));
+            }
+            continue;
+        }
+        shifted.push_back(g.clone());
+    }
+    shifted
+}
+
+/// Cuts the line to the width, or pads it with spaces up to it.
+fn fit(line: StyledGraphemes, width: u16) -> StyledGraphemes {
+    let width = width as usize;
+    let mut fitted = StyledGraphemes::default();
+    let mut current = 0;
+    for g in line.iter() {
+        if current + g.width() > width {
+            break;
+        }
+        current += g.width();
+        fitted.push_back(g.clone());
+    }
+    vec![fitted, StyledGraphemes::from(" ".repeat(width - current))]
+        .into_iter()
+        .collect()
+}
+
+fn path_prompt(input: &str) -> text::State {
+    text::State {
+        text: format!("Write to: {}", input),
+        style: StyleBuilder::new().build(),
+    }
+}
+
+fn search_prompt(input: &str) -> text::State {
+    text::State {
+        text: format!("/{}", input),
+        style: StyleBuilder::new().build(),
+    }
+}
+
+fn identity_note(text: &str) -> text::State {
+    text::State {
+        text: text.to_string(),
+        style: StyleBuilder::new()
+            .attrs(Attributes::from(Attribute::Dim))
+            .build(),
+    }
+}
+
+/// Maximum number of values compared by `looks_identical`.
+const IDENTITY_SAMPLE_SIZE: usize = 16;
+
+/// Cheaply estimates whether `output` is identical to `input`.
+///
+/// Rather than comparing everything, this compares the number of values
+/// and the hashes of the serialized form of up to `IDENTITY_SAMPLE_SIZE` values
+/// spread evenly over the stream.
+fn looks_identical(input: &[Value], output: &[Value]) -> bool {
+    if input.len() != output.len() {
+        return false;
+    }
+    sampled_indices(input.len()).all(|i| hash_value(&input[i]) == hash_value(&output[i]))
+}
+
+/// Returns up to `IDENTITY_SAMPLE_SIZE` indices spread evenly over `len` values,
+/// the first one included.
+fn sampled_indices(len: usize) -> impl Iterator<Item = usize> {
+    (0..len).step_by(len.div_ceil(IDENTITY_SAMPLE_SIZE).max(1))
+}
+
+/// Drops the nulls and the empty objects and arrays from the value, at any depth,
+/// as they are hidden from the rows.
+fn without_blanks(value: Value) -> Value {
+    let blank = |value: &Value| match value {
+        Value::Null => true,
+        Value::Array(array) => array.is_empty(),
+        Value::Object(map) => map.is_empty(),
+        _ => false,
+    };
+    match value {
+        Value::Array(array) => Value::Array(
+            array
+                .into_iter()
+                .filter(|value| !blank(value))
+                .map(without_blanks)
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, value)| !blank(value))
+                .map(|(key, value)| (key, without_blanks(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Hashes the serialized form of the value without materializing it as a string.
+fn hash_value(value: &Value) -> u64 {
+    struct HashWriter(DefaultHasher);
+
+    impl io::Write for HashWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut writer = HashWriter(DefaultHasher::new());
+    // Serialization into a hasher cannot fail.
+    let _ = serde_json::to_writer(&mut writer, value);
+    writer.0.finish()
+}
+
+/// Errors raised by a filter on some of the documents.
+struct JaqErrors {
+    /// Number of documents on which the filter failed.
+    documents: usize,
+    first: String,
+    /// Index (from 1) of the document raising the first error.
+    first_document: usize,
+    /// Whether every error comes from indexing a value that cannot be indexed,
+    /// which appending `?` to the filter would suppress.
+    all_index: bool,
+}
+
+/// Tells the error of a filter failing on every document,
+/// suggesting `?` when it would suppress all of them.
+fn failure_hint(query: &str, errors: &JaqErrors) -> String {
+    let mut text = format!("jq failed: `{}`", errors.first);
+    if errors.all_index {
+        if let Some(optional) = optional_query(query) {
+            text.push_str(&format!(", try `{}`", optional));
+        }
+    }
+    text
+}
+
+/// Returns the query with `?` appended, or put before its first `//`
+/// (out of the strings) since the alternative does not catch the errors in jaq,
+/// unless it is there already.
+fn optional_query(query: &str) -> Option<String> {
+    let query = query.trim_end();
+    let (head, tail) = {
+        let mut chars = query.char_indices().peekable();
+        let mut quoted = false;
+        let mut split = (query, "");
+        while let Some((i, ch)) = chars.next() {
+            match ch {
+                '\\' if quoted => {
+                    chars.next();
+                }
+                '"' => quoted = !quoted,
+                '/' if !quoted && chars.next_if(|(_, ch)| *ch == '/').is_some() => {
+                    split = (query[..i].trim_end(), &query[i..]);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        split
+    };
+    if head.ends_with('?') {
+        return None;
+    }
+    Some(match tail {
+        "" => format!("{}?", head),
+        tail => format!("{}? {}", head, tail),
+    })
+}
+
+struct JaqOutput {
+    values: Vec<serde_json::Value>,
+    errors: Option<JaqErrors>,
+    /// Whether the filter produced more values than the limit, left out.
+    truncated: bool,
+}
+
+/// Number of the documents below which jaq runs the filters on a single thread,
+/// so that each worker has enough of them to make up for starting it.
+const MIN_DOCUMENTS_PER_WORKER: usize = 256;
+
+/// Number of the jaq evaluations running at once, the next ones waiting for a slot.
+///
+/// An evaluation given up (superseded or timed out) keeps its thread busy
+/// until jaq checks `cancel`, i.e. until the filter outputs a value,
+/// which e.g. `[range(1e9)] | length` does only at its end.
+/// The slots keep such evaluations from piling up.
+const MAX_JAQ_EVALUATIONS: usize = 4;
+
+static JAQ_EVALUATIONS: tokio::sync::Semaphore =
+    tokio::sync::Semaphore::const_new(MAX_JAQ_EVALUATIONS);
+
+/// Error of a filter which does not parse or compile.
+#[derive(Debug)]
+pub struct FilterError {
+    message: String,
+    /// Characters of the filter the error is about (past its end for a missing token).
+    span: Range<usize>,
+    /// Number of the errors besides this one.
+    others: usize,
+}
+
+impl FilterError {
+    /// Returns the first of the errors, counting the others.
+    fn first<I: ExactSizeIterator<Item = (String, Range<usize>)>>(mut errors: I) -> Option<Self> {
+        let others = errors.len().saturating_sub(1);
+        errors.next().map(|(message, span)| Self {
+            message,
+            span,
+            others,
+        })
+    }
+
+    /// Returns the guide showing the filter with the span of the error in `span_style`,
+    /// followed by the message.
+    fn guide(&self, query: &str, span_style: ContentStyle) -> StyledGraphemes {
+        // Highlight at least a character, past the end where a token is missing.
+        let span = self.span.start..self.span.end.max(self.span.start + 1);
+        let mut query = query.to_string();
+        while query.chars().count() < span.end {
+            query.push(' ');
+        }
+        let offset = "jq failed: `".chars().count();
+        let mut ret =
+            StyledGraphemes::from_str(format!("jq failed: `{}`: {}", query, self), error_style());
+        for i in span {
+            ret = ret.apply_style_at(offset + i, span_style);
+        }
+        ret
+    }
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.others {
+            0 => write!(f, "{}", self.message),
+            1 => write!(f, "{} (and 1 more error)", self.message),
+            n => write!(f, "{} (and {} more errors)", self.message, n),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+/// Error of a filter whose evaluation took longer than the timeout.
+#[derive(Debug)]
+pub struct Timeout(Duration);
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "query timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// Parses and compiles the filter, once for all the documents it runs on.
+///
+/// Fails with a [`FilterError`].
+fn compile_jaq(query: &str, scope: &FilterScope) -> anyhow::Result<Filter> {
+    #[cfg(test)]
+    tests::COMPILATIONS.with(|n| n.set(n.get() + 1));
+    let mut names = scope
+        .variables
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    if scope.env {
+        names.push("ENV".to_string());
+    }
+    let mut ctx = ParseCtx::new(names);
+    ctx.insert_natives(jaq_core::core().filter(|(name, _, _)| scope.env || name != "env"));
+    ctx.insert_defs(jaq_std::std());
+
+    // The positions in the filter still apply once its `include` directives are taken off.
+    let (query, modules) = scope
+        .library
+        .resolve(query)
+        .map_err(|(message, span)| FilterError {
+            message,
+            span,
+            others: 0,
+        })?;
+    ctx.insert_defs(modules);
+
+    let (f, errs) = jaq_parse::parse(&query, jaq_parse::main());
+    if let Some(e) = FilterError::first(errs.iter().map(|e| (e.to_string(), e.span()))) {
+        return Err(e.into());
+    }
+    let f = ctx.compile(f.unwrap());
+    // E.g. a function or a variable that is not defined.
+    let compile_errs = ctx.errs.iter().map(|(e, span)| {
+        let name = query
+            .chars()
+            .skip(span.start)
+            .take(span.len())
+            .collect::<String>();
+        let name = name.trim();
+        if !scope.env && matches!(name, "env" | "$ENV") {
+            return (
+                format!(
+                    "`{}` is not available, as the environment is hidden by --no-env",
+                    name
+                ),
+                span.clone(),
+            );
+        }
+        (format!("{} `{}`", e, name), span.clone())
+    });
+    if let Some(e) = FilterError::first(compile_errs) {
+        return Err(e.into());
+    }
+    Ok(f)
+}
+
+/// Runs the filter over every document with the engine of the scope:
+/// jaq on a blocking thread, where only `cancel` can stop a long evaluation
+/// (see [`MAX_JAQ_EVALUATIONS`]), or the external jq binary.
+///
+/// Gives up with a [`Timeout`] once the evaluation takes longer than the timeout of the scope.
+/// Its own flag being set then, `cancel` tells only whether a newer query superseded it.
+async fn evaluate(
+    query: String,
+    json_stream: Arc<Vec<Value>>,
+    scope: Arc<FilterScope>,
+    strict_errors: bool,
+    limit: Option<usize>,
+    cancel: Arc<AtomicBool>,
+) -> anyhow::Result<JaqOutput> {
+    let Some(timeout) = scope.timeout else {
+        return run_engine(query, json_stream, scope, strict_errors, limit, cancel).await;
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    let evaluation = run_engine(
+        query,
+        json_stream,
+        scope,
+        strict_errors,
+        limit,
+        stop.clone(),
+    );
+    tokio::select! {
+        output = evaluation => output,
+        _ = tokio::time::sleep(timeout) => {
+            // jaq gives up at its next check, its thread not being awaited any longer.
+            stop.store(true, Ordering::Relaxed);
+            Err(Timeout(timeout).into())
+        }
+        _ = jq::cancelled(&cancel) => {
+            stop.store(true, Ordering::Relaxed);
+            Err(anyhow::anyhow!("cancelled by a newer query"))
+        }
+    }
+}
+
+async fn run_engine(
+    query: String,
+    json_stream: Arc<Vec<Value>>,
+    scope: Arc<FilterScope>,
+    strict_errors: bool,
+    limit: Option<usize>,
+    cancel: Arc<AtomicBool>,
+) -> anyhow::Result<JaqOutput> {
+    match &scope.external {
+        Some(binary) => {
+            jq::run(
+                binary,
+                &query,
+                &json_stream,
+                &scope,
+                strict_errors,
+                limit,
+                &cancel,
+            )
+            .await
+        }
+        None => {
+            // Held until jaq returns, even once the evaluation is given up.
+            let slot = JAQ_EVALUATIONS.acquire().await?;
+            tokio::task::spawn_blocking(move || {
+                let _slot = slot;
+                run_jaq(&query, &json_stream, &scope, strict_errors, limit, &cancel)
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.into()))
+        }
+    }
+}
+
+/// Runs the filter over every document with jaq, as jq does.
+///
+/// An error stops the filter on its document only, so that the values
+/// produced by the others are still shown, unless `strict_errors` is set.
+///
+/// The documents are split between the workers of the scope, their values
+/// being put back in the order of the documents.
+///
+/// Stops once the filter produces more than `limit` values, keeping the first ones.
+///
+/// Gives up between documents and between values once `cancel` is set.
+fn run_jaq(
+    query: &str,
+    json_stream: &[serde_json::Value],
+    scope: &FilterScope,
+    strict_errors: bool,
+    limit: Option<usize>,
+    cancel: &AtomicBool,
+) -> anyhow::Result<JaqOutput> {
+    let f = compile_jaq(query, scope)?;
+    // Starting a worker costs more than filtering a few documents.
+    let workers = scope
+        .workers
+        .min(json_stream.len() / MIN_DOCUMENTS_PER_WORKER)
+        .max(1);
+    if workers == 1 {
+        return run_jaq_on(&f, json_stream, 0, scope, strict_errors, limit, cancel);
+    }
+    let chunk_len = json_stream.len().div_ceil(workers);
+    let outputs = std::thread::scope(|s| {
+        json_stream
+            .chunks(chunk_len)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let offset = i * chunk_len;
+                let f = &f;
+                s.spawn(move || run_jaq_on(f, chunk, offset, scope, strict_errors, limit, cancel))
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("jaq panicked")))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut ret = JaqOutput {
+        values: Vec::new(),
+        errors: None,
+        truncated: false,
+    };
+    for output in outputs {
+        let output = output?;
+        ret.values.extend(output.values);
+        ret.truncated |= output.truncated;
+        match (&mut ret.errors, output.errors) {
+            (Some(errors), Some(more)) => {
+                errors.documents += more.documents;
+                errors.all_index &= more.all_index;
+            }
+            (errors @ None, more) => *errors = more,
+            (Some(_), None) => {}
+        }
+    }
+    // Each worker stops at the limit, not knowing about the values of the others.
+    if let Some(limit) = limit.filter(|limit| ret.values.len() > *limit) {
+        ret.values.truncate(limit);
+        ret.truncated = true;
+    }
+    Ok(ret)
+}
+
+/// Runs the compiled filter over the documents, the first of which is at `offset` in the input.
+fn run_jaq_on(
+    f: &Filter,
+    json_stream: &[serde_json::Value],
+    offset: usize,
+    scope: &FilterScope,
+    strict_errors: bool,
+    limit: Option<usize>,
+    cancel: &AtomicBool,
+) -> anyhow::Result<JaqOutput> {
+    let mut values = scope
+        .variables
+        .iter()
+        .map(|(_, value)| Val::from(value.clone()))
+        .collect::<Vec<_>>();
+    if scope.env {
+        let env = std::env::vars()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect::<serde_json::Map<_, _>>();
+        values.push(Val::from(Value::Object(env)));
+    }
+    // No document reads further inputs (`input`, `inputs`).
+    let inputs = RcIter::new(core::iter::empty());
+
+    let mut ret = JaqOutput {
+        values: Vec::new(),
+        errors: None,
+        truncated: false,
+    };
+    let cancelled = || {
+        cancel
+            .load(Ordering::Relaxed)
+            .then(|| anyhow::anyhow!("cancelled by a newer query"))
+    };
+    for (i, input) in json_stream.iter().enumerate() {
+        if let Some(e) = cancelled() {
+            return Err(e);
+        }
+        let out = f.run((
+            Ctx::new(values.iter().cloned(), &inputs),
+            Val::from(input.clone()),
+        ));
+
+        for val in out {
+            if let Some(e) = cancelled() {
+                return Err(e);
+            }
+            match val {
+                Ok(_) if limit.is_some_and(|limit| ret.values.len() >= limit) => {
+                    ret.truncated = true;
+                    return Ok(ret);
+                }
+                Ok(val) => ret.values.push(val.into()),
+                Err(e) => {
+                    if strict_errors {
+                        return Err(anyhow::anyhow!("{} (at document {})", e, offset + i + 1));
+                    }
+                    let is_index = matches!(
+                        e,
+                        jaq_interpret::Error::Index(..)
+                            | jaq_interpret::Error::Type(_, jaq_interpret::error::Type::Iter)
+                    );
+                    match &mut ret.errors {
+                        Some(errors) => {
+                            errors.documents += 1;
+                            errors.all_index &= is_index;
+                        }
+                        None => {
+                            ret.errors = Some(JaqErrors {
+                                documents: 1,
+                                first: e.to_string(),
+                                first_document: offset + i + 1,
+                                all_index: is_index,
+                            })
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+/// How the values are written, as by the options of jq of the same names.
+pub struct OutputFormat {
+    /// Write each value on a single line.
+    pub compact: bool,
+    /// Write the strings unquoted.
+    pub raw: bool,
+    /// Number of the spaces the values are pretty-printed with.
+    pub indent: usize,
+}
+
+/// Writes the values one after another as `jq` does with the format.
+fn write_values<W: Write>(values: &[Value], format: &OutputFormat, out: &mut W) -> io::Result<()> {
+    let indent = " ".repeat(format.indent);
+    for value in values {
+        if let (true, Value::String(s)) = (format.raw, value) {
+            out.write_all(s.as_bytes())?;
+        } else if format.compact {
+            serde_json::to_writer(&mut *out, value)?;
+        } else {
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            value.serialize(&mut serde_json::Serializer::with_formatter(
+                &mut *out, formatter,
+            ))?;
+        }
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+/// Writes the values to the file, creating its parent directories,
+/// as `write_values` does unless `raw`.
+///
+/// An existing file is only replaced if `overwrite`.
+fn write_values_to(
+    path: &Path,
+    values: &[Value],
+    compact: bool,
+    indent: usize,
+    overwrite: bool,
+    safe_mode: SafeMode,
+) -> anyhow::Result<()> {
+    if !overwrite && path.exists() {
+        return Err(anyhow::anyhow!(
+            "the file exists (end the path with ! to overwrite it)"
+        ));
+    }
+    let mut out = io::BufWriter::new(safe_mode.create_file(path)?);
+    write_values(
+        values,
+        &OutputFormat {
+            compact,
+            raw: false,
+            indent,
+        },
+        &mut out,
+    )?;
+    Ok(())
+}
+
+/// Runs the filter over the documents without the interface, as jq does,
+/// writing the values with the format.
+///
+/// Fails if the filter does not parse or raises an error on any document,
+/// once the values of the others are written.
+pub async fn run_filter<W: Write>(
+    query: &str,
+    json_stream: Vec<serde_json::Value>,
+    scope: FilterScope,
+    strict_errors: bool,
+    format: &OutputFormat,
+    out: &mut W,
+) -> anyhow::Result<()> {
+    let output = evaluate(
+        query.to_string(),
+        Arc::new(json_stream),
+        Arc::new(scope),
+        strict_errors,
+        None,
+        Default::default(),
+    )
+    .await?;
+    write_values(&output.values, format, out)?;
+
+    match output.errors {
+        Some(errors) => Err(anyhow::anyhow!(
+            "{} document(s) errored: {} (first at document {})",
+            format_count(errors.documents),
+            errors.first,
+            errors.first_document
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Wraps the documents into one array if `slurp`, as `jq -s` does, moving them.
+pub fn slurp_if(slurp: bool, documents: Vec<Value>) -> Vec<Value> {
+    if slurp {
+        vec![Value::Array(documents)]
+    } else {
+        documents
+    }
+}
+
+/// Formats the time taken, in milliseconds under a second.
+fn format_elapsed(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(1) {
+        format!("{}ms", elapsed.as_millis())
+    } else {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    }
+}
+
+/// Formats the number with thousands separators (e.g. "1,742").
+pub fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut ret = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, ch) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            ret.push(',');
+        }
+        ret.push(ch);
+    }
+    ret
+}
+
+fn error_style() -> ContentStyle {
+    StyleBuilder::new()
+        .fgc(Color::Red)
+        .attrs(Attributes::from(Attribute::Bold))
+        .build()
+}
+
+/// Creates the pane of the line, wrapped to the width.
+fn line_pane(line: StyledGraphemes, area: (u16, u16)) -> Pane {
+    let (matrix, _) = line.matrixify(area.0 as usize, area.1 as usize, 0);
+    Pane::new(matrix, 0)
+}
+
+#[derive(Clone)]
+pub struct JsonStreamProvider {
+    settings: JsonSettings,
+    max_streams: Option<usize>,
+    matcher: SequenceMatcher<JsonViewerAction>,
+    state_file: Option<StateFile>,
+    /// Skip the lines of the input which are not JSON.
+    lenient: bool,
+    /// Wrap the documents of the input into one array, as `jq -s` does.
+    slurp: bool,
+    progress: StartupProgress,
+    /// Keep the latest `max_streams` documents rather than the first ones,
+    /// as more are appended to the followed input.
+    follow: bool,
+}
+
+/// How the documents are read from the input.
+pub struct ReadSettings {
+    /// Number of the documents kept (all if unset).
+    pub max_streams: Option<usize>,
+    /// Skip the lines of the input which are not JSON.
+    pub lenient: bool,
+    /// Wrap the documents of the input into one array, as `jq -s` does.
+    pub slurp: bool,
+    /// Keep the latest `max_streams` documents rather than the first ones,
+    /// as more are appended to the followed input.
+    pub follow: bool,
+}
+
+impl JsonStreamProvider {
+    pub fn new(
+        settings: &JsonSettings,
+        read: &ReadSettings,
+        keybinds: &Keybinds,
+        state_file: Option<StateFile>,
+        progress: StartupProgress,
+    ) -> Self {
+        Self {
+            settings: settings.clone(),
+            max_streams: read.max_streams,
+            matcher: SequenceMatcher::new(
+                keybinds.on_json_viewer.bindings(),
+                keybinds.sequence_timeout,
+            ),
+            state_file,
+            lenient: read.lenient,
+            slurp: read.slurp,
+            progress,
+            follow: read.follow,
+        }
+    }
+
+    /// Deserializes up to `max_streams` documents,
+    /// reporting the bytes parsed so far to `progress`.
+    ///
+    /// If `lenient`, the lines which do not parse (e.g. plain-text warnings among logs)
+    /// are skipped instead of failing, and their number is returned with the documents.
+    pub fn deserialize_json(
+        json_str: &str,
+        max_streams: Option<usize>,
+        lenient: bool,
+        progress: Option<&StartupProgress>,
+    ) -> anyhow::Result<(Vec<serde_json::Value>, usize)> {
+        let mut ret = Vec::new();
+        let mut skipped = 0;
+        // Start of the part being parsed, past the lines skipped.
+        let mut offset = 0;
+        'parse: loop {
+            let mut deserializer: serde_json::StreamDeserializer<
+                '_,
+                serde_json::de::StrRead<'_>,
+                Value,
+            > = Deserializer::from_str(&json_str[offset..]).into_iter::<serde_json::Value>();
+            while max_streams.is_none_or(|max| ret.len() < max) {
+                match deserializer.next() {
+                    Some(Ok(value)) => ret.push(value),
+                    // Skip the line the error is on, from the end of the last document.
+                    Some(Err(_)) if lenient => {
+                        let rest = &json_str[offset + deserializer.byte_offset()..];
+                        let start = json_str.len() - rest.trim_start().len();
+                        skipped += 1;
+                        match json_str[start..].find('\n') {
+                            Some(end) => {
+                                offset = start + end + 1;
+                                continue 'parse;
+                            }
+                            None => break 'parse,
+                        }
+                    }
+                    Some(Err(e)) => return Err(e.into()),
+                    None => break 'parse,
+                }
+                if let Some(progress) = progress {
+                    progress.set_processed(offset + deserializer.byte_offset());
+                }
+            }
+            break;
+        }
+        Ok((ret, skipped))
+    }
+}
+
+#[async_trait::async_trait]
+impl ViewProvider for JsonStreamProvider {
+    async fn provide(&mut self, item: Arc<str>) -> anyhow::Result<Json> {
+        // When following, the latest documents are kept by `Json` instead.
+        let limit = if self.follow { None } else { self.max_streams };
+        let (stream, skipped_lines) =
+            Self::deserialize_json(&item, limit, self.lenient, Some(&self.progress))?;
+        let stream = slurp_if(self.slurp, stream);
+        Json::new(
+            &self.settings,
+            stream,
+            self.follow.then_some(self.max_streams).flatten(),
+            self.matcher.clone(),
+            self.state_file.clone(),
+            skipped_lines,
+            self.slurp,
+        )
+    }
+
+    fn deserialize(&self, item: &str) -> anyhow::Result<Vec<serde_json::Value>> {
+        let limit = if self.follow { None } else { self.max_streams };
+        Self::deserialize_json(item, limit, self.lenient, None)
+            .map(|(stream, _)| slurp_if(self.slurp, stream))
+    }
+
+    fn set_slurp(&mut self, slurp: bool) {
+        self.slurp = slurp;
+    }
+}
+
+#[async_trait::async_trait]
+impl SearchProvider for JsonStreamProvider {
+    async fn provide(
+        &mut self,
+        item: Arc<str>,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = PathEntry> + Send>> {
+        // Deserialize on a blocking thread so that the editor stays responsive.
+        let max_streams = if self.follow { None } else { self.max_streams };
+        let lenient = self.lenient;
+        let (stream, _) = tokio::task::spawn_blocking(move || {
+            JsonStreamProvider::deserialize_json(&item, max_streams, lenient, None)
+        })
+        .await??;
+        let stream = slurp_if(self.slurp, stream);
+        // The paths of a document are collected before moving to the next one,
+        // so that the documents are owned (and freed) by the iterator.
+        Ok(Box::new(stream.into_iter().flat_map(|value| {
+            paths::get_all_paths([&value]).collect::<Vec<_>>()
+        })))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use promkit::serde_json::json;
+
+    use crate::{
+        processor::{Context, Processor},
+        render::{Layout, Renderer, Viewport},
+        ContextMonitor,
+    };
+
+    pub(crate) fn scope() -> FilterScope {
+        FilterScope {
+            safe_mode: SafeMode::default(),
+            variables: Vec::new(),
+            library: Library::new(Vec::new()),
+            env: false,
+            external: None,
+            timeout: None,
+            workers: 1,
+        }
+    }
+
+    async fn eval(query: &str, documents: Vec<Value>) -> anyhow::Result<JaqOutput> {
+        evaluate(
+            query.to_string(),
+            Arc::new(documents),
+            Arc::new(scope()),
+            false,
+            None,
+            Default::default(),
+        )
+        .await
+    }
+
+    /// Filter taking seconds over a single document,
+    /// outputting a value now and then for jaq to check whether to stop.
+    const SLOW_QUERY: &str = "range(1e9) | select(. % 100000 == 0)";
+
+    #[tokio::test]
+    async fn superseded_query_stops_for_the_newer_one() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let slow = tokio::spawn(evaluate(
+            SLOW_QUERY.to_string(),
+            Arc::new(vec![Value::Null]),
+            Arc::new(scope()),
+            false,
+            None,
+            cancel.clone(),
+        ));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.store(true, Ordering::Relaxed);
+        let fast = eval(".a", vec![json!({"a": 1})]).await.unwrap();
+        assert_eq!(fast.values, vec![json!(1)]);
+
+        let slow = tokio::time::timeout(Duration::from_secs(5), slow)
+            .await
+            .expect("the slow query should stop")
+            .unwrap();
+        assert_eq!(
+            slow.err().map(|e| e.to_string()).as_deref(),
+            Some("cancelled by a newer query")
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_query_times_out() {
+        let timeout = Duration::from_millis(100);
+        let started = Instant::now();
+        let result = evaluate(
+            SLOW_QUERY.to_string(),
+            Arc::new(vec![Value::Null]),
+            Arc::new(FilterScope {
+                timeout: Some(timeout),
+                ..scope()
+            }),
+            false,
+            None,
+            Default::default(),
+        )
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let e = result.err().unwrap();
+        assert!(e.is::<Timeout>());
+        assert_eq!(e.to_string(), "query timed out after 100ms");
+    }
+
+    #[tokio::test]
+    async fn fast_query_within_the_timeout() {
+        let output = evaluate(
+            ".[]".to_string(),
+            Arc::new(vec![json!([1, 2])]),
+            Arc::new(FilterScope {
+                timeout: Some(Duration::from_secs(5)),
+                ..scope()
+            }),
+            false,
+            None,
+            Default::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.values, vec![json!(1), json!(2)]);
+    }
+
+    #[tokio::test]
+    async fn quoted_keys_select_their_value() {
+        for key in [
+            "if",
+            "__loc__",
+            "0",
+            "a.b",
+            "a-b",
+            "@timestamp",
+            "名前",
+            "tab\there",
+            "bell\u{7}",
+            "say \"hi\"",
+            "back\\slash",
+            "",
+        ] {
+            let mut object = serde_json::Map::new();
+            object.insert(key.to_string(), Value::from(1));
+            object.insert("other".to_string(), Value::from(2));
+            let output = eval(&paths::quote_segment(key), vec![Value::Object(object)])
+                .await
+                .unwrap_or_else(|e| panic!("key {:?}: {}", key, e));
+            assert_eq!(output.values, vec![Value::from(1)], "key {:?}", key);
+        }
+    }
+
+    /// Returns the values of the filter, or the hint shown as it fails on every document.
+    async fn outcome(query: &str, documents: Vec<Value>) -> Result<Vec<Value>, String> {
+        let output = eval(query, documents).await.map_err(|e| e.to_string())?;
+        match output.errors {
+            Some(errors) if output.values.is_empty() => Err(failure_hint(query, &errors)),
+            _ => Ok(output.values),
+        }
+    }
+
+    #[tokio::test]
+    async fn suggests_the_optional_operator_on_index_errors() {
+        let documents = || vec![json!({"a": "text"}), json!({"a": 1})];
+        let hint = outcome(".a.b", documents()).await.unwrap_err();
+        assert!(hint.starts_with("jq failed: `"), "{}", hint);
+        assert!(hint.ends_with(", try `.a.b?`"), "{}", hint);
+        assert_eq!(outcome(".a.b?", documents()).await, Ok(vec![]));
+
+        let hint = outcome(".a[] ", documents()).await.unwrap_err();
+        assert!(hint.ends_with(", try `.a[]?`"), "{}", hint);
+        assert_eq!(outcome(".a[]?", documents()).await, Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn suppressed_errors_need_no_hint() {
+        let documents = || vec![json!({"a": "text"}), json!({"a": {"b": 1}})];
+        assert_eq!(outcome("try .a.b", documents()).await, Ok(vec![json!(1)]));
+        assert_eq!(
+            outcome("try .a.b catch \"none\"", documents()).await,
+            Ok(vec![json!("none"), json!(1)])
+        );
+        assert_eq!(
+            outcome(".a.b? // \"none\"", documents()).await,
+            Ok(vec![json!("none"), json!(1)])
+        );
+        // `//` does not catch the errors by itself.
+        let hint = outcome(".a.b // \"none\"", vec![json!({"a": "text"})])
+            .await
+            .unwrap_err();
+        assert!(hint.ends_with(", try `.a.b? // \"none\"`"), "{}", hint);
+        assert_eq!(
+            optional_query(".a | test(\"a//b\") // .b"),
+            Some(".a | test(\"a//b\")? // .b".to_string())
+        );
+        assert_eq!(optional_query(".a.b? // .c"), None);
+    }
+
+    #[tokio::test]
+    async fn other_errors_get_no_suggestion() {
+        let hint = outcome("error(\"boom\")", vec![json!(1)])
+            .await
+            .unwrap_err();
+        assert!(!hint.contains("try"), "{}", hint);
+        // Already optional.
+        let hint = failure_hint(
+            ".a?",
+            &JaqErrors {
+                documents: 1,
+                first: "cannot index".to_string(),
+                first_document: 1,
+                all_index: true,
+            },
+        );
+        assert_eq!(hint, "jq failed: `cannot index`");
+    }
+
+    thread_local! {
+        /// Number of the filters compiled on the thread.
+        pub static COMPILATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    #[test]
+    fn filter_is_compiled_once_for_the_stream() {
+        let documents = (0..2000).map(|i| json!({"a": i})).collect::<Vec<_>>();
+        for workers in [1, 4] {
+            let scope = FilterScope { workers, ..scope() };
+            let before = COMPILATIONS.with(|n| n.get());
+            let output = run_jaq(
+                ".a * 2",
+                &documents,
+                &scope,
+                false,
+                None,
+                &AtomicBool::new(false),
+            )
+            .unwrap();
+            assert_eq!(
+                COMPILATIONS.with(|n| n.get()) - before,
+                1,
+                "{} workers",
+                workers
+            );
+            assert_eq!(output.values.len(), documents.len());
+            assert_eq!(output.values[1999], json!(3998));
+        }
+    }
+
+    #[test]
+    fn parse_error_is_the_same_without_document() {
+        let error = |documents: &[Value]| {
+            run_jaq(
+                ".a |",
+                documents,
+                &scope(),
+                false,
+                None,
+                &AtomicBool::new(false),
+            )
+            .err()
+            .map(|e| e.to_string())
+        };
+        assert!(error(&[]).is_some());
+        assert_eq!(error(&[]), error(&[json!({"a": 1})]));
+    }
+
+    pub(crate) fn settings() -> JsonSettings {
+        JsonSettings {
+            formatter: RowFormatter {
+                curly_brackets_style: ContentStyle::default(),
+                square_brackets_style: ContentStyle::default(),
+                key_style: ContentStyle::default(),
+                string_value_style: ContentStyle::default(),
+                number_value_style: ContentStyle::default(),
+                boolean_value_style: ContentStyle::default(),
+                null_value_style: ContentStyle::default(),
+                active_item_attribute: Attribute::Bold,
+                inactive_item_attribute: Attribute::Dim,
+                indent: 2,
+            },
+            strict_errors: false,
+            insert_path: PathInsertion::default(),
+            case_sensitive_search: false,
+            expand_depth: None,
+            cache_entries: 16,
+            error_span_style: ContentStyle::default(),
+            bookmark_style: ContentStyle::default(),
+            raw_output: false,
+            show_line_numbers: false,
+            show_position: false,
+            sticky_path: false,
+            hide_nulls: false,
+            copy_hidden: false,
+            show_timing: false,
+            safe_mode: SafeMode::new(true),
+            scope: Arc::new(scope()),
+            max_results: None,
+            split: false,
+        }
+    }
+
+    fn viewer_with(settings: &JsonSettings, documents: Vec<Value>) -> Json {
+        let keybinds = Keybinds::default();
+        Json::new(
+            settings,
+            documents,
+            None,
+            SequenceMatcher::new(
+                keybinds.on_json_viewer.bindings(),
+                keybinds.sequence_timeout,
+            ),
+            None,
+            0,
+            false,
+        )
+        .unwrap()
+    }
+
+    fn viewer(documents: Vec<Value>) -> Json {
+        viewer_with(&settings(), documents)
+    }
+
+    /// Processes the queries one after the other as the prompt does,
+    /// returning those sent to be remembered.
+    async fn remembered(json: Json, queries: &[&str]) -> Vec<String> {
+        let (succeeded_tx, mut succeeded_rx) = tokio::sync::mpsc::channel(queries.len());
+        let processor = Processor::new(
+            Arc::new(tokio::sync::Mutex::new(Context::new((80, 24)))),
+            succeeded_tx,
+            None,
+        );
+        let renderer = Arc::new(tokio::sync::Mutex::new(
+            Renderer::try_init_draw(
+                std::array::from_fn(|_| empty_pane()),
+                Viewport::new(Layout::default(), (80, 24)),
+                (0, 0),
+                false,
+                Box::new(io::sink()),
+            )
+            .unwrap(),
+        ));
+        let json = Arc::new(tokio::sync::Mutex::new(json));
+        for query in queries {
+            processor
+                .render_result(json.clone(), query.to_string(), renderer.clone())
+                .await;
+            processor.finish().await;
+        }
+        drop(processor);
+        let mut ret = Vec::new();
+        while let Some(query) = succeeded_rx.recv().await {
+            ret.push(query);
+        }
+        ret
+    }
+
+    #[tokio::test]
+    async fn failing_query_is_not_remembered_despite_the_cached_prefix() {
+        let mut json = viewer(vec![json!({"items": [1, 2]})]);
+        let area = (80, 24);
+        let (_, _, outcome) = json
+            .create_panes_from_query(area, ".items".to_string(), Default::default())
+            .await;
+        assert_eq!(outcome, QueryOutcome::Succeeded);
+        // The result of `.items` is still shown.
+        let (_, pane, outcome) = json
+            .create_panes_from_query(area, ".items[".to_string(), Default::default())
+            .await;
+        assert_eq!(outcome, QueryOutcome::Fallback);
+        assert!(pane.is_some());
+        assert_eq!(json.query, ".items");
+
+        let json = viewer(vec![json!({"items": [1, 2]})]);
+        assert_eq!(
+            remembered(json, &[".items", ".items[", ".items[0]"]).await,
+            [".items", ".items[0]"]
+        );
+    }
+
+    #[tokio::test]
+    async fn timed_out_query_is_not_remembered() {
+        let json = viewer_with(
+            &JsonSettings {
+                scope: Arc::new(FilterScope {
+                    timeout: Some(Duration::from_millis(100)),
+                    ..scope()
+                }),
+                ..settings()
+            },
+            vec![Value::Null],
+        );
+        let slow = format!(". as $v | {} | $v", SLOW_QUERY);
+        assert_eq!(
+            remembered(json, &["[.]", &slow, "[., .]"]).await,
+            ["[.]", "[., .]"]
+        );
+    }
+
+    #[test]
+    fn identical_streams_look_identical() {
+        let input = (0..100).map(|i| json!({"id": i})).collect::<Vec<_>>();
+        assert!(looks_identical(&input, &input.clone()));
+        assert!(looks_identical(&[], &[]));
+    }
+
+    #[test]
+    fn streams_of_different_lengths_differ() {
+        let input = (0..100).map(|i| json!({"id": i})).collect::<Vec<_>>();
+        assert!(!looks_identical(&input, &input[..99]));
+        assert!(!looks_identical(&input[..1], &[]));
+    }
+
+    #[test]
+    fn change_at_a_sampled_index_is_noticed() {
+        let input = (0..100).map(|i| json!({"id": i})).collect::<Vec<_>>();
+        for i in sampled_indices(input.len()) {
+            let mut output = input.clone();
+            output[i] = json!({"id": -1});
+            assert!(!looks_identical(&input, &output), "index {}", i);
+        }
+    }
+
+    #[test]
+    fn samples_are_bounded() {
+        for len in 0..=300 {
+            let indices = sampled_indices(len).collect::<Vec<_>>();
+            assert!(indices.len() <= IDENTITY_SAMPLE_SIZE, "length {}", len);
+            assert!(indices.iter().all(|&i| i < len), "length {}", len);
+            if len <= IDENTITY_SAMPLE_SIZE {
+                assert_eq!(indices, (0..len).collect::<Vec<_>>());
+            }
+        }
+        assert_eq!(sampled_indices(31).count(), 16);
+        assert_eq!(sampled_indices(17).count(), 9);
+    }
+
+    fn deserialize(input: &str, max_streams: Option<usize>) -> (Vec<Value>, usize) {
+        JsonStreamProvider::deserialize_json(input, max_streams, true, None)
+            .unwrap_or_else(|e| panic!("input {:?}: {}", input, e))
+    }
+
+    #[test]
+    fn lenient_skips_the_bad_lines() {
+        let (a, b, c) = (json!({"a": 1}), json!({"b": 2}), json!({"c": 3}));
+        for (input, expected, skipped) in [
+            ("{\"a\": 1}\n{\"b\": 2}\n", vec![&a, &b], 0),
+            ("warning: slow\n{\"a\": 1}\n{\"b\": 2}\n", vec![&a, &b], 1),
+            ("{\"a\": 1}\nwarning: slow\n{\"b\": 2}\n", vec![&a, &b], 1),
+            ("{\"a\": 1}\n{\"b\": 2}\nwarning: slow\n", vec![&a, &b], 1),
+            ("{\"a\": 1}\n{\"b\": 2}\nwarning: slow", vec![&a, &b], 1),
+            (
+                "oops\n{\"a\": 1}\n  oops\n\n{\"b\": 2}\n{\"c\"\n{\"c\": 3}\noops",
+                vec![&a, &b, &c],
+                4,
+            ),
+            ("{\n  \"a\": 1\n}\n[\n{\"b\": 2}\n", vec![&a, &b], 1),
+            ("oops\noops\n", vec![], 2),
+            ("", vec![], 0),
+        ] {
+            let (values, n) = deserialize(input, None);
+            assert_eq!(
+                values.iter().collect::<Vec<_>>(),
+                expected,
+                "input {:?}",
+                input
+            );
+            assert_eq!(n, skipped, "input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn lenient_stops_at_max_streams() {
+        let (values, skipped) = deserialize("oops\n1\noops\n2\n3\noops\n", Some(2));
+        assert_eq!(values, vec![json!(1), json!(2)]);
+        assert_eq!(skipped, 2);
+    }
+
+    #[test]
+    fn strict_fails_on_a_bad_line() {
+        for input in ["oops\n1\n", "1\noops\n2\n", "1\n2\noops"] {
+            assert!(
+                JsonStreamProvider::deserialize_json(input, None, false, None).is_err(),
+                "input {:?}",
+                input
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn reloading_drops_the_former_input() {
+        let mut provider = JsonStreamProvider::new(
+            &settings(),
+            &ReadSettings {
+                max_streams: None,
+                lenient: false,
+                slurp: false,
+                follow: false,
+            },
+            &Keybinds::default(),
+            None,
+            StartupProgress::new(Duration::ZERO),
+        );
+        let searcher = crate::search::tests::searcher();
+        let monitor =
+            || ContextMonitor::new(Arc::new(tokio::sync::Mutex::new(Context::new((80, 24)))));
+
+        let item = Arc::<str>::from(r#"{"before": 1}"#);
+        let mut json = ViewProvider::provide(&mut provider, item.clone())
+            .await
+            .unwrap();
+        searcher
+            .spawn_load_task(&mut provider, item.clone(), 100, Duration::ZERO, monitor())
+            .await
+            .unwrap()
+            .unwrap();
+        let former_item = Arc::downgrade(&item);
+        let former_documents = Arc::downgrade(&json.json);
+        drop(item);
+
+        // As the input file is read again, the query being applied again.
+        let item = Arc::<str>::from(r#"{"after": 2}"#);
+        json.replace(provider.deserialize(&item).unwrap());
+        json.create_panes_from_query((80, 24), ".".to_string(), Default::default())
+            .await;
+        searcher
+            .spawn_load_task(&mut provider, item, 100, Duration::ZERO, monitor())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(former_item.upgrade().is_none());
+        assert!(former_documents.upgrade().is_none());
+        assert_eq!(*json.json, [json!({"after": 2})]);
+        let paths = searcher.shared_index().lock().await.candidates(|_| Some(0));
+        assert!(paths.contains(&String::from(".after")), "{:?}", paths);
+        assert!(!paths.contains(&String::from(".before")), "{:?}", paths);
+    }
+
+    #[tokio::test]
+    async fn table_falls_back_to_the_tree_once_the_result_is_not_tabular() {
+        let mut json = viewer(vec![json!({"items": [{"a": 1}, {"b": 2}], "n": 3})]);
+        let area = (80, 24);
+        json.create_panes_from_query(area, ".items".to_string(), Default::default())
+            .await;
+        assert_eq!(json.show_table().text, "Showing 2 rows as a table");
+        assert!(matches!(json.view, Some(View::Table(_))));
+
+        json.create_panes_from_query(area, ".n".to_string(), Default::default())
+            .await;
+        assert!(json.view.is_none());
+        assert_eq!(
+            json.show_table().text,
+            "Only an array of objects can be shown as a table"
+        );
+        assert!(json.view.is_none());
+    }
+
+    #[test]
+    fn workers_keep_the_order_of_the_documents() {
+        let documents = (0..10_000)
+            .map(|i| json!({"i": i, "tags": ["a", "b"]}))
+            .collect::<Vec<_>>();
+        let run = |query: &str, workers, strict, limit| {
+            let scope = FilterScope { workers, ..scope() };
+            run_jaq(
+                query,
+                &documents,
+                &scope,
+                strict,
+                limit,
+                &AtomicBool::new(false),
+            )
+        };
+        for query in [".i", ".tags[] as $t | [.i, $t]", "select(.i % 7 == 0) | .i"] {
+            let expected = run(query, 1, false, None).unwrap().values;
+            let output = run(query, 4, false, None).unwrap();
+            assert_eq!(output.values, expected, "query {}", query);
+            assert!(!output.truncated);
+        }
+        assert_eq!(
+            run(".i", 4, false, None).unwrap().values,
+            (0..10_000).map(|i| json!(i)).collect::<Vec<_>>()
+        );
+
+        // The first values past the limit, whichever worker produced them.
+        let output = run(".i", 4, false, Some(10)).unwrap();
+        assert_eq!(output.values, (0..10).map(|i| json!(i)).collect::<Vec<_>>());
+        assert!(output.truncated);
+
+        // The errors count the documents of every worker,
+        // and the first one tells where it is in the whole input.
+        let output = run(
+            "if .i % 1000 == 999 then error else . end | .i",
+            4,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(output.values.len(), 9_990);
+        assert_eq!(output.errors.unwrap().documents, 10);
+        let e = run("if .i >= 6000 then .i[0] else . end", 4, true, None)
+            .err()
+            .unwrap();
+        assert!(e.to_string().ends_with("(at document 6001)"), "{}", e);
+    }
+
+    #[test]
+    fn cancelling_stops_every_worker() {
+        let documents = (0..4 * MIN_DOCUMENTS_PER_WORKER)
+            .map(|i| json!(i))
+            .collect::<Vec<_>>();
+        let scope = FilterScope {
+            workers: 4,
+            ..scope()
+        };
+        let cancel = AtomicBool::new(false);
+        let output = std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                cancel.store(true, Ordering::Relaxed);
+            });
+            // Every worker is still on its first document, which would take minutes.
+            run_jaq(
+                "range(1e9) | select(. % 1000 == 0)",
+                &documents,
+                &scope,
+                false,
+                None,
+                &cancel,
+            )
+        });
+        assert_eq!(
+            output.err().map(|e| e.to_string()).as_deref(),
+            Some("cancelled by a newer query")
+        );
     }
 }
@@ -0,0 +1,185 @@
+use promkit::jsonz::Value;
+use serde::{Deserialize, Serialize};
+
+use super::stream::JsonStream;
+use crate::paths;
+
+/// Path patterns of containers to collapse or expand
+/// whenever the rows are rebuilt (initial load and every query result).
+///
+/// Patterns follow [`paths::matches_pattern`],
+/// e.g. `.items[].metadata.managedFields`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExpansionMemory {
+    pub collapse: Vec<String>,
+    pub expand: Vec<String>,
+}
+
+impl ExpansionMemory {
+    pub fn is_empty(&self) -> bool {
+        self.collapse.is_empty() && self.expand.is_empty()
+    }
+
+    /// Starts or stops remembering the pattern as collapsed (or expanded),
+    /// returning whether it is remembered now.
+    pub fn toggle(&mut self, pattern: &str, collapsed: bool) -> bool {
+        let (list, other) = if collapsed {
+            (&mut self.collapse, &mut self.expand)
+        } else {
+            (&mut self.expand, &mut self.collapse)
+        };
+        other.retain(|p| p != pattern);
+        if let Some(i) = list.iter().position(|p| p == pattern) {
+            list.remove(i);
+            false
+        } else {
+            list.push(pattern.to_string());
+            true
+        }
+    }
+
+    /// Remembers the pattern as collapsed (`Some(true)`) or expanded (`Some(false)`),
+    /// or forgets it (`None`).
+    pub fn set(&mut self, pattern: &str, collapsed: Option<bool>) {
+        self.collapse.retain(|p| p != pattern);
+        self.expand.retain(|p| p != pattern);
+        match collapsed {
+            Some(true) => self.collapse.push(pattern.to_string()),
+            Some(false) => self.expand.push(pattern.to_string()),
+            None => {}
+        }
+    }
+
+    /// Applies the remembered expansion to the rows.
+    ///
+    /// Collapsing is applied before expanding,
+    /// so expanding wins when a container matches both.
+    pub fn apply(&self, stream: &mut JsonStream) {
+        if self.is_empty() {
+            return;
+        }
+        let containers = stream
+            .rows()
+            .iter()
+            .zip(stream.paths())
+            .enumerate()
+            .filter(|(_, (row, _))| matches!(row.v, Value::Open { .. }))
+            .map(|(i, (_, path))| (i, path))
+            .collect::<Vec<_>>();
+        for (patterns, collapsed) in [(&self.collapse, true), (&self.expand, false)] {
+            for (i, path) in &containers {
+                if patterns.iter().any(|p| paths::matches_pattern(p, path)) {
+                    stream.set_collapsed(*i, collapsed);
+                }
+            }
+        }
+    }
+
+    /// Describes the remembered patterns in a line.
+    pub fn describe(&self) -> String {
+        if self.is_empty() {
+            return "No expansion is remembered".to_string();
+        }
+        let mut parts = Vec::new();
+        if !self.collapse.is_empty() {
+            parts.push(format!("Always collapsed: {}", self.collapse.join(", ")));
+        }
+        if !self.expand.is_empty() {
+            parts.push(format!("Always expanded: {}", self.expand.join(", ")));
+        }
+        parts.join(" | ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::serde_json::{self, json};
+
+    use super::*;
+
+    /// Returns the paths of the containers collapsed in the rows built from the values.
+    fn collapsed_after(memory: &ExpansionMemory, values: &[serde_json::Value]) -> Vec<String> {
+        let mut stream = JsonStream::new(values);
+        memory.apply(&mut stream);
+        stream
+            .rows()
+            .iter()
+            .zip(stream.paths())
+            .filter(|(row, _)| {
+                matches!(
+                    row.v,
+                    Value::Open {
+                        collapsed: true,
+                        ..
+                    }
+                )
+            })
+            .map(|(_, path)| path)
+            .collect()
+    }
+
+    #[test]
+    fn applies_to_the_result_of_every_query() {
+        let mut memory = ExpansionMemory::default();
+        assert!(memory.toggle(".items[].meta", true));
+
+        // The result of a query, then that of another one with other values.
+        assert_eq!(
+            collapsed_after(
+                &memory,
+                &[json!({"items": [{"meta": {"a": 1}}, {"meta": {"b": 2}}]})]
+            ),
+            [".items[0].meta", ".items[1].meta"]
+        );
+        assert_eq!(
+            collapsed_after(
+                &memory,
+                &[json!({"items": [{"meta": [3]}], "other": {"meta": {}}})]
+            ),
+            [".items[0].meta"]
+        );
+        // Every document of the results.
+        assert_eq!(
+            collapsed_after(
+                &memory,
+                &[
+                    json!({"items": [{"meta": {"a": 1}}]}),
+                    json!({"items": [{"meta": {"b": 2}}]}),
+                ]
+            ),
+            [".items[0].meta", ".items[0].meta"]
+        );
+    }
+
+    #[test]
+    fn expanding_wins_over_collapsing() {
+        let mut memory = ExpansionMemory::default();
+        memory.set(".items*", Some(true));
+        memory.set(".items[].meta", Some(false));
+        assert_eq!(
+            collapsed_after(&memory, &[json!({"items": [{"meta": {"a": 1}}]})]),
+            [".items", ".items[0]"]
+        );
+    }
+
+    #[test]
+    fn toggling_moves_the_pattern_between_the_lists() {
+        let mut memory = ExpansionMemory::default();
+        assert!(memory.toggle(".a", true));
+        assert_eq!(
+            (memory.collapse.clone(), memory.expand.clone()),
+            (vec![".a".to_string()], vec![])
+        );
+        // Remembered as expanded instead.
+        assert!(memory.toggle(".a", false));
+        assert_eq!(
+            (memory.collapse.clone(), memory.expand.clone()),
+            (vec![], vec![".a".to_string()])
+        );
+        // Forgotten on the second toggle.
+        assert!(!memory.toggle(".a", false));
+        assert!(memory.is_empty());
+        assert_eq!(memory.describe(), "No expansion is remembered");
+    }
+}
@@ -0,0 +1,167 @@
+//! Presentation of the rows containing a pattern, under the containers holding them,
+//! to narrow the values down without a filter.
+
+use promkit::{
+    grapheme::StyledGraphemes,
+    jsonz::{format::RowFormatter, Row, Value},
+};
+
+use super::{
+    format_count,
+    stream::{row_text, JsonStream},
+};
+
+#[derive(Clone)]
+pub struct Grep {
+    pattern: String,
+    case_sensitive: bool,
+    /// Rows shown, expanded: those containing the pattern with the containers holding them.
+    rows: Vec<Row>,
+    /// jq path of each row shown.
+    paths: Vec<String>,
+    /// Number of the rows containing the pattern.
+    matched: usize,
+    /// Number of the rows grepped.
+    total: usize,
+    /// Row at the cursor, among those shown.
+    position: usize,
+}
+
+impl Grep {
+    /// Keeps the rows whose key or value contains the pattern,
+    /// and the opening and closing rows of the containers they are in.
+    pub fn new(stream: &JsonStream, pattern: &str, case_sensitive: bool) -> Self {
+        let rows = stream.rows();
+        let needle = if case_sensitive {
+            pattern.to_string()
+        } else {
+            pattern.to_lowercase()
+        };
+        let mut kept = vec![false; rows.len()];
+        let mut matched = 0;
+        // Opening rows of the containers the row is in.
+        let mut parents = Vec::new();
+        for (index, row) in rows.iter().enumerate() {
+            if let Value::Close { .. } = row.v {
+                parents.pop();
+                continue;
+            }
+            let text = row_text(row);
+            let found = if case_sensitive {
+                text.contains(&needle)
+            } else {
+                text.to_lowercase().contains(&needle)
+            };
+            if found {
+                matched += 1;
+                kept[index] = true;
+                for &parent in &parents {
+                    kept[parent] = true;
+                }
+            }
+            if let Value::Open { .. } = row.v {
+                parents.push(index);
+            }
+        }
+        for index in 0..rows.len() {
+            if let Value::Open { close_index, .. } = rows[index].v {
+                if kept[index] {
+                    kept[close_index] = true;
+                }
+            }
+        }
+        let (rows, paths) = rows
+            .iter()
+            .zip(stream.paths())
+            .zip(&kept)
+            .filter(|(_, kept)| **kept)
+            .map(|((row, path), _)| (expanded(row), path))
+            .unzip();
+        Self {
+            pattern: pattern.to_string(),
+            case_sensitive,
+            rows,
+            paths,
+            matched,
+            total: stream.rows().len(),
+            position: 0,
+        }
+    }
+
+    /// Greps the rows of another stream for the same pattern.
+    pub fn again(&self, stream: &JsonStream) -> Self {
+        Self::new(stream, &self.pattern, self.case_sensitive)
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position.min(self.rows.len().saturating_sub(1));
+    }
+
+    /// Returns the path of the row at the cursor.
+    pub fn current_path(&self) -> Option<&str> {
+        self.paths.get(self.position).map(String::as_str)
+    }
+
+    /// Tells the number of the rows containing the pattern, e.g. "38 of 12,400 rows".
+    pub fn describe(&self) -> String {
+        format!(
+            "{} of {} rows",
+            format_count(self.matched),
+            format_count(self.total)
+        )
+    }
+
+    /// Returns the rows from the cursor, cut to the width.
+    pub fn create_lines(
+        &self,
+        formatter: &RowFormatter,
+        width: u16,
+        height: u16,
+    ) -> Vec<StyledGraphemes> {
+        let rows = &self.rows[self.position.min(self.rows.len())..];
+        formatter.format_for_terminal_display(&rows[..rows.len().min(height as usize)], width)
+    }
+
+    /// Returns the rows shown as JSON.
+    pub fn to_text(&self, formatter: &RowFormatter) -> String {
+        formatter.format_raw_json(&self.rows)
+    }
+}
+
+/// Returns the row with its container expanded, as the rows kept inside it are shown.
+fn expanded(row: &Row) -> Row {
+    let v = match &row.v {
+        Value::Open {
+            typ, close_index, ..
+        } => Value::Open {
+            typ: typ.clone(),
+            collapsed: false,
+            close_index: *close_index,
+        },
+        Value::Close {
+            typ, open_index, ..
+        } => Value::Close {
+            typ: typ.clone(),
+            collapsed: false,
+            open_index: *open_index,
+        },
+        v => v.clone(),
+    };
+    Row {
+        depth: row.depth,
+        k: row.k.clone(),
+        v,
+    }
+}
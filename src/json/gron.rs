@@ -0,0 +1,310 @@
+//! Presentation of the values as `gron` does, with an assignment per line
+//! (e.g. `json.items[0].name = "x";`), to be read (or grepped) path by path.
+
+use promkit::{
+    grapheme::StyledGraphemes,
+    jsonz::format::RowFormatter,
+    serde_json::{self, Value},
+};
+
+use crate::paths;
+
+/// Name of the root, as `gron` calls it.
+const ROOT: &str = "json";
+
+#[derive(Clone)]
+struct Statement {
+    path: String,
+    /// Value assigned, empty for an object or an array.
+    value: Value,
+    /// Whether the statement declares an object or an array assigned by those following it.
+    declares: bool,
+}
+
+impl Statement {
+    fn value_text(&self) -> String {
+        match &self.value {
+            Value::Object(_) => "{}".to_string(),
+            Value::Array(_) => "[]".to_string(),
+            value => serde_json::to_string(value).unwrap_or_default(),
+        }
+    }
+
+    fn text(&self) -> String {
+        format!("{} = {};", self.path, self.value_text())
+    }
+}
+
+#[derive(Clone)]
+pub struct Gron {
+    /// Every statement in order, the declarations of the containers included.
+    statements: Vec<Statement>,
+    /// Indices of the statements shown, those of the leaves.
+    leaves: Vec<usize>,
+    /// Leaf at the cursor.
+    position: usize,
+}
+
+impl Gron {
+    /// Builds the statements of the values,
+    /// each document being an element of the root array if there are several.
+    pub fn new(values: &[Value]) -> Self {
+        let mut statements = Vec::new();
+        match values {
+            [value] => push_statements(ROOT.to_string(), value, &mut statements),
+            values => push_statements(
+                ROOT.to_string(),
+                &Value::Array(values.to_vec()),
+                &mut statements,
+            ),
+        }
+        let leaves = statements
+            .iter()
+            .enumerate()
+            .filter(|(_, statement)| !statement.declares)
+            .map(|(i, _)| i)
+            .collect();
+        Self {
+            statements,
+            leaves,
+            position: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position.min(self.leaves.len().saturating_sub(1));
+    }
+
+    /// Returns the path of the leaf at the cursor.
+    pub fn current_path(&self) -> Option<&str> {
+        let index = self.leaves.get(self.position)?;
+        Some(&self.statements[*index].path)
+    }
+
+    /// Returns the next (or previous) leaf containing the needle from the cursor,
+    /// and whether the search wrapped around.
+    pub fn find(&self, needle: &str, case_sensitive: bool, forward: bool) -> Option<(usize, bool)> {
+        let len = self.leaves.len();
+        let needle = if case_sensitive {
+            needle.to_string()
+        } else {
+            needle.to_lowercase()
+        };
+        (1..=len)
+            .map(|offset| {
+                if forward {
+                    (self.position + offset) % len
+                } else {
+                    (self.position + len - offset % len) % len
+                }
+            })
+            .find(|&i| {
+                let text = self.statements[self.leaves[i]].text();
+                if case_sensitive {
+                    text.contains(&needle)
+                } else {
+                    text.to_lowercase().contains(&needle)
+                }
+            })
+            .map(|i| {
+                let wrapped = if forward {
+                    i <= self.position
+                } else {
+                    i >= self.position
+                };
+                (i, wrapped)
+            })
+    }
+
+    /// Returns the leaves from the cursor, cut to the width.
+    pub fn create_lines(
+        &self,
+        formatter: &RowFormatter,
+        width: u16,
+        height: u16,
+    ) -> Vec<StyledGraphemes> {
+        self.leaves[self.position.min(self.leaves.len())..]
+            .iter()
+            .take(height as usize)
+            .enumerate()
+            .map(|(i, index)| {
+                let statement = &self.statements[*index];
+                let value_style = match &statement.value {
+                    Value::String(_) => formatter.string_value_style,
+                    Value::Number(_) => formatter.number_value_style,
+                    Value::Bool(_) => formatter.boolean_value_style,
+                    Value::Null => formatter.null_value_style,
+                    Value::Object(_) => formatter.curly_brackets_style,
+                    Value::Array(_) => formatter.square_brackets_style,
+                };
+                let line: StyledGraphemes = [
+                    StyledGraphemes::from_str(&statement.path, formatter.key_style),
+                    StyledGraphemes::from(" = "),
+                    StyledGraphemes::from_str(statement.value_text(), value_style),
+                    StyledGraphemes::from(";"),
+                ]
+                .into_iter()
+                .collect();
+                truncate(line, width as usize).apply_attribute(if i == 0 {
+                    formatter.active_item_attribute
+                } else {
+                    formatter.inactive_item_attribute
+                })
+            })
+            .collect()
+    }
+
+    /// Returns every statement, which `gron --ungron` turns back into the values.
+    pub fn to_text(&self) -> String {
+        self.statements
+            .iter()
+            .map(Statement::text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Appends the statements of the value and its descendants, in document order.
+fn push_statements(path: String, value: &Value, statements: &mut Vec<Statement>) {
+    let mut stack = vec![(path, value)];
+    while let Some((path, value)) = stack.pop() {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map.iter().rev() {
+                    stack.push((join_key(&path, key), child));
+                }
+            }
+            Value::Array(array) => {
+                for (i, child) in array.iter().enumerate().rev() {
+                    stack.push((format!("{}[{}]", path, i), child));
+                }
+            }
+            _ => {}
+        }
+        let (value, declares) = match value {
+            Value::Object(map) => (Value::Object(Default::default()), !map.is_empty()),
+            Value::Array(array) => (Value::Array(Vec::new()), !array.is_empty()),
+            value => (value.clone(), false),
+        };
+        statements.push(Statement {
+            path,
+            value,
+            declares,
+        });
+    }
+}
+
+/// Appends the key as `gron` does: bare if it is an identifier, quoted otherwise.
+fn join_key(path: &str, key: &str) -> String {
+    if paths::is_identifier(key) {
+        format!("{}.{}", path, key)
+    } else {
+        format!(
+            "{}[{}]",
+            path,
+            serde_json::to_string(key).unwrap_or_default()
+        )
+    }
+}
+
+/// Cuts the line to the width, ending with `…` if it is cut.
+fn truncate(line: StyledGraphemes, width: usize) -> StyledGraphemes {
+    if line.widths() <= width {
+        return line;
+    }
+    let mut used = 0;
+    let mut ret: StyledGraphemes = line
+        .iter()
+        .take_while(|grapheme| {
+            used += grapheme.width();
+            used < width
+        })
+        .cloned()
+        .collect();
+    ret.push_back('…'.into());
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn quotes_the_keys_which_are_no_identifiers() {
+        let gron = Gron::new(&[json!({
+            "a.b": 1,
+            "say \"hi\"": 2,
+            "café": 3,
+            "日本": 4,
+            "with space": 5,
+            "1st": 6,
+            "back\\slash": 7,
+            "_ok": {"x9": 8}
+        })]);
+        assert_eq!(
+            gron.to_text(),
+            [
+                "json = {};",
+                r#"json["a.b"] = 1;"#,
+                r#"json["say \"hi\""] = 2;"#,
+                r#"json["café"] = 3;"#,
+                r#"json["日本"] = 4;"#,
+                r#"json["with space"] = 5;"#,
+                r#"json["1st"] = 6;"#,
+                r#"json["back\\slash"] = 7;"#,
+                "json._ok = {};",
+                "json._ok.x9 = 8;",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn shows_the_empty_containers_as_leaves() {
+        let gron = Gron::new(&[json!({"a": [], "b": {}, "c": [[], {}], "d": "é\n"})]);
+        let leaves = gron
+            .leaves
+            .iter()
+            .map(|&i| gron.statements[i].text())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            leaves,
+            [
+                "json.a = [];",
+                "json.b = {};",
+                "json.c[0] = [];",
+                "json.c[1] = {};",
+                r#"json.d = "é\n";"#,
+            ]
+        );
+        // The containers holding something are declared without being shown.
+        assert_eq!(gron.to_text().lines().count(), leaves.len() + 2);
+
+        // Even at the root.
+        for value in [json!([]), json!({})] {
+            let gron = Gron::new(&[value]);
+            assert_eq!(gron.len(), 1);
+            assert_eq!(gron.current_path(), Some("json"));
+        }
+        assert_eq!(Gron::new(&[]).to_text(), "json = [];");
+    }
+
+    #[test]
+    fn puts_several_documents_in_an_array() {
+        let gron = Gron::new(&[json!(1), json!({"a": 2})]);
+        assert_eq!(
+            gron.to_text(),
+            "json = [];\njson[0] = 1;\njson[1] = {};\njson[1].a = 2;"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Running of the filters with an external jq binary instead of jaq,
+//! for the builtins and the behaviors jaq lacks.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use promkit::serde_json::{self, Deserializer, Value};
+use tokio::io::AsyncWriteExt;
+
+use super::{FilterScope, JaqErrors, JaqOutput};
+
+/// Interval at which `cancel` is checked while jq runs.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Runs the filter over every document with the jq binary, fed with a document per line.
+///
+/// As with jaq, an error stops the filter on its document only (which jq does too)
+/// unless `strict_errors` is set. The messages of jq are kept as they are.
+///
+/// Keeps the first `limit` values, jq running to its end nonetheless.
+///
+/// Kills jq once `cancel` is set.
+pub async fn run(
+    binary: &str,
+    query: &str,
+    json_stream: &[Value],
+    scope: &FilterScope,
+    strict_errors: bool,
+    limit: Option<usize>,
+    cancel: &AtomicBool,
+) -> anyhow::Result<JaqOutput> {
+    let mut input = Vec::new();
+    for value in json_stream {
+        serde_json::to_writer(&mut input, value)?;
+        input.push(b'\n');
+    }
+
+    let mut command = scope.safe_mode.command(resolve(binary))?;
+    for (name, value) in &scope.variables {
+        command
+            .arg("--argjson")
+            .arg(name)
+            .arg(serde_json::to_string(value)?);
+    }
+    for path in scope.library.paths() {
+        command.arg("-L").arg(path);
+    }
+    if !scope.env {
+        // Past resolving the binary, which needs `PATH`.
+        command.env_clear();
+    }
+    let mut child = command
+        .arg(query)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Killed as the output is no longer awaited, e.g. once cancelled.
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("failed to run {}: {}", binary, e))?;
+
+    let mut stdin = child.stdin.take();
+    let feed = async move {
+        if let Some(stdin) = &mut stdin {
+            // jq may exit before reading everything, e.g. on a syntax error.
+            let _ = stdin.write_all(&input).await;
+        }
+    };
+    let output = async { tokio::join!(feed, child.wait_with_output()).1 };
+    let output = tokio::select! {
+        output = output => output?,
+        _ = cancelled(cancel) => return Err(anyhow::anyhow!("cancelled by a newer query")),
+    };
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let errors = stderr
+        .lines()
+        .filter(|line| line.starts_with("jq: error"))
+        .collect::<Vec<_>>();
+    // 5 is for the errors raised on documents, the others failing the filter as a whole.
+    match output.status.code() {
+        Some(0) => {}
+        Some(5) if !strict_errors && !errors.is_empty() => {}
+        _ => {
+            let message = stderr.trim();
+            return Err(anyhow::anyhow!(if message.is_empty() {
+                format!("{} exited with {}", binary, output.status)
+            } else {
+                message.to_string()
+            }));
+        }
+    }
+
+    let mut values = Deserializer::from_slice(&output.stdout)
+        .into_iter::<Value>()
+        .take(limit.map_or(usize::MAX, |limit| limit + 1))
+        .collect::<Result<Vec<_>, _>>()?;
+    let truncated = limit.is_some_and(|limit| values.len() > limit);
+    if let Some(limit) = limit {
+        values.truncate(limit);
+    }
+    Ok(JaqOutput {
+        values,
+        truncated,
+        errors: errors.first().map(|first| JaqErrors {
+            documents: errors.len(),
+            first: first.to_string(),
+            first_document: document_of(first).unwrap_or_default(),
+            all_index: errors
+                .iter()
+                .all(|line| line.contains("Cannot index") || line.contains("Cannot iterate")),
+        }),
+    })
+}
+
+/// Returns the document an error is raised on, told by jq as the line of its input
+/// (e.g. `jq: error (at <stdin>:3): ...`), which holds one document per line.
+fn document_of(error: &str) -> Option<usize> {
+    let (_, rest) = error.split_once("(at <stdin>:")?;
+    rest.split_once(')')?.0.parse().ok()
+}
+
+/// Returns the path of the binary, looked up on `PATH` unless it is a path already,
+/// so that it is found even once the environment of jq is cleared.
+fn resolve(binary: &str) -> PathBuf {
+    if Path::new(binary).components().count() > 1 {
+        return PathBuf::from(binary);
+    }
+    env::var_os("PATH")
+        .into_iter()
+        .flat_map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+        .flat_map(|dir| {
+            [
+                dir.join(binary),
+                dir.join(format!("{}{}", binary, env::consts::EXE_SUFFIX)),
+            ]
+        })
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(binary))
+}
+
+/// Resolves once `cancel` is set.
+pub async fn cancelled(cancel: &AtomicBool) {
+    while !cancel.load(Ordering::Relaxed) {
+        tokio::time::sleep(CANCEL_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::serde_json::json;
+
+    use super::*;
+    use crate::{json::Library, safe::SafeMode};
+
+    fn scope(env: bool, safe_mode: bool) -> FilterScope {
+        FilterScope {
+            variables: Vec::new(),
+            library: Library::new(Vec::new()),
+            env,
+            external: Some("jq".to_string()),
+            safe_mode: SafeMode::new(safe_mode),
+            timeout: None,
+            workers: 1,
+        }
+    }
+
+    fn jq_is_installed() -> bool {
+        resolve("jq").is_absolute()
+    }
+
+    #[test]
+    fn resolve_bare_names_on_the_path() {
+        assert!(resolve("sh").is_absolute());
+        assert_eq!(resolve("./jq"), PathBuf::from("./jq"));
+        assert_eq!(resolve("/opt/jq"), PathBuf::from("/opt/jq"));
+        assert_eq!(
+            resolve("no-such-jq-binary"),
+            PathBuf::from("no-such-jq-binary")
+        );
+    }
+
+    #[tokio::test]
+    async fn safe_mode_refuses_to_run_jq() {
+        let result = run(
+            "jq",
+            ".",
+            &[json!(1)],
+            &scope(true, true),
+            false,
+            None,
+            &AtomicBool::new(false),
+        )
+        .await;
+        assert_eq!(
+            result.err().map(|e| e.to_string()).as_deref(),
+            Some("Running an external command is disabled in safe mode")
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_without_the_environment() {
+        if !jq_is_installed() {
+            return;
+        }
+        let output = run(
+            "jq",
+            "$ENV | length",
+            &[json!(null)],
+            &scope(false, false),
+            false,
+            None,
+            &AtomicBool::new(false),
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.values, vec![json!(0)]);
+    }
+}
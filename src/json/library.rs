@@ -0,0 +1,251 @@
+//! Modules of jq definitions included at the start of the filters (`include "name";`),
+//! looked up in the library paths given with `-L`.
+
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use jaq_syn::Def;
+
+#[derive(Default)]
+pub struct Library {
+    paths: Vec<PathBuf>,
+    /// Definitions of the modules parsed so far, with the time their file was modified at,
+    /// so that a module is parsed again only once it is edited.
+    cache: Mutex<HashMap<PathBuf, (SystemTime, Vec<Def>)>>,
+}
+
+impl Library {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self {
+            paths,
+            cache: Default::default(),
+        }
+    }
+
+    /// Directories the modules are looked up in, in order.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Takes the `include` directives off the start of the filter, returning it
+    /// with the directives blanked out (so that the positions in it still apply)
+    /// and the definitions of the modules they include.
+    ///
+    /// Fails with the message and the characters of the directive at fault.
+    pub fn resolve(&self, query: &str) -> Result<(String, Vec<Def>), (String, Range<usize>)> {
+        let mut blanked = String::with_capacity(query.len());
+        let mut defs = Vec::new();
+        let mut start = 0;
+        loop {
+            let rest = &query[start..];
+            let space = rest.len() - rest.trim_start().len();
+            blanked.push_str(&rest[..space]);
+            start += space;
+            let Some((keyword, name, len)) = directive(&query[start..]) else {
+                break;
+            };
+            let span = query[..start].chars().count()..query[..start + len].chars().count();
+            if keyword == "import" {
+                return Err((
+                    format!(
+                        "modules can only be included (`include \"{}\";`), not imported",
+                        name
+                    ),
+                    span,
+                ));
+            }
+            let path = self.find(name).ok_or_else(|| {
+                (
+                    format!("module `{}` not found in the library paths", name),
+                    span.clone(),
+                )
+            })?;
+            let module = self
+                .load(&path)
+                .map_err(|e| (format!("module `{}`: {}", name, e), span))?;
+            defs.extend(module);
+            blanked.extend(
+                query[start..start + len]
+                    .chars()
+                    .map(|c| if c == '\n' { c } else { ' ' }),
+            );
+            start += len;
+        }
+        blanked.push_str(&query[start..]);
+        Ok((blanked, defs))
+    }
+
+    /// Returns the file of the module, `name.jq` (or `name/name.jq`) in the first path holding one.
+    fn find(&self, name: &str) -> Option<PathBuf> {
+        let last = Path::new(name).file_name()?.to_string_lossy().into_owned();
+        self.paths
+            .iter()
+            .flat_map(|dir| {
+                [
+                    dir.join(format!("{}.jq", name)),
+                    dir.join(name).join(format!("{}.jq", last)),
+                ]
+            })
+            .find(|path| path.is_file())
+    }
+
+    fn load(&self, path: &Path) -> Result<Vec<Def>, String> {
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| e.to_string())?;
+        if let Ok(cache) = self.cache.lock() {
+            if let Some((at, defs)) = cache.get(path) {
+                if *at == modified {
+                    return Ok(defs.clone());
+                }
+            }
+        }
+        let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let (defs, errs) = jaq_parse::parse(&source, jaq_parse::defs());
+        if let Some(e) = errs.first() {
+            let line = source
+                .chars()
+                .take(e.span().start)
+                .filter(|c| *c == '\n')
+                .count()
+                + 1;
+            return Err(format!("{} at line {}", e, line));
+        }
+        let defs = defs.unwrap_or_default();
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(path.to_path_buf(), (modified, defs.clone()));
+        }
+        Ok(defs)
+    }
+}
+
+/// Returns the keyword, the module name and the length (in bytes)
+/// of the `include` or `import` directive at the start of the text, up to its `;`.
+fn directive(s: &str) -> Option<(&'static str, &str, usize)> {
+    let keyword = ["include", "import"].into_iter().find(|keyword| {
+        s.strip_prefix(keyword)
+            .is_some_and(|rest| rest.starts_with(|c: char| c.is_whitespace() || c == '"'))
+    })?;
+    let rest = s[keyword.len()..].trim_start();
+    let name = rest.strip_prefix('"')?;
+    let name = &name[..name.find('"')?];
+    // E.g. `as alias` or metadata may follow the name.
+    let after_name = s.len() - rest.len() + name.len() + 2;
+    let len = after_name + s[after_name..].find(';')? + 1;
+    Some((keyword, name, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use promkit::serde_json::json;
+
+    use super::*;
+    use crate::json::{run_jaq, tests::scope, FilterScope};
+
+    /// Directory of the modules, removed once dropped.
+    struct Modules(PathBuf);
+
+    impl Modules {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("jnv-library-{}-{}", name, std::process::id()));
+            for (path, source) in files {
+                let path = dir.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, source).unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for Modules {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn includes_the_modules_of_the_library() {
+        let modules = Modules::new(
+            "include",
+            &[
+                ("util.jq", "def double: . * 2;"),
+                ("math/math.jq", "def inc: . + 1;\ndef dec: . - 1;"),
+            ],
+        );
+        let library = Library::new(vec![modules.0.join("missing"), modules.0.clone()]);
+
+        let query = "include \"util\";\ninclude \"math\"; .a | double | inc";
+        let (blanked, defs) = library.resolve(query).unwrap();
+        assert_eq!(
+            blanked,
+            "               \n                .a | double | inc"
+        );
+        assert_eq!(defs.len(), 3);
+
+        let scope = FilterScope { library, ..scope() };
+        let output = run_jaq(
+            query,
+            &[json!({"a": 2})],
+            &scope,
+            false,
+            None,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        assert_eq!(output.values, [json!(5)]);
+    }
+
+    #[test]
+    fn points_at_the_directive_at_fault() {
+        let modules = Modules::new("fault", &[("broken.jq", "def ok: 1;\ndef bad: (;")]);
+        let library = Library::new(vec![modules.0.clone()]);
+        let error = |query| library.resolve(query).unwrap_err();
+
+        let query = "include \"missing\"; .";
+        assert_eq!(
+            error(query),
+            (
+                "module `missing` not found in the library paths".to_string(),
+                0..18
+            )
+        );
+        // Past the directives before it.
+        let (message, span) = error("include \"broken\" ;  .");
+        assert!(message.starts_with("module `broken`: "), "{}", message);
+        assert!(message.ends_with(" at line 2"), "{}", message);
+        assert_eq!(span, 0..18);
+        assert_eq!(
+            error("  import \"broken\" as b; ."),
+            (
+                "modules can only be included (`include \"broken\";`), not imported".to_string(),
+                2..23
+            )
+        );
+
+        let scope = FilterScope { library, ..scope() };
+        let e = run_jaq(
+            query,
+            &[json!(1)],
+            &scope,
+            false,
+            None,
+            &AtomicBool::new(false),
+        )
+        .err()
+        .unwrap();
+        assert!(
+            e.to_string().contains("module `missing` not found"),
+            "{}",
+            e
+        );
+    }
+}
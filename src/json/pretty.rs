@@ -0,0 +1,66 @@
+//! Presentation of the values as the text `jq` prints, pretty-printed,
+//! scrolled a line at a time as in a pager rather than row by row.
+
+use promkit::{
+    grapheme::StyledGraphemes,
+    serde_json::{self, Value},
+};
+
+#[derive(Clone)]
+pub struct Pretty {
+    /// Lines of the values pretty-printed one after the other.
+    lines: Vec<String>,
+    /// Line at the top of the pane.
+    position: usize,
+}
+
+impl Pretty {
+    pub fn new(values: &[Value]) -> Self {
+        let lines = values
+            .iter()
+            .flat_map(|value| {
+                serde_json::to_string_pretty(value)
+                    .unwrap_or_default()
+                    .lines()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Self { lines, position: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position.min(self.lines.len().saturating_sub(1));
+    }
+
+    /// Returns the lines from the top one, those longer than the width wrapped.
+    pub fn create_lines(&self, width: u16, height: u16) -> Vec<StyledGraphemes> {
+        self.lines[self.position.min(self.lines.len())..]
+            .iter()
+            .flat_map(|line| {
+                let wrapped = StyledGraphemes::from(line.as_str())
+                    .matrixify(width.max(1) as usize, usize::MAX, 0)
+                    .0;
+                if wrapped.is_empty() {
+                    vec![StyledGraphemes::default()]
+                } else {
+                    wrapped
+                }
+            })
+            .take(height as usize)
+            .collect()
+    }
+
+    /// Returns the text shown, every line included.
+    pub fn to_text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
@@ -0,0 +1,220 @@
+//! Grouping of consecutive array elements sharing the same structure,
+//! e.g. the samples of a metric, to show them as one.
+
+use promkit::jsonz::{ContainerType, Row, Value};
+
+/// Minimum number of elements worth folding behind the first of a run.
+const MIN_HIDDEN: usize = 2;
+
+/// Elements of an array following one with the same structure,
+/// hidden until the fold is expanded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fold {
+    /// Index of the first row of the first hidden element.
+    pub start: usize,
+    /// Index of the last row of the last hidden element.
+    pub end: usize,
+    /// Number of hidden elements.
+    pub count: usize,
+    pub expanded: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Null,
+    Boolean,
+    Number,
+    String,
+    Object,
+    Array,
+}
+
+fn kind(value: &Value) -> Kind {
+    match value {
+        Value::Null => Kind::Null,
+        Value::Boolean(_) => Kind::Boolean,
+        Value::Number(_) => Kind::Number,
+        Value::String(_) => Kind::String,
+        Value::Empty { typ } | Value::Open { typ, .. } | Value::Close { typ, .. } => match typ {
+            ContainerType::Object => Kind::Object,
+            ContainerType::Array => Kind::Array,
+        },
+    }
+}
+
+/// Returns the index of the last row of the value starting at `index`.
+fn end_of(rows: &[Row], index: usize) -> usize {
+    match rows[index].v {
+        Value::Open { close_index, .. } => close_index,
+        _ => index,
+    }
+}
+
+/// Keys and value types of the children of a container.
+type Signature<'a> = Vec<(Option<&'a str>, Kind)>;
+
+/// Returns the keys and value types of the children of the container at `index`,
+/// or `None` for scalars and empty containers, which are never folded.
+fn signature(rows: &[Row], index: usize) -> Option<Signature<'_>> {
+    let Value::Open { close_index, .. } = rows[index].v else {
+        return None;
+    };
+    let mut ret = Vec::new();
+    let mut child = index + 1;
+    while child < close_index {
+        ret.push((rows[child].k.as_deref(), kind(&rows[child].v)));
+        child = end_of(rows, child) + 1;
+    }
+    Some(ret)
+}
+
+/// Finds, in every array, the runs of consecutive objects (or arrays)
+/// with the same keys and value types, and folds each run behind its first element.
+pub fn find_folds(rows: &[Row]) -> Vec<Fold> {
+    let mut ret = Vec::new();
+    for (index, row) in rows.iter().enumerate() {
+        let Value::Open {
+            typ: ContainerType::Array,
+            close_index,
+            ..
+        } = row.v
+        else {
+            continue;
+        };
+
+        let mut current: Option<(Signature, Option<Fold>)> = None;
+        let mut element = index + 1;
+        while element < close_index {
+            let end = end_of(rows, element);
+            let sig = signature(rows, element);
+            match (&mut current, sig) {
+                (Some((run, fold)), Some(sig)) if *run == sig => match fold {
+                    Some(fold) => {
+                        fold.end = end;
+                        fold.count += 1;
+                    }
+                    None => {
+                        *fold = Some(Fold {
+                            start: element,
+                            end,
+                            count: 1,
+                            expanded: false,
+                        })
+                    }
+                },
+                (_, sig) => {
+                    if let Some((_, Some(fold))) = current.take() {
+                        if fold.count >= MIN_HIDDEN {
+                            ret.push(fold);
+                        }
+                    }
+                    current = sig.map(|sig| (sig, None));
+                }
+            }
+            element = end + 1;
+        }
+        if let Some((_, Some(fold))) = current {
+            if fold.count >= MIN_HIDDEN {
+                ret.push(fold);
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::{jsonz, serde_json::json};
+
+    use super::*;
+    use crate::json::stream::{row_text, JsonStream};
+
+    fn rows(value: promkit::serde_json::Value) -> Vec<Row> {
+        jsonz::create_rows([&value])
+    }
+
+    #[test]
+    fn folds_the_elements_like_the_first_of_a_run() {
+        let rows = rows(json!([{"a": 1, "b": "x"}, {"a": 2, "b": "y"}, {"a": 3, "b": "z"}]));
+        assert_eq!(
+            find_folds(&rows),
+            [Fold {
+                start: 5,
+                end: 12,
+                count: 2,
+                expanded: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn folding_keeps_the_values() {
+        let rows = rows(json!([{"a": 1}, {"a": 2}, {"a": 3}]));
+        let fold = &find_folds(&rows)[0];
+        let hidden = rows[fold.start..=fold.end]
+            .iter()
+            .map(row_text)
+            .collect::<Vec<_>>();
+        // The brackets of the objects, around their key and value.
+        assert_eq!(hidden, ["", "a 2", "", "", "a 3", ""]);
+    }
+
+    #[test]
+    fn leaves_mismatched_siblings_alone() {
+        // Too short a run, another key, another type, then a run to fold.
+        let siblings = rows(json!([
+            {"a": 1},
+            {"a": 2},
+            {"b": 3},
+            {"b": "4"},
+            {"a": 5},
+            {"a": 6},
+            {"a": 7}
+        ]));
+        let folds = find_folds(&siblings);
+        assert_eq!(folds.len(), 1, "{:?}", folds);
+        assert_eq!(siblings[folds[0].start].depth, 1);
+        assert_eq!(row_text(&siblings[folds[0].start + 1]), "a 6");
+        assert_eq!(folds[0].count, 2);
+
+        // Scalars and empty containers are never folded.
+        assert!(find_folds(&rows(json!([1, 2, 3, 4, [], [], [], {}, {}, {}]))).is_empty());
+    }
+
+    #[test]
+    fn expands_the_fold_at_the_cursor() {
+        let value = json!({"samples": [{"t": 1}, {"t": 2}, {"t": 3}], "after": true});
+        let mut stream = JsonStream::new([&value]);
+        stream.set_fold_similar(true);
+        let shown = |stream: &JsonStream| {
+            let (rows, folds) = stream.extract_rows_from_current(100);
+            (rows.iter().map(row_text).collect::<Vec<_>>(), folds)
+        };
+        let (folded, folds) = shown(&stream);
+        assert_eq!(
+            folded,
+            ["", "samples ", "", "t 1", "", "", "", "after true", ""]
+        );
+        assert_eq!(folds, [(5, 2)]);
+
+        // Onto the fold, standing for the hidden elements.
+        for _ in 0..5 {
+            stream.down();
+        }
+        assert_eq!(stream.position(), 5);
+        stream.toggle();
+        stream.head();
+        let (unfolded, folds) = shown(&stream);
+        assert_eq!(
+            unfolded,
+            stream.rows().iter().map(row_text).collect::<Vec<_>>()
+        );
+        assert!(unfolded.contains(&"t 3".to_string()));
+        assert!(folds.is_empty());
+        // Past the elements shown again.
+        for _ in 0..12 {
+            stream.down();
+        }
+        assert_eq!(row_text(&stream.rows()[stream.position()]), "after true");
+    }
+}
@@ -0,0 +1,709 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use promkit::{
+    jsonz::{self, ContainerType, Row, RowOperation, Value},
+    serde_json,
+};
+
+use super::similar::{self, Fold};
+use crate::paths;
+
+/// Rows of the JSON stream together with the cursor position.
+///
+/// This mirrors `promkit::jsonstream::JsonStream`,
+/// additionally exposing the position and the jq path of each row.
+#[derive(Clone)]
+pub struct JsonStream {
+    rows: Vec<Row>,
+    position: usize,
+    /// Folds of similar array elements by the index of their first row,
+    /// found the first time folding is enabled.
+    folds: Option<BTreeMap<usize, Fold>>,
+    fold_similar: bool,
+    /// Whether the rows of nulls and empty containers are left out (the documents aside).
+    hide_blank: bool,
+    /// Whether every value is a string, found once as the rows are built
+    /// rather than on every frame.
+    strings_only: bool,
+}
+
+impl JsonStream {
+    pub fn new<'a, I: IntoIterator<Item = &'a serde_json::Value>>(iter: I) -> Self {
+        let rows = jsonz::create_rows(iter);
+        let strings_only = !rows.is_empty()
+            && rows
+                .iter()
+                .all(|row| row.depth == 0 && matches!(row.v, Value::String(_)));
+        Self {
+            rows,
+            position: 0,
+            folds: None,
+            fold_similar: false,
+            hide_blank: false,
+            strings_only,
+        }
+    }
+
+    pub fn strings_only(&self) -> bool {
+        self.strings_only
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns up to `n` rows from the cursor, together with the indices
+    /// (among the returned rows) of the folds and their number of hidden elements.
+    ///
+    /// A fold is returned as the first row of its first hidden element.
+    pub fn extract_rows_from_current(&self, n: usize) -> (Vec<Row>, Vec<(usize, usize)>) {
+        if !self.fold_similar && !self.hide_blank {
+            return (self.rows.extract(self.position, n), Vec::new());
+        }
+        let mut rows = Vec::new();
+        let mut folds = Vec::new();
+        for i in self.shown_from_current().take(n) {
+            rows.push(self.rows[i].clone());
+            if let Some(fold) = self.active_fold(i) {
+                folds.push((rows.len() - 1, fold.count));
+            }
+        }
+        (rows, folds)
+    }
+
+    /// Returns the indices of up to `n` rows from the cursor,
+    /// as returned by `extract_rows_from_current`.
+    pub fn row_indices_from_current(&self, n: usize) -> Vec<usize> {
+        self.shown_from_current().take(n).collect()
+    }
+
+    /// Iterates over the indices of the rows shown from the cursor,
+    /// skipping those inside collapsed containers and folds, and the blank ones
+    /// once hidden (unless at the cursor).
+    fn shown_from_current(&self) -> impl Iterator<Item = usize> + '_ {
+        let first = (self.position < self.rows.len()).then_some(self.position);
+        std::iter::successors(first, |&i| {
+            let next = match (self.active_fold(i), &self.rows[i].v) {
+                (Some(fold), _) => fold.end + 1,
+                (
+                    None,
+                    Value::Open {
+                        collapsed: true,
+                        close_index,
+                        ..
+                    },
+                ) => close_index + 1,
+                _ => i + 1,
+            };
+            // Ends here rather than in a `take_while`, as the next index is computed
+            // as soon as the current one is returned.
+            (next < self.rows.len()).then_some(next)
+        })
+        .filter(|&i| i == self.position || !self.hidden(i))
+    }
+
+    /// Leaves out (or shows) the rows of nulls and empty containers,
+    /// moving the cursor off them.
+    pub fn set_hide_blank(&mut self, hide: bool) {
+        self.hide_blank = hide;
+        if self.hidden(self.position) && !self.down() {
+            self.up();
+        }
+    }
+
+    /// Tells whether the row is left out as it is blank.
+    fn hidden(&self, index: usize) -> bool {
+        self.hide_blank && is_blank(&self.rows[index])
+    }
+
+    /// Folds (or shows) the array elements with the same structure as the one before them.
+    pub fn set_fold_similar(&mut self, enabled: bool) {
+        if enabled && self.folds.is_none() {
+            self.folds = Some(
+                similar::find_folds(&self.rows)
+                    .into_iter()
+                    .map(|fold| (fold.start, fold))
+                    .collect(),
+            );
+        }
+        self.fold_similar = enabled;
+        self.position = self.visible(self.position);
+    }
+
+    /// Returns the unexpanded fold starting at the row, if any.
+    fn active_fold(&self, index: usize) -> Option<&Fold> {
+        if !self.fold_similar {
+            return None;
+        }
+        self.folds
+            .as_ref()?
+            .get(&index)
+            .filter(|fold| !fold.expanded)
+    }
+
+    /// Returns the row standing for the given one,
+    /// i.e. the outermost unexpanded fold hiding it or the row itself.
+    fn visible(&self, index: usize) -> usize {
+        match (&self.folds, self.fold_similar) {
+            (Some(folds), true) => folds
+                .range(..=index)
+                .filter(|(_, fold)| !fold.expanded && index <= fold.end)
+                .map(|(start, _)| *start)
+                .next()
+                .unwrap_or(index),
+            _ => index,
+        }
+    }
+
+    /// Toggles the container at the cursor, or expands the fold at the cursor.
+    pub fn toggle(&mut self) {
+        if self.active_fold(self.position).is_some() {
+            if let Some(fold) = self
+                .folds
+                .as_mut()
+                .and_then(|folds| folds.get_mut(&self.position))
+            {
+                fold.expanded = true;
+            }
+            return;
+        }
+        self.position = self.rows.toggle(self.position);
+    }
+
+    pub fn set_nodes_visibility(&mut self, collapsed: bool) {
+        self.rows.set_rows_visibility(collapsed);
+        self.position = 0;
+    }
+
+    pub fn up(&mut self) -> bool {
+        let mut index = self.position;
+        loop {
+            let next = self.visible(self.rows.up(index));
+            if next == index {
+                // Stays put rather than on a hidden row.
+                if self.hidden(index) {
+                    index = self.position;
+                }
+                break;
+            }
+            index = next;
+            if !self.hidden(index) {
+                break;
+            }
+        }
+        let ret = index != self.position;
+        self.position = index;
+        ret
+    }
+
+    /// Moves up by `n` visible rows, stopping at the first one.
+    pub fn up_by(&mut self, n: usize) -> bool {
+        let mut ret = false;
+        for _ in 0..n {
+            if !self.up() {
+                break;
+            }
+            ret = true;
+        }
+        ret
+    }
+
+    /// Moves down by `n` visible rows, stopping at the last one.
+    pub fn down_by(&mut self, n: usize) -> bool {
+        let mut ret = false;
+        for _ in 0..n {
+            if !self.down() {
+                break;
+            }
+            ret = true;
+        }
+        ret
+    }
+
+    pub fn head(&mut self) -> bool {
+        self.position = self.rows.head();
+        true
+    }
+
+    pub fn down(&mut self) -> bool {
+        let mut index = self.position;
+        loop {
+            let next = match self.active_fold(index) {
+                Some(fold) if fold.end + 1 < self.rows.len() => fold.end + 1,
+                Some(_) => index,
+                None => self.rows.down(index),
+            };
+            if next == index {
+                if self.hidden(index) {
+                    index = self.position;
+                }
+                break;
+            }
+            index = next;
+            if !self.hidden(index) {
+                break;
+            }
+        }
+        let ret = index != self.position;
+        self.position = index;
+        ret
+    }
+
+    pub fn tail(&mut self) -> bool {
+        self.position = self.visible(self.rows.tail());
+        true
+    }
+
+    /// Moves to the opening row of the container holding the row at the cursor,
+    /// staying on a document.
+    pub fn parent(&mut self) -> bool {
+        let Some(parent) = self.parent_of(self.entry(self.position)) else {
+            return false;
+        };
+        self.position = self.visible(parent);
+        true
+    }
+
+    /// Returns the jq path of the container holding the row at the cursor,
+    /// unless the row is a document.
+    pub fn parent_path(&self) -> Option<String> {
+        let parent = self.parent_of(self.entry(self.position))?;
+        // Steps from the container up to its document.
+        let mut steps = Vec::new();
+        let mut index = parent;
+        while let Some(parent) = self.parent_of(index) {
+            steps.push((parent, index));
+            index = parent;
+        }
+        Some(
+            steps
+                .into_iter()
+                .rev()
+                .fold(".".to_string(), |path, (parent, index)| {
+                    match &self.rows[index].k {
+                        Some(key) => paths::join_key(&path, key),
+                        None => paths::join_index(&path, self.entry_index(parent, index)),
+                    }
+                }),
+        )
+    }
+
+    /// Returns the opening row of the container holding the entry at `index`, if any.
+    fn parent_of(&self, index: usize) -> Option<usize> {
+        let depth = self.rows[index].depth;
+        if depth == 0 {
+            return None;
+        }
+        (0..index).rev().find(|&i| {
+            self.rows[i].depth + 1 == depth && matches!(self.rows[i].v, Value::Open { .. })
+        })
+    }
+
+    /// Returns the position of the entry at `index` among those of the container opened at `parent`.
+    fn entry_index(&self, parent: usize, index: usize) -> usize {
+        std::iter::successors(Some(parent + 1), |&i| match self.rows[i].v {
+            Value::Open { close_index, .. } => Some(close_index + 1),
+            _ => Some(i + 1),
+        })
+        .take_while(|&i| i < index)
+        .count()
+    }
+
+    /// Moves to the next entry of the container holding the row at the cursor
+    /// (or to the next document), staying on the last one.
+    pub fn next_sibling(&mut self) -> bool {
+        let index = self.entry(self.position);
+        let next = match self.rows[index].v {
+            Value::Open { close_index, .. } => close_index + 1,
+            _ => index + 1,
+        };
+        match self.rows.get(next) {
+            Some(row)
+                if row.depth == self.rows[index].depth && !matches!(row.v, Value::Close { .. }) =>
+            {
+                self.position = self.visible(next);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Moves to the previous entry of the container holding the row at the cursor
+    /// (or to the previous document), staying on the first one.
+    pub fn prev_sibling(&mut self) -> bool {
+        let index = self.entry(self.position);
+        let Some(previous) = index.checked_sub(1) else {
+            return false;
+        };
+        let row = &self.rows[previous];
+        if row.depth != self.rows[index].depth {
+            return false;
+        }
+        let previous = match row.v {
+            Value::Close { open_index, .. } => open_index,
+            _ => previous,
+        };
+        self.position = self.visible(previous);
+        true
+    }
+
+    /// Returns the row the entry at `index` starts at, i.e. its opening row for a closing one.
+    fn entry(&self, index: usize) -> usize {
+        match self.rows.get(index).map(|row| &row.v) {
+            Some(Value::Close { open_index, .. }) => *open_index,
+            _ => index,
+        }
+    }
+
+    /// Returns the jq path of the row at the cursor.
+    pub fn current_path(&self) -> String {
+        self.paths()
+            .nth(self.position)
+            .unwrap_or_else(|| ".".to_string())
+    }
+
+    /// Rebuilds the value at the cursor, including the collapsed children.
+    pub fn current_value(&self) -> Option<serde_json::Value> {
+        let index = match self.rows.get(self.position)?.v {
+            Value::Close { open_index, .. } => open_index,
+            _ => self.position,
+        };
+        Some(to_value(&self.rows, index))
+    }
+
+    /// Yields the jq path of every row, relative to the document it belongs to.
+    ///
+    /// A closing bracket has the same path as its opening one.
+    pub fn paths(&self) -> impl Iterator<Item = String> + '_ {
+        // Path of each open container, with the number of its children seen so far.
+        let mut parents: Vec<(String, usize)> = Vec::new();
+        self.rows.iter().map(move |row| {
+            if let Value::Close { .. } = row.v {
+                return parents.pop().map(|(path, _)| path).unwrap_or_default();
+            }
+
+            let path = match parents.last_mut() {
+                None => ".".to_string(),
+                Some((parent, count)) => {
+                    *count += 1;
+                    match &row.k {
+                        Some(key) => paths::join_key(parent, key),
+                        None => paths::join_index(parent, *count - 1),
+                    }
+                }
+            };
+            if let Value::Open { .. } = row.v {
+                parents.push((path.clone(), 0));
+            }
+            path
+        })
+    }
+
+    /// Collapses and expands the containers as they are in `previous`,
+    /// and puts the cursor back on the same row,
+    /// telling the rows apart by their path and the contents of their document
+    /// (which may have moved, e.g. as documents are appended to a followed file).
+    ///
+    /// With `match_paths`, the rows whose document changed (e.g. as the file is read again)
+    /// are told apart by their path alone.
+    pub fn keep_state_of(&mut self, previous: &JsonStream, match_paths: bool) {
+        let previous_keys = previous.keys().collect::<Vec<_>>();
+        let collapsed = previous
+            .rows
+            .iter()
+            .zip(&previous_keys)
+            .filter_map(|(row, key)| match row.v {
+                Value::Open { collapsed, .. } => Some((key, collapsed)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let mut collapsed_by_path = HashMap::new();
+        if match_paths {
+            for ((_, path), collapsed) in &collapsed {
+                collapsed_by_path.entry(path).or_insert(*collapsed);
+            }
+        }
+        let cursor = previous_keys.get(previous.position);
+
+        let keys = self.keys().collect::<Vec<_>>();
+        let mut position = None;
+        let mut position_by_path = None;
+        for (i, key) in keys.iter().enumerate() {
+            if let Some(collapsed) = collapsed.get(key).or_else(|| collapsed_by_path.get(&key.1)) {
+                self.set_collapsed(i, *collapsed);
+            }
+            if position.is_none() && Some(key) == cursor {
+                position = Some(i);
+            }
+            if match_paths && position_by_path.is_none() && cursor.is_some_and(|c| c.1 == key.1) {
+                position_by_path = Some(i);
+            }
+        }
+        if let Some(position) = position.or(position_by_path) {
+            self.position = self.visible(position);
+        }
+    }
+
+    /// Yields the hash of the document of every row together with its path.
+    pub fn keys(&self) -> impl Iterator<Item = (u64, String)> + '_ {
+        let mut hashes = Vec::with_capacity(self.rows.len());
+        let mut start = 0;
+        while start < self.rows.len() {
+            let end = match self.rows[start].v {
+                Value::Open { close_index, .. } => close_index,
+                _ => start,
+            };
+            let mut hasher = DefaultHasher::new();
+            self.rows[start..=end]
+                .iter()
+                .for_each(|row| hash_row(row, &mut hasher));
+            let hash = hasher.finish();
+            hashes.extend(std::iter::repeat_n(hash, end + 1 - start));
+            start = end + 1;
+        }
+        hashes.into_iter().zip(self.paths())
+    }
+
+    /// Expands the containers shallower than `depth` and collapses the others.
+    pub fn expand_to_depth(&mut self, depth: usize) {
+        for index in 0..self.rows.len() {
+            if let Value::Open { .. } = self.rows[index].v {
+                self.set_collapsed(index, self.rows[index].depth >= depth);
+            }
+        }
+    }
+
+    /// Finds the next row (or the previous one if `forward` is unset) after the cursor
+    /// whose key or value contains `needle`, wrapping around the ends.
+    ///
+    /// Returns the index of the row and whether the search wrapped.
+    pub fn find(&self, needle: &str, case_sensitive: bool, forward: bool) -> Option<(usize, bool)> {
+        let len = self.rows.len();
+        let needle = if case_sensitive {
+            needle.to_string()
+        } else {
+            needle.to_lowercase()
+        };
+        (1..=len)
+            .map(|offset| {
+                if forward {
+                    (self.position + offset) % len
+                } else {
+                    (self.position + len - offset % len) % len
+                }
+            })
+            .find(|&index| {
+                let text = row_text(&self.rows[index]);
+                if case_sensitive {
+                    text.contains(&needle)
+                } else {
+                    text.to_lowercase().contains(&needle)
+                }
+            })
+            .map(|index| {
+                let wrapped = if forward {
+                    index <= self.position
+                } else {
+                    index >= self.position
+                };
+                (index, wrapped)
+            })
+    }
+
+    /// Moves the cursor to the row, expanding the containers and folds hiding it.
+    pub fn reveal(&mut self, index: usize) {
+        let ancestors = (0..index)
+            .filter(|&i| {
+                matches!(
+                    self.rows[i].v,
+                    Value::Open { collapsed: true, close_index, .. } if close_index > index
+                )
+            })
+            .collect::<Vec<_>>();
+        for ancestor in ancestors {
+            self.set_collapsed(ancestor, false);
+        }
+        if let Some(folds) = &mut self.folds {
+            for fold in folds.values_mut() {
+                if (fold.start..=fold.end).contains(&index) {
+                    fold.expanded = true;
+                }
+            }
+        }
+        self.position = index;
+    }
+
+    /// Sets whether the container at `index` is collapsed.
+    ///
+    /// The cursor moves to the opening row if it was inside the collapsed container.
+    pub fn set_collapsed(&mut self, index: usize, collapsed: bool) {
+        let close_index = match &self.rows[index].v {
+            Value::Open {
+                collapsed: current,
+                close_index,
+                ..
+            } if *current != collapsed => *close_index,
+            _ => return,
+        };
+        self.rows.toggle(index);
+        if collapsed && (index..=close_index).contains(&self.position) {
+            self.position = index;
+        }
+    }
+}
+
+/// Rebuilds the value of the row at `index`, with its children if it opens a container.
+fn to_value(rows: &[Row], index: usize) -> serde_json::Value {
+    match &rows[index].v {
+        Value::Null => serde_json::Value::Null,
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::Number(n) => serde_json::Value::Number(n.clone()),
+        Value::String(s) => serde_json::Value::String(s.clone()),
+        Value::Empty {
+            typ: ContainerType::Object,
+        } => serde_json::Value::Object(Default::default()),
+        Value::Empty {
+            typ: ContainerType::Array,
+        } => serde_json::Value::Array(Vec::new()),
+        Value::Open {
+            typ, close_index, ..
+        } => {
+            let mut children = Vec::new();
+            let mut child = index + 1;
+            while child < *close_index {
+                children.push((rows[child].k.clone(), to_value(rows, child)));
+                child = match rows[child].v {
+                    Value::Open { close_index, .. } => close_index + 1,
+                    _ => child + 1,
+                };
+            }
+            match typ {
+                ContainerType::Object => serde_json::Value::Object(
+                    children
+                        .into_iter()
+                        .map(|(k, v)| (k.unwrap_or_default(), v))
+                        .collect(),
+                ),
+                ContainerType::Array => {
+                    serde_json::Value::Array(children.into_iter().map(|(_, v)| v).collect())
+                }
+            }
+        }
+        // Only reached for a malformed stream, as closing rows are skipped above.
+        Value::Close { .. } => serde_json::Value::Null,
+    }
+}
+
+/// Returns the text of the row searched by [`JsonStream::find`],
+/// i.e. its key and its value unless it is a container.
+pub fn row_text(row: &Row) -> String {
+    let value = match &row.v {
+        Value::Null => "null".to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Empty { .. } | Value::Open { .. } | Value::Close { .. } => String::new(),
+    };
+    match (&row.k, &row.v) {
+        // The key of a container is on its opening row only.
+        (_, Value::Close { .. }) => String::new(),
+        (Some(key), _) => format!("{} {}", key, value),
+        (None, _) => value,
+    }
+}
+
+/// Tells whether the row holds a null or an empty container inside a document.
+pub fn is_blank(row: &Row) -> bool {
+    row.depth > 0 && matches!(row.v, Value::Null | Value::Empty { .. })
+}
+
+/// Hashes the key and the value of the row, leaving out its expansion.
+fn hash_row<H: Hasher>(row: &Row, hasher: &mut H) {
+    row.k.hash(hasher);
+    match &row.v {
+        Value::Null => 0.hash(hasher),
+        Value::Boolean(b) => (1, b).hash(hasher),
+        Value::Number(n) => (2, n.to_string()).hash(hasher),
+        Value::String(s) => (3, s).hash(hasher),
+        Value::Empty { typ } => (4, matches!(typ, ContainerType::Object)).hash(hasher),
+        Value::Open { typ, .. } => (5, matches!(typ, ContainerType::Object)).hash(hasher),
+        Value::Close { typ, .. } => (6, matches!(typ, ContainerType::Object)).hash(hasher),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::serde_json::json;
+
+    use super::*;
+    use crate::{editor::tests::allocations, json::tests::settings};
+
+    fn samples(n: usize) -> serde_json::Value {
+        serde_json::Value::Array(
+            (0..n)
+                .map(|i| json!({"id": i % 10, "name": "sample", "tags": [i % 3]}))
+                .collect(),
+        )
+    }
+
+    /// Returns the allocations made to extract and format `n` rows from the cursor.
+    fn formatting_allocations(stream: &JsonStream, n: usize) -> usize {
+        let formatter = settings().formatter;
+        let before = allocations();
+        let (rows, _) = stream.extract_rows_from_current(n);
+        let lines = formatter.format_for_terminal_display(&rows, 80);
+        let ret = allocations() - before;
+        assert_eq!(lines.len(), n);
+        ret
+    }
+
+    #[test]
+    fn formats_only_the_rows_on_screen() {
+        let small = samples(10);
+        let large = samples(100_000);
+        for hide_blank in [false, true] {
+            let mut streams = [JsonStream::new([&small]), JsonStream::new([&large])];
+            for stream in &mut streams {
+                stream.set_hide_blank(hide_blank);
+            }
+            let [small, large] = &streams;
+            assert_eq!(
+                formatting_allocations(small, 20),
+                formatting_allocations(large, 20),
+                "hide_blank: {}",
+                hide_blank
+            );
+        }
+    }
+
+    #[test]
+    fn stops_scrolling_at_either_end() {
+        let value = json!([1, 2, 3]);
+        let mut stream = JsonStream::new([&value]);
+        let last = stream.rows().len() - 1;
+
+        assert!(!stream.up());
+        assert_eq!(stream.position(), 0);
+
+        assert!(stream.down_by(last + 10));
+        assert_eq!(stream.position(), last);
+        assert!(!stream.down());
+        assert_eq!(stream.position(), last);
+        // Only the rows left below the cursor.
+        assert_eq!(stream.extract_rows_from_current(10).0.len(), 1);
+
+        assert!(stream.up_by(last + 10));
+        assert_eq!(stream.position(), 0);
+        assert_eq!(
+            stream.extract_rows_from_current(10).0.len(),
+            stream.rows().len()
+        );
+    }
+}
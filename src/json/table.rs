@@ -0,0 +1,307 @@
+//! Presentation of an array of objects as a table,
+//! with a column per key and a row per element.
+
+use promkit::{
+    crossterm::style::{Attribute, ContentStyle},
+    grapheme::{StyledGrapheme, StyledGraphemes},
+    serde_json::{self, Value},
+};
+
+/// Maximum number of columns taken by the values of a column.
+const MAX_CELL_WIDTH: usize = 32;
+
+const SEPARATOR: &str = "  ";
+
+#[derive(Clone)]
+pub struct Table {
+    /// Keys of the objects, in the order they first appear.
+    keys: Vec<String>,
+    /// Elements of the array, one object per row.
+    rows: Vec<serde_json::Map<String, Value>>,
+    /// Width of each column, fitting its key and its values up to `MAX_CELL_WIDTH`.
+    widths: Vec<usize>,
+    /// Row at the cursor.
+    position: usize,
+    /// First column shown.
+    column: usize,
+}
+
+impl Table {
+    /// Builds the table of the elements of the array if the values are one,
+    /// or of the values themselves, provided they are all objects.
+    pub fn new(values: &[Value]) -> Option<Self> {
+        let elements = match values {
+            [Value::Array(elements)] => elements.as_slice(),
+            _ => values,
+        };
+        let rows = elements
+            .iter()
+            .map(|value| value.as_object().cloned())
+            .collect::<Option<Vec<_>>>()?;
+        if rows.is_empty() {
+            return None;
+        }
+        let mut keys: Vec<String> = Vec::new();
+        for row in &rows {
+            for key in row.keys() {
+                if !keys.contains(key) {
+                    keys.push(key.clone());
+                }
+            }
+        }
+        let widths = keys
+            .iter()
+            .map(|key| {
+                rows.iter()
+                    .filter_map(|row| row.get(key))
+                    .map(|value| width(&cell(value)))
+                    .chain([width(key)])
+                    .max()
+                    .unwrap_or(0)
+                    .min(MAX_CELL_WIDTH)
+            })
+            .collect();
+        Some(Self {
+            keys,
+            rows,
+            widths,
+            position: 0,
+            column: 0,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position.min(self.rows.len() - 1);
+    }
+
+    pub fn scroll_left(&mut self) {
+        self.column = self.column.saturating_sub(1);
+    }
+
+    pub fn scroll_home(&mut self) {
+        self.column = 0;
+    }
+
+    pub fn scroll_right(&mut self) {
+        self.column = (self.column + 1).min(self.keys.len() - 1);
+    }
+
+    /// Returns the range of the shown columns, for the given width.
+    pub fn describe_columns(&self, width: u16) -> String {
+        let shown = self.shown_columns(width as usize);
+        format!(
+            "columns {}-{} of {}",
+            self.column + 1,
+            self.column + shown,
+            self.keys.len()
+        )
+    }
+
+    /// Number of the columns from the first shown fitting in the width, at least one.
+    fn shown_columns(&self, width: usize) -> usize {
+        let mut used = 0;
+        let mut count = 0;
+        for column_width in &self.widths[self.column..] {
+            used += column_width + SEPARATOR.len();
+            if count > 0 && used > width + SEPARATOR.len() {
+                break;
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// Returns the header and the rows from the cursor, cut to the width.
+    pub fn create_lines(
+        &self,
+        width: u16,
+        height: u16,
+        header_style: ContentStyle,
+        active_attribute: Attribute,
+        inactive_attribute: Attribute,
+    ) -> Vec<StyledGraphemes> {
+        let width = width as usize;
+        let header = self
+            .line(|key| key.clone(), width)
+            .apply_style(header_style);
+        let rows = self.rows[self.position..]
+            .iter()
+            .take((height as usize).saturating_sub(1))
+            .enumerate()
+            .map(|(i, row)| {
+                self.line(|key| row.get(key).map(cell).unwrap_or_default(), width)
+                    .apply_attribute(if i == 0 {
+                        active_attribute
+                    } else {
+                        inactive_attribute
+                    })
+            });
+        std::iter::once(header).chain(rows).collect()
+    }
+
+    /// Lays out the cells of the shown columns, padded to their width.
+    fn line<F: Fn(&String) -> String>(&self, cell_of: F, width: usize) -> StyledGraphemes {
+        let line = self.keys[self.column..]
+            .iter()
+            .zip(&self.widths[self.column..])
+            .map(|(key, column_width)| pad(&cell_of(key), *column_width))
+            .collect::<Vec<_>>()
+            .join(SEPARATOR);
+        StyledGraphemes::from(truncate(line.trim_end(), width))
+    }
+
+    /// Returns the table as tab-separated values, with a header line of the keys.
+    ///
+    /// Nested objects and arrays are written as compact JSON.
+    pub fn to_tsv(&self) -> String {
+        let header = self
+            .keys
+            .iter()
+            .map(|key| escape_tsv(key))
+            .collect::<Vec<_>>();
+        std::iter::once(header.join("\t"))
+            .chain(self.rows.iter().map(|row| {
+                self.keys
+                    .iter()
+                    .map(|key| match row.get(key) {
+                        None | Some(Value::Null) => String::new(),
+                        Some(Value::String(s)) => escape_tsv(s),
+                        Some(value) => value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            }))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Returns the text of a value in a cell: strings unquoted and containers elided.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.replace('\n', "\\n").replace('\t', "\\t"),
+        Value::Object(map) if map.is_empty() => "{}".to_string(),
+        Value::Object(_) => "{…}".to_string(),
+        Value::Array(array) if array.is_empty() => "[]".to_string(),
+        Value::Array(_) => "[…]".to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// Escapes a string as `jq`'s `@tsv` does.
+fn escape_tsv(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn char_width(ch: char) -> usize {
+    StyledGrapheme::from(ch).width()
+}
+
+fn width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// Cuts the string to the width, ending with `…` if it is cut.
+fn truncate(s: &str, max: usize) -> String {
+    if width(s) <= max {
+        return s.to_string();
+    }
+    let mut ret = String::new();
+    let mut used = 0;
+    for ch in s.chars() {
+        used += char_width(ch);
+        if used + 1 > max {
+            break;
+        }
+        ret.push(ch);
+    }
+    if max > 0 {
+        ret.push('…');
+    }
+    ret
+}
+
+/// Cuts or pads the string to exactly the width.
+fn pad(s: &str, column_width: usize) -> String {
+    let mut ret = truncate(s, column_width);
+    ret.push_str(&" ".repeat(column_width.saturating_sub(width(&ret))));
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::serde_json::json;
+
+    use super::*;
+
+    fn lines(table: &Table) -> Vec<String> {
+        table
+            .create_lines(
+                80,
+                10,
+                ContentStyle::default(),
+                Attribute::Bold,
+                Attribute::Dim,
+            )
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn leaves_the_missing_keys_blank() {
+        let table = Table::new(&[json!([
+            {"id": 1, "name": "a"},
+            {"name": "b", "tag": "x"},
+            {}
+        ])])
+        .unwrap();
+        // The keys in the order they first appear.
+        assert_eq!(lines(&table), ["id  name  tag", "1   a", "    b     x", ""]);
+        assert_eq!(table.to_tsv(), "id\tname\ttag\n1\ta\t\n\tb\tx\n\t\t");
+    }
+
+    #[test]
+    fn elides_the_nested_values() {
+        let table = Table::new(&[
+            json!({"a": {"b": 1}, "c": [1, 2], "d": {}, "e": []}),
+            json!({"a": null, "c": "x\ty"}),
+        ])
+        .unwrap();
+        assert_eq!(
+            lines(&table),
+            ["a     c     d   e", "{…}   […]   {}  []", "null  x\\ty"]
+        );
+        // Written out as compact JSON instead.
+        assert_eq!(
+            table.to_tsv(),
+            "a\tc\td\te\n{\"b\":1}\t[1,2]\t{}\t[]\n\tx\\ty\t\t"
+        );
+    }
+
+    #[test]
+    fn needs_objects_only() {
+        for values in [
+            vec![json!([{"a": 1}, 2])],
+            vec![json!([{"a": 1}, [{"a": 2}]])],
+            vec![json!([])],
+            vec![json!({"a": 1}), json!([{"a": 2}])],
+            vec![json!([{"a": 1}]), json!([{"a": 2}])],
+            vec![json!("a")],
+            vec![],
+        ] {
+            assert!(Table::new(&values).is_none(), "{:?}", values);
+        }
+    }
+}
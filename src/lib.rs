@@ -0,0 +1,604 @@
+//! JSON navigator and interactive filter leveraging jq.
+//!
+//! Besides the `jnv` command, the interface can be run from another program by [`run`],
+//! which returns the filter the user ended up with and the values it results in.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, Read, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context as _, Result};
+use crossterm::{
+    event::EventStream,
+    style::{Attribute, Attributes, Colored, ContentStyle},
+    terminal,
+};
+pub use promkit::text_editor::Mode as EditMode;
+use promkit::{
+    jsonz::format::RowFormatter,
+    listbox::{self, Listbox},
+    serde_json,
+    style::StyleBuilder,
+    text_editor,
+};
+use tokio::sync::mpsc;
+
+mod builtins;
+mod clipboard;
+mod command;
+use command::CommandTemplate;
+mod config;
+pub use config::{Config, Engine, Keybinds, ThemeName};
+mod editor;
+mod external;
+mod follow;
+mod footer;
+mod help;
+mod history;
+use history::HistoryFile;
+mod input;
+use editor::{BracketStyles, Editor, EditorSettings, EditorTheme};
+use footer::Footer;
+use input::InputFile;
+pub use input::InputFormat;
+mod json;
+mod options;
+use json::{FilterScope, JsonSettings, JsonStreamProvider, Library, OutputFormat, ReadSettings};
+pub use options::Options;
+mod paths;
+mod pipe;
+mod processor;
+use processor::{
+    init::ViewInitializer, monitor::ContextMonitor, spinner::SpinnerSpawner, Context, Processor,
+    ViewProvider, Visualizer,
+};
+mod progress;
+mod projection;
+use progress::{Phase, StartupProgress};
+mod prompt;
+use prompt::TerminalUse;
+pub use prompt::{restore_terminal, OutputMode};
+mod record;
+use record::EventSource;
+mod registers;
+use registers::Registers;
+mod render;
+use render::{empty_pane, Layout, PaneIndex, Renderer};
+mod safe;
+use safe::SafeMode;
+mod search;
+use search::{IncrementalSearcher, SearchProvider, SearchSettings};
+mod sequence;
+mod state;
+use state::StateFile;
+mod stats;
+mod trie;
+mod tty;
+mod watch;
+
+/// How a session ended.
+#[derive(Debug)]
+pub struct Outcome {
+    /// Filter in the editor.
+    pub query: String,
+    /// Values resulting from the filter, or the input if it is empty.
+    pub values: Vec<serde_json::Value>,
+    /// Whether the session was left with the `exit_with_output` keybind
+    /// rather than quit.
+    pub accepted: bool,
+}
+
+/// Runs the interface on the input, a stream of JSON documents,
+/// until the user leaves it.
+///
+/// The keys are read from the terminal, on which the interface is drawn,
+/// unless the options give other events and another screen.
+/// Builds its own tokio runtime, hence must not be called from within one.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Write};
+///
+/// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+/// use jnv::Options;
+/// use tokio::sync::mpsc;
+///
+/// /// Screen sending the text of each frame drawn on it.
+/// struct Screen(Vec<u8>, mpsc::UnboundedSender<String>);
+///
+/// impl Write for Screen {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         self.0.extend_from_slice(buf);
+///         Ok(buf.len())
+///     }
+///
+///     // Once a frame is drawn.
+///     fn flush(&mut self) -> io::Result<()> {
+///         let frame = String::from_utf8_lossy(&std::mem::take(&mut self.0)).into_owned();
+///         let _ = self.1.send(without_escapes(&frame));
+///         Ok(())
+///     }
+/// }
+/// # fn without_escapes(frame: &str) -> String {
+/// #     let mut escaping = false;
+/// #     frame
+/// #         .chars()
+/// #         .filter(|&ch| {
+/// #             let keep = !escaping && ch != '\x1b';
+/// #             escaping = (escaping || ch == '\x1b') && !ch.is_ascii_alphabetic();
+/// #             keep
+/// #         })
+/// #         .collect()
+/// # }
+///
+/// // Quit once the result of the query is drawn, rather than the input.
+/// let (frames_tx, mut frames_rx) = mpsc::unbounded_channel::<String>();
+/// let quit = Event::Key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+/// let events = futures::stream::once(async move {
+///     while let Some(frame) = frames_rx.recv().await {
+///         if frame.contains("\"jnv\"") && !frame.contains("\"name\"") {
+///             break;
+///         }
+///     }
+///     Ok(quit)
+/// });
+/// let options = Options::default()
+///     .query(".name")
+///     .safe_mode(true)
+///     .event_source(events)
+///     .screen(Screen(Vec::new(), frames_tx), (80, 24));
+///
+/// let outcome = jnv::run(r#"{"name": "jnv"}"#.to_string(), options)?;
+/// assert_eq!(outcome.query, ".name");
+/// assert_eq!(outcome.values, ["jnv"]);
+/// # anyhow::Ok(())
+/// ```
+pub fn run(input: String, options: Options) -> Result<Outcome> {
+    block_on(async move {
+        let output = separate_output(&options)?;
+        let progress = StartupProgress::new(options.config.hint.progress_after);
+        progress.enter(Phase::Parsing, Some(input.len()));
+        finish(session(input, None, options, progress).await?, output)
+    })
+}
+
+/// Reads the input file of the options, or standard input if there is none,
+/// then runs the interface on it as [`run`] does.
+///
+/// A reading that takes long is reported on the terminal.
+/// Standard input piped from a command still writing to it (e.g. `kubectl logs -f`)
+/// is shown as soon as it pauses, the documents that follow being appended.
+/// Input in another format than JSON is converted to JSON.
+pub fn run_from_input(options: Options) -> Result<Outcome> {
+    block_on(async move {
+        let output = separate_output(&options)?;
+        let progress = StartupProgress::new(options.config.hint.progress_after);
+        let reporting = tokio::spawn(progress.clone().report_reading());
+        let input = match options.input_file() {
+            // Piped input may go on long after its first documents, e.g. from `kubectl logs -f`.
+            None if InputFormat::detect(options.input_format, None) == InputFormat::Json
+                && pipe::stdin_is_pipe() =>
+            {
+                pipe::read_stdin(&progress)
+            }
+            _ => read_input(&options, &progress).map(|input| (input, None)),
+        };
+        progress.enter(
+            Phase::Parsing,
+            input.as_ref().ok().map(|(input, _)| input.len()),
+        );
+        let _ = reporting.await;
+        let (input, receiving) = input?;
+        finish(session(input, receiving, options, progress).await?, output)
+    })
+}
+
+/// Runs the filter over the input file of the options (or standard input)
+/// without the interface, writing the results as jq does.
+///
+/// Fails if the filter does not parse or fails.
+pub fn run_filter<W: Write>(
+    filter: &str,
+    compact: bool,
+    options: &Options,
+    out: &mut W,
+) -> Result<()> {
+    // No terminal is involved, so nothing is reported while loading.
+    let input = read_input(options, &StartupProgress::new(Duration::MAX))?;
+    let (stream, skipped_lines) =
+        JsonStreamProvider::deserialize_json(&input, options.max_streams, options.lenient, None)?;
+    if skipped_lines > 0 {
+        eprintln!("jnv: skipped {} non-JSON line(s)", skipped_lines);
+    }
+    let stream = json::slurp_if(options.slurp, stream);
+    tokio::runtime::Runtime::new()?.block_on(json::run_filter(
+        filter,
+        stream,
+        // Nothing is shown meanwhile, so the filter runs for as long as it takes.
+        FilterScope {
+            timeout: None,
+            ..filter_scope(options)
+        },
+        options.config.jq.strict_errors,
+        &OutputFormat {
+            compact,
+            raw: options.raw_output,
+            indent: options.indent,
+        },
+        out,
+    ))
+}
+
+fn block_on<F: std::future::Future<Output = Result<Outcome>>>(future: F) -> Result<Outcome> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = runtime.block_on(future);
+    // The terminal is restored by now, so exit without waiting for the work in flight
+    // on blocking threads (e.g. indexing suggestions) nor dropping what it holds.
+    runtime.shutdown_background();
+    result
+}
+
+/// Returns the variables and the modules the filters are compiled with.
+fn filter_scope(options: &Options) -> FilterScope {
+    FilterScope {
+        safe_mode: safe_mode(options),
+        variables: options.variables.clone(),
+        library: Library::new(options.library_paths.clone()),
+        env: !options.no_env,
+        external: match options.engine.unwrap_or(options.config.jq.engine) {
+            Engine::Jaq => None,
+            Engine::Jq => Some(options.config.jq.binary.clone()),
+        },
+        timeout: Some(options.config.jq.query_timeout).filter(|timeout| !timeout.is_zero()),
+        workers: match options.config.jq.workers {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        },
+    }
+}
+
+fn safe_mode(options: &Options) -> SafeMode {
+    SafeMode::new(options.safe_mode || options.config.safe_mode)
+}
+
+/// Sets standard output apart if the result is to be written to it.
+fn separate_output(options: &Options) -> Result<Option<tty::Output>> {
+    match options.output_mode {
+        Some(_) => tty::separate_output().map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Writes what the session left with to standard output.
+fn finish(
+    (outcome, result): (Outcome, Option<String>),
+    output: Option<tty::Output>,
+) -> Result<Outcome> {
+    if let (Some(output), Some(result)) = (output, result) {
+        output.write_line(&result)?;
+    }
+    Ok(outcome)
+}
+
+/// Reads the input from either the input file or standard input.
+///
+/// If there is no input file, or if it is "-", data is read from standard input.
+/// Input in another format is converted to JSON.
+fn read_input(options: &Options, progress: &StartupProgress) -> Result<String> {
+    let format = InputFormat::detect(options.input_format, options.input.as_deref());
+    let input = match options.input_file() {
+        Some(path) => {
+            let file = File::open(path)?;
+            let len = file.metadata().ok().map(|metadata| metadata.len() as usize);
+            progress.enter(Phase::Reading, len);
+            read_with_progress(file, progress)
+        }
+        None => read_with_progress(io::stdin(), progress),
+    }?;
+    input::to_json(input, format)
+}
+
+/// Reads everything by chunks, reporting the bytes read so far.
+fn read_with_progress<R: Read>(mut reader: R, progress: &StartupProgress) -> Result<String> {
+    let mut ret = Vec::new();
+    let mut chunk = vec![0; 1 << 20];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                ret.extend_from_slice(&chunk[..n]);
+                progress.advance(n);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    String::from_utf8(ret).map_err(|_| anyhow!("stream did not contain valid UTF-8"))
+}
+
+/// Runs the interface on the input read,
+/// appending the documents received meanwhile if the input is still open.
+/// Disables the colors of crossterm, with which the panes are drawn,
+/// until dropped, as the setting is global to the process
+/// (e.g. also used by the application calling the library).
+/// Tells whether to draw without colors, as asked by --no-color
+/// or by the NO_COLOR environment variable unless it is empty (https://no-color.org/).
+fn colors_disabled(flag: bool, no_color_env: Option<&std::ffi::OsStr>) -> bool {
+    flag || no_color_env.is_some_and(|value| !value.is_empty())
+}
+
+struct ColorsDisabled {
+    previous: bool,
+}
+
+impl ColorsDisabled {
+    fn new() -> Self {
+        let previous = Colored::ansi_color_disabled();
+        Colored::set_ansi_color_disabled(true);
+        Self { previous }
+    }
+}
+
+impl Drop for ColorsDisabled {
+    fn drop(&mut self) {
+        Colored::set_ansi_color_disabled(self.previous);
+    }
+}
+
+async fn session(
+    input: String,
+    receiving: Option<mpsc::Receiver<Vec<serde_json::Value>>>,
+    mut options: Options,
+    progress: StartupProgress,
+) -> Result<(Outcome, Option<String>)> {
+    let follow = match options.input_file() {
+        _ if !options.follow => None,
+        Some(path) => {
+            if InputFormat::detect(options.input_format, Some(path)) != InputFormat::Json {
+                return Err(anyhow!("--follow only supports JSON input"));
+            }
+            let (documents_tx, documents_rx) = mpsc::channel(1);
+            follow::spawn_follow_task(path.clone(), input.len() as u64, documents_tx);
+            Some(documents_rx)
+        }
+        _ => return Err(anyhow!("--follow requires an input file")),
+    }
+    .or(receiving);
+    let watch = match options.input_file() {
+        _ if !options.watch => None,
+        Some(path) => {
+            let (changed_tx, changed_rx) = mpsc::channel(1);
+            watch::spawn_watch_task(
+                path.clone(),
+                options.config.reactivity_control.watch_interval,
+                changed_tx,
+            );
+            Some(changed_rx)
+        }
+        _ => return Err(anyhow!("--watch requires an input file")),
+    };
+    let input_file = options.input_file().map(|path| {
+        InputFile::new(
+            path.clone(),
+            InputFormat::detect(options.input_format, Some(path)),
+        )
+    });
+    let terminal = TerminalUse {
+        keyboard: options.event_source.is_none(),
+        screen: options.screen.is_none(),
+    };
+    if terminal.keyboard {
+        tty::select_keyboard(options.tty.as_deref())?;
+    }
+    let safe_mode = safe_mode(&options);
+    // Show a persistent badge next to the prompt.
+    let badge = if safe_mode.is_enabled() { "SAFE " } else { "" };
+
+    let mut event_source = options
+        .event_source
+        .take()
+        .unwrap_or_else(|| Box::pin(EventStream::new()));
+    if let Some((path, speed)) = &options.replay {
+        let (source, warning) = record::replay(path, *speed, &input, event_source)
+            .with_context(|| format!("Failed to load the recording {}", path.display()))?;
+        if let Some(warning) = warning {
+            eprintln!("{}", warning);
+        }
+        event_source = source;
+    }
+    if let Some(path) = &options.record {
+        event_source = record::record(event_source, path, &input, safe_mode)
+            .with_context(|| format!("Failed to create the recording {}", path.display()))?;
+    }
+
+    let mut texteditor = text_editor::TextEditor::default();
+    // Insert by character so that the cursor lands at the end.
+    for ch in options.query.iter().flat_map(|query| query.chars()) {
+        texteditor.insert(ch);
+    }
+
+    let no_color = colors_disabled(options.no_color, std::env::var_os("NO_COLOR").as_deref());
+    let _colors = no_color.then(ColorsDisabled::new);
+    // Without colors, the cursor of the other themes would not show.
+    let theme = options
+        .config
+        .theme
+        .resolve(options.theme.or(no_color.then_some(ThemeName::Monochrome)));
+    let (screen, screen_size) = match options.screen.take() {
+        Some(screen) => screen,
+        None => (Box::new(io::stdout()) as render::Screen, terminal::size()?),
+    };
+    prompt::run(
+        &mut JsonStreamProvider::new(
+            &JsonSettings {
+                formatter: RowFormatter {
+                    curly_brackets_style: theme.curly_brackets,
+                    square_brackets_style: theme.square_brackets,
+                    key_style: theme.key,
+                    string_value_style: theme.string_value,
+                    number_value_style: theme.number_value,
+                    boolean_value_style: theme.boolean_value,
+                    null_value_style: theme.null_value,
+                    active_item_attribute: Attribute::Bold,
+                    inactive_item_attribute: Attribute::Dim,
+                    indent: options.indent,
+                },
+                strict_errors: options.config.jq.strict_errors,
+                insert_path: options.config.jq.insert_path,
+                case_sensitive_search: options.config.json.case_sensitive_search,
+                expand_depth: options
+                    .expand_depth
+                    .or(options.config.json.default_expand_depth),
+                cache_entries: options.config.jq.cache_entries,
+                error_span_style: theme.error_span,
+                bookmark_style: theme.bookmark,
+                raw_output: options.raw_output,
+                show_line_numbers: options.config.json.show_line_numbers,
+                show_position: options.config.json.show_position,
+                sticky_path: options.config.json.sticky_path,
+                hide_nulls: options.config.json.hide_nulls,
+                copy_hidden: options.config.json.copy_hidden,
+                show_timing: options.config.hint.timing,
+                safe_mode,
+                scope: Arc::new(filter_scope(&options)),
+                max_results: options.max_results.or(options.config.json.max_results),
+                split: options.config.layout.split,
+            },
+            &ReadSettings {
+                max_streams: options.max_streams,
+                lenient: options.lenient,
+                slurp: options.slurp,
+                follow: follow.is_some(),
+            },
+            &options.config.keybinds,
+            StateFile::for_input(options.input.as_deref(), safe_mode),
+            progress.clone(),
+        ),
+        prompt::Session {
+            item: Arc::from(input),
+            event_source,
+            screen,
+            screen_size,
+            terminal,
+            history_file: HistoryFile::new(&options.config.history, safe_mode),
+            registers: Registers::new(&options.config.registers, safe_mode),
+            progress,
+            follow,
+            input_file,
+            watch,
+        },
+        &prompt::Settings {
+            spin_duration: options.config.reactivity_control.spin_duration,
+            query_debounce_duration: options.config.reactivity_control.query_debounce_duration,
+            resize_debounce_duration: options.config.reactivity_control.resize_debounce_duration,
+            viewer_coalesce_duration: options.config.reactivity_control.viewer_coalesce_duration,
+            text_editor_state: text_editor::State {
+                texteditor,
+                history: Some(Default::default()),
+                prefix: format!("{}❯❯ ", badge),
+                mask: Default::default(),
+                prefix_style: theme.prompt,
+                active_char_style: theme.cursor,
+                inactive_char_style: StyleBuilder::new().build(),
+                edit_mode: options.edit_mode.clone(),
+                word_break_chars: HashSet::from(['.', '|', '(', ')', '[', ']']),
+                lines: Default::default(),
+            },
+            editor: EditorSettings {
+                focus_theme: EditorTheme {
+                    prefix: format!("{}❯❯ ", badge),
+                    prefix_style: theme.prompt,
+                    active_char_style: theme.cursor,
+                    inactive_char_style: StyleBuilder::new().build(),
+                    bracket_styles: Some(BracketStyles {
+                        matching: theme.matching_bracket,
+                        unmatched: theme.unmatched_bracket,
+                    }),
+                },
+                defocus_theme: EditorTheme {
+                    prefix: format!("{}▼", badge),
+                    prefix_style: ContentStyle {
+                        attributes: theme.prompt.attributes | Attribute::Dim,
+                        ..theme.prompt
+                    },
+                    active_char_style: StyleBuilder::new()
+                        .attrs(Attributes::from(Attribute::Dim))
+                        .build(),
+                    inactive_char_style: StyleBuilder::new()
+                        .attrs(Attributes::from(Attribute::Dim))
+                        .build(),
+                    bracket_styles: None,
+                },
+                snippets: options.config.editor.snippet_bindings()?,
+            },
+            listbox_state: listbox::State {
+                listbox: Listbox::from_displayable(Vec::<String>::new()),
+                cursor: String::from("❯ "),
+                active_item_style: Some(theme.selected_suggestion),
+                inactive_item_style: Some(theme.suggestion),
+                lines: Some(options.suggestions),
+            },
+            search: SearchSettings {
+                result_chunk_size: 100,
+                load_chunk_size: 50000,
+                load_throttle: options.config.completion.load_throttle,
+                order: options.config.completion.order,
+                matching: options.config.completion.matching,
+                case_insensitive: options.config.completion.case_insensitive,
+                array_indices: options.config.completion.array_indices,
+                result_paths: options.config.completion.result_paths,
+                index_input: options.index_paths,
+                variables: options
+                    .variables
+                    .iter()
+                    .map(|(name, _)| format!("${}", name))
+                    .chain((!options.no_env).then(|| "$ENV".to_string()))
+                    .collect(),
+            },
+            keybinds: options.config.keybinds.clone(),
+            confirm_quit: options.config.confirm_quit,
+            command_template: CommandTemplate::new(
+                options.config.jq.command_template.clone(),
+                options.config.jq.stdin_command_template.clone(),
+                options.input_file().cloned(),
+            ),
+            mouse: options.config.mouse,
+            footer: options
+                .config
+                .hint
+                .footer
+                .then(|| Footer::new(&options.config.keybinds)),
+            layout: Layout {
+                alt_screen: options.alt_screen || options.config.layout.alt_screen,
+                ..options.config.layout
+            },
+            safe_mode,
+            output_mode: options.output_mode,
+            no_hint: options.no_hint,
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+
+    #[test]
+    fn colors_are_disabled_by_the_flag_or_the_environment() {
+        assert!(!colors_disabled(false, None));
+        // Set but empty, as NO_COLOR= does.
+        assert!(!colors_disabled(false, Some(OsStr::new(""))));
+        assert!(colors_disabled(false, Some(OsStr::new("1"))));
+        assert!(colors_disabled(true, None));
+        assert!(colors_disabled(true, Some(OsStr::new(""))));
+        assert!(colors_disabled(true, Some(OsStr::new("1"))));
+    }
+}
@@ -1,35 +1,9 @@
-use std::{
-    collections::HashSet,
-    fs::File,
-    io::{self, Read},
-    path::PathBuf,
-    time::Duration,
-};
+use std::{io, panic::PanicHookInfo, path::PathBuf};
 
 use anyhow::{anyhow, Result};
 use clap::Parser;
-use crossterm::style::{Attribute, Attributes, Color};
-use promkit::{
-    jsonz::format::RowFormatter,
-    listbox::{self, Listbox},
-    style::StyleBuilder,
-    text_editor,
-};
-
-mod editor;
-use editor::{Editor, EditorTheme};
-mod json;
-use json::JsonStreamProvider;
-mod processor;
-use processor::{
-    init::ViewInitializer, monitor::ContextMonitor, spinner::SpinnerSpawner, Context, Processor,
-    ViewProvider, Visualizer,
-};
-mod prompt;
-mod render;
-use render::{PaneIndex, Renderer, EMPTY_PANE};
-mod search;
-use search::{IncrementalSearcher, SearchProvider};
+use jnv::{Config, EditMode, Engine, InputFormat, Options, OutputMode, ThemeName};
+use promkit::serde_json;
 
 /// JSON navigator and interactive filter leveraging jq
 #[derive(Parser)]
@@ -61,6 +35,85 @@ pub struct Args {
     /// reads from standard input.
     pub input: Option<PathBuf>,
 
+    #[arg(
+        long = "input-format",
+        value_parser = input_format_validator,
+        help = "Format of the input ('json', 'jsonc' or 'yaml').",
+        long_help = "
+        Specifies the format of the input.
+        If not provided, files ending with .yaml or .yml are read as YAML,
+        files ending with .jsonc as JSON with comments,
+        and everything else (including standard input) as JSON.
+        Every document of a multi-document YAML is shown as a JSON stream.
+        With 'jsonc', the comments (// and /* */) and trailing commas are ignored,
+        as in VS Code settings or tsconfig files.
+        "
+    )]
+    pub input_format: Option<InputFormat>,
+
+    #[arg(
+        long = "run",
+        value_name = "FILTER",
+        help = "Runs the jq filter without the interface and prints the results.",
+        long_help = "
+        Skips the interface: runs the given jq filter over the input
+        and prints the results to standard output, as jq does,
+        so that jnv can be used in scripts.
+        Exits with a non-zero status if the filter does not parse or fails.
+        "
+    )]
+    pub run: Option<String>,
+
+    #[arg(
+        short = 'c',
+        long = "compact",
+        requires = "run",
+        help = "Prints each result of --run on a single line."
+    )]
+    pub compact: bool,
+
+    #[arg(
+        short = 'r',
+        long = "raw-output",
+        help = "Shows the strings unquoted when every value is one.",
+        long_help = "
+        Shows the strings unquoted and unescaped, one after another,
+        when every value of the result is a string, as `jq -r` does.
+        They are also copied and written on exit as such.
+        Can be toggled in the JSON viewer (r by default).
+        With --run, prints the strings as such.
+        "
+    )]
+    pub raw_output: bool,
+
+    #[arg(
+        short = 'q',
+        long = "query",
+        help = "Initial jq filter.",
+        long_help = "
+        Starts with the given jq filter typed in the editor
+        and its result displayed, e.g. '.items[] | select(.active)'.
+        "
+    )]
+    pub query: Option<String>,
+
+    #[arg(
+        short = 'o',
+        long = "output-mode",
+        default_value = "result",
+        value_parser = output_mode_validator,
+        help = "What Ctrl+x writes to standard output on exit ('query', 'result' or 'both').",
+        long_help = r#"
+        Specifies what is written to standard output when leaving with
+        the exit_with_output keybind (Ctrl+x by default).
+        - "query" writes the jq filter.
+        - "result" writes the filtered JSON.
+        - "both" writes the filter, then the filtered JSON.
+        The interface is drawn on standard error while standard output is redirected.
+        "#,
+    )]
+    pub output_mode: OutputMode,
+
     #[arg(
         short = 'e',
         long = "edit-mode",
@@ -74,7 +127,7 @@ pub struct Args {
         - "overwrite" mode replaces existing characters with new input at the cursor's position.
         "#,
     )]
-    pub edit_mode: text_editor::Mode,
+    pub edit_mode: EditMode,
 
     #[arg(
         short = 'i',
@@ -88,6 +141,41 @@ pub struct Args {
     )]
     pub indent: usize,
 
+    #[arg(
+        long = "alt-screen",
+        help = "Draws on the alternate screen, restoring the terminal on exit.",
+        long_help = "
+        Draws the interface on the alternate screen from its top
+        instead of below the cursor, so that the content of the terminal
+        is left as it was once quitting.
+        Also enabled by `layout.alt_screen = true` in the configuration file.
+        "
+    )]
+    pub alt_screen: bool,
+
+    #[arg(
+        long = "theme",
+        value_parser = theme_validator,
+        help = "Built-in theme ('dark', 'light', 'solarized-dark' or 'monochrome').",
+        long_help = "
+        Selects the built-in theme, overriding theme.name in the configuration.
+        The styles set under [theme] in the configuration still take precedence.
+        "
+    )]
+    pub theme: Option<ThemeName>,
+
+    #[arg(
+        long = "no-color",
+        help = "Disables the colors, as the NO_COLOR environment variable does.",
+        long_help = "
+        Draws without any color, keeping the attributes (bold, dim...)
+        so that the active row and the cursor remain visible.
+        The monochrome theme is used unless --theme is given.
+        Also enabled by a non-empty NO_COLOR environment variable.
+        "
+    )]
+    pub no_color: bool,
+
     #[arg(
         short = 'n',
         long = "no-hint",
@@ -99,6 +187,45 @@ pub struct Args {
     )]
     pub no_hint: bool,
 
+    #[arg(
+        long = "safe-mode",
+        help = "Disables everything letting data leave the terminal.",
+        long_help = "
+        Disables the clipboard, saving files (including the state
+        remembered per input), recording the session and running
+        external commands (the editor, the jq engine),
+        so that nothing leaves the terminal.
+        It cannot be turned off while running.
+        Also enabled by `safe_mode = true` in the configuration file.
+        "
+    )]
+    pub safe_mode: bool,
+
+    #[arg(
+        long = "follow",
+        help = "Keeps reading the lines appended to the input file.",
+        long_help = "
+        Keeps the input file open and reads the JSON documents
+        of the lines appended to it (e.g. logs in JSON Lines),
+        applying the filter again as they arrive.
+        With --max-streams, only the latest documents are kept.
+        "
+    )]
+    pub follow: bool,
+
+    #[arg(
+        long = "watch",
+        conflicts_with = "follow",
+        help = "Reads the input file again whenever it changes.",
+        long_help = "
+        Checks the input file for changes (its modification time and size)
+        and reads it again once it has changed, applying the filter again.
+        The viewer keeps its position and collapsed nodes where the structure still matches.
+        The interval between the checks is `watch_interval` in the configuration file.
+        "
+    )]
+    pub watch: bool,
+
     #[arg(
         long = "max-streams",
         help = "Maximum number of JSON streams to display",
@@ -110,6 +237,119 @@ pub struct Args {
     )]
     pub max_streams: Option<usize>,
 
+    #[arg(
+        long = "limit",
+        value_name = "N",
+        help = "Maximum number of values of a result to display",
+        long_help = "
+        Keeps the first N values produced by the filter, stopping it there,
+        so that filters exploding into millions of values leave the interface usable.
+        Lifted for the current filter with Alt+l.
+        Overrides json.max_results in the configuration.
+        "
+    )]
+    pub limit: Option<usize>,
+
+    #[arg(
+        long = "lenient",
+        help = "Skips the lines of the input which are not JSON.",
+        long_help = "
+        Skips the lines which do not parse as JSON (e.g. plain-text warnings
+        interleaved with logs in JSON Lines) instead of failing on the first one.
+        The number of lines skipped is shown once the input is loaded.
+        "
+    )]
+    pub lenient: bool,
+
+    #[arg(
+        short = 's',
+        long = "slurp",
+        help = "Wraps the documents of the input into one array.",
+        long_help = "
+        Reads the documents of the input (e.g. the lines of JSON Lines)
+        into one array, which the filter runs on, as `jq --slurp` does
+        (e.g. to `group_by` across the records).
+        Toggled at runtime with Alt+a.
+        "
+    )]
+    pub slurp: bool,
+
+    #[arg(
+        long = "arg",
+        num_args = 2,
+        value_names = ["NAME", "VALUE"],
+        help = "Binds $NAME to the string VALUE in the filters.",
+        long_help = "
+        Binds $NAME to the string VALUE in the filters, as `jq --arg` does
+        (e.g. `--arg env prod` for `select(.env == $env)`).
+        May be given several times. The variables are suggested after a `$`.
+        "
+    )]
+    pub arg: Vec<String>,
+
+    #[arg(
+        long = "argjson",
+        num_args = 2,
+        value_names = ["NAME", "JSON"],
+        help = "Binds $NAME to the JSON value in the filters.",
+        long_help = "
+        Binds $NAME to the JSON value in the filters, as `jq --argjson` does
+        (e.g. `--argjson limit 10`). May be given several times.
+        "
+    )]
+    pub argjson: Vec<String>,
+
+    #[arg(
+        short = 'L',
+        long = "library-path",
+        value_name = "DIR",
+        help = "Directory the modules included by the filters are looked up in.",
+        long_help = "
+        Adds a directory the modules included at the start of the filters
+        (`include \"name\";`) are looked up in, as name.jq or name/name.jq.
+        May be given several times, the directories being searched in order.
+        jaq has no namespaces, so modules cannot be imported with `import`.
+        "
+    )]
+    pub library_path: Vec<PathBuf>,
+
+    #[arg(
+        long = "no-env",
+        help = "Hides the environment from the filters (env and $ENV).",
+        long_help = "
+        Hides the environment variables of the process from the filters,
+        which otherwise read them with `env` and `$ENV` as in jq,
+        since they may hold secrets (e.g. when sharing the screen).
+        "
+    )]
+    pub no_env: bool,
+
+    #[arg(
+        long = "engine",
+        value_parser = engine_validator,
+        help = "What runs the filters ('jaq' or 'jq').",
+        long_help = "
+        Runs the filters with the embedded jaq ('jaq'),
+        or with the jq binary of jq.binary in the configuration ('jq'),
+        run for each filter, for the builtins and the behaviors jaq lacks.
+        The jq binary is not run in safe mode, the filters failing instead.
+        Overrides jq.engine in the configuration.
+        "
+    )]
+    pub engine: Option<Engine>,
+
+    #[arg(
+        long = "expand-depth",
+        value_name = "N",
+        help = "Depth up to which the JSON is expanded at first",
+        long_help = "
+        Expands the containers shallower than N and collapses the deeper ones
+        (0 collapses every document), as the number keys do in the JSON viewer.
+        Overrides json.default_expand_depth in the configuration.
+        "
+    )]
+    pub expand_depth: Option<usize>,
+
     #[arg(
         long = "suggestions",
         default_value = "3",
@@ -121,119 +361,285 @@ pub struct Args {
         "
     )]
     pub suggestions: usize,
+
+    #[arg(
+        long = "no-suggestions",
+        help = "Skips indexing the paths of the input for suggestions.",
+        long_help = "
+        Leaves the paths of the input out of the autocomplete suggestions,
+        skipping their indexing, which takes time and memory for huge inputs.
+        The jq functions and the paths of the results before `|` are still suggested.
+        "
+    )]
+    pub no_suggestions: bool,
+
+    #[arg(
+        long = "config",
+        help = "Path to the configuration file.",
+        long_help = "
+        Specifies the path to a TOML configuration file.
+        If not provided, jnv reads `jnv/config.toml` under
+        the user's configuration directory (e.g. ~/.config on Linux)
+        when it exists, and falls back to the defaults otherwise.
+        "
+    )]
+    pub config_file: Option<PathBuf>,
+
+    #[arg(
+        long = "default-config",
+        conflicts_with = "current_config",
+        help = "Prints the default configuration and exits.",
+        long_help = "
+        Prints the configuration file with every key set to its default,
+        e.g. to start customizing it:
+        jnv --default-config > ~/.config/jnv/config.toml
+        "
+    )]
+    pub default_config: bool,
+
+    #[arg(
+        long = "current-config",
+        help = "Prints the configuration in effect and exits.",
+        long_help = "
+        Prints the configuration file read (see --config)
+        with the defaults filled in for the keys it omits.
+        "
+    )]
+    pub current_config: bool,
+
+    #[arg(
+        long = "record",
+        help = "Record input events of the session to the file.",
+        long_help = "
+        Appends every key, mouse, and resize event with its timestamp to the file
+        in JSON Lines format, together with the size and hash of the input.
+        The document contents and pasted text are never recorded.
+        Useful for attaching a reproducible session to bug reports.
+        "
+    )]
+    pub record: Option<PathBuf>,
+
+    #[arg(
+        long = "replay",
+        help = "Replay input events recorded with --record.",
+        long_help = "
+        Feeds the events recorded with --record back into the interface,
+        then continues accepting input from the keyboard.
+        The input may differ from the recorded one, in which case a warning is shown.
+        "
+    )]
+    pub replay: Option<PathBuf>,
+
+    #[arg(
+        long = "replay-speed",
+        default_value = "1.0",
+        help = "Speed factor for --replay (0 replays without delays).",
+        requires = "replay"
+    )]
+    pub replay_speed: f64,
+
+    #[arg(
+        long = "tty",
+        help = "Terminal device to read the keyboard from.",
+        long_help = "
+        Reads the keys from the given terminal device (e.g. /dev/pts/3)
+        instead of standard input, or of the controlling terminal
+        when the input is piped.
+        Useful where there is no controlling terminal.
+        "
+    )]
+    pub tty: Option<PathBuf>,
 }
 
-fn edit_mode_validator(val: &str) -> Result<text_editor::Mode> {
+fn edit_mode_validator(val: &str) -> Result<EditMode> {
     match val {
-        "insert" | "" => Ok(text_editor::Mode::Insert),
-        "overwrite" => Ok(text_editor::Mode::Overwrite),
+        "insert" | "" => Ok(EditMode::Insert),
+        "overwrite" => Ok(EditMode::Overwrite),
         _ => Err(anyhow!("edit-mode must be 'insert' or 'overwrite'")),
     }
 }
 
-/// Parses the input based on the provided arguments.
-///
-/// This function reads input data from either a specified file or standard input.
-/// If the `input` argument is `None`, or if it is a path
-/// that equals "-", data is read from standard input.
-/// Otherwise, the function attempts to open and
-/// read from the file specified in the `input` argument.
-fn parse_input(args: &Args) -> Result<String> {
-    let mut ret = String::new();
-
-    match &args.input {
-        None => {
-            io::stdin().read_to_string(&mut ret)?;
+fn input_format_validator(val: &str) -> Result<InputFormat> {
+    match val {
+        "json" => Ok(InputFormat::Json),
+        "jsonc" => Ok(InputFormat::Jsonc),
+        "yaml" | "yml" => Ok(InputFormat::Yaml),
+        _ => Err(anyhow!("input-format must be 'json', 'jsonc' or 'yaml'")),
+    }
+}
+
+fn engine_validator(val: &str) -> Result<Engine> {
+    match val {
+        "jaq" => Ok(Engine::Jaq),
+        "jq" => Ok(Engine::Jq),
+        _ => Err(anyhow!("engine must be 'jaq' or 'jq'")),
+    }
+}
+
+fn output_mode_validator(val: &str) -> Result<OutputMode> {
+    match val {
+        "query" => Ok(OutputMode::Query),
+        "result" | "" => Ok(OutputMode::Result),
+        "both" => Ok(OutputMode::Both),
+        _ => Err(anyhow!("output-mode must be 'query', 'result' or 'both'")),
+    }
+}
+
+fn theme_validator(val: &str) -> Result<ThemeName> {
+    match val {
+        "dark" => Ok(ThemeName::Dark),
+        "light" => Ok(ThemeName::Light),
+        "solarized-dark" => Ok(ThemeName::SolarizedDark),
+        "monochrome" => Ok(ThemeName::Monochrome),
+        _ => Err(anyhow!(
+            "theme must be 'dark', 'light', 'solarized-dark' or 'monochrome'"
+        )),
+    }
+}
+
+impl Args {
+    fn options(&self, config: Config) -> Result<Options> {
+        let mut options = Options::default()
+            .config(config)
+            .output_mode(self.output_mode)
+            .edit_mode(self.edit_mode.clone())
+            .indent(self.indent)
+            .raw_output(self.raw_output)
+            .alt_screen(self.alt_screen)
+            .no_color(self.no_color)
+            .no_hint(self.no_hint)
+            .safe_mode(self.safe_mode)
+            .follow(self.follow)
+            .watch(self.watch)
+            .lenient(self.lenient)
+            .slurp(self.slurp)
+            .no_env(self.no_env)
+            .suggestions(self.suggestions)
+            .index_paths(!self.no_suggestions);
+        if let Some(input) = &self.input {
+            options = options.input(input.clone());
+        }
+        if let Some(format) = self.input_format {
+            options = options.input_format(format);
+        }
+        if let Some(query) = &self.query {
+            options = options.query(query.clone());
+        }
+        if let Some(theme) = self.theme {
+            options = options.theme(theme);
+        }
+        if let Some(max_streams) = self.max_streams {
+            options = options.max_streams(max_streams);
+        }
+        if let Some(limit) = self.limit {
+            options = options.max_results(limit);
         }
-        Some(path) => {
-            if path == &PathBuf::from("-") {
-                io::stdin().read_to_string(&mut ret)?;
-            } else {
-                File::open(path)?.read_to_string(&mut ret)?;
-            }
+        if let Some(engine) = self.engine {
+            options = options.engine(engine);
         }
+        if let Some(depth) = self.expand_depth {
+            options = options.expand_depth(depth);
+        }
+        if let Some(path) = &self.record {
+            options = options.record(path.clone());
+        }
+        if let Some(path) = &self.replay {
+            options = options.replay(path.clone(), self.replay_speed);
+        }
+        if let Some(path) = &self.tty {
+            options = options.tty(path.clone());
+        }
+        for path in &self.library_path {
+            options = options.library_path(path.clone());
+        }
+        for pair in self.arg.chunks(2) {
+            options = options.arg(&pair[0], &pair[1]);
+        }
+        for pair in self.argjson.chunks(2) {
+            let value = serde_json::from_str(&pair[1])
+                .map_err(|e| anyhow!("--argjson {}: invalid JSON: {}", pair[0], e))?;
+            options = options.argjson(&pair[0], value);
+        }
+        Ok(options)
     }
+}
+
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Send + Sync>;
 
-    Ok(ret)
+/// Chains the panic hook after `restore`, so that the message is readable,
+/// then calls `exit` since the other tasks cannot go on drawing.
+fn restoring_hook(
+    previous: PanicHook,
+    restore: impl Fn() + Send + Sync + 'static,
+    exit: impl Fn() + Send + Sync + 'static,
+) -> PanicHook {
+    Box::new(move |info| {
+        restore();
+        previous(info);
+        exit();
+    })
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
-    let input = parse_input(&args)?;
-
-    prompt::run(
-        Box::leak(input.into_boxed_str()),
-        Duration::from_millis(300),
-        Duration::from_millis(600),
-        Duration::from_millis(200),
-        &mut JsonStreamProvider::new(
-            RowFormatter {
-                curly_brackets_style: StyleBuilder::new()
-                    .attrs(Attributes::from(Attribute::Bold))
-                    .build(),
-                square_brackets_style: StyleBuilder::new()
-                    .attrs(Attributes::from(Attribute::Bold))
-                    .build(),
-                key_style: StyleBuilder::new().fgc(Color::Cyan).build(),
-                string_value_style: StyleBuilder::new().fgc(Color::Green).build(),
-                number_value_style: StyleBuilder::new().build(),
-                boolean_value_style: StyleBuilder::new().build(),
-                null_value_style: StyleBuilder::new().fgc(Color::Grey).build(),
-                active_item_attribute: Attribute::Bold,
-                inactive_item_attribute: Attribute::Dim,
-                indent: args.indent,
-            },
-            args.max_streams,
-        ),
-        text_editor::State {
-            texteditor: Default::default(),
-            history: Default::default(),
-            prefix: String::from("❯❯ "),
-            mask: Default::default(),
-            prefix_style: StyleBuilder::new().fgc(Color::Blue).build(),
-            active_char_style: StyleBuilder::new().bgc(Color::Magenta).build(),
-            inactive_char_style: StyleBuilder::new().build(),
-            edit_mode: args.edit_mode,
-            word_break_chars: HashSet::from(['.', '|', '(', ')', '[', ']']),
-            lines: Default::default(),
-        },
-        EditorTheme {
-            prefix: String::from("❯❯ "),
-            prefix_style: StyleBuilder::new().fgc(Color::Blue).build(),
-            active_char_style: StyleBuilder::new().bgc(Color::Magenta).build(),
-            inactive_char_style: StyleBuilder::new().build(),
-        },
-        EditorTheme {
-            prefix: String::from("▼"),
-            prefix_style: StyleBuilder::new()
-                .fgc(Color::Blue)
-                .attrs(Attributes::from(Attribute::Dim))
-                .build(),
-            active_char_style: StyleBuilder::new()
-                .attrs(Attributes::from(Attribute::Dim))
-                .build(),
-            inactive_char_style: StyleBuilder::new()
-                .attrs(Attributes::from(Attribute::Dim))
-                .build(),
+fn main() -> anyhow::Result<()> {
+    std::panic::set_hook(restoring_hook(
+        std::panic::take_hook(),
+        || {
+            let _ = jnv::restore_terminal();
         },
-        listbox::State {
-            listbox: Listbox::from_displayable(Vec::<String>::new()),
-            cursor: String::from("❯ "),
-            active_item_style: Some(
-                StyleBuilder::new()
-                    .fgc(Color::Grey)
-                    .bgc(Color::Yellow)
-                    .build(),
-            ),
-            inactive_item_style: Some(StyleBuilder::new().fgc(Color::Grey).build()),
-            lines: Some(args.suggestions),
-        },
-        100,
-        50000,
-        args.no_hint,
-    )
-    .await?;
+        || std::process::exit(101),
+    ));
 
+    let args = Args::parse();
+    if args.default_config {
+        print!("{}", toml::to_string_pretty(&Config::default())?);
+        return Ok(());
+    }
+    let config = Config::load(args.config_file.as_deref())?;
+    if args.current_config {
+        print!("{}", toml::to_string_pretty(&config)?);
+        return Ok(());
+    }
+    let options = args.options(config)?;
+    if let Some(filter) = &args.run {
+        return jnv::run_filter(
+            filter,
+            args.compact,
+            &options,
+            &mut io::BufWriter::new(io::stdout().lock()),
+        );
+    }
+    jnv::run_from_input(options)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    #[test]
+    fn panic_hook_restores_before_the_previous_one() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let record = |call: &'static str| {
+            let calls = calls.clone();
+            move || calls.lock().unwrap().push(call)
+        };
+        let previous = {
+            let record = record("previous");
+            Box::new(move |info: &PanicHookInfo<'_>| {
+                assert_eq!(info.payload().downcast_ref::<&str>(), Some(&"boom"));
+                record();
+            })
+        };
+        panic::set_hook(restoring_hook(previous, record("restore"), record("exit")));
+        let result = panic::catch_unwind(|| panic!("boom"));
+        let _ = panic::take_hook();
+
+        assert!(result.is_err());
+        assert_eq!(*calls.lock().unwrap(), ["restore", "previous", "exit"]);
+    }
+}
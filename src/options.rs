@@ -0,0 +1,308 @@
+//! Options of a session, as given on the command line.
+
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use crossterm::event::Event;
+use futures::Stream;
+use promkit::{serde_json, text_editor};
+
+use crate::{
+    config::{Config, Engine, Keybinds, ThemeName},
+    input::InputFormat,
+    prompt::OutputMode,
+    record::EventSource,
+    render::Screen,
+};
+
+/// Options of a session run by [`run`](crate::run),
+/// built by changing the defaults one after another
+/// (e.g. `Options::default().query(".items[]").indent(4)`),
+/// as in the example of [`run`](crate::run).
+pub struct Options {
+    pub(crate) config: Config,
+    pub(crate) input: Option<PathBuf>,
+    pub(crate) input_format: Option<InputFormat>,
+    pub(crate) query: Option<String>,
+    pub(crate) output_mode: Option<OutputMode>,
+    pub(crate) edit_mode: text_editor::Mode,
+    pub(crate) indent: usize,
+    pub(crate) raw_output: bool,
+    pub(crate) alt_screen: bool,
+    pub(crate) theme: Option<ThemeName>,
+    pub(crate) no_color: bool,
+    pub(crate) no_hint: bool,
+    pub(crate) safe_mode: bool,
+    pub(crate) follow: bool,
+    pub(crate) watch: bool,
+    pub(crate) max_streams: Option<usize>,
+    pub(crate) max_results: Option<usize>,
+    pub(crate) lenient: bool,
+    pub(crate) slurp: bool,
+    pub(crate) variables: Vec<(String, serde_json::Value)>,
+    pub(crate) library_paths: Vec<PathBuf>,
+    pub(crate) no_env: bool,
+    pub(crate) engine: Option<Engine>,
+    pub(crate) expand_depth: Option<usize>,
+    pub(crate) suggestions: usize,
+    pub(crate) index_paths: bool,
+    pub(crate) record: Option<PathBuf>,
+    pub(crate) replay: Option<(PathBuf, f64)>,
+    pub(crate) tty: Option<PathBuf>,
+    pub(crate) event_source: Option<EventSource>,
+    pub(crate) screen: Option<(Screen, (u16, u16))>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            config: Config::default(),
+            input: None,
+            input_format: None,
+            query: None,
+            output_mode: None,
+            edit_mode: text_editor::Mode::Insert,
+            indent: 2,
+            raw_output: false,
+            alt_screen: false,
+            theme: None,
+            no_color: false,
+            no_hint: false,
+            safe_mode: false,
+            follow: false,
+            watch: false,
+            max_streams: None,
+            max_results: None,
+            lenient: false,
+            slurp: false,
+            variables: Vec::new(),
+            library_paths: Vec::new(),
+            no_env: false,
+            engine: None,
+            expand_depth: None,
+            suggestions: 3,
+            index_paths: true,
+            record: None,
+            replay: None,
+            tty: None,
+            event_source: None,
+            screen: None,
+        }
+    }
+}
+
+impl Options {
+    /// Configuration, as read from the configuration file.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Keybinds, replacing those of the configuration.
+    pub fn keybinds(mut self, keybinds: Keybinds) -> Self {
+        self.config.keybinds = keybinds;
+        self
+    }
+
+    /// File the input comes from, read by [`run_from_input`](crate::run_from_input)
+    /// (standard input if `-`), and needed by `follow` and `watch`.
+    pub fn input(mut self, path: PathBuf) -> Self {
+        self.input = Some(path);
+        self
+    }
+
+    /// Format of the input, told by the extension of its file if not given.
+    pub fn input_format(mut self, format: InputFormat) -> Self {
+        self.input_format = Some(format);
+        self
+    }
+
+    /// Filter typed in the editor at first.
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    /// Writes to standard output on leaving with `exit_with_output`,
+    /// drawing the interface elsewhere if standard output is redirected.
+    pub fn output_mode(mut self, mode: OutputMode) -> Self {
+        self.output_mode = Some(mode);
+        self
+    }
+
+    pub fn edit_mode(mut self, mode: text_editor::Mode) -> Self {
+        self.edit_mode = mode;
+        self
+    }
+
+    /// Number of spaces per level of indentation.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Shows the strings unquoted when every value is one, as `jq -r` does.
+    pub fn raw_output(mut self, enabled: bool) -> Self {
+        self.raw_output = enabled;
+        self
+    }
+
+    /// Draws on the alternate screen, besides `layout.alt_screen` in the configuration.
+    pub fn alt_screen(mut self, enabled: bool) -> Self {
+        self.alt_screen = enabled;
+        self
+    }
+
+    /// Built-in theme, overriding `theme.name` in the configuration.
+    pub fn theme(mut self, name: ThemeName) -> Self {
+        self.theme = Some(name);
+        self
+    }
+
+    /// Draws without any color, as the NO_COLOR environment variable does.
+    pub fn no_color(mut self, enabled: bool) -> Self {
+        self.no_color = enabled;
+        self
+    }
+
+    pub fn no_hint(mut self, enabled: bool) -> Self {
+        self.no_hint = enabled;
+        self
+    }
+
+    /// Disables everything letting data leave the terminal,
+    /// besides `safe_mode` in the configuration.
+    pub fn safe_mode(mut self, enabled: bool) -> Self {
+        self.safe_mode = enabled;
+        self
+    }
+
+    /// Keeps reading the lines appended to the input file.
+    pub fn follow(mut self, enabled: bool) -> Self {
+        self.follow = enabled;
+        self
+    }
+
+    /// Reads the input file again whenever it changes.
+    pub fn watch(mut self, enabled: bool) -> Self {
+        self.watch = enabled;
+        self
+    }
+
+    pub fn max_streams(mut self, max_streams: usize) -> Self {
+        self.max_streams = Some(max_streams);
+        self
+    }
+
+    /// Number of the values of a result beyond which the others are left out,
+    /// overriding `json.max_results` in the configuration.
+    pub fn max_results(mut self, max_results: usize) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// Skips the lines of the input which are not JSON (e.g. plain-text log lines)
+    /// instead of failing on them.
+    pub fn lenient(mut self, enabled: bool) -> Self {
+        self.lenient = enabled;
+        self
+    }
+
+    /// Wraps the documents of the input into one array, as `jq -s` does.
+    pub fn slurp(mut self, enabled: bool) -> Self {
+        self.slurp = enabled;
+        self
+    }
+
+    /// Binds `$name` to the string in the filters, as `jq --arg` does.
+    pub fn arg(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.argjson(name, serde_json::Value::String(value.into()))
+    }
+
+    /// Binds `$name` to the JSON value in the filters, as `jq --argjson` does.
+    pub fn argjson(mut self, name: impl Into<String>, value: serde_json::Value) -> Self {
+        self.variables.push((name.into(), value));
+        self
+    }
+
+    /// Directory the modules included by the filters (`include "name";`) are looked up in,
+    /// after those given before.
+    pub fn library_path(mut self, path: PathBuf) -> Self {
+        self.library_paths.push(path);
+        self
+    }
+
+    /// Hides the environment of the process from the filters (`env` and `$ENV`).
+    pub fn no_env(mut self, enabled: bool) -> Self {
+        self.no_env = enabled;
+        self
+    }
+
+    /// What runs the filters, overriding `jq.engine` in the configuration.
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Depth up to which the JSON is expanded at first,
+    /// overriding `json.default_expand_depth` in the configuration.
+    pub fn expand_depth(mut self, depth: usize) -> Self {
+        self.expand_depth = Some(depth);
+        self
+    }
+
+    /// Number of autocomplete suggestions shown.
+    pub fn suggestions(mut self, suggestions: usize) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
+    /// Whether the paths of the input are indexed for suggestions.
+    pub fn index_paths(mut self, enabled: bool) -> Self {
+        self.index_paths = enabled;
+        self
+    }
+
+    /// Records the input events of the session to the file.
+    pub fn record(mut self, path: PathBuf) -> Self {
+        self.record = Some(path);
+        self
+    }
+
+    /// Replays the events recorded to the file at the given speed (0 for no delays).
+    pub fn replay(mut self, path: PathBuf, speed: f64) -> Self {
+        self.replay = Some((path, speed));
+        self
+    }
+
+    /// Terminal device the keyboard is read from.
+    pub fn tty(mut self, path: PathBuf) -> Self {
+        self.tty = Some(path);
+        self
+    }
+
+    /// Events driving the interface, instead of those of the terminal
+    /// (e.g. a scripted sequence of keys).
+    pub fn event_source<S>(mut self, events: S) -> Self
+    where
+        S: Stream<Item = io::Result<Event>> + Send + 'static,
+    {
+        self.event_source = Some(Box::pin(events));
+        self
+    }
+
+    /// Writer the frames of the interface are drawn to, instead of standard output,
+    /// as on a terminal of the given size (columns, rows)
+    /// until a resize event of the event source changes it.
+    pub fn screen<W: Write + Send + 'static>(mut self, screen: W, size: (u16, u16)) -> Self {
+        self.screen = Some((Box::new(screen), size));
+        self
+    }
+
+    /// Input file, if not standard input.
+    pub(crate) fn input_file(&self) -> Option<&PathBuf> {
+        self.input.as_ref().filter(|path| path.as_os_str() != "-")
+    }
+}
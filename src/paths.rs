@@ -0,0 +1,569 @@
+//! Path generation for suggestions.
+//!
+//! Features consuming paths rely on the following contract:
+//! - paths are generated per document, in the order of the documents,
+//! - every object key is quoted with [`quote_segment`],
+//! - paths are deduplicated across documents by [`PathIndex`],
+//!   which also stands every array index for `[]` (e.g. `.items[].name`),
+//! - candidates are ordered by depth, then lexicographically,
+//!   unless [`PathOrder::Frequency`] is configured.
+
+use std::collections::{BTreeSet, HashMap};
+
+use promkit::serde_json;
+use serde::{Deserialize, Serialize};
+
+/// Words that jq treats as keywords, which cannot appear as bare identifiers.
+const KEYWORDS: [&str; 17] = [
+    "__loc__", "and", "as", "catch", "def", "elif", "else", "end", "foreach", "if", "import",
+    "include", "label", "or", "reduce", "then", "try",
+];
+
+/// Returns the jq path segment (including the leading `.`) for an object key.
+///
+/// Keys matching `[A-Za-z_][A-Za-z0-9_]*` are emitted bare (e.g. `.name`),
+/// anything else is emitted as a quoted string (e.g. `."app.kubernetes.io/name"`)
+/// with quotes, backslashes and control characters escaped.
+pub fn quote_segment(key: &str) -> String {
+    if is_identifier(key) {
+        return format!(".{}", key);
+    }
+
+    let mut ret = String::with_capacity(key.len() + 3);
+    ret.push_str(".\"");
+    for ch in key.chars() {
+        match ch {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if c.is_control() => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+/// Tells whether the key can be written bare after a `.` (e.g. `.name`).
+pub fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && !KEYWORDS.contains(&key)
+}
+
+/// Appends an object key to the given path.
+pub fn join_key(path: &str, key: &str) -> String {
+    if path == "." {
+        quote_segment(key)
+    } else {
+        format!("{}{}", path, quote_segment(key))
+    }
+}
+
+/// Appends an array index to the given path.
+pub fn join_index(path: &str, index: usize) -> String {
+    if path == "." {
+        format!(".[{}]", index)
+    } else {
+        format!("{}[{}]", path, index)
+    }
+}
+
+/// Replaces every array index in the path with `[]` (e.g. `.items[0].name` to `.items[].name`).
+pub fn generalize_indices(path: &str) -> String {
+    let mut ret = String::with_capacity(path.len());
+    let mut chars = path.chars().peekable();
+    let mut quoted = false;
+    while let Some(ch) = chars.next() {
+        ret.push(ch);
+        match ch {
+            '\\' if quoted => {
+                if let Some(escaped) = chars.next() {
+                    ret.push(escaped);
+                }
+            }
+            '"' => quoted = !quoted,
+            '[' if !quoted => while chars.next_if(|c| c.is_ascii_digit()).is_some() {},
+            _ => {}
+        }
+    }
+    ret
+}
+
+/// Returns the largest array index in the path, if any.
+fn max_index(path: &str) -> Option<usize> {
+    let mut ret = None;
+    let mut chars = path.chars().peekable();
+    let mut quoted = false;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if quoted => {
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            '[' if !quoted => {
+                let mut digits = String::new();
+                while let Some(digit) = chars.next_if(|c| c.is_ascii_digit()) {
+                    digits.push(digit);
+                }
+                if let Ok(index) = digits.parse::<usize>() {
+                    ret = ret.max(Some(index));
+                }
+            }
+            _ => {}
+        }
+    }
+    ret
+}
+
+/// Tells whether the path matches the pattern.
+///
+/// In the pattern, `[]` matches any array index
+/// and a trailing `*` matches anything that follows.
+pub fn matches_pattern(pattern: &str, path: &str) -> bool {
+    let matches = |path: &str| match pattern.strip_suffix('*') {
+        Some(prefix) => path.starts_with(prefix),
+        None => path == pattern,
+    };
+    matches(path) || matches(&generalize_indices(path))
+}
+
+/// A generated path together with its number of segments.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PathEntry {
+    /// Number of segments, where the root `.` is 0.
+    pub depth: usize,
+    pub path: String,
+}
+
+pub struct PathIterator<'a> {
+    stack: Vec<(PathEntry, &'a serde_json::Value)>,
+}
+
+impl<'a> PathIterator<'a> {
+    fn new(value: &'a serde_json::Value) -> Self {
+        Self {
+            stack: vec![(
+                PathEntry {
+                    depth: 0,
+                    path: ".".to_string(),
+                },
+                value,
+            )],
+        }
+    }
+}
+
+impl Iterator for PathIterator<'_> {
+    type Item = PathEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (current, value) = self.stack.pop()?;
+        match value {
+            serde_json::Value::Object(obj) => {
+                for (key, val) in obj.iter() {
+                    let path = join_key(&current.path, key);
+                    self.stack.push((
+                        PathEntry {
+                            depth: current.depth + 1,
+                            path,
+                        },
+                        val,
+                    ));
+                }
+            }
+            serde_json::Value::Array(arr) => {
+                for (i, val) in arr.iter().enumerate() {
+                    let path = join_index(&current.path, i);
+                    self.stack.push((
+                        PathEntry {
+                            depth: current.depth + 1,
+                            path,
+                        },
+                        val,
+                    ));
+                }
+            }
+            _ => {}
+        }
+        Some(current)
+    }
+}
+
+/// Yields every path in the given documents, one document after another.
+pub fn get_all_paths<'a, T: IntoIterator<Item = &'a serde_json::Value>>(
+    iter: T,
+) -> impl Iterator<Item = PathEntry> + 'a
+where
+    T::IntoIter: 'a,
+{
+    iter.into_iter().flat_map(PathIterator::new)
+}
+
+/// Order in which candidates are returned from [`PathIndex`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathOrder {
+    /// Shallower paths first, then lexicographically.
+    #[default]
+    Depth,
+    /// Paths seen more often across documents first, then as `Depth`.
+    Frequency,
+}
+
+/// How the suggestions are matched against the filter being typed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathMatching {
+    /// Paths starting with the filter.
+    #[default]
+    Prefix,
+    /// Paths containing the filter, those starting with it first.
+    Substring,
+    /// Paths containing the characters of the filter in order
+    /// (e.g. `.labels` for `.metadata.labels`), the closest together first.
+    Fuzzy,
+}
+
+impl PathMatching {
+    /// Scores how the path matches the input (lower is better),
+    /// or returns `None` if it does not match.
+    pub fn score(&self, input: &str, path: &str) -> Option<usize> {
+        match self {
+            PathMatching::Prefix => path.starts_with(input).then_some(0),
+            PathMatching::Substring if path.starts_with(input) => Some(0),
+            PathMatching::Substring => path.contains(input).then_some(1),
+            PathMatching::Fuzzy => fuzzy_score(input, path),
+        }
+    }
+}
+
+/// Returns the number of characters interleaved with those of the input
+/// in the shortest span of the path containing them in order,
+/// plus one if the span does not start the path.
+fn fuzzy_score(input: &str, path: &str) -> Option<usize> {
+    let input = input.chars().collect::<Vec<_>>();
+    let Some(&first) = input.first() else {
+        return Some(0);
+    };
+    let path = path.chars().collect::<Vec<_>>();
+    let mut best: Option<usize> = None;
+    for start in (0..path.len()).filter(|&i| path[i] == first) {
+        // Match the rest of the input greedily from this start.
+        let mut matched = 1;
+        let mut end = start;
+        for (i, &ch) in path.iter().enumerate().skip(start + 1) {
+            if matched == input.len() {
+                break;
+            }
+            if ch == input[matched] {
+                matched += 1;
+                end = i;
+            }
+        }
+        if matched < input.len() {
+            // No later start can match the whole input either.
+            break;
+        }
+        let score = (end + 1 - start - input.len()) + usize::from(start > 0);
+        if best.is_none_or(|best| score < best) {
+            best = Some(score);
+        }
+    }
+    best
+}
+
+/// How the path picked in the JSON viewer is put into the filter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathInsertion {
+    /// Applied to the result of the filter (e.g. `.items[] | .name`).
+    #[default]
+    Append,
+    /// In place of the filter.
+    Replace,
+}
+
+/// Deduplicated set of paths with a deterministic order.
+#[derive(Default)]
+pub struct PathIndex {
+    order: PathOrder,
+    /// Number of the first elements of the arrays whose paths are kept,
+    /// the others being only suggested with `[]` in place of their index.
+    array_indices: usize,
+    entries: BTreeSet<PathEntry>,
+    frequency: HashMap<String, usize>,
+}
+
+impl PathIndex {
+    pub fn new(order: PathOrder, array_indices: usize) -> Self {
+        Self {
+            order,
+            array_indices,
+            ..Default::default()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.frequency.clear();
+    }
+
+    /// Adds the path, and the same path with `[]` for its array indices
+    /// (keeping the indexed one only if its indices are among the first ones).
+    pub fn insert(&mut self, entry: PathEntry) {
+        if let Some(index) = max_index(&entry.path) {
+            self.insert_path(PathEntry {
+                depth: entry.depth,
+                path: generalize_indices(&entry.path),
+            });
+            if index >= self.array_indices {
+                return;
+            }
+        }
+        self.insert_path(entry);
+    }
+
+    fn insert_path(&mut self, entry: PathEntry) {
+        if self.order == PathOrder::Frequency {
+            *self.frequency.entry(entry.path.clone()).or_default() += 1;
+        }
+        self.entries.insert(entry);
+    }
+
+    /// Returns the paths given a score by `score`, the lowest first,
+    /// then in the configured order.
+    pub fn candidates<F: Fn(&str) -> Option<usize>>(&self, score: F) -> Vec<String> {
+        let mut ret: Vec<(usize, &PathEntry)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| score(&entry.path).map(|score| (score, entry)))
+            .collect();
+        if self.order == PathOrder::Frequency {
+            // Stable sort keeps the depth order among paths with the same frequency.
+            ret.sort_by_key(|(_, entry)| {
+                std::cmp::Reverse(self.frequency.get(&entry.path).copied().unwrap_or(0))
+            });
+        }
+        ret.sort_by_key(|(score, _)| *score);
+        ret.into_iter()
+            .map(|(_, entry)| entry.path.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_segment_table() {
+        for (key, expected) in [
+            ("name", ".name"),
+            ("_private", "._private"),
+            ("snake_case_2", ".snake_case_2"),
+            ("select", ".select"),
+            ("if", ".\"if\""),
+            ("__loc__", ".\"__loc__\""),
+            ("reduce", ".\"reduce\""),
+            ("0", ".\"0\""),
+            ("2fa", ".\"2fa\""),
+            ("a.b", ".\"a.b\""),
+            ("kebab-case", ".\"kebab-case\""),
+            ("with space", ".\"with space\""),
+            ("@timestamp", ".\"@timestamp\""),
+            ("app.kubernetes.io/name", ".\"app.kubernetes.io/name\""),
+            ("名前", ".\"名前\""),
+            ("café", ".\"café\""),
+            ("tab\there", ".\"tab\\there\""),
+            ("line\nbreak", ".\"line\\nbreak\""),
+            ("carriage\rreturn", ".\"carriage\\rreturn\""),
+            ("bell\u{7}", ".\"bell\\u0007\""),
+            ("nul\u{0}", ".\"nul\\u0000\""),
+            ("say \"hi\"", ".\"say \\\"hi\\\"\""),
+            ("back\\slash", ".\"back\\\\slash\""),
+            ("", ".\"\""),
+        ] {
+            assert_eq!(quote_segment(key), expected, "key {:?}", key);
+        }
+    }
+
+    #[test]
+    fn is_identifier_table() {
+        for (key, expected) in [
+            ("a", true),
+            ("_", true),
+            ("A1_b", true),
+            ("select", true),
+            ("if", false),
+            ("then", false),
+            ("__loc__", false),
+            ("__loc", true),
+            ("0", false),
+            ("1a", false),
+            ("a.b", false),
+            ("a-b", false),
+            ("a b", false),
+            ("tab\there", false),
+            ("é", false),
+            ("", false),
+        ] {
+            assert_eq!(is_identifier(key), expected, "key {:?}", key);
+        }
+    }
+
+    #[test]
+    fn join_from_the_root() {
+        assert_eq!(join_key(".", "a"), ".a");
+        assert_eq!(join_key(".", "a.b"), ".\"a.b\"");
+        assert_eq!(join_key(".a", "b"), ".a.b");
+        assert_eq!(join_index(".", 0), ".[0]");
+        assert_eq!(join_index(".a", 3), ".a[3]");
+        assert_eq!(join_key(".[0]", "if"), ".[0].\"if\"");
+    }
+
+    #[test]
+    fn generalize_indices_outside_quoted_keys() {
+        for (path, expected) in [
+            (".items[0].name", ".items[].name"),
+            (".[12][3]", ".[][]"),
+            (".a[]", ".a[]"),
+            (".\"a[0]\"[1]", ".\"a[0]\"[]"),
+            (".\"x\\\"[0]\"[2]", ".\"x\\\"[0]\"[]"),
+            (".\"back\\\\\"[0]", ".\"back\\\\\"[]"),
+            (".\"[3]\".b[4].\"[5]\"", ".\"[3]\".b[].\"[5]\""),
+        ] {
+            assert_eq!(generalize_indices(path), expected, "path {:?}", path);
+        }
+    }
+
+    #[test]
+    fn max_index_outside_quoted_keys() {
+        assert_eq!(max_index(".a"), None);
+        assert_eq!(max_index(".\"[9]\""), None);
+        assert_eq!(max_index(".\"[9]\"[2]"), Some(2));
+        assert_eq!(max_index(".[3].a[10][1]"), Some(10));
+    }
+
+    #[test]
+    fn matches_pattern_with_indices_and_prefixes() {
+        assert!(matches_pattern(".items[].name", ".items[3].name"));
+        assert!(matches_pattern(".items[3].name", ".items[3].name"));
+        assert!(!matches_pattern(".items[3].name", ".items[4].name"));
+        assert!(matches_pattern(".items*", ".items[0].spec"));
+        assert!(matches_pattern(".\"a[0]\"", ".\"a[0]\""));
+        assert!(!matches_pattern(".\"a[]\"", ".\"a[0]\""));
+    }
+
+    fn index(
+        documents: &[serde_json::Value],
+        order: PathOrder,
+        array_indices: usize,
+    ) -> Vec<String> {
+        let mut index = PathIndex::new(order, array_indices);
+        for entry in get_all_paths(documents) {
+            index.insert(entry);
+        }
+        index.candidates(|_| Some(0))
+    }
+
+    fn documents() -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({"b": {"c": 1}, "a": [{"x": 1}, {"y": 2}]}),
+            serde_json::json!({"a": [{"x": 3}], "名前": "v"}),
+        ]
+    }
+
+    #[test]
+    fn golden_depth_order() {
+        assert_eq!(
+            index(&documents(), PathOrder::Depth, 1),
+            [
+                ".",
+                ".\"名前\"",
+                ".a",
+                ".b",
+                ".a[0]",
+                ".a[]",
+                ".b.c",
+                ".a[0].x",
+                ".a[].x",
+                ".a[].y",
+            ]
+        );
+    }
+
+    #[test]
+    fn golden_frequency_order() {
+        assert_eq!(
+            index(&documents(), PathOrder::Frequency, 1),
+            [
+                ".a[]",
+                ".",
+                ".a",
+                ".a[0]",
+                ".a[0].x",
+                ".a[].x",
+                ".\"名前\"",
+                ".b",
+                ".b.c",
+                ".a[].y",
+            ]
+        );
+    }
+
+    #[test]
+    fn golden_deep_nesting() {
+        let mut document = serde_json::json!(1);
+        for _ in 0..100 {
+            document = serde_json::json!({ "a": document });
+        }
+        let paths = get_all_paths([&document]).collect::<Vec<_>>();
+        assert_eq!(paths.len(), 101);
+        assert_eq!(paths[100].depth, 100);
+        assert_eq!(paths[100].path, ".a".repeat(100));
+    }
+
+    #[test]
+    fn golden_huge_array() {
+        let document = serde_json::Value::Array((0..10_000).map(serde_json::Value::from).collect());
+        assert_eq!(
+            index(&[document], PathOrder::Depth, 3),
+            [".", ".[0]", ".[1]", ".[2]", ".[]"]
+        );
+    }
+
+    #[test]
+    fn golden_heterogeneous_array() {
+        let document = serde_json::json!([1, {"a": 1}, [2], "s", null, {"a": {"b": true}}]);
+        assert_eq!(
+            index(&[document], PathOrder::Depth, 0),
+            [".", ".[]", ".[].a", ".[][]", ".[].a.b"]
+        );
+    }
+
+    #[test]
+    fn candidates_by_score_then_order() {
+        let mut index = PathIndex::new(PathOrder::Depth, 0);
+        for entry in get_all_paths(&documents()) {
+            index.insert(entry);
+        }
+        assert_eq!(
+            index.candidates(|path| PathMatching::Prefix.score(".a", path)),
+            [".a", ".a[]", ".a[].x", ".a[].y"]
+        );
+        assert_eq!(
+            index.candidates(|path| PathMatching::Substring.score("x", path)),
+            [".a[].x"]
+        );
+        assert_eq!(
+            index.candidates(|path| PathMatching::Fuzzy.score(".bc", path)),
+            [".b.c"]
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Reading of standard input piped from a command which keeps writing to it
+//! (e.g. `kubectl logs -f | jnv`), whose documents are appended as they come.
+
+use std::{
+    io::{self, Read},
+    sync::mpsc as std_mpsc,
+    time::Duration,
+};
+
+use promkit::serde_json::{Deserializer, Value};
+use serde::de::IgnoredAny;
+use tokio::sync::mpsc;
+
+use crate::progress::StartupProgress;
+
+/// Time without any data after which the interface starts
+/// with the documents read so far, the others being appended as they come.
+const QUIET_PERIOD: Duration = Duration::from_millis(300);
+
+/// Tells whether standard input is a pipe (or a socket), which may stay open
+/// long after its first documents are written.
+#[cfg(unix)]
+pub fn stdin_is_pipe() -> bool {
+    use std::os::{fd::AsFd, unix::fs::FileTypeExt};
+
+    io::stdin()
+        .as_fd()
+        .try_clone_to_owned()
+        .and_then(|fd| std::fs::File::from(fd).metadata())
+        .is_ok_and(|metadata| metadata.file_type().is_fifo() || metadata.file_type().is_socket())
+}
+
+#[cfg(windows)]
+pub fn stdin_is_pipe() -> bool {
+    false
+}
+
+/// Reads standard input until it ends, or until at least a document is complete
+/// and nothing more comes for a while.
+///
+/// In the latter case, the complete documents are returned with a receiver
+/// of the documents that follow, which is closed once standard input ends.
+pub fn read_stdin(
+    progress: &StartupProgress,
+) -> anyhow::Result<(String, Option<mpsc::Receiver<Vec<Value>>>)> {
+    let (chunk_tx, chunk_rx) = std_mpsc::channel();
+    std::thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut chunk = vec![0; 1 << 16];
+        loop {
+            match stdin.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if chunk_tx.send(Ok(chunk[..n].to_vec())).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    let _ = chunk_tx.send(Err(e));
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut buf = Vec::new();
+    loop {
+        match chunk_rx.recv_timeout(QUIET_PERIOD) {
+            Ok(chunk) => {
+                let chunk = chunk?;
+                progress.advance(chunk.len());
+                buf.extend_from_slice(&chunk);
+            }
+            Err(std_mpsc::RecvTimeoutError::Disconnected) => return Ok((into_string(buf)?, None)),
+            Err(std_mpsc::RecvTimeoutError::Timeout) => {
+                let complete = complete_len(&buf);
+                if complete == 0 {
+                    continue;
+                }
+                let rest = buf.split_off(complete);
+                let (documents_tx, documents_rx) = mpsc::channel(1);
+                spawn_receiving_thread(chunk_rx, rest, documents_tx);
+                return Ok((into_string(buf)?, Some(documents_rx)));
+            }
+        }
+    }
+}
+
+/// Parses the documents of the chunks still coming, sending them as they are complete.
+fn spawn_receiving_thread(
+    chunk_rx: std_mpsc::Receiver<io::Result<Vec<u8>>>,
+    mut buf: Vec<u8>,
+    documents_tx: mpsc::Sender<Vec<Value>>,
+) {
+    std::thread::spawn(move || {
+        for chunk in chunk_rx {
+            let Ok(chunk) = chunk else {
+                break;
+            };
+            buf.extend_from_slice(&chunk);
+            let documents = take_documents(&mut buf);
+            if !documents.is_empty() && documents_tx.blocking_send(documents).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Returns the length of the complete documents at the start of the bytes.
+fn complete_len(buf: &[u8]) -> usize {
+    let mut stream = Deserializer::from_slice(buf).into_iter::<IgnoredAny>();
+    let mut len = 0;
+    while let Some(Ok(_)) = stream.next() {
+        len = stream.byte_offset();
+    }
+    len
+}
+
+/// Takes the complete documents off the start of the bytes,
+/// skipping the lines which do not hold one.
+fn take_documents(buf: &mut Vec<u8>) -> Vec<Value> {
+    let mut documents = Vec::new();
+    let mut consumed = 0;
+    'parse: loop {
+        let base = consumed;
+        let mut stream = Deserializer::from_slice(&buf[base..]).into_iter::<Value>();
+        loop {
+            match stream.next() {
+                Some(Ok(value)) => {
+                    documents.push(value);
+                    consumed = base + stream.byte_offset();
+                }
+                // The document is still being written.
+                Some(Err(e)) if e.is_eof() => break 'parse,
+                Some(Err(_)) => match buf[consumed..].iter().position(|b| *b == b'\n') {
+                    Some(end) => {
+                        consumed += end + 1;
+                        continue 'parse;
+                    }
+                    None => break 'parse,
+                },
+                None => {
+                    consumed = buf.len();
+                    break 'parse;
+                }
+            }
+        }
+    }
+    buf.drain(..consumed);
+    documents
+}
+
+fn into_string(buf: Vec<u8>) -> anyhow::Result<String> {
+    String::from_utf8(buf).map_err(|_| anyhow::anyhow!("stream did not contain valid UTF-8"))
+}
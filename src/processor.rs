@@ -1,11 +1,20 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 use async_trait::async_trait;
 use crossterm::event::Event;
-use promkit::pane::Pane;
-use tokio::{sync::Mutex, task::JoinHandle};
+use promkit::{pane::Pane, serde_json};
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
 
-use crate::{PaneIndex, Renderer, EMPTY_PANE};
+use crate::{empty_pane, PaneIndex, Renderer};
 pub mod init;
 pub use init::ViewProvider;
 pub mod monitor;
@@ -18,22 +27,100 @@ enum State {
     Processing,
 }
 
+/// How the query was applied, telling whether to remember it (e.g. in the history).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueryOutcome {
+    /// The result of the query is shown.
+    Succeeded,
+    /// The query failed, but a previous result is shown
+    /// (e.g. that of a prefix of the query).
+    Fallback,
+    Failed,
+}
+
 #[async_trait]
 pub trait Visualizer: Send + Sync + 'static {
     async fn content_to_copy(&self) -> String;
+    /// Returns the values resulting from the query, as they are shown.
+    fn values(&self) -> Vec<serde_json::Value>;
+    /// Returns the values shown, shared with the visualizer rather than copied.
+    fn shown_values(&self) -> Arc<Vec<serde_json::Value>>;
     async fn create_init_pane(&mut self, area: (u16, u16)) -> Pane;
-    async fn create_pane_from_event(&mut self, area: (u16, u16), event: &Event) -> Pane;
+    /// Returns the pane for the guide once the input is shown,
+    /// e.g. telling what was skipped of it.
+    fn create_init_guide_pane(&self, width: u16) -> Pane;
+    /// Returns the pane shown below the result, e.g. the position of the cursor in it.
+    fn create_status_pane(&self, width: u16) -> Pane;
+    /// Handles the events in order, returning the pane for the Guide (if it should change)
+    /// and the pane updated once after all of them.
+    async fn create_panes_from_events(
+        &mut self,
+        area: (u16, u16),
+        events: &[Event],
+    ) -> (Option<Pane>, Pane);
+    /// Returns the time at which a partially typed keybind gives up waiting.
+    fn sequence_deadline(&self) -> Option<Instant>;
+    /// Handles the events held for a partially typed keybind after its timeout.
+    async fn create_panes_on_sequence_timeout(&mut self, area: (u16, u16)) -> (Option<Pane>, Pane);
+    /// Discards the result of the query and returns the panes showing the input as is.
+    async fn create_panes_on_reset(&mut self, area: (u16, u16)) -> (Option<Pane>, Pane);
+    /// Applies the query, giving up as soon as `cancel` is set,
+    /// and tells how it went besides the panes.
     async fn create_panes_from_query(
         &mut self,
         area: (u16, u16),
         query: String,
-    ) -> (Option<Pane>, Option<Pane>);
+        cancel: Arc<AtomicBool>,
+    ) -> (Option<Pane>, Option<Pane>, QueryOutcome);
+    /// Takes the filter to put into the editor, produced by the last event if any.
+    fn take_query_to_edit(&mut self) -> Option<String>;
+    /// Takes the text to copy to the clipboard (e.g. the path of the selected row),
+    /// produced by the last event if any.
+    fn take_text_to_copy(&mut self) -> Option<String>;
+    /// Takes the values produced by the last query,
+    /// unless it showed the input as is.
+    fn take_result(&mut self) -> Option<Arc<Vec<serde_json::Value>>>;
+    /// Appends the documents to the input, e.g. as a followed file grows.
+    ///
+    /// Shown once the query is applied again.
+    fn append(&mut self, documents: Vec<serde_json::Value>);
+    /// Tells whether more documents may still be appended, as long as the input is open.
+    fn set_receiving(&mut self, receiving: bool);
+    /// Replaces the input, e.g. once the file is read again.
+    ///
+    /// Shown once the query is applied again.
+    fn replace(&mut self, documents: Vec<serde_json::Value>);
+    /// Takes the values produced by the last query as the ones the filters run on,
+    /// remembering the values they replace, and returns them
+    /// (none if the query showed the input as is).
+    ///
+    /// Shown once the query is applied again.
+    fn commit_result(&mut self) -> Option<Arc<Vec<serde_json::Value>>>;
+    /// Restores the values the last committed result replaced, returning its query
+    /// with the values restored, unless they are the input.
+    ///
+    /// Shown once the query is applied again.
+    fn pop_base(&mut self) -> Option<(String, Option<Arc<Vec<serde_json::Value>>>)>;
+    /// Wraps the documents of the input into one array, as `jq -s` does,
+    /// or splits it back into documents, returning whether they are wrapped now.
+    ///
+    /// Shown once the query is applied again.
+    fn toggle_slurp(&mut self) -> bool;
+    /// Lifts the limit on the number of the values of a result for the query,
+    /// until another one is applied.
+    fn show_all_results(&mut self, query: String);
+    /// Returns the length of the array produced by the filter from the first document,
+    /// if it produces one.
+    async fn array_length(&self, array: &str) -> Option<usize>;
 }
 
 pub struct Context {
     state: State,
     area: (u16, u16),
     current_task: Option<JoinHandle<()>>,
+    /// Set to stop the evaluation of `current_task`,
+    /// which aborting the task does not stop once it runs on a blocking thread.
+    current_cancel: Option<Arc<AtomicBool>>,
 }
 
 impl Context {
@@ -42,40 +129,83 @@ impl Context {
             state: State::Idle,
             area,
             current_task: None,
+            current_cancel: None,
+        }
+    }
+
+    /// Stops the task in progress, if any.
+    fn cancel_task(&mut self) {
+        if let Some(cancel) = self.current_cancel.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        if let Some(task) = self.current_task.take() {
+            task.abort();
         }
     }
 }
 
 pub struct Processor {
     shared: Arc<Mutex<Context>>,
+    /// Receives the queries which produced a result, e.g. for the history.
+    succeeded_tx: mpsc::Sender<String>,
+    /// Receives the values produced by the queries, e.g. for the suggestions.
+    result_tx: Option<mpsc::Sender<(String, Arc<Vec<serde_json::Value>>)>>,
 }
 
 impl Processor {
-    pub fn new(shared: Arc<Mutex<Context>>) -> Self {
-        Self { shared }
+    pub fn new(
+        shared: Arc<Mutex<Context>>,
+        succeeded_tx: mpsc::Sender<String>,
+        result_tx: Option<mpsc::Sender<(String, Arc<Vec<serde_json::Value>>)>>,
+    ) -> Self {
+        Self {
+            shared,
+            succeeded_tx,
+            result_tx,
+        }
     }
 
     fn spawn_process_task(
         &self,
         query: String,
+        cancel: Arc<AtomicBool>,
         shared_visualizer: Arc<Mutex<impl Visualizer>>,
         shared_renderer: Arc<Mutex<Renderer>>,
     ) -> JoinHandle<()> {
         let shared = self.shared.clone();
+        let succeeded_tx = self.succeeded_tx.clone();
+        let result_tx = self.result_tx.clone();
         tokio::spawn(async move {
             {
                 let mut shared_state = shared.lock().await;
                 shared_state.state = State::Processing;
             }
 
-            let (maybe_guide, maybe_resp) = {
+            let (maybe_guide, maybe_resp, outcome, status, result) = {
                 let shared_state = shared.lock().await;
                 let area = shared_state.area;
                 drop(shared_state);
 
                 let mut visualizer = shared_visualizer.lock().await;
-                visualizer.create_panes_from_query(area, query).await
+                let (maybe_guide, maybe_resp, outcome) = visualizer
+                    .create_panes_from_query(area, query.clone(), cancel.clone())
+                    .await;
+                let status = match maybe_resp {
+                    Some(_) => visualizer.create_status_pane(area.0),
+                    None => empty_pane(),
+                };
+                (
+                    maybe_guide,
+                    maybe_resp,
+                    outcome,
+                    status,
+                    visualizer.take_result(),
+                )
             };
+            // Superseded by a newer query, whose task owns the state and the panes now.
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
 
             // Set state to Idle to prevent overwriting by spinner frames in terminal.
             {
@@ -87,17 +217,51 @@ impl Processor {
                 let _ = shared_renderer.lock().await.update_and_draw([
                     (
                         PaneIndex::ProcessorGuide,
-                        maybe_guide.unwrap_or(EMPTY_PANE.to_owned()),
-                    ),
-                    (
-                        PaneIndex::Processor,
-                        maybe_resp.unwrap_or(EMPTY_PANE.to_owned()),
+                        maybe_guide.unwrap_or(empty_pane()),
                     ),
+                    (PaneIndex::Processor, maybe_resp.unwrap_or(empty_pane())),
+                    (PaneIndex::ProcessorStatus, status),
                 ]);
             }
+            if let (Some(result_tx), Some(result)) = (result_tx, result) {
+                let _ = result_tx.send((query.clone(), result)).await;
+            }
+            // Not for a failed query, even if a previous result is still shown.
+            if outcome == QueryOutcome::Succeeded {
+                let _ = succeeded_tx.send(query).await;
+            }
         })
     }
 
+    async fn spawn_current_task(
+        &self,
+        query: String,
+        shared_visualizer: Arc<Mutex<impl Visualizer>>,
+        shared_renderer: Arc<Mutex<Renderer>>,
+    ) {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let process_task =
+            self.spawn_process_task(query, cancel.clone(), shared_visualizer, shared_renderer);
+
+        let mut shared_state = self.shared.lock().await;
+        shared_state.current_task = Some(process_task);
+        shared_state.current_cancel = Some(cancel);
+    }
+
+    /// Waits for the query being processed, if any.
+    #[cfg(test)]
+    pub async fn finish(&self) {
+        let task = self.shared.lock().await.current_task.take();
+        if let Some(task) = task {
+            let _ = task.await;
+        }
+    }
+
+    /// Returns the area the panes are laid out in, as of the last resize.
+    pub async fn area(&self) -> (u16, u16) {
+        self.shared.lock().await.area
+    }
+
     pub async fn render_on_resize(
         &self,
         shared_visualizer: Arc<Mutex<impl Visualizer>>,
@@ -108,16 +272,41 @@ impl Processor {
         {
             let mut shared_state = self.shared.lock().await;
             shared_state.area = area;
-            if let Some(task) = shared_state.current_task.take() {
-                task.abort();
-            }
+            shared_state.cancel_task();
         }
 
-        let process_task = self.spawn_process_task(query, shared_visualizer, shared_renderer);
+        self.spawn_current_task(query, shared_visualizer, shared_renderer)
+            .await;
+    }
 
-        {
+    /// Cancels the query being processed and renders the input as is.
+    pub async fn render_reset(
+        &self,
+        shared_visualizer: Arc<Mutex<impl Visualizer>>,
+        shared_renderer: Arc<Mutex<Renderer>>,
+    ) {
+        let area = {
             let mut shared_state = self.shared.lock().await;
-            shared_state.current_task = Some(process_task);
+            shared_state.cancel_task();
+            shared_state.state = State::Idle;
+            shared_state.area
+        };
+
+        let (maybe_guide, pane, status) = {
+            let mut visualizer = shared_visualizer.lock().await;
+            let (maybe_guide, pane) = visualizer.create_panes_on_reset(area).await;
+            (maybe_guide, pane, visualizer.create_status_pane(area.0))
+        };
+        {
+            // TODO: error handling
+            let _ = shared_renderer.lock().await.update_and_draw([
+                (
+                    PaneIndex::ProcessorGuide,
+                    maybe_guide.unwrap_or(empty_pane()),
+                ),
+                (PaneIndex::Processor, pane),
+                (PaneIndex::ProcessorStatus, status),
+            ]);
         }
     }
 
@@ -129,16 +318,10 @@ impl Processor {
     ) {
         {
             let mut shared_state = self.shared.lock().await;
-            if let Some(task) = shared_state.current_task.take() {
-                task.abort();
-            }
+            shared_state.cancel_task();
         }
 
-        let process_task = self.spawn_process_task(query, shared_visualizer, shared_renderer);
-
-        {
-            let mut shared_state = self.shared.lock().await;
-            shared_state.current_task = Some(process_task);
-        }
+        self.spawn_current_task(query, shared_visualizer, shared_renderer)
+            .await;
     }
 }
@@ -1,42 +1,50 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use promkit::serde_json;
 use tokio::sync::Mutex;
 
 use super::{Context, State, Visualizer};
-use crate::{PaneIndex, Renderer};
+use crate::{PaneIndex, Phase, Renderer, StartupProgress};
 
 #[async_trait]
 pub trait ViewProvider {
-    async fn provide(&mut self, item: &'static str) -> anyhow::Result<impl Visualizer>;
+    async fn provide(&mut self, item: Arc<str>) -> anyhow::Result<impl Visualizer>;
+    /// Parses the documents of the input, e.g. once it is read again.
+    fn deserialize(&self, item: &str) -> anyhow::Result<Vec<serde_json::Value>>;
+    /// Sets whether the documents are wrapped into one array as they are parsed.
+    fn set_slurp(&mut self, slurp: bool);
 }
 
 pub struct ViewInitializer {
     shared: Arc<Mutex<Context>>,
+    progress: StartupProgress,
 }
 
 impl ViewInitializer {
-    pub fn new(shared: Arc<Mutex<Context>>) -> Self {
-        Self { shared }
+    pub fn new(shared: Arc<Mutex<Context>>, progress: StartupProgress) -> Self {
+        Self { shared, progress }
     }
 
     pub async fn initialize<'a, T: ViewProvider>(
         &self,
         provider: &'a mut T,
-        item: &'static str,
+        item: Arc<str>,
         area: (u16, u16),
         shared_renderer: Arc<Mutex<Renderer>>,
     ) -> anyhow::Result<impl Visualizer + 'a> {
         {
             let mut shared_state = self.shared.lock().await;
-            if let Some(task) = shared_state.current_task.take() {
-                task.abort();
-            }
+            shared_state.cancel_task();
             shared_state.state = State::Loading;
         }
 
         let mut visualizer = provider.provide(item).await?;
+        self.progress.enter(Phase::Rendering, None);
         let pane = visualizer.create_init_pane(area).await;
+        let status = visualizer.create_status_pane(area.0);
+        let guide = visualizer.create_init_guide_pane(area.0);
+        self.progress.enter(Phase::Done, None);
 
         // Set state to Idle to prevent overwriting by spinner frames in terminal.
         {
@@ -45,10 +53,11 @@ impl ViewInitializer {
         }
         {
             // TODO: error handling
-            let _ = shared_renderer
-                .lock()
-                .await
-                .update_and_draw([(PaneIndex::Processor, pane)]);
+            let _ = shared_renderer.lock().await.update_and_draw([
+                (PaneIndex::Processor, pane),
+                (PaneIndex::ProcessorStatus, status),
+                (PaneIndex::ProcessorGuide, guide),
+            ]);
         }
 
         Ok(visualizer)
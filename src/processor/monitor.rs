@@ -17,4 +17,9 @@ impl ContextMonitor {
         let context = self.shared.lock().await;
         context.state == State::Idle
     }
+
+    /// Same as `is_idle`, from a blocking thread rather than the runtime.
+    pub fn is_idle_blocking(&self) -> bool {
+        self.shared.blocking_lock().state == State::Idle
+    }
 }
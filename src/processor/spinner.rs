@@ -4,7 +4,7 @@ use promkit::pane::Pane;
 use tokio::{sync::Mutex, task::JoinHandle, time::Duration};
 
 use super::{Context, State};
-use crate::{PaneIndex, Renderer};
+use crate::{empty_pane, PaneIndex, Renderer, StartupProgress};
 
 const LOADING_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
 
@@ -21,6 +21,7 @@ impl SpinnerSpawner {
         &self,
         shared_renderer: Arc<Mutex<Renderer>>,
         spin_duration: Duration,
+        progress: StartupProgress,
     ) -> JoinHandle<()> {
         let shared = self.shared.clone();
         let mut frame_index = 0;
@@ -38,18 +39,18 @@ impl SpinnerSpawner {
 
                 frame_index = (frame_index + 1) % LOADING_FRAMES.len();
 
-                let pane = Pane::new(
-                    vec![promkit::grapheme::StyledGraphemes::from(
-                        LOADING_FRAMES[frame_index],
-                    )],
-                    0,
-                );
+                // Tell what takes so long on startup.
+                let frame = match progress.describe() {
+                    Some(text) => format!("{} {}", LOADING_FRAMES[frame_index], text),
+                    None => LOADING_FRAMES[frame_index].to_string(),
+                };
+                let pane = Pane::new(vec![promkit::grapheme::StyledGraphemes::from(frame)], 0);
                 {
                     // TODO: error handling
-                    let _ = shared_renderer
-                        .lock()
-                        .await
-                        .update_and_draw([(PaneIndex::Processor, pane)]);
+                    let _ = shared_renderer.lock().await.update_and_draw([
+                        (PaneIndex::Processor, pane),
+                        (PaneIndex::ProcessorStatus, empty_pane()),
+                    ]);
                 }
             }
         })
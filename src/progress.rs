@@ -0,0 +1,205 @@
+//! Progress of the startup, shown once it takes longer than a budget
+//! so that a large input does not look like a hang.
+
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor, style,
+    terminal::{self, ClearType},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Reading = 0,
+    Parsing = 1,
+    Rendering = 2,
+    Done = 3,
+}
+
+impl Phase {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Phase::Reading,
+            1 => Phase::Parsing,
+            2 => Phase::Rendering,
+            _ => Phase::Done,
+        }
+    }
+}
+
+struct Inner {
+    started: Instant,
+    budget: Duration,
+    phase: AtomicU8,
+    /// Bytes processed by the current phase.
+    processed: AtomicUsize,
+    /// Bytes to process in the current phase (0 if unknown).
+    total: AtomicUsize,
+}
+
+/// Shared progress of the startup phases.
+#[derive(Clone)]
+pub struct StartupProgress(Arc<Inner>);
+
+impl StartupProgress {
+    pub fn new(budget: Duration) -> Self {
+        Self(Arc::new(Inner {
+            started: Instant::now(),
+            budget,
+            phase: AtomicU8::new(Phase::Reading as u8),
+            processed: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+        }))
+    }
+
+    pub fn phase(&self) -> Phase {
+        Phase::from_u8(self.0.phase.load(Ordering::Relaxed))
+    }
+
+    /// Moves on to the phase, which processes `total` bytes if known.
+    pub fn enter(&self, phase: Phase, total: Option<usize>) {
+        self.0.processed.store(0, Ordering::Relaxed);
+        self.0.total.store(total.unwrap_or(0), Ordering::Relaxed);
+        self.0.phase.store(phase as u8, Ordering::Relaxed);
+    }
+
+    pub fn set_processed(&self, bytes: usize) {
+        self.0.processed.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, bytes: usize) {
+        self.0.processed.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Describes the phase in progress (e.g. "parsing… 312MB/1.2GB (2.4s)"),
+    /// or `None` while the startup is within the budget or done.
+    pub fn describe(&self) -> Option<String> {
+        self.describe_after(self.0.started.elapsed())
+    }
+
+    /// Describes the phase in progress once `elapsed` has passed since the startup.
+    fn describe_after(&self, elapsed: Duration) -> Option<String> {
+        let name = match self.phase() {
+            _ if elapsed < self.0.budget => return None,
+            Phase::Reading => "reading input",
+            Phase::Parsing => "parsing",
+            Phase::Rendering => "rendering",
+            Phase::Done => return None,
+        };
+        let processed = self.0.processed.load(Ordering::Relaxed);
+        let amount = match self.0.total.load(Ordering::Relaxed) {
+            0 if processed == 0 => String::new(),
+            0 => format!(" {}", format_bytes(processed)),
+            total => format!(" {}/{}", format_bytes(processed), format_bytes(total)),
+        };
+        Some(format!(
+            "{}…{} ({:.1}s)",
+            name,
+            amount,
+            elapsed.as_secs_f64()
+        ))
+    }
+
+    /// Reports the progress of reading the input on the terminal
+    /// until the next phase is entered, then clears the report.
+    ///
+    /// The prompt is not set up yet while reading,
+    /// so the report is written on the current line as is.
+    pub async fn report_reading(self) {
+        let mut reported = false;
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+        while self.phase() == Phase::Reading {
+            interval.tick().await;
+            if let Some(text) = self.describe() {
+                reported = true;
+                let _ = crossterm::execute!(
+                    io::stdout(),
+                    cursor::MoveToColumn(0),
+                    terminal::Clear(ClearType::CurrentLine),
+                    style::Print(text),
+                );
+            }
+        }
+        if reported {
+            let _ = crossterm::execute!(
+                io::stdout(),
+                cursor::MoveToColumn(0),
+                terminal::Clear(ClearType::CurrentLine),
+            );
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const KB: usize = 1024;
+    const MB: usize = KB * 1024;
+    const GB: usize = MB * 1024;
+    match bytes {
+        b if b >= GB => format!("{:.1}GB", b as f64 / GB as f64),
+        b if b >= MB => format!("{}MB", b / MB),
+        b if b >= KB => format!("{}KB", b / KB),
+        b => format!("{}B", b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGET: Duration = Duration::from_secs(2);
+    const OVER: Duration = Duration::from_millis(2400);
+
+    #[test]
+    fn silent_within_the_budget() {
+        let progress = StartupProgress::new(BUDGET);
+        progress.enter(Phase::Parsing, Some(1 << 30));
+        assert_eq!(progress.describe_after(Duration::from_millis(1999)), None);
+    }
+
+    #[test]
+    fn tells_the_phase_and_the_amount_over_the_budget() {
+        let progress = StartupProgress::new(BUDGET);
+        assert_eq!(
+            progress.describe_after(OVER).as_deref(),
+            Some("reading input… (2.4s)")
+        );
+        progress.advance(5 << 20);
+        progress.advance(1 << 20);
+        assert_eq!(
+            progress.describe_after(OVER).as_deref(),
+            Some("reading input… 6MB (2.4s)")
+        );
+
+        progress.enter(Phase::Parsing, Some(1288490189));
+        progress.set_processed(312 << 20);
+        assert_eq!(
+            progress.describe_after(OVER).as_deref(),
+            Some("parsing… 312MB/1.2GB (2.4s)")
+        );
+
+        // As the view is initialized.
+        progress.enter(Phase::Rendering, None);
+        assert_eq!(
+            progress.describe_after(OVER).as_deref(),
+            Some("rendering… (2.4s)")
+        );
+        progress.enter(Phase::Done, None);
+        assert_eq!(progress.describe_after(OVER), None);
+    }
+
+    #[test]
+    fn formats_the_bytes() {
+        assert_eq!(format_bytes(512), "512B");
+        assert_eq!(format_bytes(2048), "2KB");
+        assert_eq!(format_bytes(3 << 20), "3MB");
+        assert_eq!(format_bytes(3 << 30), "3.0GB");
+    }
+}
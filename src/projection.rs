@@ -0,0 +1,257 @@
+//! Generation of an object-construction filter from collected paths,
+//! e.g. `.items[0].metadata.name` and `.items[3].status.phase`
+//! into `.items[] | {name: .metadata.name, phase: .status.phase}`.
+
+use promkit::serde_json;
+
+use crate::paths;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Segment {
+    /// Tells whether the segments are the same, any index matching any other.
+    fn matches(&self, other: &Segment) -> bool {
+        match (self, other) {
+            (Segment::Key(a), Segment::Key(b)) => a == b,
+            (Segment::Index(_), Segment::Index(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Builds the filter constructing an object with a field per path.
+///
+/// The paths (as generated by [`paths::join_key`] and [`paths::join_index`])
+/// are made relative to the arrays they have in common, which are iterated,
+/// and each field is named after the last key of its path,
+/// with numeric suffixes to tell apart the same names.
+pub fn build(paths: &[String]) -> String {
+    let segments = paths.iter().map(|path| parse(path)).collect::<Vec<_>>();
+
+    let mut common = segments.first().map(Vec::len).unwrap_or(0);
+    for path in &segments[1.min(segments.len())..] {
+        common = segments[0]
+            .iter()
+            .zip(path)
+            .take(common)
+            .take_while(|(a, b)| a.matches(b))
+            .count();
+    }
+    // Iterate up to the last array the paths have in common.
+    let prefix_len = segments
+        .first()
+        .and_then(|first| {
+            first[..common]
+                .iter()
+                .rposition(|segment| matches!(segment, Segment::Index(_)))
+        })
+        .map(|i| i + 1)
+        .unwrap_or(0);
+
+    let mut names: Vec<String> = Vec::new();
+    let fields = segments
+        .iter()
+        .map(|path| {
+            let name = dedup(field_name(path), &names);
+            names.push(name.clone());
+            format!(
+                "{}: {}",
+                quote_name(&name),
+                render(&path[prefix_len..], false)
+            )
+        })
+        .collect::<Vec<_>>();
+    let object = format!("{{{}}}", fields.join(", "));
+
+    match segments.first() {
+        Some(first) if prefix_len > 0 => {
+            format!("{} | {}", render(&first[..prefix_len], true), object)
+        }
+        _ => object,
+    }
+}
+
+/// Splits a path into its segments; the path is assumed to be well formed.
+fn parse(path: &str) -> Vec<Segment> {
+    let mut ret = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix(".\"") {
+            // Find the closing quote, skipping the escaped characters.
+            let mut end = 0;
+            let mut escaped = false;
+            for (i, ch) in after.char_indices() {
+                match ch {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => {
+                        end = i;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            let quoted = &rest[1..end + 3];
+            ret.push(Segment::Key(
+                serde_json::from_str(quoted).unwrap_or_else(|_| quoted.to_string()),
+            ));
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix(".[").or(rest.strip_prefix('[')) {
+            let end = after.find(']').unwrap_or(after.len());
+            ret.push(Segment::Index(after[..end].parse().unwrap_or(0)));
+            rest = after.get(end + 1..).unwrap_or("");
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end > 0 {
+                ret.push(Segment::Key(after[..end].to_string()));
+            }
+            rest = &after[end..];
+        } else {
+            break;
+        }
+    }
+    ret
+}
+
+/// Renders the segments as a path, iterating over the arrays if `iterate` is set.
+fn render(segments: &[Segment], iterate: bool) -> String {
+    let mut ret = ".".to_string();
+    for segment in segments {
+        match segment {
+            Segment::Key(key) => ret = paths::join_key(&ret, key),
+            Segment::Index(_) if iterate => {
+                ret = if ret == "." {
+                    ".[]".to_string()
+                } else {
+                    format!("{}[]", ret)
+                }
+            }
+            Segment::Index(index) => ret = paths::join_index(&ret, *index),
+        }
+    }
+    ret
+}
+
+fn field_name(path: &[Segment]) -> String {
+    path.iter()
+        .rev()
+        .find_map(|segment| match segment {
+            Segment::Key(key) => Some(key.clone()),
+            Segment::Index(_) => None,
+        })
+        .unwrap_or_else(|| "value".to_string())
+}
+
+/// Appends `_2`, `_3`... to the name while it is already taken.
+fn dedup(name: String, taken: &[String]) -> String {
+    if !taken.contains(&name) {
+        return name;
+    }
+    (2..)
+        .map(|n| format!("{}_{}", name, n))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or(name)
+}
+
+/// Quotes the field name unless jq accepts it bare.
+fn quote_name(name: &str) -> String {
+    if paths::quote_segment(name).starts_with(".\"") {
+        serde_json::to_string(name).unwrap_or_else(|_| name.to_string())
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_from(paths: &[&str]) -> String {
+        build(
+            &paths
+                .iter()
+                .map(|path| path.to_string())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn iterates_the_arrays_in_common() {
+        assert_eq!(
+            build_from(&[".items[0].metadata.name", ".items[3].status.phase"]),
+            ".items[] | {name: .metadata.name, phase: .status.phase}"
+        );
+        assert_eq!(
+            build_from(&[".a[0].b[1].c", ".a[2].b[0].d"]),
+            ".a[].b[] | {c: .c, d: .d}"
+        );
+        // Up to where the paths part.
+        assert_eq!(
+            build_from(&[".a[0].b[1].c", ".a[2].e"]),
+            ".a[] | {c: .b[1].c, e: .e}"
+        );
+        assert_eq!(build_from(&[".[0].a", ".[1].b"]), ".[] | {a: .a, b: .b}");
+    }
+
+    #[test]
+    fn keeps_the_paths_without_array_in_common() {
+        assert_eq!(build_from(&[".a.b", ".c"]), "{b: .a.b, c: .c}");
+        assert_eq!(
+            build_from(&[".a[0].b", ".c[0].d"]),
+            "{b: .a[0].b, d: .c[0].d}"
+        );
+        assert_eq!(build_from(&[".a.b.c"]), "{c: .a.b.c}");
+    }
+
+    #[test]
+    fn names_the_fields_apart() {
+        assert_eq!(
+            build_from(&[".a.name", ".b.name", ".c.name"]),
+            "{name: .a.name, name_2: .b.name, name_3: .c.name}"
+        );
+        // Named after the last key of the whole path.
+        assert_eq!(build_from(&[".a[0]", ".a[1]"]), ".a[] | {a: ., a_2: .}");
+        assert_eq!(
+            build_from(&[".[0]", ".[1]"]),
+            ".[] | {value: ., value_2: .}"
+        );
+    }
+
+    #[test]
+    fn quotes_the_keys_jq_does_not_take_bare() {
+        assert_eq!(
+            build_from(&[r#".items[0]."a.b""#, r#".items[1]."@timestamp""#]),
+            r#".items[] | {"a.b": ."a.b", "@timestamp": ."@timestamp"}"#
+        );
+        assert_eq!(
+            build_from(&[r#"."say \"hi\"".x"#, r#"."back\\slash""#]),
+            r#"{x: ."say \"hi\"".x, "back\\slash": ."back\\slash"}"#
+        );
+    }
+
+    #[test]
+    fn parses_the_keys_back() {
+        for key in [
+            "a",
+            "a.b",
+            "a[0]",
+            "@timestamp",
+            "名前",
+            "say \"hi\"",
+            "back\\slash",
+            "",
+        ] {
+            let path = paths::join_index(&paths::join_key(".", key), 2);
+            assert_eq!(
+                parse(&path),
+                [Segment::Key(key.to_string()), Segment::Index(2)],
+                "{}",
+                path
+            );
+        }
+    }
+}
@@ -1,27 +1,176 @@
-use std::{io, sync::Arc, time::Duration};
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use arboard::Clipboard;
 use crossterm::{
     self, cursor,
-    event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers},
+    event::{
+        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     style::Color,
-    terminal::{self, disable_raw_mode, enable_raw_mode},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use futures::StreamExt;
 use futures_timer::Delay;
-use promkit::{listbox, style::StyleBuilder, text, text_editor, PaneFactory};
+use promkit::{
+    listbox, pane::Pane, serde_json, style::StyleBuilder, text, text_editor, PaneFactory,
+};
 use tokio::{
     sync::{mpsc, Mutex, RwLock},
     task::JoinHandle,
 };
 
 use crate::{
-    Context, ContextMonitor, Editor, EditorTheme, IncrementalSearcher, PaneIndex, Processor,
-    Renderer, SearchProvider, SpinnerSpawner, ViewInitializer, ViewProvider, Visualizer,
-    EMPTY_PANE,
+    clipboard::{Clipboard, Copied},
+    command::CommandTemplate,
+    config::{GlobalAction, Keybinds},
+    editor::{EditorSettings, IndexStep},
+    empty_pane, external,
+    footer::{Footer, FooterContext},
+    help::Help,
+    history::{HistoryFile, SAVE_TIMEOUT},
+    input::InputFile,
+    json::format_count,
+    paths,
+    progress::StartupProgress,
+    registers::Registers,
+    render::{Layout, Screen, Viewport},
+    safe::SafeMode,
+    search::{self, SearchSettings},
+    sequence::{SequenceMatcher, Step},
+    stats::Profile,
+    Context, ContextMonitor, Editor, EventSource, IncrementalSearcher, Outcome, PaneIndex,
+    Processor, Renderer, SearchProvider, SpinnerSpawner, ViewInitializer, ViewProvider, Visualizer,
 };
 
+/// Parts of the terminal the session takes over,
+/// the others being replaced through the options (e.g. in tests).
+#[derive(Clone, Copy)]
+pub struct TerminalUse {
+    /// Whether the keys are read from the terminal.
+    pub keyboard: bool,
+    /// Whether the panes are drawn on the terminal.
+    pub screen: bool,
+}
+
+/// What `run` set up of the terminal (raw mode, the alternate screen and so on),
+/// to be undone once only whoever restores it first (e.g. the panic hook).
+struct TerminalState {
+    raw_mode: AtomicBool,
+    alt_screen: AtomicBool,
+    mouse_capture: AtomicBool,
+    cursor_hidden: AtomicBool,
+}
+
+static TERMINAL: TerminalState = TerminalState::new();
+
+impl TerminalState {
+    const fn new() -> Self {
+        Self {
+            raw_mode: AtomicBool::new(false),
+            alt_screen: AtomicBool::new(false),
+            mouse_capture: AtomicBool::new(false),
+            cursor_hidden: AtomicBool::new(false),
+        }
+    }
+
+    /// Undoes what is set up by writing to `out` and calling `disable_raw_mode`,
+    /// forgetting it so that restoring again does nothing.
+    fn restore(
+        &self,
+        out: &mut impl Write,
+        disable_raw_mode: impl FnOnce() -> io::Result<()>,
+    ) -> io::Result<()> {
+        if self.cursor_hidden.swap(false, Ordering::SeqCst) {
+            execute!(out, cursor::Show)?;
+        }
+        if self.mouse_capture.swap(false, Ordering::SeqCst) {
+            execute!(out, DisableMouseCapture)?;
+        }
+        if self.alt_screen.swap(false, Ordering::SeqCst) {
+            execute!(out, LeaveAlternateScreen)?;
+        }
+        if self.raw_mode.swap(false, Ordering::SeqCst) {
+            execute!(out, DisableBracketedPaste)?;
+            disable_raw_mode()?;
+        }
+        Ok(())
+    }
+}
+
+fn set_up_terminal(layout: &Layout, mouse: bool, terminal: TerminalUse) -> io::Result<()> {
+    if terminal.keyboard {
+        enable_raw_mode()?;
+        TERMINAL.raw_mode.store(true, Ordering::SeqCst);
+        execute!(io::stdout(), EnableBracketedPaste)?;
+        if mouse {
+            execute!(io::stdout(), EnableMouseCapture)?;
+            TERMINAL.mouse_capture.store(true, Ordering::SeqCst);
+        }
+    }
+    if terminal.screen {
+        if layout.alt_screen {
+            execute!(io::stdout(), EnterAlternateScreen)?;
+            TERMINAL.alt_screen.store(true, Ordering::SeqCst);
+        }
+        execute!(io::stdout(), cursor::Hide)?;
+        TERMINAL.cursor_hidden.store(true, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
+/// Restores the terminal as it was before `run`, as far as it is set up.
+///
+/// Called from the panic hook too, since a panic in a task
+/// would otherwise leave the terminal in raw mode.
+pub fn restore_terminal() -> io::Result<()> {
+    TERMINAL.restore(&mut io::stdout(), disable_raw_mode)
+}
+
+/// Signals asking to end the session (SIGTERM and SIGHUP),
+/// after which the terminal is restored as on exit.
+struct Termination {
+    #[cfg(unix)]
+    signals: [tokio::signal::unix::Signal; 2],
+}
+
+impl Termination {
+    fn listen() -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self {
+                signals: [
+                    signal(SignalKind::terminate())?,
+                    signal(SignalKind::hangup())?,
+                ],
+            })
+        }
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        {
+            let [terminate, hangup] = &mut self.signals;
+            tokio::select! {
+                _ = terminate.recv() => {},
+                _ = hangup.recv() => {},
+            }
+        }
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await
+    }
+}
+
 fn spawn_debouncer<T: Send + 'static>(
     mut debounce_rx: mpsc::Receiver<T>,
     last_tx: mpsc::Sender<T>,
@@ -51,77 +200,267 @@ fn spawn_debouncer<T: Send + 'static>(
     })
 }
 
-fn copy_to_clipboard(content: &str) -> text::State {
-    match Clipboard::new() {
-        Ok(mut clipboard) => match clipboard.set_text(content) {
-            Ok(_) => text::State {
-                text: "Copied to clipboard".to_string(),
-                style: StyleBuilder::new().fgc(Color::Green).build(),
-            },
-            Err(e) => text::State {
-                text: format!("Failed to copy to clipboard: {}", e),
-                style: StyleBuilder::new().fgc(Color::Red).build(),
-            },
-        },
-        // arboard fails (in the specific environment like linux?) on Clipboard::new()
-        // suppress the errors (but still show them) not to break the prompt
-        // https://github.com/1Password/arboard/issues/153
-        Err(e) => text::State {
-            text: format!("Failed to setup clipboard: {}", e),
-            style: StyleBuilder::new().fgc(Color::Red).build(),
-        },
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => futures::future::pending().await,
     }
 }
 
+/// Copies the content, showing the outcome in the Guide
+/// (once the clipboard is set up if it is still initializing).
+async fn copy_and_report(
+    clipboard: &Clipboard,
+    content: String,
+    description: Option<String>,
+    viewport: &Viewport,
+    shared_renderer: &Arc<Mutex<Renderer>>,
+) -> anyhow::Result<()> {
+    let guide = match clipboard.copy(content, description).await {
+        Copied::Now(guide) => guide,
+        Copied::Later(copied) => {
+            let shared_renderer = shared_renderer.clone();
+            let viewport = viewport.clone();
+            tokio::spawn(async move {
+                if let Ok(guide) = copied.await {
+                    let size = viewport.area();
+                    let _ = shared_renderer
+                        .lock()
+                        .await
+                        .update_and_draw([(PaneIndex::Guide, guide.create_pane(size.0, size.1))]);
+                }
+            });
+            text::State {
+                text: "Clipboard initializing…".to_string(),
+                style: StyleBuilder::new().fgc(Color::Grey).build(),
+            }
+        }
+    };
+    let size = viewport.area();
+    shared_renderer
+        .lock()
+        .await
+        .update_and_draw([(PaneIndex::Guide, guide.create_pane(size.0, size.1))])?;
+    Ok(())
+}
+
+/// Copies the text taken from the selected row
+/// unless the rows are about to be replaced by the query being processed.
+/// Builds the panes of the editor, its guide and its suggestions for the area,
+/// e.g. once resized, so that none of them is left at the former width.
+fn editor_panes(editor: &Editor, area: (u16, u16)) -> Vec<(PaneIndex, Pane)> {
+    vec![
+        (PaneIndex::Editor, editor.create_editor_pane(area.0, area.1)),
+        (PaneIndex::Guide, editor.create_guide_pane(area.0, area.1)),
+        (
+            PaneIndex::Search,
+            editor.create_searcher_pane(area.0, area.1),
+        ),
+    ]
+}
+
+async fn copy_selection(
+    clipboard: &Clipboard,
+    text: String,
+    context_monitor: &ContextMonitor,
+    viewport: &Viewport,
+    shared_renderer: &Arc<Mutex<Renderer>>,
+) -> anyhow::Result<()> {
+    if context_monitor.is_idle().await {
+        return copy_and_report(clipboard, text, None, viewport, shared_renderer).await;
+    }
+    let size = viewport.area();
+    let pane = text::State {
+        text: "Failed to copy while rendering is in progress.".to_string(),
+        style: StyleBuilder::new().fgc(Color::Yellow).build(),
+    }
+    .create_pane(size.0, size.1);
+    shared_renderer
+        .lock()
+        .await
+        .update_and_draw([(PaneIndex::Guide, pane)])?;
+    Ok(())
+}
+
+fn footer_context(editor: &Editor, focused: bool) -> FooterContext {
+    FooterContext::select(focused, editor.is_completing(), editor.text().is_empty())
+}
+
 enum Focus {
     Editor,
     Processor,
 }
 
-#[allow(clippy::too_many_arguments)]
+/// What the editor copies: the filter alone or the `jq` command line running it.
+enum QueryCopy {
+    Filter,
+    Command,
+}
+
+/// Change of the values the filters run on.
+#[derive(Clone, Copy)]
+enum BaseChange {
+    /// Take the result of the query as the values, the editor starting over from `.`.
+    Commit,
+    /// Go back to the values the last committed result replaced, and to its query.
+    Pop,
+}
+
+/// What is written to standard output when leaving with `GlobalAction::ExitWithOutput`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputMode {
+    Query,
+    Result,
+    Both,
+}
+
+/// What the session runs on: the input, the devices and the files it keeps.
+pub struct Session {
+    pub item: Arc<str>,
+    pub event_source: EventSource,
+    pub screen: Screen,
+    /// Size of the screen until it is resized.
+    pub screen_size: (u16, u16),
+    pub terminal: TerminalUse,
+    pub history_file: Option<HistoryFile>,
+    pub registers: Registers,
+    pub progress: StartupProgress,
+    /// Documents appended to the input as it is followed.
+    pub follow: Option<mpsc::Receiver<Vec<serde_json::Value>>>,
+    pub input_file: Option<InputFile>,
+    /// Changes of the input file as it is watched.
+    pub watch: Option<mpsc::Receiver<()>>,
+}
+
+/// How the session looks and reacts, as resolved from the configuration and the options.
+pub struct Settings {
+    pub spin_duration: Duration,
+    pub query_debounce_duration: Duration,
+    pub resize_debounce_duration: Duration,
+    pub viewer_coalesce_duration: Duration,
+    pub text_editor_state: text_editor::State,
+    pub editor: EditorSettings,
+    pub listbox_state: listbox::State,
+    pub search: SearchSettings,
+    pub keybinds: Keybinds,
+    pub confirm_quit: bool,
+    pub command_template: CommandTemplate,
+    pub mouse: bool,
+    pub footer: Option<Footer>,
+    pub layout: Layout,
+    pub safe_mode: SafeMode,
+    pub output_mode: Option<OutputMode>,
+    pub no_hint: bool,
+}
+
+/// Runs the session, returning its outcome
+/// and what to write to standard output if it is left to do so.
 pub async fn run<T: ViewProvider + SearchProvider>(
-    item: &'static str,
-    spin_duration: Duration,
-    query_debounce_duration: Duration,
-    resize_debounce_duration: Duration,
     provider: &mut T,
-    text_editor_state: text_editor::State,
-    editor_focus_theme: EditorTheme,
-    editor_defocus_theme: EditorTheme,
-    listbox_state: listbox::State,
-    search_result_chunk_size: usize,
-    search_load_chunk_size: usize,
-    no_hint: bool,
-) -> anyhow::Result<()> {
-    enable_raw_mode()?;
-    execute!(io::stdout(), cursor::Hide)?;
+    session: Session,
+    settings: &Settings,
+) -> anyhow::Result<(Outcome, Option<String>)> {
+    let Session {
+        item,
+        mut event_source,
+        screen,
+        screen_size,
+        terminal,
+        history_file,
+        registers,
+        progress,
+        follow,
+        input_file,
+        watch,
+    } = session;
+    let Settings {
+        spin_duration,
+        query_debounce_duration,
+        resize_debounce_duration,
+        viewer_coalesce_duration,
+        confirm_quit,
+        mouse,
+        layout,
+        safe_mode,
+        output_mode,
+        no_hint,
+        ..
+    } = *settings;
+    let keybinds = settings.keybinds.clone();
+    let command_template = settings.command_template.clone();
+    let footer = settings.footer.clone();
+    let SearchSettings {
+        load_chunk_size: search_load_chunk_size,
+        load_throttle: search_load_throttle,
+        result_paths: search_result_paths,
+        index_input,
+        ..
+    } = settings.search;
+
+    set_up_terminal(&layout, mouse, terminal)?;
+    let mut termination = Termination::listen()?;
 
-    let size = terminal::size()?;
+    let viewport = Viewport::new(layout, screen_size);
+    let size = viewport.area();
+    // Another screen than the terminal is drawn on from its top.
+    let position = if layout.alt_screen || !terminal.screen {
+        (0, 0)
+    } else {
+        cursor::position()?
+    };
+
+    let ctx = Arc::new(Mutex::new(Context::new(size)));
 
-    let searcher = IncrementalSearcher::new(listbox_state, search_result_chunk_size);
-    let loading_suggestions_task = searcher.spawn_load_task(provider, item, search_load_chunk_size);
+    let clipboard = Clipboard::spawn(safe_mode);
+
+    let searcher = IncrementalSearcher::new(settings.listbox_state.clone(), &settings.search);
+    // Replaced as the input is read again.
+    let loading_suggestions_task = Arc::new(Mutex::new(index_input.then(|| {
+        searcher.spawn_load_task(
+            provider,
+            item.clone(),
+            search_load_chunk_size,
+            search_load_throttle,
+            ContextMonitor::new(ctx.clone()),
+        )
+    })));
+    let shared_index = searcher.shared_index();
+    let shared_result_paths = searcher.shared_result_paths();
     let editor = Editor::new(
-        text_editor_state,
+        settings.text_editor_state.clone(),
         searcher,
-        editor_focus_theme,
-        editor_defocus_theme,
+        &settings.editor,
+        &keybinds,
+        history_file,
+        registers,
+        clipboard.clone(),
     );
 
     let shared_renderer = Arc::new(Mutex::new(Renderer::try_init_draw(
         [
             editor.create_editor_pane(size.0, size.1),
-            EMPTY_PANE.to_owned(),
-            EMPTY_PANE.to_owned(),
-            EMPTY_PANE.to_owned(),
-            EMPTY_PANE.to_owned(),
+            empty_pane(),
+            empty_pane(),
+            empty_pane(),
+            empty_pane(),
+            empty_pane(),
+            match &footer {
+                Some(footer) => footer.create_pane(footer_context(&editor, true), size.0, size.1),
+                None => empty_pane(),
+            },
+            empty_pane(),
         ],
+        viewport.clone(),
+        position,
         no_hint,
+        screen,
     )?));
 
-    let ctx = Arc::new(Mutex::new(Context::new(size)));
-
     let (last_query_tx, mut last_query_rx) = mpsc::channel(1);
-    let (debounce_query_tx, debounce_query_rx) = mpsc::channel(1);
+    let (debounce_query_tx, debounce_query_rx) = mpsc::channel::<String>(1);
+    let refresh_query_tx = debounce_query_tx.clone();
+    let reload_query_tx = debounce_query_tx.clone();
+    let unlimited_query_tx = debounce_query_tx.clone();
     let query_debouncer =
         spawn_debouncer(debounce_query_rx, last_query_tx, query_debounce_duration);
 
@@ -131,137 +470,486 @@ pub async fn run<T: ViewProvider + SearchProvider>(
         spawn_debouncer(debounce_resize_rx, last_resize_tx, resize_debounce_duration);
 
     let spinner_spawner = SpinnerSpawner::new(ctx.clone());
-    let spinning = spinner_spawner.spawn_spin_task(shared_renderer.clone(), spin_duration);
+    let spinning =
+        spinner_spawner.spawn_spin_task(shared_renderer.clone(), spin_duration, progress.clone());
 
     let mut focus = Focus::Editor;
     let (editor_event_tx, mut editor_event_rx) = mpsc::channel::<Event>(1);
-    let (processor_event_tx, mut processor_event_rx) = mpsc::channel::<Event>(1);
+    // Large enough to hold the keys pressed while drawing, which are then drawn at once.
+    let (processor_event_tx, mut processor_event_rx) = mpsc::channel::<Event>(256);
 
-    let (editor_copy_tx, mut editor_copy_rx) = mpsc::channel::<()>(1);
+    let (editor_copy_tx, mut editor_copy_rx) = mpsc::channel::<QueryCopy>(1);
     let (processor_copy_tx, mut processor_copy_rx) = mpsc::channel::<()>(1);
+    let (reload_tx, mut reload_rx) = mpsc::channel::<()>(1);
+    let (slurp_tx, mut slurp_rx) = mpsc::channel::<()>(1);
+    let (show_all_tx, mut show_all_rx) = mpsc::channel::<()>(1);
+    let (base_tx, mut base_rx) = mpsc::channel::<BaseChange>(1);
+    let (stats_tx, mut stats_rx) = mpsc::channel::<()>(1);
+    // Reloads once the watched file has stopped changing for a while (e.g. while being written).
+    let watch_debouncer = watch
+        .map(|changed_rx| spawn_debouncer(changed_rx, reload_tx.clone(), query_debounce_duration));
 
     let (editor_focus_tx, mut editor_focus_rx) = mpsc::channel::<bool>(1);
 
-    let mut text_diff = [editor.text(), editor.text()];
+    let (editor_reset_tx, mut editor_reset_rx) = mpsc::channel::<()>(1);
+    // Filter built from the JSON viewer (e.g. a projection), to be edited further.
+    let (query_to_edit_tx, mut query_to_edit_rx) = mpsc::channel::<String>(1);
+    let (editor_query_tx, mut editor_query_rx) = mpsc::channel::<String>(1);
+    let base_query_tx = editor_query_tx.clone();
+    // Steps of an array index, and then with the length of the array to wrap around.
+    let (index_step_tx, mut index_step_rx) = mpsc::channel::<IndexStep>(1);
+    let (editor_index_step_tx, mut editor_index_step_rx) =
+        mpsc::channel::<(IndexStep, Option<usize>)>(1);
+    // Size of the area after a (debounced) resize, to rebuild the panes of the editor.
+    let (editor_relayout_tx, mut editor_relayout_rx) = mpsc::channel::<(u16, u16)>(1);
+    let (processor_reset_tx, mut processor_reset_rx) = mpsc::channel::<()>(1);
+
+    let mut last_text = editor.text().to_string();
+    let initial_query = last_text.clone();
     let shared_editor = Arc::new(RwLock::new(editor));
-    let processor = Processor::new(ctx.clone());
+    // Queries which produced a result, to be remembered in the history.
+    let (succeeded_query_tx, mut succeeded_query_rx) = mpsc::channel::<String>(1);
+    // Values of the queries, whose paths are suggested after a `|`.
+    let (result_tx, result_rx) = if search_result_paths {
+        let (result_tx, result_rx) = mpsc::channel(1);
+        (Some(result_tx), Some(result_rx))
+    } else {
+        (None, None)
+    };
+    let processor = Processor::new(ctx.clone(), succeeded_query_tx, result_tx);
     let context_monitor = ContextMonitor::new(ctx.clone());
-    let initializer = ViewInitializer::new(ctx.clone());
+    let initializer = ViewInitializer::new(ctx.clone(), progress);
+    let mut reload_provider = provider.clone();
+    let reload_item = item.clone();
     let initializing = initializer.initialize(provider, item, size, shared_renderer.clone());
 
-    let main_task: JoinHandle<anyhow::Result<()>> = {
-        let mut stream = EventStream::new();
+    // Whether the session is left with its result.
+    let main_task: JoinHandle<anyhow::Result<bool>> = {
+        let viewport = viewport.clone();
         let shared_renderer = shared_renderer.clone();
+        let shared_editor = shared_editor.clone();
+        let mut matcher =
+            SequenceMatcher::new(keybinds.global.bindings(), keybinds.sequence_timeout);
+        let quit_again = match keybinds.global.exit.iter().next() {
+            Some(seq) => format!("Press {} again to quit", seq),
+            None => "Press the exit keybind again to quit".to_string(),
+        };
+        // Whether the exit keybind was pressed once, waiting for confirmation.
+        let mut quitting = false;
+        let mut help = Help::new(&keybinds);
+        let mut help_open = false;
         tokio::spawn(async move {
             'main: loop {
-                tokio::select! {
-                    Some(Ok(event)) = stream.next() => {
-                        match event {
-                            Event::Resize(width, height) => {
-                                debounce_resize_tx.send((width, height)).await?;
-                            },
-                            Event::Key(KeyEvent {
-                                code: KeyCode::Char('c'),
-                                modifiers: KeyModifiers::CONTROL,
+                let deadline = matcher.deadline();
+                let had_pending = matcher.pending_text().is_some();
+                let steps = tokio::select! {
+                    Some(Ok(event)) = event_source.next() => {
+                        // Reported on every move of the pointer while capturing the mouse.
+                        if let Event::Mouse(MouseEvent { kind: MouseEventKind::Moved, .. }) = event {
+                            continue;
+                        }
+                        if let Event::Resize(width, height) = event {
+                            if help_open {
+                                shared_renderer
+                                    .lock()
+                                    .await
+                                    .update_and_draw([(PaneIndex::Help, help.create_pane(height))])?;
+                            }
+                            debounce_resize_tx.send(viewport.resize((width, height))).await?;
+                            continue;
+                        }
+                        matcher.feed(&event)
+                    },
+                    _ = sleep_until(deadline), if deadline.is_some() => {
+                        matcher.flush()
+                    },
+                    _ = termination.recv() => {
+                        break 'main;
+                    },
+                    Some(query) = query_to_edit_rx.recv() => {
+                        if let Focus::Processor = focus {
+                            focus = Focus::Editor;
+                            editor_focus_tx.send(true).await?;
+                        }
+                        editor_query_tx.send(query).await?;
+                        continue;
+                    },
+                    else => {
+                        break 'main;
+                    }
+                };
+
+                for step in steps {
+                    let confirming = std::mem::take(&mut quitting);
+                    // The help takes the keys while it is shown, except for exiting.
+                    if help_open && !matches!(step, Step::Action(GlobalAction::Exit)) {
+                        let height = viewport.area().1;
+                        let pane = match step {
+                            Step::Action(GlobalAction::Help)
+                            | Step::Event(Event::Key(KeyEvent {
+                                code: KeyCode::Esc,
                                 kind: KeyEventKind::Press,
-                                state: KeyEventState::NONE,
-                            }) => {
-                                break 'main
-                            },
-                            Event::Key(KeyEvent {
-                                code: KeyCode::Char('q'),
-                                modifiers: KeyModifiers::CONTROL,
+                                ..
+                            })) => {
+                                help_open = false;
+                                empty_pane()
+                            }
+                            Step::Event(Event::Key(KeyEvent {
+                                code: KeyCode::Down | KeyCode::PageDown | KeyCode::Char(' '),
                                 kind: KeyEventKind::Press,
-                                state: KeyEventState::NONE,
-                            }) => {
-                                editor_copy_tx.send(()).await?;
-                            },
-                            Event::Key(KeyEvent {
-                                code: KeyCode::Char('o'),
-                                modifiers: KeyModifiers::CONTROL,
+                                ..
+                            }))
+                            | Step::Event(Event::Mouse(MouseEvent {
+                                kind: MouseEventKind::ScrollDown,
+                                ..
+                            })) => {
+                                help.next_page(height);
+                                help.create_pane(height)
+                            }
+                            Step::Event(Event::Key(KeyEvent {
+                                code: KeyCode::Up | KeyCode::PageUp,
                                 kind: KeyEventKind::Press,
-                                state: KeyEventState::NONE,
-                            }) => {
-                                let mut pane = EMPTY_PANE.to_owned();
-                                if context_monitor.is_idle().await {
-                                    processor_copy_tx.send(()).await?;
-                                } else {
-                                    let size = terminal::size()?;
-                                    pane = text::State {
-                                        text: "Failed to copy while rendering is in progress.".to_string(),
-                                        style: StyleBuilder::new().fgc(Color::Yellow).build(),
-                                    }.create_pane(size.0, size.1);
-                                }
-                                {
-                                    shared_renderer.lock().await.update_and_draw([
-                                        (PaneIndex::Guide, pane),
-                                    ])?;
+                                ..
+                            }))
+                            | Step::Event(Event::Mouse(MouseEvent {
+                                kind: MouseEventKind::ScrollUp,
+                                ..
+                            })) => {
+                                help.previous_page();
+                                help.create_pane(height)
+                            }
+                            _ => continue,
+                        };
+                        shared_renderer
+                            .lock()
+                            .await
+                            .update_and_draw([(PaneIndex::Help, pane)])?;
+                        continue;
+                    }
+                    if let Step::Action(action) = &step {
+                        if let Err(e) = safe_mode.check_action(*action) {
+                            let size = viewport.area();
+                            let pane = text::State {
+                                text: e.to_string(),
+                                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                            }
+                            .create_pane(size.0, size.1);
+                            shared_renderer
+                                .lock()
+                                .await
+                                .update_and_draw([(PaneIndex::Guide, pane)])?;
+                            continue;
+                        }
+                    }
+                    match step {
+                        Step::Action(GlobalAction::Exit) if !confirm_quit || confirming => {
+                            break 'main
+                        }
+                        Step::Action(GlobalAction::Exit) => {
+                            quitting = true;
+                            let size = viewport.area();
+                            let pane = text::State {
+                                text: quit_again.clone(),
+                                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                            }
+                            .create_pane(size.0, size.1);
+                            shared_renderer
+                                .lock()
+                                .await
+                                .update_and_draw([(PaneIndex::Guide, pane)])?;
+                        }
+                        Step::Action(GlobalAction::ExitWithOutput) => {
+                            if context_monitor.is_idle().await {
+                                return Ok(true);
+                            }
+                            let size = viewport.area();
+                            let pane = text::State {
+                                text:
+                                    "Failed to exit with the result while rendering is in progress."
+                                        .to_string(),
+                                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                            }
+                            .create_pane(size.0, size.1);
+                            shared_renderer
+                                .lock()
+                                .await
+                                .update_and_draw([(PaneIndex::Guide, pane)])?;
+                        }
+                        Step::Action(GlobalAction::CopyQuery) => {
+                            editor_copy_tx.send(QueryCopy::Filter).await?;
+                        }
+                        Step::Action(GlobalAction::CopyCommand) => {
+                            editor_copy_tx.send(QueryCopy::Command).await?;
+                        }
+                        Step::Action(GlobalAction::ExternalEditor) => {
+                            let size = viewport.area();
+                            let Some(command) = external::editor_command() else {
+                                let pane = text::State {
+                                    text: "Set $VISUAL or $EDITOR to edit the filter in an editor"
+                                        .to_string(),
+                                    style: StyleBuilder::new().fgc(Color::Yellow).build(),
                                 }
-                            },
-                            Event::Key(KeyEvent {
-                                code: KeyCode::Down,
-                                modifiers: KeyModifiers::SHIFT,
-                                kind: KeyEventKind::Press,
-                                state: KeyEventState::NONE,
-                            }) | Event::Key(KeyEvent {
-                                code: KeyCode::Up,
-                                modifiers: KeyModifiers::SHIFT,
-                                kind: KeyEventKind::Press,
-                                state: KeyEventState::NONE,
-                            }) => {
-                                match focus {
-                                    Focus::Editor => {
-                                        let mut pane = EMPTY_PANE.to_owned();
-                                        if context_monitor.is_idle().await {
-                                            focus = Focus::Processor;
-                                            editor_focus_tx.send(false).await?;
-                                        } else {
-                                            let size = terminal::size()?;
-                                            pane = text::State {
-                                                text: "Failed to switch pane while rendering is in progress.".to_string(),
-                                                style: StyleBuilder::new().fgc(Color::Yellow).build(),
-                                            }.create_pane(size.0, size.1);
-                                        }
-                                        {
-                                            shared_renderer.lock().await.update_and_draw([
-                                                (PaneIndex::Guide, pane),
-                                            ])?;
-                                        }
-                                    },
-                                    Focus::Processor => {
+                                .create_pane(size.0, size.1);
+                                shared_renderer
+                                    .lock()
+                                    .await
+                                    .update_and_draw([(PaneIndex::Guide, pane)])?;
+                                continue;
+                            };
+                            let query = shared_editor.read().await.text().to_string();
+                            let edited = {
+                                // Holding the renderer keeps the other tasks from drawing
+                                // while the editor has the terminal.
+                                let mut renderer = shared_renderer.lock().await;
+                                restore_terminal()?;
+                                let edited = external::edit(&command, &query, safe_mode).await;
+                                set_up_terminal(&layout, mouse, terminal)?;
+                                renderer.redraw()?;
+                                edited
+                            };
+                            match edited {
+                                Ok(query) => {
+                                    if let Focus::Processor = focus {
                                         focus = Focus::Editor;
                                         editor_focus_tx.send(true).await?;
-                                    },
+                                    }
+                                    editor_query_tx.send(query).await?;
                                 }
-                            },
-                            event => {
-                                match focus {
-                                    Focus::Editor => {
-                                        editor_event_tx.send(event).await?;
-                                    },
-                                    Focus::Processor => {
-                                        processor_event_tx.send(event).await?;
-                                    },
+                                Err(e) => {
+                                    let pane = text::State {
+                                        text: format!("Failed to edit the filter: {}", e),
+                                        style: StyleBuilder::new().fgc(Color::Red).build(),
+                                    }
+                                    .create_pane(size.0, size.1);
+                                    shared_renderer
+                                        .lock()
+                                        .await
+                                        .update_and_draw([(PaneIndex::Guide, pane)])?;
                                 }
-                            },
+                            }
                         }
-                    },
-                    else => {
-                        break 'main;
+                        Step::Action(GlobalAction::CopyResult) => {
+                            let mut pane = empty_pane();
+                            if context_monitor.is_idle().await {
+                                processor_copy_tx.send(()).await?;
+                            } else {
+                                let size = viewport.area();
+                                pane = text::State {
+                                    text: "Failed to copy while rendering is in progress."
+                                        .to_string(),
+                                    style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                                }
+                                .create_pane(size.0, size.1);
+                            }
+                            {
+                                shared_renderer
+                                    .lock()
+                                    .await
+                                    .update_and_draw([(PaneIndex::Guide, pane)])?;
+                            }
+                        }
+                        Step::Action(GlobalAction::SwitchMode) => {
+                            match focus {
+                                Focus::Editor => {
+                                    let mut pane = empty_pane();
+                                    if context_monitor.is_idle().await {
+                                        focus = Focus::Processor;
+                                        editor_focus_tx.send(false).await?;
+                                    } else {
+                                        let size = viewport.area();
+                                        pane = text::State {
+                                            text: "Failed to switch pane while rendering is in progress.".to_string(),
+                                            style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                                        }.create_pane(size.0, size.1);
+                                    }
+                                    {
+                                        shared_renderer
+                                            .lock()
+                                            .await
+                                            .update_and_draw([(PaneIndex::Guide, pane)])?;
+                                    }
+                                }
+                                Focus::Processor => {
+                                    focus = Focus::Editor;
+                                    editor_focus_tx.send(true).await?;
+                                }
+                            }
+                        }
+                        Step::Action(GlobalAction::Help) => {
+                            help_open = true;
+                            help.first_page();
+                            let height = viewport.area().1;
+                            shared_renderer
+                                .lock()
+                                .await
+                                .update_and_draw([(PaneIndex::Help, help.create_pane(height))])?;
+                        }
+                        Step::Action(GlobalAction::Reload) => {
+                            // Pressed again while reloading, it is reloaded once more.
+                            let _ = reload_tx.try_send(());
+                        }
+                        Step::Action(GlobalAction::ToggleSlurp) => {
+                            slurp_tx.send(()).await?;
+                        }
+                        Step::Action(GlobalAction::ShowAllResults) => {
+                            show_all_tx.send(()).await?;
+                        }
+                        Step::Action(GlobalAction::CommitResult) => {
+                            base_tx.send(BaseChange::Commit).await?;
+                        }
+                        Step::Action(GlobalAction::PopBase) => {
+                            base_tx.send(BaseChange::Pop).await?;
+                        }
+                        Step::Action(GlobalAction::ToggleStats) => {
+                            stats_tx.send(()).await?;
+                        }
+                        Step::Action(GlobalAction::Reset) => {
+                            if let Focus::Processor = focus {
+                                focus = Focus::Editor;
+                                editor_focus_tx.send(true).await?;
+                            }
+                            editor_reset_tx.send(()).await?;
+                            processor_reset_tx.send(()).await?;
+                        }
+                        // Pasted in the terminal, for the filter whatever the focus.
+                        Step::Event(event @ Event::Paste(_)) => {
+                            if let Focus::Processor = focus {
+                                focus = Focus::Editor;
+                                editor_focus_tx.send(true).await?;
+                            }
+                            editor_event_tx.send(event).await?;
+                        }
+                        // A click focuses the pane under the pointer.
+                        Step::Event(Event::Mouse(MouseEvent {
+                            kind: MouseEventKind::Down(MouseButton::Left),
+                            row,
+                            ..
+                        })) => {
+                            let clicked = shared_renderer.lock().await.pane_at(row);
+                            let idle = context_monitor.is_idle().await;
+                            match (clicked, &focus) {
+                                (
+                                    Some(PaneIndex::Editor | PaneIndex::Guide | PaneIndex::Search),
+                                    Focus::Processor,
+                                ) => {
+                                    focus = Focus::Editor;
+                                    editor_focus_tx.send(true).await?;
+                                }
+                                // Not while rendering, as for `switch_mode`.
+                                (
+                                    Some(
+                                        PaneIndex::ProcessorGuide
+                                        | PaneIndex::Processor
+                                        | PaneIndex::ProcessorStatus,
+                                    ),
+                                    Focus::Editor,
+                                ) if idle => {
+                                    focus = Focus::Processor;
+                                    editor_focus_tx.send(false).await?;
+                                }
+                                _ => {}
+                            }
+                        }
+                        // Scrolling (or any other mouse action) is for the JSON viewer,
+                        // leaving the focus where it is.
+                        Step::Event(event @ Event::Mouse(_)) => {
+                            processor_event_tx.send(event).await?;
+                        }
+                        Step::Event(event) => match focus {
+                            Focus::Editor => {
+                                editor_event_tx.send(event).await?;
+                            }
+                            Focus::Processor => {
+                                processor_event_tx.send(event).await?;
+                            }
+                        },
+                    }
+                }
+
+                let pane = match matcher.pending_text() {
+                    Some(text) => {
+                        let size = viewport.area();
+                        Some(
+                            text::State {
+                                text,
+                                style: StyleBuilder::new().fgc(Color::Grey).build(),
+                            }
+                            .create_pane(size.0, size.1),
+                        )
                     }
+                    None if had_pending => Some(empty_pane()),
+                    None => None,
+                };
+                if let Some(pane) = pane {
+                    shared_renderer
+                        .lock()
+                        .await
+                        .update_and_draw([(PaneIndex::Guide, pane)])?;
                 }
             }
-            Ok(())
+            Ok(false)
         })
     };
 
+    // Indexes the paths of each result, giving up on the previous one still being indexed.
+    let indexing_results: Option<JoinHandle<()>> = result_rx.map(|mut result_rx| {
+        let shared_result_paths = shared_result_paths.clone();
+        tokio::spawn(async move {
+            let mut indexing: Option<JoinHandle<()>> = None;
+            while let Some((query, values)) = result_rx.recv().await {
+                if let Some(task) = indexing.take() {
+                    task.abort();
+                }
+                indexing = Some(search::spawn_result_load_task(
+                    shared_result_paths.clone(),
+                    query,
+                    values,
+                    search_load_chunk_size,
+                ));
+            }
+        })
+    });
+
     let editor_task: JoinHandle<anyhow::Result<()>> = {
+        let viewport = viewport.clone();
         let shared_renderer = shared_renderer.clone();
+        let clipboard = clipboard.clone();
         let shared_editor = shared_editor.clone();
         tokio::spawn(async move {
+            let mut deadline = None;
+            let mut focused = true;
+            let mut last_footer_context = None;
             loop {
                 tokio::select! {
+                    _ = sleep_until(deadline), if deadline.is_some() => {
+                        let size = viewport.area();
+                        let (editor_pane, guide_pane, searcher_pane) = {
+                            let mut editor = shared_editor.write().await;
+                            editor.flush_sequence();
+                            deadline = editor.sequence_deadline();
+
+                            if editor.text() != last_text {
+                                last_text.clear();
+                                last_text.push_str(editor.text());
+                                debounce_query_tx.send(last_text.clone()).await?;
+                            }
+                            (
+                                editor.create_editor_pane(size.0, size.1),
+                                editor.create_guide_pane(size.0, size.1),
+                                editor.create_searcher_pane(size.0, size.1),
+                            )
+                        };
+                        {
+                            shared_renderer.lock().await.update_and_draw([
+                                (PaneIndex::Editor, editor_pane),
+                                (PaneIndex::Guide, guide_pane),
+                                (PaneIndex::Search, searcher_pane),
+                            ])?;
+                        }
+                    }
                     Some(focus) = editor_focus_rx.recv() => {
+                        focused = focus;
+                        let size = viewport.area();
                         let (editor_pane, guide_pane) = {
                             let mut editor = shared_editor.write().await;
                             if focus {
@@ -269,6 +957,7 @@ pub async fn run<T: ViewProvider + SearchProvider>(
                             } else {
                                 editor.defocus();
                             }
+                            deadline = editor.sequence_deadline();
                             (
                                 editor.create_editor_pane(size.0, size.1),
                                 editor.create_guide_pane(size.0, size.1),
@@ -281,32 +970,125 @@ pub async fn run<T: ViewProvider + SearchProvider>(
                             ])?;
                         }
                     }
-                    Some(()) = editor_copy_rx.recv() => {
-                        let text = {
-                            let editor = shared_editor.write().await;
-                            editor.text()
+                    Some(()) = editor_reset_rx.recv() => {
+                        let size = viewport.area();
+                        let (editor_pane, guide_pane, searcher_pane) = {
+                            let mut editor = shared_editor.write().await;
+                            editor.reset();
+                            deadline = None;
+
+                            // Supersede the query waiting to be debounced, if any.
+                            if editor.text() != last_text {
+                                last_text.clear();
+                                last_text.push_str(editor.text());
+                                debounce_query_tx.send(last_text.clone()).await?;
+                            }
+                            (
+                                editor.create_editor_pane(size.0, size.1),
+                                editor.create_guide_pane(size.0, size.1),
+                                editor.create_searcher_pane(size.0, size.1),
+                            )
                         };
-                        let guide = copy_to_clipboard(&text);
-                        let size = terminal::size()?;
-                        let pane = guide.create_pane(size.0, size.1);
                         {
                             shared_renderer.lock().await.update_and_draw([
-                                (PaneIndex::Guide, pane),
+                                (PaneIndex::Editor, editor_pane),
+                                (PaneIndex::Guide, guide_pane),
+                                (PaneIndex::Search, searcher_pane),
                             ])?;
                         }
                     }
+                    Some(query) = editor_query_rx.recv() => {
+                        let size = viewport.area();
+                        let (editor_pane, guide_pane, searcher_pane) = {
+                            let mut editor = shared_editor.write().await;
+                            editor.set_text(&query);
+                            deadline = None;
+
+                            if editor.text() != last_text {
+                                last_text.clear();
+                                last_text.push_str(editor.text());
+                                debounce_query_tx.send(last_text.clone()).await?;
+                            }
+                            (
+                                editor.create_editor_pane(size.0, size.1),
+                                editor.create_guide_pane(size.0, size.1),
+                                editor.create_searcher_pane(size.0, size.1),
+                            )
+                        };
+                        {
+                            shared_renderer.lock().await.update_and_draw([
+                                (PaneIndex::Editor, editor_pane),
+                                (PaneIndex::Guide, guide_pane),
+                                (PaneIndex::Search, searcher_pane),
+                            ])?;
+                        }
+                    }
+                    Some((step, length)) = editor_index_step_rx.recv() => {
+                        let size = viewport.area();
+                        let editor_pane = {
+                            let mut editor = shared_editor.write().await;
+                            editor.apply_index_step(&step, length);
+
+                            if editor.text() != last_text {
+                                last_text.clear();
+                                last_text.push_str(editor.text());
+                                debounce_query_tx.send(last_text.clone()).await?;
+                            }
+                            editor.create_editor_pane(size.0, size.1)
+                        };
+                        shared_renderer
+                            .lock()
+                            .await
+                            .update_and_draw([(PaneIndex::Editor, editor_pane)])?;
+                    }
+                    Some(area) = editor_relayout_rx.recv() => {
+                        let panes = {
+                            let editor = shared_editor.read().await;
+                            let mut panes = editor_panes(&editor, area);
+                            if let Some(footer) = &footer {
+                                let context = footer_context(&editor, focused);
+                                last_footer_context = Some(context);
+                                panes.push((PaneIndex::Footer, footer.create_pane(context, area.0, area.1)));
+                            }
+                            panes
+                        };
+                        // Drawn at once, so that no pane is shown at the former width.
+                        shared_renderer.lock().await.update_and_draw(panes)?;
+                    }
+                    Some(query) = succeeded_query_rx.recv() => {
+                        shared_editor.write().await.remember(&query);
+                    }
+                    Some(copy) = editor_copy_rx.recv() => {
+                        let text = {
+                            let editor = shared_editor.write().await;
+                            editor.text().to_string()
+                        };
+                        match copy {
+                            QueryCopy::Filter => {
+                                copy_and_report(&clipboard, text, None, &viewport, &shared_renderer).await?;
+                            }
+                            QueryCopy::Command => {
+                                let command = command_template.render(&text);
+                                let description = format!("`{}`", command);
+                                copy_and_report(&clipboard, command, Some(description), &viewport, &shared_renderer).await?;
+                            }
+                        }
+                    }
                     Some(event) = editor_event_rx.recv() => {
-                        let size = terminal::size()?;
+                        let size = viewport.area();
                         let (editor_pane, guide_pane, searcher_pane) = {
 
                             let mut editor = shared_editor.write().await;
                             editor.operate(&event).await?;
+                            deadline = editor.sequence_deadline();
+                            if let Some(step) = editor.take_index_step() {
+                                index_step_tx.send(step).await?;
+                            }
 
-                            let current_text = editor.text();
-                            if current_text != text_diff[1] {
-                                debounce_query_tx.send(current_text.clone()).await?;
-                                text_diff[0] = text_diff[1].clone();
-                                text_diff[1] = current_text;
+                            if editor.text() != last_text {
+                                last_text.clear();
+                                last_text.push_str(editor.text());
+                                debounce_query_tx.send(last_text.clone()).await?;
                             }
                             (
                                 editor.create_editor_pane(size.0, size.1),
@@ -326,40 +1108,409 @@ pub async fn run<T: ViewProvider + SearchProvider>(
                         break
                     }
                 }
+
+                if let Some(footer) = &footer {
+                    let context = footer_context(&*shared_editor.read().await, focused);
+                    if last_footer_context != Some(context) {
+                        last_footer_context = Some(context);
+                        let size = viewport.area();
+                        shared_renderer.lock().await.update_and_draw([(
+                            PaneIndex::Footer,
+                            footer.create_pane(context, size.0, size.1),
+                        )])?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    };
+
+    let shared_visualizer = Arc::new(Mutex::new(initializing.await?));
+
+    // Looks up the length of the array whose index is stepped, to wrap around it.
+    let stepping_index: JoinHandle<anyhow::Result<()>> = {
+        let shared_visualizer = shared_visualizer.clone();
+        tokio::spawn(async move {
+            while let Some(step) = index_step_rx.recv().await {
+                let length = shared_visualizer
+                    .lock()
+                    .await
+                    .array_length(&step.array)
+                    .await;
+                editor_index_step_tx.send((step, length)).await?;
+            }
+            Ok(())
+        })
+    };
+
+    // Appends the documents of the followed file (or piped input), then applies the query again.
+    let following: Option<JoinHandle<anyhow::Result<()>>> = follow.map(|mut documents_rx| {
+        let shared_visualizer = shared_visualizer.clone();
+        let shared_editor = shared_editor.clone();
+        let shared_renderer = shared_renderer.clone();
+        let viewport = viewport.clone();
+        tokio::spawn(async move {
+            let set_receiving = |receiving: bool| {
+                let shared_visualizer = shared_visualizer.clone();
+                let shared_renderer = shared_renderer.clone();
+                let viewport = viewport.clone();
+                async move {
+                    let pane = {
+                        let mut visualizer = shared_visualizer.lock().await;
+                        visualizer.set_receiving(receiving);
+                        visualizer.create_status_pane(viewport.area().0)
+                    };
+                    shared_renderer
+                        .lock()
+                        .await
+                        .update_and_draw([(PaneIndex::ProcessorStatus, pane)])
+                }
+            };
+            set_receiving(true).await?;
+            while let Some(documents) = documents_rx.recv().await {
+                if index_input {
+                    let mut index = shared_index.lock().await;
+                    for entry in paths::get_all_paths(documents.iter()) {
+                        index.insert(entry);
+                    }
+                }
+                shared_visualizer.lock().await.append(documents);
+                let query = shared_editor.read().await.text().to_string();
+                refresh_query_tx.send(query).await?;
+            }
+            set_receiving(false).await
+        })
+    });
+
+    // Tells how many paths are indexed for the suggestions while it goes on,
+    // drawing once more when it is over to clear it.
+    let indexing_progress: JoinHandle<anyhow::Result<()>> = {
+        let viewport = viewport.clone();
+        let shared_editor = shared_editor.clone();
+        let shared_renderer = shared_renderer.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(spin_duration);
+            // A load may start and end between two ticks, after the editor drew its progress.
+            let mut loads_seen = 0;
+            loop {
+                interval.tick().await;
+                let pane = {
+                    let editor = shared_editor.read().await;
+                    let loads = editor.searcher().loads_completed();
+                    if !editor.searcher().is_loading() && loads == loads_seen {
+                        continue;
+                    }
+                    loads_seen = loads;
+                    let size = viewport.area();
+                    editor.create_searcher_pane(size.0, size.1)
+                };
+                shared_renderer
+                    .lock()
+                    .await
+                    .update_and_draw([(PaneIndex::Search, pane)])?;
+            }
+        })
+    };
+
+    // Reads the input file again, wraps its documents into an array (or splits it back),
+    // or commits the result as the values the filters run on (or goes back from it),
+    // replacing the documents and the suggestions, then applies the query again.
+    // The former ones are kept if reading fails.
+    let reloading: JoinHandle<anyhow::Result<()>> = {
+        let viewport = viewport.clone();
+        let shared_visualizer = shared_visualizer.clone();
+        let shared_editor = shared_editor.clone();
+        let shared_renderer = shared_renderer.clone();
+        let loading_suggestions_task = loading_suggestions_task.clone();
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            // Input as last read, whose paths are suggested.
+            let mut item = reload_item;
+            // Committed result the filters run on instead of the input, one document per line.
+            let mut base_item: Option<Arc<str>> = None;
+            loop {
+                let replaced = tokio::select! {
+                    Some(()) = reload_rx.recv() => match &input_file {
+                        None => Err(text::State {
+                            text: "Reloading needs an input file, not standard input".to_string(),
+                            style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                        }),
+                        Some(file) => {
+                            let read = {
+                                let file = file.clone();
+                                let provider = reload_provider.clone();
+                                tokio::task::spawn_blocking(move || {
+                                    let item = Arc::<str>::from(file.read()?);
+                                    let documents = provider.deserialize(&item)?;
+                                    anyhow::Ok((item, documents))
+                                })
+                                .await?
+                            };
+                            match read {
+                                Ok((read_item, documents)) => {
+                                    shared_visualizer.lock().await.replace(documents);
+                                    item = read_item;
+                                    base_item = None;
+                                    Ok(None)
+                                }
+                                Err(e) => Err(text::State {
+                                    text: format!(
+                                        "Failed to reload {}: {}",
+                                        file.path.display(),
+                                        e
+                                    ),
+                                    style: StyleBuilder::new().fgc(Color::Red).build(),
+                                }),
+                            }
+                        }
+                    },
+                    Some(()) = slurp_rx.recv() => {
+                        let slurp = shared_visualizer.lock().await.toggle_slurp();
+                        reload_provider.set_slurp(slurp);
+                        base_item = None;
+                        Ok(None)
+                    }
+                    Some(change) = base_rx.recv() => {
+                        let changed = {
+                            let mut visualizer = shared_visualizer.lock().await;
+                            match change {
+                                BaseChange::Commit => visualizer
+                                    .commit_result()
+                                    .map(|base| (".".to_string(), Some(base))),
+                                BaseChange::Pop => visualizer.pop_base(),
+                            }
+                        };
+                        match changed {
+                            Some((query, base)) => {
+                                base_item = match base {
+                                    Some(values) => Some(
+                                        tokio::task::spawn_blocking(move || {
+                                            let lines = values
+                                                .iter()
+                                                .map(serde_json::to_string)
+                                                .collect::<Result<Vec<_>, _>>()?;
+                                            anyhow::Ok(Arc::<str>::from(lines.join("\n")))
+                                        })
+                                        .await??,
+                                    ),
+                                    None => None,
+                                };
+                                Ok(Some(query))
+                            }
+                            None => Err(text::State {
+                                text: match change {
+                                    BaseChange::Commit => "No result to commit, as the input is shown as is",
+                                    BaseChange::Pop => "No committed result to go back from",
+                                }
+                                .to_string(),
+                                style: StyleBuilder::new().fgc(Color::Yellow).build(),
+                            }),
+                        }
+                    }
+                    else => break,
+                };
+                match replaced {
+                    Ok(query) => {
+                        shared_result_paths.lock().await.clear();
+                        if let Some(loading) = loading_suggestions_task.lock().await.as_mut() {
+                            loading.abort();
+                            // A committed result is suggested from as is, being no longer slurped.
+                            let (mut provider, suggested) = match &base_item {
+                                Some(base_item) => {
+                                    let mut provider = reload_provider.clone();
+                                    provider.set_slurp(false);
+                                    (provider, base_item.clone())
+                                }
+                                None => (reload_provider.clone(), item.clone()),
+                            };
+                            *loading = shared_editor.read().await.searcher().spawn_load_task(
+                                &mut provider,
+                                suggested,
+                                search_load_chunk_size,
+                                search_load_throttle,
+                                ContextMonitor::new(ctx.clone()),
+                            );
+                        }
+                        let query = match query {
+                            Some(query) => {
+                                base_query_tx.send(query.clone()).await?;
+                                query
+                            }
+                            None => shared_editor.read().await.text().to_string(),
+                        };
+                        // Told by the viewer once the query is applied again.
+                        reload_query_tx.send(query).await?;
+                    }
+                    Err(guide) => {
+                        let size = viewport.area();
+                        shared_renderer.lock().await.update_and_draw([(
+                            PaneIndex::Guide,
+                            guide.create_pane(size.0, size.1),
+                        )])?;
+                    }
+                }
             }
             Ok(())
         })
     };
 
+    // Profiles the values shown in the guide, in the background as they may be many,
+    // and hides the profile (giving up on it if still running) once asked again.
+    let profiling: JoinHandle<anyhow::Result<()>> = {
+        let viewport = viewport.clone();
+        let shared_visualizer = shared_visualizer.clone();
+        let shared_renderer = shared_renderer.clone();
+        tokio::spawn(async move {
+            let mut shown = false;
+            let mut running: Option<(Arc<AtomicBool>, JoinHandle<anyhow::Result<()>>)> = None;
+            while let Some(()) = stats_rx.recv().await {
+                if let Some((cancel, task)) = running.take() {
+                    cancel.store(true, Ordering::Relaxed);
+                    task.abort();
+                }
+                if shown {
+                    shown = false;
+                    shared_renderer
+                        .lock()
+                        .await
+                        .update_and_draw([(PaneIndex::Guide, empty_pane())])?;
+                    continue;
+                }
+                shown = true;
+                let values = shared_visualizer.lock().await.shown_values();
+                let size = viewport.area();
+                shared_renderer.lock().await.update_and_draw([(
+                    PaneIndex::Guide,
+                    text::State {
+                        text: format!("Profiling {} value(s)…", format_count(values.len())),
+                        style: StyleBuilder::new().fgc(Color::Grey).build(),
+                    }
+                    .create_pane(size.0, size.1),
+                )])?;
+                let cancel = Arc::new(AtomicBool::new(false));
+                let task = {
+                    let cancel = cancel.clone();
+                    let shared_renderer = shared_renderer.clone();
+                    tokio::spawn(async move {
+                        let profile =
+                            tokio::task::spawn_blocking(move || Profile::new(&values, &cancel))
+                                .await?;
+                        if let Some(profile) = profile {
+                            shared_renderer.lock().await.update_and_draw([(
+                                PaneIndex::Guide,
+                                profile.create_pane(size.0),
+                            )])?;
+                        }
+                        Ok(())
+                    })
+                };
+                running = Some((cancel, task));
+            }
+            Ok(())
+        })
+    };
+
+    let processor_monitor = ContextMonitor::new(ctx.clone());
     let processor_task: JoinHandle<anyhow::Result<()>> = {
         let shared_renderer = shared_renderer.clone();
+        let clipboard = clipboard.clone();
         let shared_editor = shared_editor.clone();
-        let visualizer = initializing.await?;
-        let shared_visualizer = Arc::new(Mutex::new(visualizer));
+        let shared_visualizer = shared_visualizer.clone();
         tokio::spawn(async move {
+            if !initial_query.is_empty() {
+                processor
+                    .render_result(
+                        shared_visualizer.clone(),
+                        initial_query,
+                        shared_renderer.clone(),
+                    )
+                    .await;
+            }
+            let mut deadline = None;
             loop {
                 tokio::select! {
-                    Some(()) = processor_copy_rx.recv() => {
-                        let visualizer = shared_visualizer.lock().await;
-                        let guide = copy_to_clipboard(&visualizer.content_to_copy().await);
-                        let size = terminal::size()?;
-                        let pane = guide.create_pane(size.0, size.1);
+                    _ = sleep_until(deadline), if deadline.is_some() => {
+                        let ((guide_pane, pane), status, text_to_copy) = {
+                            let area = processor.area().await;
+                            let mut visualizer = shared_visualizer.lock().await;
+                            let panes = visualizer.create_panes_on_sequence_timeout(area).await;
+                            deadline = visualizer.sequence_deadline();
+                            if let Some(query) = visualizer.take_query_to_edit() {
+                                query_to_edit_tx.send(query).await?;
+                            }
+                            (panes, visualizer.create_status_pane(area.0), visualizer.take_text_to_copy())
+                        };
                         {
-                            shared_renderer.lock().await.update_and_draw([
-                                (PaneIndex::Guide, pane),
+                            let mut renderer = shared_renderer.lock().await;
+                            if let Some(guide_pane) = guide_pane {
+                                renderer.update_and_draw([(PaneIndex::Guide, guide_pane)])?;
+                            }
+                            renderer.update_and_draw([
+                                (PaneIndex::Processor, pane),
+                                (PaneIndex::ProcessorStatus, status),
                             ])?;
                         }
+                        if let Some(text) = text_to_copy {
+                            copy_selection(&clipboard, text, &processor_monitor, &viewport, &shared_renderer).await?;
+                        }
+                    }
+                    Some(()) = processor_copy_rx.recv() => {
+                        let content = {
+                            let visualizer = shared_visualizer.lock().await;
+                            visualizer.content_to_copy().await
+                        };
+                        copy_and_report(&clipboard, content, None, &viewport, &shared_renderer).await?;
+                    }
+                    Some(()) = show_all_rx.recv() => {
+                        let query = shared_editor.read().await.text().to_string();
+                        shared_visualizer.lock().await.show_all_results(query.clone());
+                        unlimited_query_tx.send(query).await?;
                     }
                     Some(event) = processor_event_rx.recv() => {
-                        let pane = {
+                        let mut events = vec![event];
+                        if !viewer_coalesce_duration.is_zero() {
+                            let window = tokio::time::sleep(viewer_coalesce_duration);
+                            tokio::pin!(window);
+                            loop {
+                                tokio::select! {
+                                    Some(event) = processor_event_rx.recv() => events.push(event),
+                                    _ = &mut window => break,
+                                }
+                            }
+                        }
+                        while let Ok(event) = processor_event_rx.try_recv() {
+                            events.push(event);
+                        }
+                        let ((guide_pane, pane), status, text_to_copy) = {
+                            let area = processor.area().await;
                             let mut visualizer = shared_visualizer.lock().await;
-                            visualizer.create_pane_from_event((size.0, size.1), &event).await
+                            let panes = visualizer.create_panes_from_events(area, &events).await;
+                            deadline = visualizer.sequence_deadline();
+                            if let Some(query) = visualizer.take_query_to_edit() {
+                                query_to_edit_tx.send(query).await?;
+                            }
+                            (panes, visualizer.create_status_pane(area.0), visualizer.take_text_to_copy())
                         };
                         {
-                            shared_renderer.lock().await.update_and_draw([
+                            let mut renderer = shared_renderer.lock().await;
+                            if let Some(guide_pane) = guide_pane {
+                                renderer.update_and_draw([(PaneIndex::Guide, guide_pane)])?;
+                            }
+                            renderer.update_and_draw([
                                 (PaneIndex::Processor, pane),
+                                (PaneIndex::ProcessorStatus, status),
                             ])?;
                         }
+                        if let Some(text) = text_to_copy {
+                            copy_selection(&clipboard, text, &processor_monitor, &viewport, &shared_renderer).await?;
+                        }
+                    }
+                    Some(()) = processor_reset_rx.recv() => {
+                        processor.render_reset(
+                            shared_visualizer.clone(),
+                            shared_renderer.clone(),
+                        ).await;
+                        deadline = None;
                     }
                     Some(query) = last_query_rx.recv() => {
                         processor.render_result(
@@ -369,24 +1520,10 @@ pub async fn run<T: ViewProvider + SearchProvider>(
                         ).await;
                     }
                     Some(area) = last_resize_rx.recv() => {
-                        let (editor_pane, guide_pane, searcher_pane) = {
-                            let editor = shared_editor.read().await;
-                            (
-                                editor.create_editor_pane(size.0, size.1),
-                                editor.create_guide_pane(size.0, size.1),
-                                editor.create_searcher_pane(size.0, size.1),
-                            )
-                        };
-                        {
-                            shared_renderer.lock().await.update_and_draw([
-                                (PaneIndex::Editor, editor_pane),
-                                (PaneIndex::Guide, guide_pane),
-                                (PaneIndex::Search, searcher_pane),
-                            ])?;
-                        }
+                        editor_relayout_tx.send(area).await?;
                         let text = {
                             let editor = shared_editor.read().await;
-                            editor.text()
+                            editor.text().to_string()
                         };
                         processor.render_on_resize(
                             shared_visualizer.clone(),
@@ -404,17 +1541,145 @@ pub async fn run<T: ViewProvider + SearchProvider>(
         })
     };
 
-    main_task.await??;
+    let result = main_task.await;
 
-    loading_suggestions_task.abort();
+    // Restore the terminal first, so that quitting feels immediate
+    // whatever the tasks below are in the middle of.
+    shared_renderer.lock().await.close();
+    restore_terminal()?;
+
+    if let Some(loading) = loading_suggestions_task.lock().await.as_ref() {
+        loading.abort();
+    }
+    reloading.abort();
+    profiling.abort();
+    stepping_index.abort();
+    indexing_progress.abort();
     spinning.abort();
     query_debouncer.abort();
     resize_debouncer.abort();
     editor_task.abort();
     processor_task.abort();
+    if let Some(following) = following {
+        following.abort();
+    }
+    if let Some(watch_debouncer) = watch_debouncer {
+        watch_debouncer.abort();
+    }
+    if let Some(indexing_results) = indexing_results {
+        indexing_results.abort();
+    }
 
-    execute!(io::stdout(), cursor::Show)?;
-    disable_raw_mode()?;
+    if let Err(e) = shared_editor.write().await.save_history(SAVE_TIMEOUT).await {
+        eprintln!("Failed to save the history: {}", e);
+    }
 
-    Ok(())
+    let accepted = result??;
+    let query = shared_editor.read().await.text().to_string();
+    let visualizer = shared_visualizer.lock().await;
+    let output = match output_mode {
+        Some(mode) if accepted => Some(match mode {
+            OutputMode::Query => query.clone(),
+            OutputMode::Result => visualizer.content_to_copy().await,
+            OutputMode::Both => format!("{}\n{}", query, visualizer.content_to_copy().await),
+        }),
+        _ => None,
+    };
+    let outcome = Outcome {
+        values: visualizer.values(),
+        query,
+        accepted,
+    };
+    Ok((outcome, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyCode;
+
+    use super::*;
+    use crate::editor::tests::{editor, key};
+
+    #[test]
+    fn terminal_is_restored_once() {
+        let terminal = TerminalState::new();
+        for flag in [
+            &terminal.raw_mode,
+            &terminal.alt_screen,
+            &terminal.mouse_capture,
+            &terminal.cursor_hidden,
+        ] {
+            flag.store(true, Ordering::SeqCst);
+        }
+        let mut raw_mode_disabled = 0;
+        let mut restore = || {
+            let mut out = Vec::new();
+            terminal
+                .restore(&mut out, || {
+                    raw_mode_disabled += 1;
+                    Ok(())
+                })
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let restored = restore();
+        for (sequence, what) in [
+            ("\x1b[?25h", "cursor shown"),
+            ("\x1b[?1000l", "mouse released"),
+            ("\x1b[?1049l", "alternate screen left"),
+            ("\x1b[?2004l", "bracketed paste disabled"),
+        ] {
+            assert!(restored.contains(sequence), "{}: {:?}", what, restored);
+        }
+        // Restoring again, e.g. from the panic hook after `run`.
+        assert_eq!(restore(), "");
+        assert_eq!(raw_mode_disabled, 1);
+    }
+
+    #[test]
+    fn terminal_is_restored_as_far_as_it_is_set_up() {
+        let terminal = TerminalState::new();
+        terminal.cursor_hidden.store(true, Ordering::SeqCst);
+        let mut out = Vec::new();
+        terminal
+            .restore(&mut out, || panic!("raw mode is not enabled"))
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b[?25h");
+    }
+
+    /// Returns the number of the rows of each pane, checking that they fit the width.
+    fn rows_fitting(panes: &[(PaneIndex, Pane)], width: u16) -> Vec<(PaneIndex, usize)> {
+        panes
+            .iter()
+            .map(|(index, pane)| {
+                let rows = pane.extract(usize::MAX);
+                for row in &rows {
+                    assert!(row.widths() <= width as usize, "{:?}: {}", index, row);
+                }
+                (*index, rows.len())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn relayout_fits_every_pane_of_the_editor_to_the_new_width() {
+        let mut editor = editor();
+        // Suggesting the jq functions, which shows a guide too.
+        for ch in "select(.name | ascii_down".chars() {
+            editor.operate(&key(KeyCode::Char(ch))).await.unwrap();
+        }
+        editor.operate(&key(KeyCode::Tab)).await.unwrap();
+
+        let wide = rows_fitting(&editor_panes(&editor, (80, 24)), 80);
+        let narrow = rows_fitting(&editor_panes(&editor, (12, 24)), 12);
+        assert_eq!(
+            wide.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+            [PaneIndex::Editor, PaneIndex::Guide, PaneIndex::Search]
+        );
+        for ((index, wide), (_, narrow)) in wide.iter().zip(&narrow) {
+            assert!(*wide > 0, "{:?}", index);
+            assert!(narrow > wide, "{:?}: {} rows, then {}", index, wide, narrow);
+        }
+    }
 }
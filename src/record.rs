@@ -0,0 +1,182 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{Event, KeyEvent, KeyEventKind, MouseEvent};
+use futures::{Stream, StreamExt};
+use promkit::serde_json;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::event::{KeyEventDef, MouseEventDef},
+    safe::SafeMode,
+    state,
+};
+
+/// Source of terminal events driving the prompt.
+pub type EventSource = Pin<Box<dyn Stream<Item = io::Result<Event>> + Send>>;
+
+/// Recorded form of an input event.
+///
+/// Pasted text is redacted down to its length so that
+/// recordings never contain anything but the keys typed by the user.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    Key(KeyEventDef),
+    Mouse {
+        def: MouseEventDef,
+        column: u16,
+        row: u16,
+    },
+    Resize(u16, u16),
+    Paste {
+        len: usize,
+    },
+    FocusGained,
+    FocusLost,
+}
+
+impl RecordedEvent {
+    fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                Some(Self::Key(KeyEventDef::from(key)))
+            }
+            Event::Key(_) => None,
+            Event::Mouse(mouse) => Some(Self::Mouse {
+                def: MouseEventDef::from(mouse),
+                column: mouse.column,
+                row: mouse.row,
+            }),
+            Event::Resize(width, height) => Some(Self::Resize(*width, *height)),
+            Event::Paste(text) => Some(Self::Paste {
+                len: text.chars().count(),
+            }),
+            Event::FocusGained => Some(Self::FocusGained),
+            Event::FocusLost => Some(Self::FocusLost),
+        }
+    }
+
+    fn to_event(&self) -> Event {
+        match self {
+            Self::Key(def) => Event::Key(KeyEvent::from(def)),
+            Self::Mouse { def, column, row } => Event::Mouse(MouseEvent {
+                kind: def.kind,
+                column: *column,
+                row: *row,
+                modifiers: def.modifiers,
+            }),
+            Self::Resize(width, height) => Event::Resize(*width, *height),
+            Self::Paste { len } => Event::Paste("_".repeat(*len)),
+            Self::FocusGained => Event::FocusGained,
+            Self::FocusLost => Event::FocusLost,
+        }
+    }
+}
+
+/// A line of the recording file.
+#[derive(Serialize, Deserialize)]
+enum Entry {
+    /// Metadata of the input, written once at the top of the file.
+    Header {
+        version: String,
+        input_len: usize,
+        input_hash: String,
+    },
+    /// An event with the elapsed milliseconds since the session started.
+    Event { ms: u64, event: RecordedEvent },
+}
+
+/// Wraps the event source so that every event passing through is appended to `path`.
+pub fn record<P: AsRef<Path>>(
+    source: EventSource,
+    path: P,
+    input: &str,
+    safe_mode: SafeMode,
+) -> anyhow::Result<EventSource> {
+    let mut writer = BufWriter::new(safe_mode.create_file(path.as_ref())?);
+    serde_json::to_writer(
+        &mut writer,
+        &Entry::Header {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            input_len: input.len(),
+            input_hash: state::hash(input.as_bytes()),
+        },
+    )?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    let writer = Arc::new(Mutex::new(writer));
+    let started = Instant::now();
+    Ok(Box::pin(source.inspect(move |event| {
+        let Some(event) = event.as_ref().ok().and_then(RecordedEvent::from_event) else {
+            return;
+        };
+        let entry = Entry::Event {
+            ms: started.elapsed().as_millis() as u64,
+            event,
+        };
+        // Flush eagerly so that the recording survives a crash.
+        if let Ok(mut writer) = writer.lock() {
+            let _ = serde_json::to_writer(&mut *writer, &entry)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(writer))
+                .and_then(|_| writer.flush());
+        }
+    })))
+}
+
+/// Creates an event source replaying the recording at `path`,
+/// followed by the given source once all recorded events are consumed.
+///
+/// `speed` scales the recorded timing, e.g. 2.0 replays twice as fast
+/// and 0 replays without any delays.
+/// Returns a warning when the recording was made against a different input.
+pub fn replay<P: AsRef<Path>>(
+    path: P,
+    speed: f64,
+    input: &str,
+    then: EventSource,
+) -> anyhow::Result<(EventSource, Option<String>)> {
+    let mut warning = None;
+    let mut events = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Entry>(&line)? {
+            Entry::Header {
+                input_len,
+                input_hash,
+                ..
+            } => {
+                if input_len != input.len() || input_hash != state::hash(input.as_bytes()) {
+                    warning = Some(
+                        "The recording was made against a different input; replaying anyway"
+                            .to_string(),
+                    );
+                }
+            }
+            Entry::Event { ms, event } => events.push((ms, event)),
+        }
+    }
+
+    let started = Instant::now();
+    let replaying = futures::stream::iter(events).then(move |(ms, event)| async move {
+        if speed > 0. {
+            let at = Duration::from_millis(ms).div_f64(speed);
+            if let Some(wait) = at.checked_sub(started.elapsed()) {
+                tokio::time::sleep(wait).await;
+            }
+        }
+        Ok(event.to_event())
+    });
+
+    Ok((Box::pin(replaying.chain(then)), warning))
+}
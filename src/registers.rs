@@ -0,0 +1,78 @@
+//! Filters stored in numbered registers (1 to 9) to switch between them by keybind.
+//!
+//! With `registers.persist`, they are kept across sessions as JSON,
+//! in `jnv/registers.json` under the user's configuration directory by default.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use promkit::serde_json;
+
+use crate::{
+    config::RegistersConfig,
+    safe::{self, SafeMode},
+};
+
+pub struct Registers {
+    filters: BTreeMap<u32, String>,
+    /// File the registers are saved to as they are set, if they persist.
+    path: Option<PathBuf>,
+    safe_mode: SafeMode,
+}
+
+impl Registers {
+    /// Returns the registers, loaded from their file if they persist
+    /// (and empty if it cannot be read).
+    pub fn new(config: &RegistersConfig, safe_mode: SafeMode) -> Self {
+        let path = match &config.path {
+            Some(path) => Some(path.clone()),
+            None => dirs::config_dir().map(|dir| dir.join("jnv").join("registers.json")),
+        }
+        .filter(|_| config.persist);
+        let filters = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            filters,
+            path,
+            safe_mode,
+        }
+    }
+
+    pub fn get(&self, register: u32) -> Option<&str> {
+        self.filters.get(&register).map(String::as_str)
+    }
+
+    /// Stores the filter in the register, saving the registers if they persist.
+    ///
+    /// Nothing is saved in safe mode.
+    pub fn set(&mut self, register: u32, filter: &str) -> anyhow::Result<()> {
+        self.filters.insert(register, filter.to_string());
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let result = self.safe_mode.replace_file(path, |file| {
+            serde_json::to_writer_pretty(file, &self.filters)?;
+            Ok(())
+        });
+        match result {
+            Err(e) if safe::is_disabled(&e) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Describes the registers set, e.g. "1 `.items` · 3 `.[0].name`".
+    pub fn describe(&self) -> Option<String> {
+        if self.filters.is_empty() {
+            return None;
+        }
+        Some(
+            self.filters
+                .iter()
+                .map(|(register, filter)| format!("{} `{}`", register, filter))
+                .collect::<Vec<_>>()
+                .join(" · "),
+        )
+    }
+}
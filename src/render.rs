@@ -1,36 +1,171 @@
-use std::sync::LazyLock;
+use std::{
+    io::Write,
+    sync::{Arc, RwLock},
+};
 
-use crossterm::{self, cursor};
-use promkit::{pane::Pane, terminal::Terminal};
+use crossterm::{
+    self, cursor, style,
+    terminal::{self, ClearType},
+};
+use promkit::{grapheme::StyledGraphemes, pane::Pane};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PaneIndex {
     Editor = 0,
     Guide = 1,
     ProcessorGuide = 2,
     Search = 3,
     Processor = 4,
+    /// Line below the result, kept on screen however long the result is.
+    ProcessorStatus = 5,
+    Footer = 6,
+    /// Overlay shown in place of every other pane while it is not empty.
+    Help = 7,
 }
 
-pub static EMPTY_PANE: LazyLock<Pane> = LazyLock::new(|| Pane::new(vec![], 0));
-const PANE_SIZE: usize = PaneIndex::Processor as usize + 1;
+/// Returns a pane with no rows, hiding its slot (does not allocate).
+pub fn empty_pane() -> Pane {
+    Pane::new(Vec::new(), 0)
+}
+
+const PANE_SIZE: usize = PaneIndex::Help as usize + 1;
+
+const PANE_INDICES: [PaneIndex; PANE_SIZE] = [
+    PaneIndex::Editor,
+    PaneIndex::Guide,
+    PaneIndex::ProcessorGuide,
+    PaneIndex::Search,
+    PaneIndex::Processor,
+    PaneIndex::ProcessorStatus,
+    PaneIndex::Footer,
+    PaneIndex::Help,
+];
+
+/// Placement of the content within a terminal wider than `max_content_width`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+}
+
+/// Where the panes are drawn, and their width on wide terminals.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    /// Draw on the alternate screen from its top, so that the content
+    /// of the terminal is left as it was once leaving,
+    /// instead of below the cursor.
+    pub alt_screen: bool,
+    /// Maximum number of columns used by the panes (unlimited if unset).
+    pub max_content_width: Option<u16>,
+    pub align: Align,
+    /// Show the input beside the result of the filter at first.
+    pub split: bool,
+}
+
+impl Layout {
+    /// Caps the width of the given terminal size.
+    pub fn area(&self, (width, height): (u16, u16)) -> (u16, u16) {
+        (self.content_width(width), height)
+    }
+
+    fn content_width(&self, terminal_width: u16) -> u16 {
+        match self.max_content_width {
+            Some(max) => terminal_width.min(max.max(1)),
+            None => terminal_width,
+        }
+    }
+
+    /// Returns the column at which the content starts.
+    fn offset(&self, terminal_width: u16) -> u16 {
+        match self.align {
+            Align::Left => 0,
+            Align::Center => (terminal_width - self.content_width(terminal_width)) / 2,
+        }
+    }
+}
+
+/// Where the frames are written, the terminal (standard output) by default.
+pub type Screen = Box<dyn Write + Send>;
+
+/// Size of the screen shared by the tasks, told by the resize events
+/// rather than asked to the terminal, which may not be the screen.
+#[derive(Clone)]
+pub struct Viewport {
+    layout: Layout,
+    screen_size: Arc<RwLock<(u16, u16)>>,
+}
+
+impl Viewport {
+    pub fn new(layout: Layout, screen_size: (u16, u16)) -> Self {
+        Self {
+            layout,
+            screen_size: Arc::new(RwLock::new(screen_size)),
+        }
+    }
+
+    /// Returns the size of the screen.
+    pub fn screen_size(&self) -> (u16, u16) {
+        *self.screen_size.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the size available to the panes,
+    /// i.e. the screen size with the width capped by `max_content_width`.
+    pub fn area(&self) -> (u16, u16) {
+        self.layout.area(self.screen_size())
+    }
+
+    /// Records the new size of the screen, returning the area of the panes.
+    pub fn resize(&self, screen_size: (u16, u16)) -> (u16, u16) {
+        *self.screen_size.write().unwrap_or_else(|e| e.into_inner()) = screen_size;
+        self.layout.area(screen_size)
+    }
+}
 
 pub struct Renderer {
+    screen: Screen,
     no_hint: bool,
-    terminal: Terminal,
+    viewport: Viewport,
+    /// Position where the first pane starts.
+    position: (u16, u16),
     panes: [Pane; PANE_SIZE],
+    /// Panes on screen with their number of rows, from the top.
+    extents: Vec<(PaneIndex, usize)>,
+    /// Last drawn frame, to skip writing one identical to what is on screen.
+    last_frame: Option<Frame>,
+    /// Set once the terminal is restored, after which nothing is drawn anymore.
+    closed: bool,
+}
+
+struct Frame {
+    terminal_size: (u16, u16),
+    position: (u16, u16),
+    rows: Vec<StyledGraphemes>,
 }
 
 impl Renderer {
-    pub fn try_init_draw(init_panes: [Pane; PANE_SIZE], no_hint: bool) -> anyhow::Result<Self> {
+    /// Draws the panes from `position`, where the cursor of the screen is.
+    pub fn try_init_draw(
+        init_panes: [Pane; PANE_SIZE],
+        viewport: Viewport,
+        position: (u16, u16),
+        no_hint: bool,
+        screen: Screen,
+    ) -> anyhow::Result<Self> {
         let mut ret = Self {
+            screen,
             no_hint,
-            terminal: Terminal {
-                position: cursor::position()?,
-            },
+            viewport,
+            position,
             panes: init_panes,
+            extents: Vec::new(),
+            last_frame: None,
+            closed: false,
         };
-        ret.terminal.draw(&ret.panes)?;
+        ret.draw()?;
         Ok(ret)
     }
 
@@ -39,12 +174,207 @@ impl Renderer {
         iter: I,
     ) -> anyhow::Result<()> {
         for (index, pane) in iter {
-            if self.no_hint && (index == PaneIndex::Guide || index == PaneIndex::ProcessorGuide) {
+            if self.no_hint
+                && matches!(
+                    index,
+                    PaneIndex::Guide | PaneIndex::ProcessorGuide | PaneIndex::Footer
+                )
+            {
                 continue;
             }
             self.panes[index as usize] = pane;
         }
-        self.terminal.draw(&self.panes)?;
+        self.draw()
+    }
+
+    /// Draws every pane again, e.g. once the terminal is given back by another program.
+    pub fn redraw(&mut self) -> anyhow::Result<()> {
+        self.last_frame = None;
+        self.draw()
+    }
+
+    /// Stops drawing, so that the tasks still running cannot write
+    /// to the terminal once it is restored.
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    /// Returns the pane drawn at the given row of the terminal, if any.
+    pub fn pane_at(&self, row: u16) -> Option<PaneIndex> {
+        let mut offset = row.checked_sub(self.position.1)? as usize;
+        for (index, len) in &self.extents {
+            if offset < *len {
+                return Some(*index);
+            }
+            offset -= len;
+        }
+        None
+    }
+
+    /// Draws the panes from top to bottom, as `promkit::terminal::Terminal` does,
+    /// starting every row at the column given by the layout.
+    fn draw(&mut self) -> anyhow::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        let (width, height) = self.viewport.screen_size();
+        let column = self.viewport.layout.offset(width);
+
+        let help = &self.panes[PaneIndex::Help as usize];
+        let viewable_panes = if help.is_empty() {
+            PANE_INDICES
+                .into_iter()
+                .zip(self.panes.iter())
+                .filter(|(_, pane)| !pane.is_empty())
+                .collect::<Vec<(PaneIndex, &Pane)>>()
+        } else {
+            vec![(PaneIndex::Help, help)]
+        };
+
+        if height < viewable_panes.len() as u16 {
+            self.last_frame = None;
+            self.extents.clear();
+            return crossterm::execute!(
+                self.screen,
+                terminal::Clear(ClearType::FromCursorDown),
+                style::Print("⚠️ Insufficient Space"),
+            )
+            .map_err(anyhow::Error::from);
+        }
+
+        let mut used = 0;
+        let mut rows = Vec::new();
+        self.extents.clear();
+        for (i, (index, pane)) in viewable_panes.iter().enumerate() {
+            let extracted = pane.extract(
+                1.max(
+                    (height as usize)
+                        // Leave a row for each of the following panes.
+                        .saturating_sub(used + viewable_panes.len() - 1 - i),
+                ),
+            );
+            used += extracted.len();
+            self.extents.push((*index, extracted.len()));
+            rows.extend(extracted);
+        }
+
+        if let Some(last) = &self.last_frame {
+            if last.terminal_size == (width, height)
+                && last.position == self.position
+                && last.rows == rows
+            {
+                return Ok(());
+            }
+        }
+
+        crossterm::queue!(
+            self.screen,
+            cursor::MoveTo(self.position.0, self.position.1),
+            terminal::Clear(ClearType::FromCursorDown),
+        )?;
+
+        let mut current_cursor_y = height.saturating_sub(self.position.1);
+        for (i, row) in rows.iter().enumerate() {
+            if column > 0 {
+                crossterm::queue!(self.screen, cursor::MoveToColumn(column))?;
+            }
+            crossterm::queue!(self.screen, style::Print(row.styled_display()))?;
+
+            current_cursor_y = current_cursor_y.saturating_sub(1);
+            let is_last_row = i == rows.len() - 1;
+            if !is_last_row && current_cursor_y == 0 {
+                crossterm::queue!(self.screen, terminal::ScrollUp(1))?;
+                self.position.1 = self.position.1.saturating_sub(1);
+            }
+
+            crossterm::queue!(self.screen, cursor::MoveToNextLine(1))?;
+        }
+        self.last_frame = Some(Frame {
+            terminal_size: (width, height),
+            position: self.position,
+            rows,
+        });
+        self.screen.flush()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{io, sync::Mutex};
+
+    use super::*;
+
+    fn layout(max_content_width: Option<u16>, align: Align) -> Layout {
+        Layout {
+            max_content_width,
+            align,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn caps_the_width() {
+        let capped = layout(Some(80), Align::Left);
+        assert_eq!(capped.area((60, 24)), (60, 24));
+        assert_eq!(capped.area((80, 24)), (80, 24));
+        assert_eq!(capped.area((100, 24)), (80, 24));
+        assert_eq!(layout(None, Align::Left).area((300, 24)), (300, 24));
+        // A column at least.
+        assert_eq!(layout(Some(0), Align::Left).area((100, 24)), (1, 24));
+    }
+
+    #[test]
+    fn centers_the_content() {
+        let centered = layout(Some(80), Align::Center);
+        assert_eq!(centered.offset(60), 0);
+        assert_eq!(centered.offset(80), 0);
+        assert_eq!(centered.offset(100), 10);
+        // The odd column is left on the right.
+        assert_eq!(centered.offset(101), 10);
+        assert_eq!(centered.offset(83), 1);
+        assert_eq!(layout(Some(80), Align::Left).offset(100), 0);
+        assert_eq!(layout(None, Align::Center).offset(100), 0);
+    }
+
+    #[derive(Clone, Default)]
+    struct Written(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Written {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Returns what is written to draw a row on the screen of the given width.
+    fn drawn(layout: Layout, width: u16) -> String {
+        let written = Written::default();
+        let mut panes: [Pane; PANE_SIZE] = std::array::from_fn(|_| empty_pane());
+        panes[PaneIndex::Editor as usize] = Pane::new(vec![StyledGraphemes::from("row")], 0);
+        Renderer::try_init_draw(
+            panes,
+            Viewport::new(layout, (width, 24)),
+            (0, 0),
+            false,
+            Box::new(written.clone()),
+        )
+        .unwrap();
+        let bytes = written.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn draws_the_rows_from_the_column_of_the_content() {
+        let centered = layout(Some(80), Align::Center);
+        // MoveToColumn is 1-based.
+        assert!(drawn(centered, 101).contains("\x1b[11G"));
+        assert!(!drawn(centered, 80).contains('G'));
+        assert!(!drawn(centered, 60).contains('G'));
+        assert!(!drawn(layout(Some(80), Align::Left), 101).contains('G'));
+    }
+}
@@ -0,0 +1,240 @@
+//! Safe mode, the guard every operation letting data leave the terminal goes through.
+//!
+//! Rather than checking for safe mode wherever such an operation happens,
+//! the operations are performed by [`SafeMode`] itself (writing files,
+//! spawning commands) or ask it for the [`Effect`] they have (the clipboard,
+//! the output), so that none is left out.
+
+use std::{
+    ffi::OsStr,
+    fmt, fs,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use tokio::process::Command;
+
+use crate::config::GlobalAction;
+
+/// Operation letting data leave the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    Clipboard,
+    Output,
+    WriteFile,
+    Spawn,
+}
+
+impl Effect {
+    /// Returns the effect of the action, if any.
+    pub fn of_action(action: GlobalAction) -> Option<Self> {
+        match action {
+            GlobalAction::CopyQuery | GlobalAction::CopyResult | GlobalAction::CopyCommand => {
+                Some(Effect::Clipboard)
+            }
+            GlobalAction::ExitWithOutput => Some(Effect::Output),
+            GlobalAction::ExternalEditor => Some(Effect::Spawn),
+            GlobalAction::Exit
+            | GlobalAction::SwitchMode
+            | GlobalAction::Reset
+            | GlobalAction::Help
+            | GlobalAction::Reload
+            | GlobalAction::ToggleSlurp
+            | GlobalAction::ShowAllResults
+            | GlobalAction::CommitResult
+            | GlobalAction::PopBase
+            | GlobalAction::ToggleStats => None,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Effect::Clipboard => "Copying to the clipboard",
+            Effect::Output => "Writing to standard output",
+            Effect::WriteFile => "Writing to a file",
+            Effect::Spawn => "Running an external command",
+        }
+    }
+}
+
+/// Error of an operation disabled in safe mode.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Disabled(pub Effect);
+
+impl fmt::Display for Disabled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is disabled in safe mode", self.0.describe())
+    }
+}
+
+impl std::error::Error for Disabled {}
+
+/// Guard of the operations letting data leave the terminal
+/// (clipboard, files, external commands and so on).
+///
+/// Once enabled, it stays enabled until jnv is relaunched.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SafeMode {
+    enabled: bool,
+}
+
+impl SafeMode {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Fails if the effect is disabled.
+    pub fn permit(&self, effect: Effect) -> Result<(), Disabled> {
+        if self.enabled {
+            return Err(Disabled(effect));
+        }
+        Ok(())
+    }
+
+    /// Fails if the action would let data leave the terminal.
+    pub fn check_action(&self, action: GlobalAction) -> Result<(), Disabled> {
+        match Effect::of_action(action) {
+            Some(effect) => self.permit(effect),
+            None => Ok(()),
+        }
+    }
+
+    /// Opens the file with the options, creating its parent directories.
+    pub fn open_file(&self, path: &Path, options: &fs::OpenOptions) -> anyhow::Result<fs::File> {
+        self.permit(Effect::WriteFile)?;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(options.open(path)?)
+    }
+
+    /// Creates the file (truncating it if it exists), creating its parent directories.
+    pub fn create_file(&self, path: &Path) -> anyhow::Result<fs::File> {
+        self.open_file(
+            path,
+            fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true),
+        )
+    }
+
+    /// Replaces the file atomically with what `write` writes,
+    /// through a temporary file beside it.
+    pub fn replace_file<F>(&self, path: &Path, write: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut BufWriter<fs::File>) -> anyhow::Result<()>,
+    {
+        let mut tmp = path.to_path_buf().into_os_string();
+        tmp.push(format!(".{}.tmp", std::process::id()));
+        {
+            let mut file = BufWriter::new(self.create_file(Path::new(&tmp))?);
+            write(&mut file)?;
+            file.flush()?;
+        }
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Returns the command running the program.
+    pub fn command<S: AsRef<OsStr>>(&self, program: S) -> Result<Command, Disabled> {
+        self.permit(Effect::Spawn)?;
+        Ok(Command::new(program))
+    }
+}
+
+/// Tells whether the error comes from an operation disabled in safe mode.
+pub fn is_disabled(error: &anyhow::Error) -> bool {
+    error.is::<Disabled>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACTIONS: &[GlobalAction] = &[
+        GlobalAction::Exit,
+        GlobalAction::ExitWithOutput,
+        GlobalAction::CopyQuery,
+        GlobalAction::CopyResult,
+        GlobalAction::CopyCommand,
+        GlobalAction::SwitchMode,
+        GlobalAction::Reset,
+        GlobalAction::Help,
+        GlobalAction::Reload,
+        GlobalAction::ToggleSlurp,
+        GlobalAction::ShowAllResults,
+        GlobalAction::CommitResult,
+        GlobalAction::PopBase,
+        GlobalAction::ToggleStats,
+        GlobalAction::ExternalEditor,
+    ];
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join(format!("jnv-safe-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn actions_with_effects_are_refused() {
+        let safe_mode = SafeMode::new(true);
+        for &action in ACTIONS {
+            let expected = match action {
+                GlobalAction::CopyQuery | GlobalAction::CopyResult | GlobalAction::CopyCommand => {
+                    Err(Disabled(Effect::Clipboard))
+                }
+                GlobalAction::ExitWithOutput => Err(Disabled(Effect::Output)),
+                GlobalAction::ExternalEditor => Err(Disabled(Effect::Spawn)),
+                _ => Ok(()),
+            };
+            assert_eq!(safe_mode.check_action(action), expected, "{:?}", action);
+            assert_eq!(SafeMode::new(false).check_action(action), Ok(()));
+        }
+    }
+
+    #[test]
+    fn files_are_not_written() {
+        let safe_mode = SafeMode::new(true);
+        let path = temp_path("refused/file");
+        let error = safe_mode.create_file(&path).unwrap_err();
+        assert!(is_disabled(&error));
+        assert_eq!(
+            error.to_string(),
+            "Writing to a file is disabled in safe mode"
+        );
+        let error = safe_mode
+            .replace_file(&path, |file| Ok(file.write_all(b"x")?))
+            .unwrap_err();
+        assert!(is_disabled(&error));
+        // Not even the directories are created.
+        assert!(!path.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn files_are_written_otherwise() {
+        let safe_mode = SafeMode::new(false);
+        let path = temp_path("allowed/file");
+        safe_mode
+            .replace_file(&path, |file| Ok(file.write_all(b"first")?))
+            .unwrap();
+        safe_mode
+            .replace_file(&path, |file| Ok(file.write_all(b"second")?))
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn commands_are_not_spawned() {
+        assert_eq!(
+            SafeMode::new(true).command("jq").err(),
+            Some(Disabled(Effect::Spawn))
+        );
+        assert!(SafeMode::new(false).command("jq").is_ok());
+    }
+}
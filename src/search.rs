@@ -1,101 +1,299 @@
-use std::{collections::BTreeSet, sync::Arc};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 
 use anyhow::anyhow;
 use async_trait::async_trait;
 use promkit::{
+    crossterm::style::Color,
     listbox::{self, Listbox},
     pane::Pane,
-    PaneFactory,
+    serde_json,
+    style::StyleBuilder,
+    text, PaneFactory,
 };
 use tokio::{
     sync::{Mutex, RwLock},
     task::JoinHandle,
 };
 
+use crate::{
+    builtins,
+    json::format_count,
+    paths::{self, PathEntry, PathIndex, PathMatching, PathOrder},
+    ContextMonitor,
+};
+
+/// Interval for polling the processor state while indexing is paused.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Flag set once dropped, e.g. when the task holding it is aborted.
+#[derive(Default)]
+struct StopOnDrop(Arc<AtomicBool>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Number of the latest results whose paths are kept for suggestions.
+const RESULT_PATHS_CAPACITY: usize = 4;
+
 #[async_trait]
 pub trait SearchProvider: Clone + Send + 'static {
     async fn provide(
         &mut self,
-        item: &str,
-    ) -> anyhow::Result<Box<dyn Iterator<Item = String> + Send>>;
+        item: Arc<str>,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = PathEntry> + Send>>;
 }
 
 #[derive(Clone, Default)]
 pub struct LoadState {
     pub loaded: bool,
     pub loaded_item_len: usize,
+    /// Whether the paths of the input are left out of the suggestions (--no-suggestions).
+    pub skipped: bool,
 }
 
 pub struct StartSearchResult {
     pub head_item: Option<String>,
+    /// Whether functions are suggested instead of paths.
+    pub functions: bool,
+    /// Whether the variables given on the command line are suggested instead of paths.
+    pub variables: bool,
+    /// Whether the paths come from the result of the filter before the last `|`.
+    pub from_result: bool,
     pub load_state: LoadState,
 }
 
+/// Paths of the results of the latest filters, by filter,
+/// to suggest the paths available after a `|`.
+pub struct ResultPaths {
+    order: PathOrder,
+    array_indices: usize,
+    entries: VecDeque<(String, PathIndex)>,
+}
+
+impl ResultPaths {
+    fn new(order: PathOrder, array_indices: usize) -> Self {
+        Self {
+            order,
+            array_indices,
+            entries: VecDeque::with_capacity(RESULT_PATHS_CAPACITY),
+        }
+    }
+
+    fn insert(&mut self, query: String, index: PathIndex) {
+        self.entries.retain(|(q, _)| *q != query);
+        if self.entries.len() == RESULT_PATHS_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((query, index));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn get(&self, query: &str) -> Option<&PathIndex> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(q, _)| q.trim() == query)
+            .map(|(_, index)| index)
+    }
+}
+
+/// Indexes the paths of the result of the query in chunks,
+/// adding them to the suggestions once all are indexed.
+pub fn spawn_result_load_task(
+    shared_result_paths: Arc<Mutex<ResultPaths>>,
+    query: String,
+    values: Arc<Vec<serde_json::Value>>,
+    chunk_size: usize,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let (order, array_indices) = {
+            let result_paths = shared_result_paths.lock().await;
+            (result_paths.order, result_paths.array_indices)
+        };
+        let mut index = PathIndex::new(order, array_indices);
+        for (i, entry) in paths::get_all_paths(values.iter()).enumerate() {
+            index.insert(entry);
+            // Let the task be aborted by a newer result between chunks.
+            if (i + 1) % chunk_size.max(1) == 0 {
+                tokio::task::yield_now().await;
+            }
+        }
+        shared_result_paths.lock().await.insert(query, index);
+    })
+}
+
 pub struct IncrementalSearcher {
-    shared_set: Arc<Mutex<BTreeSet<String>>>,
+    shared_index: Arc<Mutex<PathIndex>>,
+    shared_result_paths: Arc<Mutex<ResultPaths>>,
     shared_load_state: Arc<RwLock<LoadState>>,
+    /// Number of the loads gone to the end, to tell one is over even if it was never seen going on.
+    loads_completed: Arc<AtomicUsize>,
     state: listbox::State,
     search_result_chunk_size: usize,
     search_chunk_remaining: Vec<String>,
+    matching: PathMatching,
+    /// Match the suggestions regardless of the case of the letters.
+    case_insensitive: bool,
+    /// Variables bound in the filters (e.g. `$env`), suggested after a `$`.
+    variables: Vec<String>,
+    /// Filter before the function (or the path applied to a result) being completed,
+    /// which the suggestions replace alone.
+    head: Option<String>,
+    /// Filter as it was before the completion, to restore it on cancel.
+    original: Option<String>,
+    /// Whether the selected suggestion is not in the filter yet,
+    /// as after taking a segment, so that the next move puts it there.
+    pending: bool,
+}
+
+/// How the paths are indexed and suggested.
+pub struct SearchSettings {
+    /// Number of the suggestions listed at once, the others as the list is scrolled.
+    pub result_chunk_size: usize,
+    /// Number of the paths indexed between pauses.
+    pub load_chunk_size: usize,
+    /// Pause between the chunks of paths indexed.
+    pub load_throttle: Duration,
+    pub order: PathOrder,
+    pub matching: PathMatching,
+    /// Match the suggestions regardless of the case of the letters.
+    pub case_insensitive: bool,
+    /// Number of the first elements of each array suggested with their index.
+    pub array_indices: usize,
+    /// Suggest the paths of the result of the filter before the last `|`.
+    pub result_paths: bool,
+    /// Index the paths of the input, which are suggested otherwise.
+    pub index_input: bool,
+    /// Variables bound in the filters, suggested after a `$`.
+    pub variables: Vec<String>,
 }
 
 impl IncrementalSearcher {
-    pub fn new(state: listbox::State, search_result_chunk_size: usize) -> Self {
+    pub fn new(state: listbox::State, settings: &SearchSettings) -> Self {
         Self {
-            shared_set: Default::default(),
-            shared_load_state: Default::default(),
+            shared_index: Arc::new(Mutex::new(PathIndex::new(
+                settings.order,
+                settings.array_indices,
+            ))),
+            shared_result_paths: Arc::new(Mutex::new(ResultPaths::new(
+                settings.order,
+                settings.array_indices,
+            ))),
+            shared_load_state: Arc::new(RwLock::new(LoadState {
+                loaded: !settings.index_input,
+                skipped: !settings.index_input,
+                ..Default::default()
+            })),
+            loads_completed: Default::default(),
             state,
-            search_result_chunk_size,
+            search_result_chunk_size: settings.result_chunk_size,
             search_chunk_remaining: Default::default(),
+            matching: settings.matching,
+            case_insensitive: settings.case_insensitive,
+            variables: settings.variables.clone(),
+            head: None,
+            original: None,
+            pending: false,
         }
     }
 
     pub fn spawn_load_task<T: SearchProvider>(
         &self,
         provider: &mut T,
-        item: &'static str,
+        item: Arc<str>,
         chunk_size: usize,
+        load_throttle: Duration,
+        context_monitor: ContextMonitor,
     ) -> JoinHandle<anyhow::Result<()>> {
-        let shared_set = self.shared_set.clone();
+        let shared_index = self.shared_index.clone();
         let shared_load_state = self.shared_load_state.clone();
+        let loads_completed = self.loads_completed.clone();
         let mut provider = provider.clone();
         tokio::spawn(async move {
-            let mut batch = Vec::with_capacity(chunk_size);
-            let iter = provider.provide(item).await?;
+            // Start over, as when the input is read again.
+            shared_index.lock().await.clear();
+            *shared_load_state.write().await = LoadState::default();
 
-            for v in iter {
-                batch.push(v);
-
-                if batch.len() >= chunk_size {
-                    let mut set = shared_set.lock().await;
-                    for item in batch.drain(..) {
-                        set.insert(item);
+            let iter = provider.provide(item).await?;
+            // The paths are walked and indexed on a blocking thread,
+            // which aborting this task stops through `stop`.
+            let stop = StopOnDrop::default();
+            let stopped = stop.0.clone();
+            tokio::task::spawn_blocking(move || {
+                let mut iter = iter.peekable();
+                while iter.peek().is_some() {
+                    // Walked before locking, so that the suggestions wait on the inserts only.
+                    let batch = iter.by_ref().take(chunk_size.max(1)).collect::<Vec<_>>();
+                    let len = batch.len();
+                    {
+                        let mut index = shared_index.blocking_lock();
+                        for item in batch {
+                            index.insert(item);
+                        }
+                    }
+                    shared_load_state.blocking_write().loaded_item_len += len;
+                    if iter.peek().is_none() {
+                        break;
                     }
-                    let mut state = shared_load_state.write().await;
-                    state.loaded_item_len += chunk_size;
-                }
-            }
 
-            let remaining = batch.len();
-            if !batch.is_empty() {
-                let mut set = shared_set.lock().await;
-                for item in batch {
-                    set.insert(item);
+                    // Trickle along at a low priority,
+                    // and back off entirely while a query is executing.
+                    if !load_throttle.is_zero() {
+                        thread::sleep(load_throttle);
+                    }
+                    while !context_monitor.is_idle_blocking() {
+                        if stopped.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        thread::sleep(PAUSE_POLL_INTERVAL);
+                    }
+                    if stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
                 }
-            }
-
-            let mut state = shared_load_state.write().await;
-            state.loaded = true;
-            state.loaded_item_len += remaining;
+                shared_load_state.blocking_write().loaded = true;
+                loads_completed.fetch_add(1, Ordering::Relaxed);
+            })
+            .await?;
             Ok(())
         })
     }
 
+    /// Returns the index of the suggestions, to add the paths of appended documents.
+    pub fn shared_index(&self) -> Arc<Mutex<PathIndex>> {
+        self.shared_index.clone()
+    }
+
+    /// Returns the paths of the latest results, to add those of new results.
+    pub fn shared_result_paths(&self) -> Arc<Mutex<ResultPaths>> {
+        self.shared_result_paths.clone()
+    }
+
     pub fn up(&mut self) {
+        if std::mem::take(&mut self.pending) {
+            return;
+        }
         self.state.listbox.backward();
     }
 
     pub fn down_with_load(&mut self) {
+        if std::mem::take(&mut self.pending) {
+            return;
+        }
         self.state.listbox.forward();
         if self
             .state
@@ -108,33 +306,126 @@ impl IncrementalSearcher {
         }
     }
 
+    /// Returns the filter completed with the selected suggestion.
     pub fn get_current_item(&self) -> String {
-        self.state.listbox.get().to_string()
+        match &self.head {
+            Some(head) => format!("{}{}", head, self.state.listbox.get()),
+            None => self.state.listbox.get().to_string(),
+        }
+    }
+
+    /// Returns whether the paths of the input are still being indexed.
+    pub fn is_loading(&self) -> bool {
+        self.shared_load_state
+            .try_read()
+            .is_ok_and(|state| !state.loaded)
+    }
+
+    /// Returns how many times the paths of the input were indexed to the end.
+    pub fn loads_completed(&self) -> usize {
+        self.loads_completed.load(Ordering::Relaxed)
     }
 
+    /// Shows the suggestions, or how many paths are indexed so far while none are shown.
     pub fn create_pane(&self, width: u16, height: u16) -> Pane {
+        if !self.is_searching() {
+            if let Ok(state) = self.shared_load_state.try_read() {
+                if !state.loaded {
+                    return text::State {
+                        text: format!(
+                            "indexing paths… {} loaded",
+                            format_count(state.loaded_item_len)
+                        ),
+                        style: StyleBuilder::new().fgc(Color::DarkGrey).build(),
+                    }
+                    .create_pane(width, height);
+                }
+            }
+        }
         self.state.create_pane(width, height)
     }
 
+    pub fn is_searching(&self) -> bool {
+        self.state.listbox.len() > 0
+    }
+
     pub fn leave_search(&mut self) {
         self.state.listbox = Listbox::from_displayable(Vec::<String>::new());
         self.search_chunk_remaining = Vec::<String>::new();
+        self.head = None;
+        self.original = None;
+        self.pending = false;
+    }
+
+    /// Leaves the search, returning the filter as it was before the completion.
+    pub fn cancel_search(&mut self) -> Option<String> {
+        let original = self.original.take();
+        self.leave_search();
+        original
     }
 
     pub fn start_search(&mut self, prefix: &str) -> anyhow::Result<StartSearchResult> {
         match (
             self.shared_load_state.try_read(),
-            self.shared_set.try_lock(),
+            self.shared_index.try_lock(),
+            self.shared_result_paths.try_lock(),
         ) {
-            (Ok(state), Ok(set)) => {
-                let mut items: Vec<_> = set
-                    .iter()
-                    .filter(|p| p.starts_with(prefix))
-                    .cloned()
-                    .collect();
+            (Ok(state), Ok(index), Ok(result_paths)) => {
+                self.head = None;
+                let mut functions = false;
+                let mut variables = false;
+                let mut from_result = false;
+                let mut items = if let Some((head, token)) = variable_token(prefix) {
+                    self.head = Some(head.to_string());
+                    variables = true;
+                    let mut names = self
+                        .variables
+                        .iter()
+                        .filter_map(|name| self.score(token, name).map(|score| (score, name)))
+                        .collect::<Vec<_>>();
+                    names.sort_by_key(|(score, _)| *score);
+                    names.into_iter().map(|(_, name)| name.clone()).collect()
+                } else if let Some((head, token)) = function_token(prefix) {
+                    self.head = Some(head.to_string());
+                    functions = true;
+                    let mut names = builtins::NAMES
+                        .iter()
+                        .filter_map(|name| self.score(token, name).map(|score| (score, *name)))
+                        .collect::<Vec<_>>();
+                    names.sort_by_key(|(score, _)| *score);
+                    names
+                        .into_iter()
+                        .map(|(_, name)| name.to_string())
+                        .collect()
+                } else if let Some((head, query, token)) = piped_path(prefix) {
+                    // Prefer the paths of the result flowing into the `|`.
+                    let index = match query {
+                        "" | "." => Some(&*index),
+                        query => {
+                            from_result = true;
+                            result_paths.get(query)
+                        }
+                    };
+                    match index {
+                        Some(index) => {
+                            self.head = Some(head.to_string());
+                            index.candidates(|p| self.score(token, p))
+                        }
+                        None => Vec::new(),
+                    }
+                } else {
+                    index.candidates(|p| self.score(prefix, p))
+                };
+                if self.head.is_none() && items.is_empty() {
+                    from_result = false;
+                    items = index.candidates(|p| self.score(prefix, p));
+                }
                 if items.is_empty() {
                     return Ok(StartSearchResult {
                         head_item: None,
+                        functions,
+                        variables,
+                        from_result: false,
                         load_state: state.clone(),
                     });
                 }
@@ -143,17 +434,72 @@ impl IncrementalSearcher {
                     .collect::<Vec<_>>();
                 self.search_chunk_remaining = items;
                 self.state.listbox = Listbox::from_displayable(used);
+                self.original = Some(prefix.to_string());
                 Ok(StartSearchResult {
-                    head_item: Some(self.state.listbox.get().to_string()),
+                    head_item: Some(self.get_current_item()),
+                    functions,
+                    variables,
+                    from_result,
                     load_state: state.clone(),
                 })
             }
-            (Err(_), _) | (_, Err(_)) => Err(anyhow!(
+            _ => Err(anyhow!(
                 "Failed to acquire lock for suggestions. Please try again."
             )),
         }
     }
 
+    /// Extends the filter from before the completion with the selected suggestion
+    /// up to the end of its next path segment, then lists the suggestions for it.
+    ///
+    /// Returns the filter extended, the search being left if nothing more is suggested.
+    pub fn accept_segment(&mut self) -> anyhow::Result<String> {
+        let original = self.original.clone().unwrap_or_default();
+        let head = self.head.clone().unwrap_or_default();
+        let segment = |item: &str| {
+            let common = original
+                .char_indices()
+                .zip(item.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(original.len().min(item.len()), |((i, _), _)| i);
+            item[..next_segment_end(item, common)].to_string()
+        };
+        // The selected suggestion may be the filter itself (e.g. `.a` for `.a`),
+        // in which case the next one going deeper is taken.
+        let prefix = std::iter::once(self.get_current_item())
+            .chain(
+                self.state.listbox.items()[self.state.listbox.position()..]
+                    .iter()
+                    .map(|item| format!("{}{}", head, item)),
+            )
+            .chain(
+                self.search_chunk_remaining
+                    .iter()
+                    .map(|item| format!("{}{}", head, item)),
+            )
+            .map(|item| segment(&item))
+            .find(|prefix| prefix.len() > original.len())
+            .unwrap_or_else(|| original.clone());
+
+        self.state.listbox = Listbox::from_displayable(Vec::<String>::new());
+        let result = self.start_search(&prefix)?;
+        if result.head_item.is_none() {
+            self.leave_search();
+        } else {
+            self.pending = true;
+        }
+        Ok(prefix)
+    }
+
+    fn score(&self, input: &str, path: &str) -> Option<usize> {
+        if self.case_insensitive {
+            self.matching
+                .score(&input.to_lowercase(), &path.to_lowercase())
+        } else {
+            self.matching.score(input, path)
+        }
+    }
+
     fn load_more(&mut self) {
         if self.search_chunk_remaining.is_empty() {
             return;
@@ -168,3 +514,246 @@ impl IncrementalSearcher {
         }
     }
 }
+
+/// Returns where the path segment following `from` ends, before the next `.` or `[`
+/// out of the quoted keys (or the end of the path).
+fn next_segment_end(path: &str, from: usize) -> usize {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, ch) in path.char_indices() {
+        if i > from && !quoted && matches!(ch, '.' | '[') {
+            return i;
+        }
+        if ch == '"' && !escaped {
+            quoted = !quoted;
+        }
+        escaped = ch == '\\' && !escaped;
+    }
+    path.len()
+}
+
+/// Splits the filter into the text before the variable typed last (e.g. `$en`)
+/// and the variable.
+fn variable_token(filter: &str) -> Option<(&str, &str)> {
+    let start = filter.rfind('$')?;
+    let token = &filter[start..];
+    token[1..]
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        .then(|| (&filter[..start], token))
+}
+
+/// Splits the filter into the text before the token typed after the last `|` or `(`
+/// and the token, unless the token is a path (starting with `.`).
+fn function_token(filter: &str) -> Option<(&str, &str)> {
+    let start = filter.rfind(['|', '('])? + 1;
+    let token = filter[start..].trim_start();
+    if token.starts_with('.') {
+        return None;
+    }
+    Some((&filter[..filter.len() - token.len()], token))
+}
+
+/// Splits the filter into the text before the path typed after the last `|`,
+/// the filter before that `|` and the path.
+fn piped_path(filter: &str) -> Option<(&str, &str, &str)> {
+    let pipe = filter.rfind('|')?;
+    let token = filter[pipe + 1..].trim_start();
+    if !token.starts_with('.') {
+        return None;
+    }
+    Some((
+        &filter[..filter.len() - token.len()],
+        filter[..pipe].trim(),
+        token,
+    ))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use promkit::serde_json::{json, Value};
+
+    use super::*;
+    use crate::processor::Context;
+
+    #[derive(Clone)]
+    struct Documents(Arc<Vec<Value>>);
+
+    #[async_trait]
+    impl SearchProvider for Documents {
+        async fn provide(
+            &mut self,
+            _item: Arc<str>,
+        ) -> anyhow::Result<Box<dyn Iterator<Item = PathEntry> + Send>> {
+            let documents = self.0.clone();
+            Ok(Box::new((0..documents.len()).flat_map(move |i| {
+                paths::get_all_paths([&documents[i]]).collect::<Vec<_>>()
+            })))
+        }
+    }
+
+    pub(crate) fn searcher() -> IncrementalSearcher {
+        IncrementalSearcher::new(
+            listbox::State {
+                listbox: Listbox::from_displayable(Vec::<String>::new()),
+                cursor: String::from("❯ "),
+                active_item_style: None,
+                inactive_item_style: None,
+                lines: Some(3),
+            },
+            &SearchSettings {
+                result_chunk_size: 100,
+                load_chunk_size: 100,
+                load_throttle: Duration::ZERO,
+                order: PathOrder::Depth,
+                matching: PathMatching::Prefix,
+                case_insensitive: false,
+                array_indices: 3,
+                result_paths: false,
+                index_input: true,
+                variables: Vec::new(),
+            },
+        )
+    }
+
+    /// Documents with many distinct paths, for the index to grow all along.
+    fn documents(len: usize) -> Vec<Value> {
+        (0..len)
+            .map(|i| {
+                let mut object = serde_json::Map::new();
+                for j in 0..8 {
+                    object.insert(format!("key{}_{}", i, j), json!({"a": [1, {"b": j}]}));
+                }
+                Value::Object(object)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn counts_the_loads_gone_to_the_end() {
+        let searcher = searcher();
+        let mut provider = Documents(Arc::new(documents(3)));
+        assert_eq!(searcher.loads_completed(), 0);
+        for loads in 1..=2 {
+            searcher
+                .spawn_load_task(
+                    &mut provider,
+                    Arc::from(""),
+                    100,
+                    Duration::ZERO,
+                    ContextMonitor::new(Arc::new(Mutex::new(Context::new((80, 24))))),
+                )
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(!searcher.is_loading());
+            assert_eq!(searcher.loads_completed(), loads);
+        }
+    }
+
+    /// Documents whose paths are walked up to the second one,
+    /// where the walk is held until it is released (or for 10 seconds at most).
+    #[derive(Clone)]
+    struct Held {
+        documents: Arc<Vec<Value>>,
+        gate: Arc<std::sync::Mutex<Option<std::sync::mpsc::Receiver<()>>>>,
+    }
+
+    #[async_trait]
+    impl SearchProvider for Held {
+        async fn provide(
+            &mut self,
+            _item: Arc<str>,
+        ) -> anyhow::Result<Box<dyn Iterator<Item = PathEntry> + Send>> {
+            let documents = self.documents.clone();
+            let gate = self.gate.lock().unwrap().take().unwrap();
+            Ok(Box::new((0..documents.len()).flat_map(move |i| {
+                if i == 1 {
+                    let _ = gate.recv_timeout(Duration::from_secs(10));
+                }
+                paths::get_all_paths([&documents[i]]).collect::<Vec<_>>()
+            })))
+        }
+    }
+
+    /// The timers of the runtime fire while the paths are walked,
+    /// with a single thread as the editor would share it.
+    ///
+    /// The walk is held in the middle while the clock goes forward,
+    /// which it could not if the paths were walked on the runtime.
+    #[tokio::test(start_paused = true)]
+    async fn indexing_keeps_the_runtime_responsive() {
+        let searcher = searcher();
+        let monitor = ContextMonitor::new(Arc::new(Mutex::new(Context::new((80, 24)))));
+        let (release, gate) = std::sync::mpsc::channel();
+        let mut provider = Held {
+            documents: Arc::new(documents(2)),
+            gate: Arc::new(std::sync::Mutex::new(Some(gate))),
+        };
+        let task =
+            searcher.spawn_load_task(&mut provider, Arc::from(""), 1, Duration::ZERO, monitor);
+
+        for _ in 0..10 {
+            let timer = tokio::time::sleep(Duration::from_millis(1));
+            tokio::time::advance(Duration::from_millis(1)).await;
+            timer.await;
+        }
+        assert!(searcher.is_loading());
+
+        release.send(()).unwrap();
+        task.await.unwrap().unwrap();
+        assert!(!searcher.is_loading());
+        let paths = searcher.shared_index().lock().await.candidates(|_| Some(0));
+        assert!(paths.contains(&String::from(".key1_7")), "{:?}", paths);
+    }
+
+    /// Returns the paths suggested for the filter, sorted.
+    fn suggested(searcher: &mut IncrementalSearcher, filter: &str) -> Vec<String> {
+        if searcher.start_search(filter).unwrap().head_item.is_none() {
+            return Vec::new();
+        }
+        let mut ret = searcher
+            .state
+            .listbox
+            .items()
+            .iter()
+            .map(|item| item.to_string())
+            .collect::<Vec<_>>();
+        ret.sort();
+        ret
+    }
+
+    #[tokio::test]
+    async fn completes_the_keys_in_any_case_if_asked() {
+        let mut searcher = searcher();
+        {
+            let index = searcher.shared_index();
+            let mut index = index.lock().await;
+            let document = json!({"userName": 1, "UserId": 2, "username": 3, "email": 4});
+            for path in paths::get_all_paths([&document]) {
+                index.insert(path);
+            }
+        }
+        assert_eq!(
+            suggested(&mut searcher, ".user"),
+            [".userName", ".username"]
+        );
+        assert_eq!(suggested(&mut searcher, ".User"), [".UserId"]);
+        assert!(suggested(&mut searcher, ".USER").is_empty());
+
+        searcher.case_insensitive = true;
+        for filter in [".user", ".User", ".USER"] {
+            assert_eq!(
+                suggested(&mut searcher, filter),
+                [".UserId", ".userName", ".username"],
+                "filter {:?}",
+                filter
+            );
+        }
+        assert_eq!(
+            suggested(&mut searcher, ".usern"),
+            [".userName", ".username"]
+        );
+        assert_eq!(suggested(&mut searcher, ".Email"), [".email"]);
+    }
+}
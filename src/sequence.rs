@@ -0,0 +1,272 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::Event;
+
+use crate::config::event::{EventDef, EventDefSet, EventSeq, KeyEventDef, MouseEventDef};
+
+/// Outcome of feeding events into a [`SequenceMatcher`].
+pub enum Step<A> {
+    /// A binding for the action has been completed.
+    Action(A),
+    /// The event is not part of any binding and should be handled as usual.
+    Event(Event),
+}
+
+/// Resolves events into actions, supporting bindings of multiple chords (e.g. "g g").
+///
+/// While the events typed so far are a prefix of a longer binding,
+/// they are held back until the binding is completed or the timeout expires.
+/// On a mismatch or a timeout, held events are released as [`Step::Event`]
+/// so that nothing typed is ever lost.
+#[derive(Clone)]
+pub struct SequenceMatcher<A> {
+    bindings: Vec<(EventSeq, A)>,
+    timeout: Duration,
+    pending: Vec<Event>,
+    deadline: Option<Instant>,
+}
+
+impl<A: Copy> SequenceMatcher<A> {
+    pub fn new<'a, I: IntoIterator<Item = (&'a EventDefSet, A)>>(
+        bindings: I,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            bindings: bindings
+                .into_iter()
+                .flat_map(|(set, action)| set.iter().map(move |seq| (seq.clone(), action)))
+                .collect(),
+            timeout,
+            pending: Vec::new(),
+            deadline: None,
+        }
+    }
+
+    pub fn feed(&mut self, event: &Event) -> Vec<Step<A>> {
+        if !EventDef::is_bindable(event) {
+            return vec![Step::Event(event.clone())];
+        }
+        self.pending.push(event.clone());
+        self.resolve(false)
+    }
+
+    /// Resolves the held events after the timeout has expired.
+    pub fn flush(&mut self) -> Vec<Step<A>> {
+        self.resolve(true)
+    }
+
+    /// Drops the held events without handling them.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.deadline = None;
+    }
+
+    /// Returns the time at which the held events should be flushed.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Describes the held events for display (e.g. "g …").
+    pub fn pending_text(&self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let chords = self
+            .pending
+            .iter()
+            .filter_map(|event| match event {
+                Event::Key(key) => Some(KeyEventDef::from(key).to_string()),
+                Event::Mouse(mouse) => Some(MouseEventDef::from(mouse).to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        Some(format!("{} …", chords.join(" ")))
+    }
+
+    fn resolve(&mut self, timed_out: bool) -> Vec<Step<A>> {
+        let mut steps = Vec::new();
+        while !self.pending.is_empty() {
+            let pending = &self.pending;
+            let is_prefix = self
+                .bindings
+                .iter()
+                .any(|(seq, _)| seq.len() > pending.len() && seq.starts_with(pending));
+            if is_prefix && !timed_out {
+                self.deadline = Some(Instant::now() + self.timeout);
+                return steps;
+            }
+
+            // The longest of the held events completing a binding resolve to its action
+            // (e.g. `z` of `z x` while both `z` and `z z` are bound).
+            let completed = (1..=pending.len()).rev().find_map(|len| {
+                self.bindings
+                    .iter()
+                    .find(|(seq, _)| seq.len() == len && seq.starts_with(&pending[..len]))
+                    .map(|(_, action)| (len, *action))
+            });
+            match completed {
+                Some((len, action)) => {
+                    steps.push(Step::Action(action));
+                    self.pending.drain(..len);
+                }
+                // Release the oldest event and retry with the rest,
+                // which may still form (the beginning of) another binding.
+                None => steps.push(Step::Event(self.pending.remove(0))),
+            }
+        }
+        self.deadline = None;
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Action {
+        Top,
+        Save,
+        Quit,
+        Zoom,
+    }
+
+    fn matcher() -> SequenceMatcher<Action> {
+        let bindings = [
+            (EventDefSet::from_defaults(&["g g"]), Action::Top),
+            (EventDefSet::from_defaults(&["space f s"]), Action::Save),
+            (EventDefSet::from_defaults(&["Ctrl+c"]), Action::Quit),
+            (EventDefSet::from_defaults(&["z", "z z"]), Action::Zoom),
+        ];
+        SequenceMatcher::new(
+            bindings.iter().map(|(set, action)| (set, *action)),
+            Duration::from_millis(500),
+        )
+    }
+
+    fn key(ch: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE))
+    }
+
+    /// Describes the steps, e.g. `[Top, 'x']`.
+    fn describe(steps: Vec<Step<Action>>) -> String {
+        let steps = steps
+            .into_iter()
+            .map(|step| match step {
+                Step::Action(action) => format!("{:?}", action),
+                Step::Event(Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch),
+                    ..
+                })) => format!("{:?}", ch),
+                Step::Event(event) => format!("{:?}", event),
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", steps.join(", "))
+    }
+
+    #[test]
+    fn single_chord_matches_at_once() {
+        let mut matcher = matcher();
+        let ctrl_c = Event::Key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert_eq!(describe(matcher.feed(&ctrl_c)), "[Quit]");
+        assert_eq!(matcher.deadline(), None);
+    }
+
+    #[test]
+    fn unbound_key_passes_through() {
+        let mut matcher = matcher();
+        assert_eq!(describe(matcher.feed(&key('x'))), "['x']");
+        assert_eq!(matcher.pending_text(), None);
+    }
+
+    #[test]
+    fn sequence_matches_once_completed() {
+        let mut matcher = matcher();
+        assert_eq!(describe(matcher.feed(&key('g'))), "[]");
+        assert!(matcher.deadline().is_some());
+        assert_eq!(matcher.pending_text().as_deref(), Some("g …"));
+        assert_eq!(describe(matcher.feed(&key('g'))), "[Top]");
+        assert_eq!(matcher.deadline(), None);
+        assert_eq!(matcher.pending_text(), None);
+    }
+
+    #[test]
+    fn longer_sequence_matches_once_completed() {
+        let mut matcher = matcher();
+        assert_eq!(describe(matcher.feed(&key(' '))), "[]");
+        assert_eq!(describe(matcher.feed(&key('f'))), "[]");
+        assert_eq!(describe(matcher.feed(&key('s'))), "[Save]");
+    }
+
+    #[test]
+    fn mismatch_flushes_the_held_keys() {
+        let mut matcher = matcher();
+        matcher.feed(&key('g'));
+        // Typing `.g` then `x` in the editor loses nothing.
+        assert_eq!(describe(matcher.feed(&key('x'))), "['g', 'x']");
+        assert_eq!(matcher.deadline(), None);
+    }
+
+    #[test]
+    fn mismatch_retries_the_rest() {
+        let mut matcher = matcher();
+        matcher.feed(&key(' '));
+        // ` ` is released, `g` starting `g g` again.
+        assert_eq!(describe(matcher.feed(&key('g'))), "[' ']");
+        assert_eq!(describe(matcher.feed(&key('g'))), "[Top]");
+    }
+
+    #[test]
+    fn timeout_flushes_the_held_keys() {
+        let mut matcher = matcher();
+        matcher.feed(&key(' '));
+        matcher.feed(&key('f'));
+        assert_eq!(describe(matcher.flush()), "[' ', 'f']");
+        assert_eq!(matcher.deadline(), None);
+        assert_eq!(matcher.pending_text(), None);
+    }
+
+    #[test]
+    fn nested_prefix_waits_for_the_longer_binding() {
+        let mut matcher = matcher();
+        // `z` is bound, but may begin `z z`.
+        assert_eq!(describe(matcher.feed(&key('z'))), "[]");
+        assert_eq!(describe(matcher.feed(&key('z'))), "[Zoom]");
+    }
+
+    #[test]
+    fn nested_prefix_matches_on_timeout() {
+        let mut matcher = matcher();
+        matcher.feed(&key('z'));
+        assert_eq!(describe(matcher.flush()), "[Zoom]");
+    }
+
+    #[test]
+    fn nested_prefix_matches_before_a_mismatch() {
+        let mut matcher = matcher();
+        matcher.feed(&key('z'));
+        assert_eq!(describe(matcher.feed(&key('x'))), "[Zoom, 'x']");
+    }
+
+    #[test]
+    fn reset_drops_the_held_keys() {
+        let mut matcher = matcher();
+        matcher.feed(&key('g'));
+        matcher.reset();
+        assert_eq!(matcher.deadline(), None);
+        assert_eq!(describe(matcher.flush()), "[]");
+    }
+
+    #[test]
+    fn unbindable_event_passes_through_while_holding() {
+        let mut matcher = matcher();
+        matcher.feed(&key('g'));
+        assert_eq!(
+            describe(matcher.feed(&Event::Paste("x".to_string()))),
+            "[Paste(\"x\")]"
+        );
+        assert_eq!(describe(matcher.feed(&key('g'))), "[Top]");
+    }
+}
@@ -0,0 +1,252 @@
+//! State remembered per input across sessions.
+//!
+//! Each input is identified by the canonical path of its file,
+//! or by "stdin" when it is read from standard input,
+//! and its state is stored as JSON under the user's state directory
+//! (e.g. `~/.local/state/jnv/inputs` on Linux).
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use promkit::serde_json;
+use serde::{Deserialize, Serialize};
+
+use crate::{json::ExpansionMemory, safe::SafeMode};
+
+/// Computes a stable (FNV-1a) hash of the bytes.
+pub fn hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Time to wait for another instance of jnv to finish saving the same file,
+/// after which the file is not saved rather than blocking the UI.
+const LOCK_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Takes the advisory lock on the file (on `<path>.lock` beside it,
+/// so that the file itself can be replaced), released once the lock is dropped.
+pub fn lock(path: &Path, safe_mode: SafeMode) -> anyhow::Result<fs::File> {
+    let mut lock_path = path.to_path_buf().into_os_string();
+    lock_path.push(".lock");
+    let file = safe_mode.open_file(
+        Path::new(&lock_path),
+        fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true),
+    )?;
+    let deadline = Instant::now() + LOCK_TIMEOUT;
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(file),
+            Err(fs::TryLockError::WouldBlock) if Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(fs::TryLockError::WouldBlock) => {
+                return Err(anyhow::anyhow!("another jnv is saving {}", path.display()))
+            }
+            Err(fs::TryLockError::Error(e)) => return Err(e.into()),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputState {
+    /// Identifier of the input, kept for those inspecting the file.
+    pub input: String,
+    pub expansion: ExpansionMemory,
+}
+
+/// File storing the [`InputState`] of an input.
+#[derive(Clone)]
+pub struct StateFile {
+    key: String,
+    path: PathBuf,
+    safe_mode: SafeMode,
+}
+
+impl StateFile {
+    /// Returns the state file for the input read from `input`
+    /// (`None` or "-" for standard input),
+    /// or `None` if there is no directory to store it.
+    pub fn for_input(input: Option<&Path>, safe_mode: SafeMode) -> Option<Self> {
+        let key = match input {
+            Some(path) if path != Path::new("-") => fs::canonicalize(path)
+                .unwrap_or_else(|_| path.to_path_buf())
+                .to_string_lossy()
+                .to_string(),
+            _ => "stdin".to_string(),
+        };
+        let dir = dirs::state_dir().or_else(dirs::data_local_dir)?;
+        let path = dir
+            .join("jnv")
+            .join("inputs")
+            .join(format!("{}.json", hash(key.as_bytes())));
+        Some(Self {
+            key,
+            path,
+            safe_mode,
+        })
+    }
+
+    /// Loads the state, falling back to the default one
+    /// if the file does not exist or cannot be parsed.
+    pub fn load(&self) -> InputState {
+        let mut state = fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<InputState>(&content).ok())
+            .unwrap_or_default();
+        state.input = self.key.clone();
+        state
+    }
+
+    /// Applies `f` to the latest saved state and saves it,
+    /// leaving whatever `f` does not touch as is.
+    ///
+    /// Instances of jnv on the same input take turns,
+    /// so that none of them overwrites what another has just saved.
+    pub fn update<F: FnOnce(&mut InputState)>(&self, f: F) -> anyhow::Result<()> {
+        let _lock = lock(&self.path, self.safe_mode)?;
+        let mut state = self.load();
+        f(&mut state);
+        self.save(&state)
+    }
+
+    /// Saves the state, replacing the file atomically.
+    fn save(&self, state: &InputState) -> anyhow::Result<()> {
+        self.safe_mode.replace_file(&self.path, |file| {
+            serde_json::to_writer_pretty(&mut *file, state)?;
+            file.write_all(b"\n")?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Barrier, thread};
+
+    use super::*;
+
+    /// Returns a state file of its own under the temporary directory.
+    fn state_file(name: &str) -> StateFile {
+        let dir = std::env::temp_dir().join(format!("jnv-state-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        StateFile {
+            key: name.to_string(),
+            path: dir.join("state.json"),
+            safe_mode: SafeMode::new(false),
+        }
+    }
+
+    #[test]
+    fn interleaved_updates_keep_each_others_patterns() {
+        let a = state_file("interleaved");
+        let b = a.clone();
+        // Both loaded the state before either saved.
+        let _ = (a.load(), b.load());
+        a.update(|state| state.expansion.set(".a", Some(true)))
+            .unwrap();
+        b.update(|state| state.expansion.set(".b", Some(false)))
+            .unwrap();
+        // The newest update of a pattern wins.
+        a.update(|state| state.expansion.set(".b", Some(true)))
+            .unwrap();
+
+        let state = a.load();
+        assert_eq!(state.input, "interleaved");
+        assert_eq!(state.expansion.collapse, vec![".a", ".b"]);
+        assert!(state.expansion.expand.is_empty());
+    }
+
+    #[test]
+    fn expansion_survives_the_session() {
+        let file = state_file("session");
+        // As the viewer remembers a pattern, saving the change alone.
+        let mut expansion = file.load().expansion;
+        for (pattern, collapsed) in [(".a[].b", true), (".c", false), (".d", true)] {
+            let remembered = expansion.toggle(pattern, collapsed);
+            file.update(|state| {
+                state
+                    .expansion
+                    .set(pattern, remembered.then_some(collapsed))
+            })
+            .unwrap();
+        }
+        // Another session on the same input forgets one and remembers another meanwhile.
+        let other = file.clone();
+        other
+            .update(|state| {
+                state.expansion.set(".d", None);
+                state.expansion.set(".e", Some(false));
+            })
+            .unwrap();
+        // Toggled back as expanded.
+        let remembered = expansion.toggle(".a[].b", false);
+        file.update(|state| state.expansion.set(".a[].b", remembered.then_some(false)))
+            .unwrap();
+
+        // As the next session starts.
+        let state = file.load();
+        assert!(state.expansion.collapse.is_empty());
+        assert_eq!(state.expansion.expand, vec![".c", ".e", ".a[].b"]);
+    }
+
+    #[test]
+    fn concurrent_updates_lose_no_pattern() {
+        const WRITERS: usize = 4;
+        const PATTERNS: usize = 10;
+        let file = state_file("concurrent");
+        let barrier = Barrier::new(WRITERS);
+        thread::scope(|scope| {
+            for writer in 0..WRITERS {
+                let (file, barrier) = (&file, &barrier);
+                scope.spawn(move || {
+                    barrier.wait();
+                    for i in 0..PATTERNS {
+                        file.update(|state| {
+                            state
+                                .expansion
+                                .set(&format!(".w{}[{}]", writer, i), Some(true))
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(file.load().expansion.collapse.len(), WRITERS * PATTERNS);
+    }
+
+    #[test]
+    fn update_gives_up_while_locked() {
+        let file = state_file("locked");
+        let _lock = lock(&file.path, file.safe_mode).unwrap();
+        let start = Instant::now();
+        assert!(file
+            .update(|state| state.expansion.set(".a", Some(true)))
+            .is_err());
+        assert!(start.elapsed() < LOCK_TIMEOUT * 5);
+        assert!(file.load().expansion.is_empty());
+    }
+
+    #[test]
+    fn corrupt_state_falls_back_to_the_default() {
+        let file = state_file("corrupt");
+        fs::create_dir_all(file.path.parent().unwrap()).unwrap();
+        fs::write(&file.path, "{ not json").unwrap();
+        assert!(file.load().expansion.is_empty());
+        file.update(|state| state.expansion.set(".a", Some(false)))
+            .unwrap();
+        assert_eq!(file.load().expansion.expand, vec![".a"]);
+    }
+}
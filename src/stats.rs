@@ -0,0 +1,165 @@
+//! Profile of the values of a result (their types, the keys of the documents
+//! and the lengths of the arrays), shown in the guide by keybind.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use promkit::{
+    crossterm::style::Color, grapheme::StyledGraphemes, pane::Pane, serde_json::Value,
+    style::StyleBuilder,
+};
+
+use crate::json::format_count;
+
+/// Number of the values (nested ones included) beyond which the documents left
+/// are not profiled, so that a profile of millions of values stays quick.
+const MAX_VALUES: usize = 1_000_000;
+
+/// Number of the values walked between two checks of the cancellation.
+const CANCEL_CHECK_INTERVAL: usize = 4096;
+
+/// Number of the keys of the documents told, the most frequent first.
+const MAX_KEYS: usize = 8;
+
+const TYPES: [&str; 6] = ["null", "boolean", "number", "string", "array", "object"];
+
+pub struct Profile {
+    documents: usize,
+    /// Number of the documents profiled, fewer than `documents` once sampled.
+    profiled: usize,
+    /// Number of the values of each of `TYPES`, nested ones included.
+    types: [usize; 6],
+    /// Number of the documents having each key, for those which are objects.
+    keys: HashMap<String, usize>,
+    /// Lengths of the shortest and the longest arrays.
+    lengths: Option<(usize, usize)>,
+}
+
+impl Profile {
+    /// Profiles the values from the first one, up to `MAX_VALUES` nested values.
+    ///
+    /// Returns nothing once `cancel` is set.
+    pub fn new(values: &[Value], cancel: &AtomicBool) -> Option<Self> {
+        let mut profile = Self {
+            documents: values.len(),
+            profiled: 0,
+            types: [0; 6],
+            keys: HashMap::new(),
+            lengths: None,
+        };
+        let mut walked = 0;
+        for document in values {
+            if walked >= MAX_VALUES {
+                break;
+            }
+            if let Value::Object(map) = document {
+                for key in map.keys() {
+                    *profile.keys.entry(key.clone()).or_default() += 1;
+                }
+            }
+            let mut stack = vec![document];
+            while let Some(value) = stack.pop() {
+                walked += 1;
+                if walked % CANCEL_CHECK_INTERVAL == 0 && cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let kind = match value {
+                    Value::Null => 0,
+                    Value::Bool(_) => 1,
+                    Value::Number(_) => 2,
+                    Value::String(_) => 3,
+                    Value::Array(array) => {
+                        profile.lengths = Some(match profile.lengths {
+                            Some((min, max)) => (min.min(array.len()), max.max(array.len())),
+                            None => (array.len(), array.len()),
+                        });
+                        stack.extend(array);
+                        4
+                    }
+                    Value::Object(map) => {
+                        stack.extend(map.values());
+                        5
+                    }
+                };
+                profile.types[kind] += 1;
+            }
+            profile.profiled += 1;
+        }
+        Some(profile)
+    }
+
+    /// Returns a line per statistic, wrapped to the width.
+    pub fn create_pane(&self, width: u16) -> Pane {
+        let total = self.types.iter().sum::<usize>().max(1);
+        let mut types = TYPES
+            .iter()
+            .zip(self.types)
+            .filter(|(_, count)| *count > 0)
+            .collect::<Vec<_>>();
+        types.sort_by_key(|(_, count)| Reverse(*count));
+        let types = types
+            .iter()
+            .map(|(name, count)| {
+                format!(
+                    "{} {} ({:.0}%)",
+                    name,
+                    format_count(*count),
+                    *count as f64 * 100.0 / total as f64
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" · ");
+
+        let mut keys = self.keys.iter().collect::<Vec<_>>();
+        keys.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let mut described = keys
+            .iter()
+            .take(MAX_KEYS)
+            .map(|(key, count)| format!("{} {}", key, format_count(**count)))
+            .collect::<Vec<_>>();
+        if keys.len() > MAX_KEYS {
+            described.push(format!("… {} more", format_count(keys.len() - MAX_KEYS)));
+        }
+
+        let mut rows = vec![(
+            "documents",
+            if self.profiled < self.documents {
+                format!(
+                    "{} (sampled: the first {} profiled)",
+                    format_count(self.documents),
+                    format_count(self.profiled)
+                )
+            } else {
+                format_count(self.documents)
+            },
+        )];
+        rows.push(("types", types));
+        if !described.is_empty() {
+            rows.push(("keys", described.join(" · ")));
+        }
+        if let Some((min, max)) = self.lengths {
+            rows.push((
+                "arrays",
+                format!("{} to {} elements", format_count(min), format_count(max)),
+            ));
+        }
+
+        let label_style = StyleBuilder::new().fgc(Color::Grey).build();
+        let lines = rows
+            .into_iter()
+            .flat_map(|(label, text)| {
+                let line: StyledGraphemes = vec![
+                    StyledGraphemes::from(format!("{:<10}", label)).apply_style(label_style),
+                    StyledGraphemes::from(text),
+                ]
+                .into_iter()
+                .collect();
+                line.matrixify(width as usize, usize::MAX, 0).0
+            })
+            .collect();
+        Pane::new(lines, 0)
+    }
+}
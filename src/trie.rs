@@ -0,0 +1,76 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use promkit::serde_json;
+
+/// Values produced by the queries which succeeded, looked up by the query
+/// or by its longest prefix among the cached queries.
+///
+/// At most `capacity` queries are kept, the least recently used being evicted first.
+#[derive(Clone, Default)]
+pub struct FilterTrie {
+    capacity: usize,
+    entries: HashMap<String, Arc<Vec<serde_json::Value>>>,
+    /// Cached queries from the least recently used.
+    recency: VecDeque<String>,
+}
+
+impl FilterTrie {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    pub fn insert(&mut self, query: &str, values: Arc<Vec<serde_json::Value>>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.insert(query.to_string(), values).is_some() {
+            self.touch(query);
+            return;
+        }
+        self.recency.push_back(query.to_string());
+        while self.recency.len() > self.capacity {
+            if let Some(evicted) = self.recency.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    /// Returns the values of exactly this query.
+    pub fn exact_search(&mut self, query: &str) -> Option<Arc<Vec<serde_json::Value>>> {
+        let values = self.entries.get(query)?.clone();
+        self.touch(query);
+        Some(values)
+    }
+
+    /// Returns the longest cached query which is a proper prefix of `query`, with its values.
+    pub fn prefix_search(&mut self, query: &str) -> Option<(String, Arc<Vec<serde_json::Value>>)> {
+        let prefix = query
+            .char_indices()
+            .rev()
+            .map(|(i, _)| &query[..i])
+            .find(|prefix| self.entries.contains_key(*prefix))?
+            .to_string();
+        let values = self.exact_search(&prefix)?;
+        Some((prefix, values))
+    }
+
+    /// Forgets every query, e.g. once the input changes.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    fn touch(&mut self, query: &str) {
+        if let Some(i) = self.recency.iter().position(|q| q == query) {
+            if let Some(q) = self.recency.remove(i) {
+                self.recency.push_back(q);
+            }
+        }
+    }
+}
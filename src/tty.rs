@@ -0,0 +1,225 @@
+//! Selection of the devices the interface runs on.
+//!
+//! The input may be piped through standard input,
+//! in which case the keys are read from the controlling terminal instead
+//! (crossterm opens `/dev/tty` on Unix and `CONIN$` on Windows).
+//! Likewise, standard output may be redirected to receive the result of the session,
+//! in which case the interface is drawn elsewhere.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+/// Device the keyboard is read from.
+#[derive(Debug, PartialEq)]
+pub enum KeyboardDevice {
+    Stdin,
+    ControllingTerminal,
+    /// Device given by --tty.
+    Explicit(PathBuf),
+}
+
+/// Selects the device the keyboard is read from, failing fast
+/// if there is none instead of waiting for keys that never come
+/// (e.g. under ssh forced commands or CI shells).
+///
+/// Must be called once standard input has been read,
+/// since an explicit device replaces it.
+pub fn select_keyboard(explicit: Option<&Path>) -> anyhow::Result<KeyboardDevice> {
+    let explicit = explicit.map(|path| (path, open(path)));
+    let device = choose_keyboard(
+        explicit.as_ref().map(|(path, file)| {
+            (
+                *path,
+                file.as_ref()
+                    .map(|file| file.is_terminal())
+                    .map_err(|e| e.to_string()),
+            )
+        }),
+        io::stdin().is_terminal(),
+        || open(Path::new(CONTROLLING_TERMINAL)).is_ok_and(|file| file.is_terminal()),
+    )?;
+    if let (KeyboardDevice::Explicit(_), Some((_, Ok(file)))) = (&device, &explicit) {
+        redirect_stdin(file)?;
+    }
+    Ok(device)
+}
+
+/// Chooses the device the keyboard is read from, as told by what each one is:
+/// the device given by --tty (whether it opened as a terminal, or why it did not),
+/// then standard input, then the controlling terminal, only probed if it comes to it.
+fn choose_keyboard(
+    explicit: Option<(&Path, Result<bool, String>)>,
+    stdin_is_terminal: bool,
+    controlling_terminal_usable: impl FnOnce() -> bool,
+) -> anyhow::Result<KeyboardDevice> {
+    match explicit {
+        Some((path, Err(e))) => Err(anyhow::anyhow!("Failed to open {}: {}", path.display(), e)),
+        Some((path, Ok(false))) => Err(anyhow::anyhow!("{} is not a terminal", path.display())),
+        Some((path, Ok(true))) => Ok(KeyboardDevice::Explicit(path.to_path_buf())),
+        None if stdin_is_terminal => Ok(KeyboardDevice::Stdin),
+        None if controlling_terminal_usable() => Ok(KeyboardDevice::ControllingTerminal),
+        None => Err(anyhow::anyhow!(
+            "no interactive terminal available for keyboard input \
+            (use --tty to point at one)"
+        )),
+    }
+}
+
+/// Standard output as given to jnv, where the result of the session is written on exit.
+pub enum Output {
+    /// Standard output is the terminal the interface is drawn on.
+    Terminal,
+    /// Standard output is redirected (e.g. piped into the next command),
+    /// and was set apart from the interface.
+    Redirected(File),
+}
+
+impl Output {
+    pub fn write_line(self, text: &str) -> io::Result<()> {
+        match self {
+            Output::Terminal => writeln!(io::stdout(), "{}", text),
+            Output::Redirected(mut file) => {
+                writeln!(file, "{}", text)?;
+                file.flush()
+            }
+        }
+    }
+}
+
+/// Sets standard output apart if it is redirected, drawing the interface
+/// on standard error (or the controlling terminal) instead,
+/// so that the result is the only thing written to it.
+///
+/// crossterm always writes to standard output (e.g. to query the cursor position),
+/// so the terminal is put in its place.
+/// Must be called before anything is written.
+#[cfg(unix)]
+pub fn separate_output() -> anyhow::Result<Output> {
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    if io::stdout().is_terminal() {
+        return Ok(Output::Terminal);
+    }
+    let terminal = if io::stderr().is_terminal() {
+        None
+    } else {
+        match open(Path::new(CONTROLLING_TERMINAL)) {
+            Ok(file) if file.is_terminal() => Some(file),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "no interactive terminal available to draw on \
+                    (standard output and standard error are redirected)"
+                ))
+            }
+        }
+    };
+
+    // SAFETY: standard output is open, and the duplicate is owned by the returned file.
+    let saved = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if saved == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let saved = unsafe { File::from_raw_fd(saved) };
+    let fd = match &terminal {
+        Some(file) => file.as_raw_fd(),
+        None => libc::STDERR_FILENO,
+    };
+    replace(fd, libc::STDOUT_FILENO)?;
+    Ok(Output::Redirected(saved))
+}
+
+/// Standard output cannot be set apart on Windows, where it is left as is.
+#[cfg(windows)]
+pub fn separate_output() -> anyhow::Result<Output> {
+    Ok(Output::Terminal)
+}
+
+#[cfg(unix)]
+const CONTROLLING_TERMINAL: &str = "/dev/tty";
+#[cfg(windows)]
+const CONTROLLING_TERMINAL: &str = "CONIN$";
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).open(path)
+}
+
+/// Makes the device standard input, which crossterm reads the keys from
+/// whenever it is a terminal.
+#[cfg(unix)]
+fn redirect_stdin(file: &File) -> anyhow::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    replace(file.as_raw_fd(), libc::STDIN_FILENO)
+}
+
+/// Makes `target` refer to the same device as `fd`.
+#[cfg(unix)]
+fn replace(fd: libc::c_int, target: libc::c_int) -> anyhow::Result<()> {
+    // SAFETY: both descriptors are open for the duration of the call.
+    if unsafe { libc::dup2(fd, target) } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn redirect_stdin(_file: &File) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!("--tty is not supported on Windows"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unprobed() -> bool {
+        panic!("the controlling terminal is probed")
+    }
+
+    fn error(result: anyhow::Result<KeyboardDevice>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn explicit_device_comes_first() {
+        let path = Path::new("/dev/pts/9");
+        assert_eq!(
+            choose_keyboard(Some((path, Ok(true))), true, unprobed).unwrap(),
+            KeyboardDevice::Explicit(path.to_path_buf())
+        );
+        assert_eq!(
+            error(choose_keyboard(Some((path, Ok(false))), true, unprobed)),
+            "/dev/pts/9 is not a terminal"
+        );
+        assert_eq!(
+            error(choose_keyboard(
+                Some((path, Err("No such file or directory".to_string()))),
+                true,
+                unprobed
+            )),
+            "Failed to open /dev/pts/9: No such file or directory"
+        );
+    }
+
+    #[test]
+    fn stdin_when_it_is_a_terminal() {
+        assert_eq!(
+            choose_keyboard(None, true, unprobed).unwrap(),
+            KeyboardDevice::Stdin
+        );
+    }
+
+    #[test]
+    fn controlling_terminal_when_stdin_is_piped() {
+        assert_eq!(
+            choose_keyboard(None, false, || true).unwrap(),
+            KeyboardDevice::ControllingTerminal
+        );
+        assert_eq!(
+            error(choose_keyboard(None, false, || false)),
+            "no interactive terminal available for keyboard input (use --tty to point at one)"
+        );
+    }
+}
@@ -0,0 +1,35 @@
+//! Watching of the input file, to read it again once it changes (e.g. as it is saved).
+
+use std::{path::PathBuf, time::Duration};
+
+use tokio::{sync::mpsc, task::JoinHandle};
+
+/// Checks the modification time and the size of the file every `interval`,
+/// sending a notice whenever either changes.
+///
+/// A file missing for a while (e.g. replaced by an editor saving it) counts as changed
+/// once it is back.
+pub fn spawn_watch_task(
+    path: PathBuf,
+    interval: Duration,
+    changed_tx: mpsc::Sender<()>,
+) -> JoinHandle<anyhow::Result<()>> {
+    tokio::spawn(async move {
+        let stamp = |metadata: std::fs::Metadata| (metadata.modified().ok(), metadata.len());
+        let mut last = tokio::fs::metadata(&path).await.map(stamp)?;
+        loop {
+            tokio::time::sleep(interval).await;
+
+            let Ok(current) = tokio::fs::metadata(&path).await.map(stamp) else {
+                continue;
+            };
+            if current == last {
+                continue;
+            }
+            last = current;
+            if changed_tx.send(()).await.is_err() {
+                return Ok(());
+            }
+        }
+    })
+}
@@ -0,0 +1,295 @@
+//! Sessions driven by scripted keys and drawn on a screen in memory,
+//! as through the library rather than the terminal.
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use futures::{stream, Stream, StreamExt};
+use jnv::{Config, Options};
+
+/// Screen recording what is drawn on it.
+#[derive(Clone, Default)]
+struct Screen(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Clearing the screen below where it starts, with which every frame begins.
+const FRAME_START: &str = "\x1b[J";
+
+impl Screen {
+    /// Returns the rows of the last frame drawn, without the escape sequences.
+    fn last_frame(&self) -> Vec<String> {
+        let written = String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned();
+        let frame = written.rsplit(FRAME_START).next().unwrap_or_default();
+        frame
+            .split("\x1b[1E")
+            .map(strip_escapes)
+            .map(|row| row.trim_end().to_string())
+            .collect()
+    }
+
+    /// Returns the number of the frames drawn.
+    fn frames(&self) -> usize {
+        String::from_utf8_lossy(&self.0.lock().unwrap())
+            .matches(FRAME_START)
+            .count()
+    }
+}
+
+/// Removes the control sequences (`ESC [ ... final byte`) from the text.
+fn strip_escapes(text: &str) -> String {
+    let mut ret = String::new();
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            if chars.next() == Some('[') {
+                for ch in chars.by_ref() {
+                    if ('@'..='~').contains(&ch) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            ret.push(ch);
+        }
+    }
+    ret
+}
+
+/// Step of a script.
+enum Step {
+    Type(&'static str),
+    Key(KeyCode, KeyModifiers),
+    Resize(u16, u16),
+    /// Waits until the last frame drawn satisfies the condition,
+    /// e.g. shows the result of the query typed before going on.
+    Until(fn(&[String]) -> bool),
+    /// Waits for a frame drawn after the last event,
+    /// e.g. once the cursor moved, which the text of the frames does not show.
+    Drawn,
+}
+
+use Step::*;
+
+/// Time after which a step waiting for the screen is given up.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the events of the script, each one taken once the previous one is handled
+/// (the stream being polled again only then), waiting for the screen in between.
+fn scripted(steps: Vec<Step>, screen: &Screen) -> impl Stream<Item = io::Result<Event>> {
+    let screen = screen.clone();
+    // Number of the frames drawn when the last event was taken.
+    let drawn = Arc::new(Mutex::new(0));
+    stream::iter(steps)
+        .then(move |step| {
+            let screen = screen.clone();
+            let drawn = drawn.clone();
+            async move {
+                let events = match step {
+                    Type(text) => text
+                        .chars()
+                        .map(|ch| Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE)))
+                        .collect(),
+                    Key(code, modifiers) => vec![Event::Key(KeyEvent::new(code, modifiers))],
+                    Resize(width, height) => vec![Event::Resize(width, height)],
+                    Until(condition) => {
+                        wait(&screen, |screen| condition(&screen.last_frame())).await;
+                        Vec::new()
+                    }
+                    Drawn => {
+                        let drawn = *drawn.lock().unwrap();
+                        wait(&screen, |screen| screen.frames() > drawn).await;
+                        Vec::new()
+                    }
+                };
+                stream::iter(events.into_iter().map(move |event| {
+                    *drawn.lock().unwrap() = screen.frames();
+                    Ok(event)
+                }))
+            }
+        })
+        .flatten()
+}
+
+/// Waits until the screen satisfies the condition, checking it now and then.
+async fn wait(screen: &Screen, condition: impl Fn(&Screen) -> bool) {
+    let started = Instant::now();
+    while !condition(screen) {
+        assert!(
+            started.elapsed() < WAIT_TIMEOUT,
+            "gave up waiting, the last frame being {:?}",
+            screen.last_frame()
+        );
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+/// Options of a session on the scripted keys, drawn on the screen,
+/// the debounce shortened so that the scripts run quickly.
+///
+/// Nothing is written outside the screen (history, clipboard) in safe mode.
+fn options(steps: Vec<Step>, screen: &Screen, size: (u16, u16)) -> Options {
+    let mut config = Config::default();
+    config.reactivity_control.query_debounce_duration = Duration::from_millis(10);
+    config.reactivity_control.resize_debounce_duration = Duration::from_millis(10);
+    config.hint.timing = false;
+    Options::default()
+        .config(config)
+        .no_color(true)
+        .safe_mode(true)
+        .event_source(scripted(steps, screen))
+        .screen(screen.clone(), size)
+}
+
+const QUIT: Step = Key(KeyCode::Char('c'), KeyModifiers::CONTROL);
+
+/// Tells whether the paths of the input are indexed, to be completed,
+/// once the frame is drawn after a key typed in the editor.
+fn indexed(frame: &[String]) -> bool {
+    !frame.iter().any(|row| row.starts_with("indexing paths"))
+}
+
+/// Tells whether the rows at the bottom of the frame are the given ones.
+fn ends_with(frame: &[String], rows: &[&str]) -> bool {
+    frame.len() > rows.len() && frame[frame.len() - rows.len()..] == *rows
+}
+
+#[test]
+fn types_completes_and_scrolls() {
+    let items: Vec<String> = (0..30).map(|i| i.to_string()).collect();
+    let input = format!(r#"{{"items":[{}],"name":"jnv"}}"#, items.join(","));
+    let screen = Screen::default();
+    let outcome = jnv::run(
+        input,
+        options(
+            vec![
+                Type(".it"),
+                Until(indexed),
+                Key(KeyCode::Tab, KeyModifiers::NONE),
+                Until(|frame| frame[0].ends_with(".items")),
+                Type("[]"),
+                Until(|frame| frame.get(1).is_some_and(|row| row == "0,")),
+                Key(KeyCode::Down, KeyModifiers::SHIFT),
+                Until(|frame| frame[0].contains('▼')),
+                Key(KeyCode::Down, KeyModifiers::NONE),
+                Until(|frame| frame.get(1).is_some_and(|row| row == ".")),
+                QUIT,
+            ],
+            &screen,
+            (40, 10),
+        ),
+    )
+    .unwrap();
+    assert_eq!(outcome.query, ".items[]");
+    assert!(!outcome.accepted);
+    assert_eq!(outcome.values.len(), 30);
+
+    let frame = screen.last_frame();
+    assert!(frame[0].ends_with(".items[]"), "{:?}", frame);
+    // The result is focused, with the path of its row above it,
+    // and scrolled past its first value.
+    assert_eq!(frame[1..5], [".", "1,", "2,", "3,"], "{:?}", frame);
+}
+
+#[test]
+fn reads_the_input_file_and_runs_the_query() {
+    let path = std::env::temp_dir().join(format!("jnv-session-{}.json", std::process::id()));
+    std::fs::write(&path, "{\"name\":\"jnv\"}\n{\"name\":\"jq\"}\n").unwrap();
+    let screen = Screen::default();
+    let outcome = jnv::run_from_input(
+        options(
+            vec![
+                Until(|frame| ends_with(frame, &["\"jnv\",", "\"jq\"", ""])),
+                QUIT,
+            ],
+            &screen,
+            (40, 10),
+        )
+        .input(path.clone())
+        .query(".name"),
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    let outcome = outcome.unwrap();
+    assert_eq!(outcome.query, ".name");
+    assert_eq!(outcome.values, ["jnv", "jq"]);
+    // At the bottom, below the editor (and the guide while indexing).
+    let frame = screen.last_frame();
+    assert!(
+        ends_with(&frame, &["\"jnv\",", "\"jq\"", ""]),
+        "{:?}",
+        frame
+    );
+}
+
+#[test]
+fn result_fits_the_screen_once_resized() {
+    let input =
+        r#"{"description":"a sentence long enough to be wider than the screen once resized"}"#;
+    let screen = Screen::default();
+    jnv::run(
+        input.to_string(),
+        options(
+            vec![
+                Until(|frame| frame.iter().any(|row| row.contains("description"))),
+                Key(KeyCode::Down, KeyModifiers::SHIFT),
+                Until(|frame| frame[0].contains('▼')),
+                Resize(30, 10),
+                Until(|frame| {
+                    frame[0].contains('▼')
+                        && frame.iter().any(|row| row.contains("description"))
+                        && frame.iter().all(|row| row.chars().count() <= 30)
+                }),
+                // Drawn again on a key, rather than on the resize.
+                Key(KeyCode::Down, KeyModifiers::NONE),
+                Drawn,
+                QUIT,
+            ],
+            &screen,
+            (80, 10),
+        ),
+    )
+    .unwrap();
+
+    let frame = screen.last_frame();
+    assert!(
+        frame.iter().all(|row| row.chars().count() <= 30),
+        "{:?}",
+        frame
+    );
+    assert!(
+        frame.iter().any(|row| row.contains("description")),
+        "{:?}",
+        frame
+    );
+}
+
+#[test]
+fn replays_a_recording() {
+    let recording =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/edit.jsonl");
+    let screen = Screen::default();
+    let outcome = jnv::run(
+        r#"{"id":1,"name":"jnv"}"#.to_string(),
+        options(Vec::new(), &screen, (40, 10)).replay(recording, 1.),
+    )
+    .unwrap();
+
+    // Typed `.name`, erased back to `.`, then typed `id` and quit.
+    assert_eq!(outcome.query, ".id");
+    assert_eq!(outcome.values, [1]);
+    assert_eq!(screen.last_frame()[0], "SAFE ❯❯ .id");
+}